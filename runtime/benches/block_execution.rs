@@ -0,0 +1,158 @@
+//! Sequential vs level-parallel vs greedy vs optimistic block execution.
+//!
+//! Each group fixes a conflict rate and sweeps the block size. Criterion
+//! reports throughput in transactions per second; after each group the bench
+//! also prints every strategy's speedup over sequential execution at each
+//! size, from the mean times of the same measurements.
+//!
+//! Run with `cargo bench --bench block_execution`.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use commonware_runtime::{
+    Runner,
+    tokio::{Config, Runner as TokioRunner},
+};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use runtime::parallel_determinism::{
+    dep_graph::DependencyGraph,
+    executor::ParallelExecutor,
+    generator::{BlockSpec, generate_tasks},
+    greedy::GreedyExecutor,
+    optimistic::OptimisticExecutor,
+    sequential::SequentialExecutor,
+    state::MemoryStorage,
+};
+
+const SIZES: [usize; 3] = [64, 256, 1024];
+const CONFLICT_RATES: [f64; 3] = [0.0, 0.1, 0.5];
+const STRATEGIES: [Strategy; 4] = [
+    Strategy::Sequential,
+    Strategy::LevelParallel,
+    Strategy::Greedy,
+    Strategy::Optimistic,
+];
+
+#[derive(Clone, Copy)]
+enum Strategy {
+    Sequential,
+    LevelParallel,
+    Greedy,
+    Optimistic,
+}
+
+impl Strategy {
+    fn name(&self) -> &'static str {
+        match self {
+            Strategy::Sequential => "sequential",
+            Strategy::LevelParallel => "level_parallel",
+            Strategy::Greedy => "greedy",
+            Strategy::Optimistic => "optimistic",
+        }
+    }
+}
+
+/// Execute `iters` fresh copies of the block on one Tokio runtime, timing
+/// only the execution itself.
+fn run(strategy: Strategy, spec: &BlockSpec, iters: u64) -> Duration {
+    let graph = DependencyGraph::from_tasks(generate_tasks(spec));
+    TokioRunner::new(Config::default()).start(|context| async move {
+        let mut total = Duration::ZERO;
+        for _ in 0..iters {
+            let store = MemoryStorage::new();
+            let start = Instant::now();
+            match strategy {
+                Strategy::Sequential => {
+                    SequentialExecutor::new()
+                        .execute_with_state(&context, &graph, &store)
+                        .await;
+                }
+                Strategy::LevelParallel => {
+                    ParallelExecutor::new()
+                        .execute_with_state(&context, &graph, &store)
+                        .await;
+                }
+                Strategy::Greedy => {
+                    GreedyExecutor::new()
+                        .execute_with_state(&context, &graph, &store)
+                        .await;
+                }
+                Strategy::Optimistic => {
+                    OptimisticExecutor::new()
+                        .execute_with_state(&context, &graph, &store)
+                        .await;
+                }
+            }
+            total += start.elapsed();
+        }
+        total
+    })
+}
+
+/// Each strategy's speedup over sequential execution at each size, from the
+/// total time and iterations measured for each. Sizes or strategies the run
+/// filtered out are left out.
+fn print_speedups(conflict_rate: f64, timings: &BTreeMap<(usize, &str), (Duration, u64)>) {
+    let mean = |size, strategy: Strategy| {
+        timings
+            .get(&(size, strategy.name()))
+            .map(|(total, iters)| total.as_secs_f64() / *iters as f64)
+    };
+    println!(
+        "speedup over sequential at conflict rate {}:",
+        conflict_rate
+    );
+    for size in SIZES {
+        let Some(sequential) = mean(size, Strategy::Sequential) else {
+            continue;
+        };
+        let speedups: Vec<_> = STRATEGIES[1..]
+            .iter()
+            .filter_map(|&strategy| {
+                mean(size, strategy)
+                    .map(|mean| format!("{} {:.2}x", strategy.name(), sequential / mean))
+            })
+            .collect();
+        println!("  {:>5} tasks: {}", size, speedups.join(", "));
+    }
+}
+
+fn bench_block_execution(c: &mut Criterion) {
+    for conflict_rate in CONFLICT_RATES {
+        let mut timings = BTreeMap::new();
+        let mut group = c.benchmark_group(format!("block_execution/conflict_{}", conflict_rate));
+        for size in SIZES {
+            let spec = BlockSpec {
+                size,
+                conflict_rate,
+                seed: 42,
+            };
+            group.throughput(Throughput::Elements(size as u64));
+            for strategy in STRATEGIES {
+                group.bench_with_input(
+                    BenchmarkId::new(strategy.name(), size),
+                    &spec,
+                    |b, spec| {
+                        b.iter_custom(|iters| {
+                            let elapsed = run(strategy, spec, iters);
+                            let (total, count) = timings
+                                .entry((size, strategy.name()))
+                                .or_insert((Duration::ZERO, 0));
+                            *total += elapsed;
+                            *count += iters;
+                            elapsed
+                        })
+                    },
+                );
+            }
+        }
+        group.finish();
+        print_speedups(conflict_rate, &timings);
+    }
+}
+
+criterion_group!(benches, bench_block_execution);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! The corpus Merkle root as a CPU-parallelism benchmark.
+//!
+//! [`merkle_root`] spawns one task per chunk and each task sorts and hashes
+//! its words, so the run is pure compute spread over tasks. Criterion reports
+//! corpus bytes per second for the sequential reference, the deterministic
+//! runtime (one thread, so the cost of the task machinery alone) and Tokio
+//! with 1, 2, 4 and 8 workers. Each run checks its root against the
+//! sequential one. Only the root computation is timed, not runtime start-up.
+//!
+//! Run with `cargo bench --bench merkle`.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use commonware_runtime::{
+    Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use runtime::{
+    corpus::Corpus,
+    merkle::{merkle_root, merkle_root_sequential},
+    tasks::read_file,
+};
+
+const REPEATS: usize = 200;
+const CHUNK_WORDS: usize = 1_024;
+const WORKERS: [usize; 4] = [1, 2, 4, 8];
+const SEED: u64 = 42;
+
+fn timed<R: Runner>(runner: R, corpus: Arc<Corpus>, expected: u64) -> Duration
+where
+    R::Context: Spawner,
+{
+    runner.start(|context| async move {
+        let start = Instant::now();
+        let root = merkle_root(&context, corpus, CHUNK_WORDS).await;
+        let elapsed = start.elapsed();
+        assert_eq!(root, expected);
+        elapsed
+    })
+}
+
+fn bench_merkle(c: &mut Criterion) {
+    let text: String = read_file().words().collect::<Vec<_>>().join(" ");
+    let corpus = Arc::new(Corpus::new(vec![text.as_str(); REPEATS].join(" ")));
+    let expected = merkle_root_sequential(&corpus, CHUNK_WORDS);
+
+    let mut group = c.benchmark_group("merkle");
+    group.throughput(Throughput::Bytes((text.len() * REPEATS) as u64));
+    group.bench_function("sequential", |b| {
+        b.iter(|| merkle_root_sequential(&corpus, CHUNK_WORDS))
+    });
+    group.bench_function("deterministic", |b| {
+        b.iter_custom(|iters| {
+            (0..iters)
+                .map(|_| {
+                    timed(
+                        DeterministicRunner::new(Config::default().with_seed(SEED)),
+                        corpus.clone(),
+                        expected,
+                    )
+                })
+                .sum()
+        })
+    });
+    for workers in WORKERS {
+        group.bench_with_input(
+            BenchmarkId::new("tokio", workers),
+            &workers,
+            |b, &workers| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| {
+                            timed(
+                                TokioRunner::new(
+                                    TokioConfig::default().with_worker_threads(workers),
+                                ),
+                                corpus.clone(),
+                                expected,
+                            )
+                        })
+                        .sum()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_merkle);
+criterion_main!(benches);
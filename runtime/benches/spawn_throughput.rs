@@ -0,0 +1,71 @@
+//! Spawn+completion throughput of trivial tasks, Tokio vs deterministic.
+//!
+//! Criterion reports tasks per second for each runtime and task count; Tokio
+//! runs with a fixed number of worker threads so results are comparable
+//! across machines. After the criterion run, one more pass per configuration
+//! is written as JSON to `$CARGO_TARGET_DIR/spawn_throughput.json` (default
+//! `target/`) so results can be tracked across commits.
+//!
+//! Run with `cargo bench --bench spawn_throughput`.
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use runtime::throughput::{measure_deterministic, measure_tokio, to_json};
+
+const TASK_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+const SEED: u64 = 42;
+const WORKER_THREADS: usize = 4;
+
+fn bench_spawn_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn_throughput");
+    for tasks in TASK_COUNTS {
+        group.throughput(Throughput::Elements(tasks as u64));
+        group.bench_with_input(BenchmarkId::new("tokio", tasks), &tasks, |b, &tasks| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| measure_tokio(tasks, WORKER_THREADS).elapsed)
+                    .sum()
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("deterministic", tasks),
+            &tasks,
+            |b, &tasks| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| measure_deterministic(tasks, SEED).elapsed)
+                        .sum::<Duration>()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn export_json() {
+    let samples: Vec<_> = TASK_COUNTS
+        .into_iter()
+        .flat_map(|tasks| {
+            [
+                measure_tokio(tasks, WORKER_THREADS),
+                measure_deterministic(tasks, SEED),
+            ]
+        })
+        .collect();
+
+    let dir = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"));
+    fs::create_dir_all(&dir).expect("Target directory should be writable");
+    let path = dir.join("spawn_throughput.json");
+    fs::write(&path, to_json(&samples)).expect("Throughput results should be writable");
+    println!("Wrote {}", path.display());
+}
+
+fn main() {
+    let mut criterion = Criterion::default().configure_from_args();
+    bench_spawn_throughput(&mut criterion);
+    criterion.final_summary();
+    export_json();
+}
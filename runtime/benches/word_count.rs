@@ -0,0 +1,45 @@
+//! Counting a word by scanning tokens, searching the raw text, or looking it
+//! up in a prebuilt index.
+//!
+//! The scan splits the corpus into words and compares each one; the search
+//! ([`Corpus::count`]) runs `memchr`'s vectorized substring finder over the
+//! bytes and only checks word boundaries at the matches; the lookup reads the
+//! count from a [`CorpusIndex`](runtime::corpus::CorpusIndex) built once
+//! outside the timed loop, and `index` times that build. Criterion reports
+//! throughput in corpus bytes per second for a common word, a rare word and
+//! a word that does not occur, on the corpus as shipped and repeated a
+//! hundred times.
+//!
+//! Run with `cargo bench --bench word_count`.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use runtime::{corpus::Corpus, tasks::read_file};
+
+const WORDS: [&str; 3] = ["the", "Kafka", "zebra"];
+const REPEATS: [usize; 2] = [1, 100];
+
+fn bench_word_count(c: &mut Criterion) {
+    let text: String = read_file().words().collect::<Vec<_>>().join(" ");
+    for repeats in REPEATS {
+        let corpus = Corpus::new(vec![text.as_str(); repeats].join(" "));
+        let mut group = c.benchmark_group(format!("word_count_x{}", repeats));
+        group.throughput(Throughput::Bytes((text.len() * repeats) as u64));
+        for word in WORDS {
+            group.bench_with_input(BenchmarkId::new("scan", word), word, |b, word| {
+                b.iter(|| corpus.words().filter(|&w| w == word).count())
+            });
+            group.bench_with_input(BenchmarkId::new("search", word), word, |b, word| {
+                b.iter(|| corpus.count(word))
+            });
+            let index = corpus.index();
+            group.bench_with_input(BenchmarkId::new("lookup", word), word, |b, word| {
+                b.iter(|| index.count(word))
+            });
+        }
+        group.bench_function("index", |b| b.iter(|| corpus.index()));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_word_count);
+criterion_main!(benches);
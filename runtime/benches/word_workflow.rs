@@ -0,0 +1,225 @@
+//! The word-selection workflow and the task-type mix, Tokio vs deterministic.
+//!
+//! The "Tokio for throughput" half of this crate's argument, measured. Each
+//! iteration runs several copies of a workload side by side and criterion
+//! reports wall-clock throughput in workloads (or tasks) per second. Tokio
+//! spreads the copies over its worker threads; the deterministic runtime runs
+//! everything on one thread, in an order fixed by the seed.
+//!
+//! The mix uses only I/O-bound and cooperative CPU-bound tasks: greedy tasks
+//! spin for the same time on either runtime and would only add noise. Note
+//! that the I/O tasks' sleeps are real time on Tokio but virtual on the
+//! deterministic runtime, which skips them.
+//!
+//! After each group the bench also prints latency percentiles read from the
+//! runtime's own clock: per workflow copy, or per batch of mixed tasks. On the
+//! deterministic runtime that clock is virtual, so these show how much
+//! simulated time the work took rather than how long the host spent on it.
+//!
+//! Run with `cargo bench --bench word_workflow`.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use commonware_runtime::{
+    Clock, Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use runtime::{
+    audit::NondeterminismAudit,
+    corpus::Corpus,
+    mix::{WorkloadMix, spawn_mix},
+    stats::{LatencyHistogram, elapsed_since},
+    tasks::{WorkflowConfig, read_file, word_workflow},
+    trace::EventLog,
+};
+
+const WORKFLOWS: [usize; 3] = [1, 4, 16];
+const ROUNDS: usize = 5;
+const SEED: u64 = 42;
+const WORKER_THREADS: usize = 4;
+const MIX_TASKS: usize = 8;
+
+/// Run `workflows` copies of the word workflow as siblings, wait for all, and
+/// return how long each copy took on the runtime's clock.
+async fn run_workflows<S: Spawner + Clock>(
+    context: S,
+    words: Arc<Corpus>,
+    workflows: usize,
+) -> Vec<Duration> {
+    let start = context.current();
+    let handles: Vec<_> = (0..workflows)
+        .map(|copy| {
+            let words = words.clone();
+            context.clone().spawn(move |context| async move {
+                let config = WorkflowConfig::default()
+                    .with_rounds(ROUNDS)
+                    .with_seed(SEED + copy as u64)
+                    .with_pause(Duration::ZERO);
+                word_workflow(
+                    &context,
+                    words,
+                    config,
+                    EventLog::discarding(),
+                    NondeterminismAudit::disabled(),
+                )
+                .await;
+                elapsed_since(&context, start)
+            })
+        })
+        .collect();
+    let mut latencies = Vec::with_capacity(workflows);
+    for handle in handles {
+        latencies.push(handle.await.expect("Workflow should run to completion"));
+    }
+    latencies
+}
+
+/// Print the latency percentiles of each benchmark in a group, by id.
+fn print_latencies<P: Ord + fmt::Display>(
+    title: &str,
+    latencies: &mut BTreeMap<(&str, P), LatencyHistogram>,
+) {
+    println!("{} on the runtime's clock:", title);
+    for ((runtime, parameter), histogram) in latencies {
+        let id = format!("{}/{}", runtime, parameter);
+        println!("  {:>22}: {}", id, histogram.percentiles());
+    }
+}
+
+fn bench_word_workflow(c: &mut Criterion) {
+    let words = Arc::new(read_file());
+    let mut latencies = BTreeMap::<_, LatencyHistogram>::new();
+    let mut group = c.benchmark_group("word_workflow");
+    group.sample_size(10);
+    for workflows in WORKFLOWS {
+        group.throughput(Throughput::Elements(workflows as u64));
+        group.bench_with_input(
+            BenchmarkId::new("tokio", workflows),
+            &workflows,
+            |b, &workflows| {
+                b.iter_custom(|iters| {
+                    let words = words.clone();
+                    let (elapsed, observed) = TokioRunner::new(
+                        TokioConfig::default().with_worker_threads(WORKER_THREADS),
+                    )
+                    .start(|context| async move {
+                        let mut observed = Vec::new();
+                        let start = Instant::now();
+                        for _ in 0..iters {
+                            observed.extend(
+                                run_workflows(context.clone(), words.clone(), workflows).await,
+                            );
+                        }
+                        (start.elapsed(), observed)
+                    });
+                    latencies
+                        .entry(("tokio", workflows))
+                        .or_default()
+                        .extend(observed);
+                    elapsed
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("deterministic", workflows),
+            &workflows,
+            |b, &workflows| {
+                b.iter_custom(|iters| {
+                    let words = words.clone();
+                    let (elapsed, observed) = DeterministicRunner::new(
+                        Config::default().with_seed(SEED),
+                    )
+                    .start(|context| async move {
+                        let mut observed = Vec::new();
+                        let start = Instant::now();
+                        for _ in 0..iters {
+                            observed.extend(
+                                run_workflows(context.clone(), words.clone(), workflows).await,
+                            );
+                        }
+                        (start.elapsed(), observed)
+                    });
+                    latencies
+                        .entry(("deterministic", workflows))
+                        .or_default()
+                        .extend(observed);
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+    print_latencies("word_workflow latency per copy", &mut latencies);
+}
+
+fn bench_task_mix(c: &mut Criterion) {
+    let mut latencies = BTreeMap::<_, LatencyHistogram>::new();
+    let mut group = c.benchmark_group("task_mix");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(MIX_TASKS as u64));
+    for (name, mix) in [
+        ("io", WorkloadMix::new(0, 100, 0)),
+        ("balanced", WorkloadMix::new(50, 50, 0)),
+    ] {
+        let kinds = mix.generate(MIX_TASKS, SEED);
+        group.bench_with_input(BenchmarkId::new("tokio", name), &kinds, |b, kinds| {
+            b.iter_custom(|iters| {
+                let (elapsed, observed) =
+                    TokioRunner::new(TokioConfig::default().with_worker_threads(WORKER_THREADS))
+                        .start(|context| async move {
+                            let mut observed = Vec::new();
+                            let start = Instant::now();
+                            for _ in 0..iters {
+                                let batch = context.current();
+                                spawn_mix(&context, kinds).await;
+                                observed.push(elapsed_since(&context, batch));
+                            }
+                            (start.elapsed(), observed)
+                        });
+                latencies
+                    .entry(("tokio", name))
+                    .or_default()
+                    .extend(observed);
+                elapsed
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("deterministic", name),
+            &kinds,
+            |b, kinds| {
+                b.iter_custom(|iters| {
+                    let (elapsed, observed) = DeterministicRunner::new(
+                        Config::default().with_seed(SEED),
+                    )
+                    .start(|context| async move {
+                        let mut observed = Vec::new();
+                        let start = Instant::now();
+                        for _ in 0..iters {
+                            let batch = context.current();
+                            spawn_mix(&context, kinds).await;
+                            observed.push(elapsed_since(&context, batch));
+                        }
+                        (start.elapsed(), observed)
+                    });
+                    latencies
+                        .entry(("deterministic", name))
+                        .or_default()
+                        .extend(observed);
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+    print_latencies("task_mix latency per batch", &mut latencies);
+}
+
+criterion_group!(benches, bench_word_workflow, bench_task_mix);
+criterion_main!(benches);
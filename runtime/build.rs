@@ -0,0 +1,14 @@
+fn main() {
+    // The gRPC service's messages and stubs are generated from its proto
+    // with a vendored `protoc`, so building needs no system install.
+    #[cfg(feature = "grpc")]
+    {
+        let mut config = tonic_prost_build::Config::new();
+        config.protoc_executable(
+            protoc_bin_vendored::protoc_bin_path().expect("Vendored protoc is available"),
+        );
+        tonic_prost_build::configure()
+            .compile_with_config(config, &["proto/simulation.proto"], &["proto"])
+            .expect("Simulation proto compiles");
+    }
+}
@@ -0,0 +1,31 @@
+//! Run the sleep demo under tokio-console, then print the crate's own traces.
+//!
+//! ```text
+//! RUSTFLAGS="--cfg tokio_unstable" cargo run --example console --features console
+//! tokio-console   # in another terminal
+//! ```
+
+use std::time::Duration;
+
+use runtime::{DEMO_SEED, console, shadow::SleepTasks};
+
+fn main() {
+    console::init();
+    let run = console::instrumented(SleepTasks, DEMO_SEED, 4, 20, Duration::from_millis(500));
+
+    for (round, trace) in run.tokio.iter().enumerate() {
+        println!("Tokio round {}:", round);
+        for event in trace {
+            println!("  {}", event);
+        }
+    }
+    println!("Deterministic, seed {}:", DEMO_SEED);
+    for event in &run.deterministic {
+        println!("  {}", event);
+    }
+    println!(
+        "{} distinct Tokio schedules in {} rounds",
+        run.tokio_schedules(),
+        run.tokio.len()
+    );
+}
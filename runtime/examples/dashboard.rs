@@ -0,0 +1,25 @@
+//! Watch a generated block execute in the terminal.
+//!
+//! `cargo run --example dashboard --features tui` runs it on the
+//! deterministic runtime; add `-- tokio` to run it on Tokio instead.
+
+use std::time::Duration;
+
+use runtime::{
+    DEMO_SEED,
+    parallel_determinism::generator::BlockSpec,
+    tui::{watch_deterministic, watch_tokio},
+};
+
+fn main() -> std::io::Result<()> {
+    let spec = BlockSpec {
+        size: 24,
+        conflict_rate: 0.3,
+        seed: DEMO_SEED,
+    };
+    let latency = Duration::from_millis(5);
+    match std::env::args().nth(1).as_deref() {
+        Some("tokio") => watch_tokio(&spec, latency),
+        _ => watch_deterministic(&spec, latency),
+    }
+}
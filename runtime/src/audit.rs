@@ -0,0 +1,328 @@
+//! Opt-in detection of nondeterminism sources in workload code.
+//!
+//! A deterministic runtime only replays what it controls. Reading the wall
+//! clock, drawing from the OS RNG, or iterating a `HashMap` (whose order is
+//! randomized per process) all slip past it: the run still completes, but a
+//! replay with the same seed can take a different path.
+//!
+//! Rust gives us no hook to intercept those calls globally, so workloads go
+//! through a [`TaskAudit`] instead. When the audit is enabled every call is
+//! recorded with the task that made it and the source location; when it is
+//! disabled the calls pass straight through. Either way the workload behaves
+//! the same, which is the point: the audit shows what *would* break replay.
+//!
+//! Wall-clock time is the source most easily reintroduced by accident, a
+//! stray `Instant::now` for a log line or a `thread::sleep` in a retry.
+//! [`forbid_wall_clock`] turns the audit into a gate for it: it runs a
+//! workload on the deterministic runtime and fails the run if any task read
+//! the wall clock or slept on it through the shims.
+
+use std::{
+    collections::{HashMap, hash_map},
+    fmt::{self, Write},
+    panic::Location,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+#[cfg(feature = "deterministic-backend")]
+use commonware_runtime::deterministic::Context;
+#[cfg(feature = "deterministic-backend")]
+use std::future::Future;
+
+#[cfg(feature = "deterministic-backend")]
+use crate::run::{AuditMode, DeterministicRun};
+
+use rand::{SeedableRng, rngs::StdRng};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    /// `SystemTime::now()` instead of the runtime clock.
+    WallClock,
+    /// `Instant::now()` instead of the runtime clock.
+    Monotonic,
+    /// `thread::sleep`, which blocks the worker instead of yielding to the
+    /// runtime's timers.
+    ThreadSleep,
+    /// An RNG seeded from the operating system instead of the run's seed.
+    OsRng,
+    /// Iteration over a `HashMap`, whose order changes between processes.
+    UnorderedIteration,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::WallClock => "wall-clock read",
+            Source::Monotonic => "monotonic-clock read",
+            Source::ThreadSleep => "thread sleep",
+            Source::OsRng => "OS RNG",
+            Source::UnorderedIteration => "unordered HashMap iteration",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Source {
+    /// Whether the source is real time rather than the runtime's clock.
+    pub fn is_wall_clock(&self) -> bool {
+        matches!(
+            self,
+            Source::WallClock | Source::Monotonic | Source::ThreadSleep
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub task_id: usize,
+    pub source: Source,
+    pub location: &'static Location<'static>,
+}
+
+/// Collects findings from every task of a run. Clones share one log.
+#[derive(Clone, Default)]
+pub struct NondeterminismAudit {
+    enabled: bool,
+    findings: Arc<Mutex<Vec<Finding>>>,
+}
+
+impl NondeterminismAudit {
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    /// An audit that records nothing; the shims behave exactly the same.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// The handle a single task uses to reach nondeterministic sources.
+    pub fn for_task(&self, task_id: usize) -> TaskAudit {
+        TaskAudit {
+            audit: self.clone(),
+            task_id,
+        }
+    }
+
+    /// Everything recorded so far, ordered by task id, then source, then
+    /// location, so the report itself does not depend on scheduling.
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = self.findings.lock().unwrap().clone();
+        findings.sort_by_key(|f| (f.task_id, f.source, f.location.file(), f.location.line()));
+        findings
+    }
+
+    pub fn report(&self) -> String {
+        let findings = self.findings();
+        if findings.is_empty() {
+            return "No nondeterminism sources detected.\n".to_string();
+        }
+        let mut report = String::new();
+        for finding in findings {
+            writeln!(
+                report,
+                "task {}: {} at {}",
+                finding.task_id, finding.source, finding.location
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    fn record(&self, task_id: usize, source: Source, location: &'static Location<'static>) {
+        if self.enabled {
+            self.findings.lock().unwrap().push(Finding {
+                task_id,
+                source,
+                location,
+            });
+        }
+    }
+}
+
+/// Audited access to nondeterministic sources on behalf of one task.
+#[derive(Clone)]
+pub struct TaskAudit {
+    audit: NondeterminismAudit,
+    task_id: usize,
+}
+
+impl TaskAudit {
+    #[track_caller]
+    pub fn wall_clock(&self) -> SystemTime {
+        self.audit
+            .record(self.task_id, Source::WallClock, Location::caller());
+        SystemTime::now()
+    }
+
+    #[track_caller]
+    pub fn instant(&self) -> Instant {
+        self.audit
+            .record(self.task_id, Source::Monotonic, Location::caller());
+        Instant::now()
+    }
+
+    #[track_caller]
+    pub fn thread_sleep(&self, duration: Duration) {
+        self.audit
+            .record(self.task_id, Source::ThreadSleep, Location::caller());
+        thread::sleep(duration);
+    }
+
+    #[track_caller]
+    pub fn os_rng(&self) -> StdRng {
+        self.audit
+            .record(self.task_id, Source::OsRng, Location::caller());
+        StdRng::from_os_rng()
+    }
+
+    #[track_caller]
+    pub fn iter_unordered<'a, K, V>(&self, map: &'a HashMap<K, V>) -> hash_map::Iter<'a, K, V> {
+        self.audit
+            .record(self.task_id, Source::UnorderedIteration, Location::caller());
+        map.iter()
+    }
+}
+
+/// A deterministic run used real time; `findings` says where.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WallClockUsed {
+    pub findings: Vec<Finding>,
+}
+
+impl fmt::Display for WallClockUsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} wall-clock uses", self.findings.len())?;
+        for finding in &self.findings {
+            write!(
+                f,
+                "\n  task {}: {} at {}",
+                finding.task_id, finding.source, finding.location
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for WallClockUsed {}
+
+/// Run `workload` on the deterministic runtime with `seed` and an enabled
+/// audit, and fail if any task used real time through it. Other sources are
+/// left to [`NondeterminismAudit::report`].
+#[cfg(feature = "deterministic-backend")]
+pub fn forbid_wall_clock<F, Fut>(seed: u64, workload: F) -> Result<Fut::Output, WallClockUsed>
+where
+    F: FnOnce(Context, NondeterminismAudit) -> Fut,
+    Fut: Future,
+{
+    DeterministicRun::builder()
+        .with_seed(seed)
+        .with_recording(false)
+        .with_audit(AuditMode::ForbidWallClock)
+        .build()
+        .run(|context, _, audit| workload(context, audit))
+        .map(|run| run.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner, Spawner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    /// Run three tasks that each hit one source, and return the audit.
+    fn run(audit: NondeterminismAudit) -> NondeterminismAudit {
+        DeterministicRunner::new(Config::default().with_seed(1)).start(|context| async move {
+            let clock = audit.for_task(0);
+            let rng = audit.for_task(1);
+            let map = audit.for_task(2);
+            let handles = [
+                context.clone().spawn(move |_| async move {
+                    clock.wall_clock();
+                }),
+                context.clone().spawn(move |_| async move {
+                    rng.os_rng();
+                }),
+                context.clone().spawn(move |_| async move {
+                    let balances = HashMap::from([("alice", 1), ("bob", 2)]);
+                    let _: Vec<_> = map.iter_unordered(&balances).collect();
+                }),
+            ];
+            for handle in handles {
+                handle.await.unwrap();
+            }
+            audit
+        })
+    }
+
+    /// Each source is reported once, against the task that used it.
+    #[test]
+    fn test_findings_name_task_and_source() {
+        let findings = run(NondeterminismAudit::enabled()).findings();
+
+        let found: Vec<_> = findings.iter().map(|f| (f.task_id, f.source)).collect();
+        assert_eq!(
+            found,
+            [
+                (0, Source::WallClock),
+                (1, Source::OsRng),
+                (2, Source::UnorderedIteration),
+            ]
+        );
+        assert!(
+            findings
+                .iter()
+                .all(|f| f.location.file().ends_with("audit.rs"))
+        );
+    }
+
+    /// A run that keeps to the runtime clock passes; one that reads
+    /// `Instant::now` or calls `thread::sleep` fails, naming each use. Other
+    /// sources don't fail it.
+    #[cfg(feature = "deterministic-backend")]
+    #[test]
+    fn test_forbid_wall_clock() {
+        use commonware_runtime::Clock;
+
+        let clean = forbid_wall_clock(0, |context, audit| async move {
+            context.sleep(Duration::from_secs(1)).await;
+            audit.for_task(0).os_rng();
+        });
+        assert_eq!(clean, Ok(()));
+
+        let error = forbid_wall_clock(0, |_, audit| async move {
+            let task = audit.for_task(3);
+            task.instant();
+            task.thread_sleep(Duration::ZERO);
+        })
+        .unwrap_err();
+        let found: Vec<_> = error
+            .findings
+            .iter()
+            .map(|f| (f.task_id, f.source))
+            .collect();
+        assert_eq!(found, [(3, Source::Monotonic), (3, Source::ThreadSleep)]);
+        assert!(
+            error
+                .to_string()
+                .starts_with("2 wall-clock uses\n  task 3:")
+        );
+    }
+
+    /// The audit is opt-in: a disabled audit records nothing.
+    #[test]
+    fn test_disabled_records_nothing() {
+        let audit = run(NondeterminismAudit::disabled());
+
+        assert!(audit.findings().is_empty());
+        assert_eq!(audit.report(), "No nondeterminism sources detected.\n");
+    }
+}
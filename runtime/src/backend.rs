@@ -0,0 +1,116 @@
+//! Choosing the runtime by value.
+//!
+//! Most of this crate comes in pairs, one function per runtime, because the
+//! two runtimes hand out different context types. For a [`Workload`], which
+//! is written once against the runtime traits, that split is unnecessary: a
+//! [`Backend`] names the runtime and its one parameter, and [`run_on`] starts
+//! it. A test can then loop over backends, and a command line can take the
+//! runtime as an argument (`tokio:4`, `deterministic:7`) through
+//! [`Backend`]'s `FromStr`.
+
+use std::{fmt, str::FromStr};
+
+use commonware_runtime::{
+    Runner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+
+use crate::{
+    shadow::Workload,
+    trace::{EventLog, TraceEvent},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "scenario",
+    derive(serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum Backend {
+    Tokio { worker_threads: usize },
+    Deterministic { seed: u64 },
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Tokio { worker_threads } => write!(f, "tokio:{}", worker_threads),
+            Backend::Deterministic { seed } => write!(f, "deterministic:{}", seed),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    /// `tokio:<worker threads>` or `deterministic:<seed>`. Tokio needs at
+    /// least one worker thread.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <kind>:<value>, got '{}'", s))?;
+        let value: u64 = value
+            .parse()
+            .map_err(|_| format!("'{}' is not a number", value))?;
+        match kind {
+            "tokio" if value == 0 => Err("tokio needs at least one worker thread".to_string()),
+            "tokio" => Ok(Backend::Tokio {
+                worker_threads: value as usize,
+            }),
+            "deterministic" => Ok(Backend::Deterministic { seed: value }),
+            _ => Err(format!("unknown backend '{}'", kind)),
+        }
+    }
+}
+
+/// Run `workload` to completion on `backend` and return what it recorded.
+pub fn run_on<W: Workload>(backend: Backend, workload: W) -> Vec<TraceEvent> {
+    let log = EventLog::new();
+    match backend {
+        Backend::Tokio { worker_threads } => {
+            TokioRunner::new(TokioConfig::default().with_worker_threads(worker_threads))
+                .start(|context| workload.run(context, log.clone()))
+        }
+        Backend::Deterministic { seed } => {
+            DeterministicRunner::new(Config::default().with_seed(seed))
+                .start(|context| workload.run(context, log.clone()))
+        }
+    }
+    log.events()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shadow::SleepTasks;
+
+    /// Backends round-trip through their text form; anything else is an
+    /// error.
+    #[test]
+    fn test_parse_backend() {
+        for backend in [
+            Backend::Tokio { worker_threads: 4 },
+            Backend::Deterministic { seed: 7 },
+        ] {
+            assert_eq!(backend.to_string().parse(), Ok(backend));
+        }
+        assert!("tokio".parse::<Backend>().is_err());
+        assert!("tokio:0".parse::<Backend>().is_err());
+        assert!("wasm:1".parse::<Backend>().is_err());
+        assert!("deterministic:x".parse::<Backend>().is_err());
+    }
+
+    /// The same workload runs on either backend, chosen by value.
+    #[test]
+    fn test_run_on_each_backend() {
+        for backend in ["tokio:2", "deterministic:1"] {
+            let events = run_on(backend.parse().unwrap(), SleepTasks);
+            assert_eq!(events.len(), 6);
+        }
+        assert_eq!(
+            run_on(Backend::Deterministic { seed: 3 }, SleepTasks),
+            run_on(Backend::Deterministic { seed: 3 }, SleepTasks)
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Backpressure through a bounded channel.
+//!
+//! [`bounded`] is a small multi-producer, single-consumer channel that holds
+//! at most `capacity` values. Sending into a full channel waits until the
+//! receiver takes something out. It uses no runtime of its own: blocked tasks
+//! are parked with their wakers and woken in the order they blocked, so which
+//! task resumes when is decided by the runtime driving them, and under the
+//! deterministic runtime that is fixed by the seed.
+//!
+//! [`backpressure`] is the demo: a producer that makes an item every
+//! `produce_every` feeds a consumer that needs `consume_every` per item.
+//! Once the consumer falls `capacity` items behind, the producer blocks, and
+//! each time it does it records a [`Stall`] with the virtual time it blocked
+//! and how long it waited. With the deterministic runtime those times are the
+//! same on every run, so the points where backpressure kicks in can be
+//! studied, and asserted, exactly.
+
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+    time::{Duration, SystemTime},
+};
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::trace::EventLog;
+
+struct Shared<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    senders: usize,
+    receiver: bool,
+    /// Senders waiting for room, in the order they blocked.
+    blocked: VecDeque<Waker>,
+    /// The receiver, if it is waiting for a value.
+    waiting: Option<Waker>,
+}
+
+/// Why [`Sender::try_send`] handed the value back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The channel holds `capacity` values already.
+    Full(T),
+    /// The receiver is gone.
+    Closed(T),
+}
+
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+/// A channel that holds at most `capacity` values.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "A bounded channel needs room for one value");
+    let shared = Arc::new(Mutex::new(Shared {
+        queue: VecDeque::with_capacity(capacity),
+        capacity,
+        senders: 1,
+        receiver: true,
+        blocked: VecDeque::new(),
+        waiting: None,
+    }));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+impl<T> Sender<T> {
+    /// Send `value` if there is room right now.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.send_or_park(value, None)
+    }
+
+    /// [`Self::try_send`], parking `waker` among the blocked senders if the
+    /// channel is full. The check and the parking happen under one lock, so
+    /// the receiver cannot free a slot in between and miss the waker.
+    fn send_or_park(&self, value: T, waker: Option<&Waker>) -> Result<(), TrySendError<T>> {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.receiver {
+            return Err(TrySendError::Closed(value));
+        }
+        if shared.queue.len() == shared.capacity {
+            if let Some(waker) = waker {
+                shared.blocked.push_back(waker.clone());
+            }
+            return Err(TrySendError::Full(value));
+        }
+        shared.queue.push_back(value);
+        if let Some(waker) = shared.waiting.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Send `value`, waiting for room if the channel is full. Hands the value
+    /// back if the receiver is gone.
+    pub async fn send(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        poll_fn(|cx| {
+            let next = value.take().expect("Polled after completion");
+            match self.send_or_park(next, Some(cx.waker())) {
+                Ok(()) => Poll::Ready(Ok(())),
+                Err(TrySendError::Closed(value)) => Poll::Ready(Err(value)),
+                Err(TrySendError::Full(rejected)) => {
+                    value = Some(rejected);
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.senders -= 1;
+        if shared.senders == 0
+            && let Some(waker) = shared.waiting.take()
+        {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// The next value, waiting for one if the channel is empty. `None` once
+    /// the channel is empty and every sender is gone.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| {
+            let mut shared = self.shared.lock().unwrap();
+            match shared.queue.pop_front() {
+                Some(value) => {
+                    // Wake every blocked sender in the order they blocked;
+                    // the first to run takes the free slot and the rest
+                    // block again behind it.
+                    for waker in std::mem::take(&mut shared.blocked) {
+                        waker.wake();
+                    }
+                    Poll::Ready(Some(value))
+                }
+                None if shared.senders == 0 => Poll::Ready(None),
+                None => {
+                    shared.waiting = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Values waiting in the channel.
+    pub fn len(&self) -> usize {
+        self.shared.lock().unwrap().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.receiver = false;
+        for waker in std::mem::take(&mut shared.blocked) {
+            waker.wake();
+        }
+    }
+}
+
+/// One time the producer found the channel full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stall {
+    /// The item it was trying to send.
+    pub item: usize,
+    /// When it blocked, measured from the start of the demo.
+    pub at: Duration,
+    /// How long it waited for room.
+    pub waited: Duration,
+}
+
+fn since(context: &impl Clock, start: SystemTime) -> Duration {
+    context
+        .current()
+        .duration_since(start)
+        .expect("Clock should not go backwards")
+}
+
+/// Produce `items` items, one every `produce_every`, into a channel of
+/// `capacity` drained by a consumer that takes `consume_every` per item.
+/// The producer records `blocked on item N` and `resumed item N` in `log`,
+/// the consumer `consumed item N`. Returns every stall, in order.
+pub async fn backpressure<S: Spawner + Clock>(
+    context: &S,
+    capacity: usize,
+    items: usize,
+    produce_every: Duration,
+    consume_every: Duration,
+    log: &EventLog,
+) -> Vec<Stall> {
+    let start = context.current();
+    let (sender, mut receiver) = bounded(capacity);
+
+    let consumer_log = log.clone();
+    let consumer = context.clone().spawn(move |context| async move {
+        while let Some(item) = receiver.recv().await {
+            context.sleep(consume_every).await;
+            consumer_log.record("consumer", format!("consumed item {}", item));
+        }
+    });
+
+    let mut stalls = Vec::new();
+    for item in 0..items {
+        context.sleep(produce_every).await;
+        if let Err(TrySendError::Full(item)) = sender.try_send(item) {
+            let at = since(context, start);
+            log.record("producer", format!("blocked on item {}", item));
+            sender
+                .send(item)
+                .await
+                .unwrap_or_else(|_| panic!("Consumer should outlive the producer"));
+            log.record("producer", format!("resumed item {}", item));
+            stalls.push(Stall {
+                item,
+                at,
+                waited: since(context, start) - at,
+            });
+        }
+    }
+    drop(sender);
+
+    consumer.await.expect("Consumer should run to completion");
+    stalls
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::trace::TraceEvent;
+
+    fn demo(seed: u64, capacity: usize) -> (Vec<Stall>, Vec<TraceEvent>) {
+        let log = EventLog::new();
+        let stalls = DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+            let log = log.clone();
+            async move {
+                backpressure(
+                    &context,
+                    capacity,
+                    20,
+                    Duration::from_millis(1),
+                    Duration::from_millis(3),
+                    &log,
+                )
+                .await
+            }
+        });
+        (stalls, log.events())
+    }
+
+    /// A producer three times faster than its consumer blocks once the
+    /// channel fills, and every later item waits for a slot.
+    #[test]
+    fn test_producer_blocks_when_full() {
+        let (stalls, events) = demo(0, 4);
+
+        assert!(!stalls.is_empty());
+        assert!(stalls.iter().all(|stall| stall.waited > Duration::ZERO));
+        assert!(stalls[0].item >= 4);
+        let consumed: Vec<_> = events
+            .iter()
+            .filter(|event| event.task == "consumer")
+            .map(|event| event.message.clone())
+            .collect();
+        let expected: Vec<_> = (0..20)
+            .map(|item| format!("consumed item {}", item))
+            .collect();
+        assert_eq!(consumed, expected);
+    }
+
+    /// Backpressure points fall at the same virtual times on every run with
+    /// the same seed.
+    #[test]
+    fn test_stalls_repeat() {
+        for seed in 0..5 {
+            assert_eq!(demo(seed, 4), demo(seed, 4));
+        }
+    }
+
+    /// A channel with room for every item never pushes back.
+    #[test]
+    fn test_no_stalls_with_room() {
+        assert!(demo(0, 20).0.is_empty());
+    }
+
+    /// Once the receiver is gone, sends hand the value back.
+    #[test]
+    fn test_send_after_receiver_dropped() {
+        let (sender, receiver) = bounded(1);
+        drop(receiver);
+
+        assert_eq!(sender.try_send(1), Err(TrySendError::Closed(1)));
+    }
+
+    /// Producers and receivers on different worker threads, racing through
+    /// a single slot, never lose a wakeup: every value arrives well within
+    /// the deadline.
+    #[test]
+    fn test_no_lost_wakeups_on_tokio() {
+        use std::{sync::mpsc, thread};
+
+        use commonware_runtime::tokio::{Config as TokioConfig, Runner as TokioRunner};
+
+        const CHANNELS: usize = 4;
+        const VALUES: usize = 50_000;
+
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            let received = TokioRunner::new(TokioConfig::default().with_worker_threads(4)).start(
+                |context| async move {
+                    let receivers: Vec<_> = (0..CHANNELS)
+                        .map(|_| {
+                            let (sender, mut receiver) = bounded::<usize>(1);
+                            context.clone().spawn(move |_| async move {
+                                for value in 0..VALUES {
+                                    sender.send(value).await.unwrap();
+                                }
+                            });
+                            context.clone().spawn(move |_| async move {
+                                let mut received = 0;
+                                while receiver.recv().await.is_some() {
+                                    received += 1;
+                                }
+                                received
+                            })
+                        })
+                        .collect();
+                    let mut received = Vec::new();
+                    for receiver in receivers {
+                        received.push(receiver.await.unwrap());
+                    }
+                    received
+                },
+            );
+            let _ = done.send(received);
+        });
+
+        let received = finished
+            .recv_timeout(Duration::from_secs(60))
+            .expect("Channel should not deadlock");
+        assert_eq!(received, [VALUES; CHANNELS]);
+    }
+}
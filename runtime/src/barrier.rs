@@ -0,0 +1,253 @@
+//! A barrier with numbered generations and a deterministic leader.
+//!
+//! Tokio's `Barrier` makes whichever task arrives last the leader of its
+//! generation, so which task does the once-per-phase work depends on the
+//! schedule. A [`DeterministicBarrier`] instead makes the arriving task with
+//! the lowest id the leader, which is the same task however the arrivals
+//! were interleaved, and records each generation's leader.
+//!
+//! [`phased_computation`] shows the use: workers finish one phase, meet at
+//! the barrier, and only then start the next, with each phase's leader
+//! recording that the phase is complete. It is written against the runtime
+//! traits and runs unchanged on either runtime.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{
+    spawn::{TaskInfo, TaskSpawner},
+    trace::EventLog,
+};
+
+/// What [`DeterministicBarrier::wait`] resolves to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BarrierWaitResult {
+    /// The generation that was released, counting from 0.
+    pub generation: u64,
+    /// Whether this task had the lowest id in its generation.
+    pub is_leader: bool,
+}
+
+#[derive(Default)]
+struct Slot {
+    result: Option<BarrierWaitResult>,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct State {
+    generation: u64,
+    arrived: Vec<(TaskInfo, Arc<Mutex<Slot>>)>,
+    leaders: Vec<TaskInfo>,
+}
+
+pub struct DeterministicBarrier {
+    parties: usize,
+    state: Mutex<State>,
+    log: Option<EventLog>,
+}
+
+impl DeterministicBarrier {
+    /// A barrier releasing every `parties` arrivals.
+    pub fn new(parties: usize) -> Self {
+        assert!(parties > 0, "A barrier should wait for at least one task");
+        Self {
+            parties,
+            state: Mutex::default(),
+            log: None,
+        }
+    }
+
+    /// Also record each generation's release into `log`, under the leader's
+    /// label.
+    pub fn with_log(mut self, log: EventLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Arrive at the barrier and wait for the rest of the generation. The
+    /// arrival counts from this call, whether or not the future is awaited.
+    pub fn wait(&self, task: &TaskInfo) -> BarrierWait {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let mut state = self.state.lock().unwrap();
+        state.arrived.push((task.clone(), slot.clone()));
+        if state.arrived.len() == self.parties {
+            self.release(&mut state);
+        }
+        BarrierWait { slot }
+    }
+
+    /// The leader of every generation released so far.
+    pub fn leaders(&self) -> Vec<TaskInfo> {
+        self.state.lock().unwrap().leaders.clone()
+    }
+
+    fn release(&self, state: &mut State) {
+        let generation = state.generation;
+        let arrived = std::mem::take(&mut state.arrived);
+        let leader = arrived
+            .iter()
+            .map(|(task, _)| task)
+            .min_by_key(|task| task.id)
+            .expect("A generation has at least one task")
+            .clone();
+        for (task, slot) in arrived {
+            let mut slot = slot.lock().unwrap();
+            slot.result = Some(BarrierWaitResult {
+                generation,
+                is_leader: task.id == leader.id,
+            });
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+        if let Some(log) = &self.log {
+            log.record(leader.label(), format!("leads generation {}", generation));
+        }
+        state.leaders.push(leader);
+        state.generation += 1;
+    }
+}
+
+/// The future returned by [`DeterministicBarrier::wait`].
+pub struct BarrierWait {
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl Future for BarrierWait {
+    type Output = BarrierWaitResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<BarrierWaitResult> {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.result {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Run `workers` tasks through `phases` phases of uneven work, meeting at a
+/// barrier between phases, and return each phase's leader. Worker `i`
+/// spends `i + 1` ms on even phases and `workers - i` ms on odd ones, so the
+/// order they finish in flips from phase to phase.
+pub async fn phased_computation<S: Spawner + Clock>(
+    context: S,
+    workers: usize,
+    phases: usize,
+    log: EventLog,
+) -> Vec<TaskInfo> {
+    let barrier = Arc::new(DeterministicBarrier::new(workers).with_log(log.clone()));
+    let spawner = TaskSpawner::new(context, log);
+    let handles: Vec<_> = (0..workers)
+        .map(|worker| {
+            let barrier = barrier.clone();
+            spawner.spawn_named(format!("worker-{}", worker), move |scope| async move {
+                for phase in 0..phases {
+                    let work = if phase % 2 == 0 {
+                        worker + 1
+                    } else {
+                        workers - worker
+                    };
+                    scope
+                        .context()
+                        .sleep(Duration::from_millis(work as u64))
+                        .await;
+                    scope.record(format!("finished phase {}", phase));
+                    if barrier.wait(scope.info()).await.is_leader {
+                        scope.record(format!("phase {} complete", phase));
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.expect("Worker should run to completion");
+    }
+    barrier.leaders()
+}
+
+#[cfg(all(test, feature = "tokio-backend", feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+        tokio::Runner as TokioRunner,
+    };
+
+    use super::*;
+    use crate::{spawn::TaskId, trace::TraceEvent};
+
+    fn phase_of(event: &TraceEvent) -> Option<usize> {
+        event
+            .message
+            .strip_prefix("finished phase ")
+            .map(|phase| phase.parse().unwrap())
+    }
+
+    /// Every worker finishes a phase before any finishes the next, and the
+    /// lowest-id worker leads every generation.
+    fn check(events: &[TraceEvent], leaders: &[TaskInfo], phases: usize) {
+        assert_eq!(leaders.len(), phases);
+        assert!(leaders.iter().all(|leader| leader.id == TaskId(0)));
+        let finished: Vec<_> = events.iter().filter_map(phase_of).collect();
+        assert_eq!(finished.len(), 4 * phases);
+        assert!(finished.is_sorted());
+    }
+
+    /// Four workers through three phases on both runtimes; the deterministic
+    /// run also replays exactly.
+    #[test]
+    fn test_phased_computation() {
+        let run = |seed| {
+            let log = EventLog::new();
+            let leaders = DeterministicRunner::new(Config::default().with_seed(seed))
+                .start(|context| phased_computation(context, 4, 3, log.clone()));
+            (leaders, log.events())
+        };
+        let (leaders, events) = run(0);
+        check(&events, &leaders, 3);
+        let complete = events
+            .iter()
+            .position(|event| event.message == "phase 0 complete")
+            .unwrap();
+        assert_eq!(events[complete - 1].message, "leads generation 0");
+        assert_eq!(run(0).1, events);
+
+        let log = EventLog::new();
+        let leaders =
+            TokioRunner::default().start(|context| phased_computation(context, 4, 3, log.clone()));
+        check(&log.events(), &leaders, 3);
+    }
+
+    /// Generations count up, and the barrier resets after each release.
+    #[test]
+    fn test_generations() {
+        let barrier = DeterministicBarrier::new(1);
+        let task = TaskInfo {
+            id: TaskId(7),
+            name: None,
+        };
+        DeterministicRunner::new(Config::default()).start(|_| async {
+            for generation in 0..3 {
+                assert_eq!(
+                    barrier.wait(&task).await,
+                    BarrierWaitResult {
+                        generation,
+                        is_leader: true
+                    }
+                );
+            }
+        });
+        assert_eq!(barrier.leaders().len(), 3);
+    }
+}
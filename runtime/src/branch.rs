@@ -0,0 +1,175 @@
+//! Exploring alternative futures from one simulation snapshot.
+//!
+//! A [`Snapshot`] fixes everything up to one instant. What happens next
+//! depends on the seed that draws the latencies from there on, and on the
+//! faults the network suffers. A [`Branch`] picks both, and [`explore`]
+//! restores the snapshot once per branch, each on a deterministic runtime of
+//! its own, and runs the same continuation on every one. "What if the
+//! partition happened now?" becomes one branch with the partition and one
+//! without, sharing a prefix that only ran once.
+
+use std::future::Future;
+
+use commonware_runtime::{
+    Runner,
+    deterministic::{Config, Context, Runner as DeterministicRunner},
+};
+
+use crate::{
+    rng::DeterministicRng,
+    simnet::{NodeId, SimNet},
+    snapshot::Snapshot,
+    trace::EventLog,
+};
+
+/// How one continuation differs from the others.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branch {
+    /// Seeds the runtime and the network's latencies from the snapshot on.
+    pub seed: u64,
+    /// Cuts the network into these groups at the snapshot, if set.
+    pub partition: Option<Vec<Vec<NodeId>>>,
+    /// The probability of losing each message sent from the snapshot on.
+    pub loss: f64,
+}
+
+impl Branch {
+    /// A branch with no faults.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            partition: None,
+            loss: 0.0,
+        }
+    }
+
+    pub fn with_partition(mut self, groups: Vec<Vec<NodeId>>) -> Self {
+        self.partition = Some(groups);
+        self
+    }
+
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    /// Restore `snapshot` on `context` with this branch's seed and faults.
+    pub fn restore<M: Send + 'static>(
+        &self,
+        context: Context,
+        snapshot: Snapshot<M>,
+        log: EventLog,
+    ) -> SimNet<Context, M> {
+        let net = SimNet::restore(
+            context,
+            snapshot,
+            DeterministicRng::derived(self.seed, "branch-network", 0),
+            log,
+        )
+        .with_loss(self.loss);
+        if let Some(groups) = &self.partition {
+            net.partition(groups);
+        }
+        net
+    }
+}
+
+/// Run `continuation` once per branch on a network restored from
+/// `snapshot`, each under a deterministic runtime seeded with the branch's
+/// seed, logging to `log`. Returns each branch's result, in branch order.
+pub fn explore<M, F, Fut>(
+    snapshot: &Snapshot<M>,
+    branches: &[Branch],
+    log: impl Fn(&Branch) -> EventLog,
+    continuation: F,
+) -> Vec<Fut::Output>
+where
+    M: Clone + Send + 'static,
+    F: Fn(Context, SimNet<Context, M>) -> Fut,
+    Fut: Future + Send + 'static,
+    Fut::Output: Send + 'static,
+{
+    branches
+        .iter()
+        .map(|branch| {
+            let log = log(branch);
+            DeterministicRunner::new(Config::default().with_seed(branch.seed)).start(|context| {
+                let net = branch.restore(context.clone(), snapshot.clone(), log);
+                continuation(context, net)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{parallel_determinism::types::Value, snapshot::relay};
+
+    fn prefix() -> Snapshot<Value> {
+        DeterministicRunner::new(Config::default().with_seed(3)).start(|context| async move {
+            let net = SimNet::new(
+                context.clone(),
+                4,
+                DeterministicRng::new(3),
+                EventLog::new(),
+            );
+            relay(&context, net, Duration::from_millis(40)).await
+        })
+    }
+
+    fn tokens(snapshot: &Snapshot<Value>) -> usize {
+        snapshot.inboxes.iter().flatten().count() + snapshot.in_flight.len()
+    }
+
+    /// Branches share the prefix and diverge after it; the same branch
+    /// always continues the same way.
+    #[test]
+    fn test_branches_diverge_after_prefix() {
+        let snapshot = prefix();
+        let branches = [Branch::new(1), Branch::new(2), Branch::new(1)];
+        let ends = explore(
+            &snapshot,
+            &branches,
+            |_| EventLog::new(),
+            |context, net| async move { relay(&context, net, Duration::from_millis(120)).await },
+        );
+
+        assert!(
+            ends.iter()
+                .all(|end| end.events.starts_with(&snapshot.events) && tokens(end) == 1)
+        );
+        assert_ne!(ends[0], ends[1]);
+        assert_eq!(ends[0], ends[2]);
+    }
+
+    /// Partitioning the ring at the snapshot stops the token at the cut,
+    /// while the unpartitioned branch keeps it moving.
+    #[test]
+    fn test_partition_stops_the_token() {
+        let snapshot = prefix();
+        let branches = [
+            Branch::new(1),
+            Branch::new(1).with_partition(vec![vec![0, 1], vec![2, 3]]),
+        ];
+        let logs = [EventLog::new(), EventLog::new()];
+        let ends = explore(
+            &snapshot,
+            &branches,
+            |branch| logs[usize::from(branch.partition.is_some())].clone(),
+            |context, net| async move { relay(&context, net, Duration::from_millis(120)).await },
+        );
+
+        assert_eq!(tokens(&ends[0]), 1);
+        assert_eq!(tokens(&ends[1]), 0);
+        assert!(ends[1].events.len() < ends[0].events.len());
+        assert!(
+            logs[1]
+                .events()
+                .iter()
+                .any(|event| event.message.contains("partition"))
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Recording a run's external inputs so it can be replayed without them.
+//!
+//! A seed makes a run deterministic only as long as everything it reads comes
+//! from the seed. A run that reads a file, takes a payload from outside the
+//! simulation or draws from OS randomness depends on inputs the seed knows
+//! nothing about, and re-running it later, or elsewhere, reads different
+//! ones. A [`Capture`] sits between the run and those inputs. While
+//! recording, it performs each operation for real and appends the result to
+//! a [`Tape`]; while replaying, it hands back the taped results in order and
+//! touches nothing outside. Save the tape with the run's seed and the run
+//! can be repeated exactly after the file is gone and the randomness spent.
+//!
+//! A replay that asks for an input other than the one taped next has gone
+//! down a different path than the recording, and gets a
+//! [`CaptureError::Diverged`] naming both rather than a silently wrong value.
+//!
+//! A tape is written in the same style as the [binary trace
+//! format](crate::tracefile): the magic bytes `DCAP`, a version byte, then per
+//! input its kind as one byte and its key and bytes, each prefixed with its
+//! length as a varint.
+
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use rand::RngCore;
+
+use crate::tracefile::{read_bytes, write_varint};
+
+const MAGIC: &[u8; 4] = b"DCAP";
+const VERSION: u8 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputKind {
+    File,
+    Payload,
+    Draw,
+}
+
+impl InputKind {
+    fn tag(self) -> u8 {
+        match self {
+            Self::File => 0,
+            Self::Payload => 1,
+            Self::Draw => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::File),
+            1 => Some(Self::Payload),
+            2 => Some(Self::Draw),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for InputKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File => write!(f, "file"),
+            Self::Payload => write!(f, "payload"),
+            Self::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+/// One input as the recording saw it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Input {
+    pub kind: InputKind,
+    /// What was asked for: a path, a payload's source, a stream's name.
+    pub key: String,
+    pub bytes: Vec<u8>,
+}
+
+impl Input {
+    fn describe(kind: InputKind, key: &str) -> String {
+        format!("{} {}", kind, key)
+    }
+}
+
+/// The inputs of one run, in the order it asked for them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tape {
+    pub inputs: Vec<Input>,
+}
+
+impl Tape {
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        for input in &self.inputs {
+            out.write_all(&[input.kind.tag()])?;
+            write_varint(&mut out, input.key.len() as u64)?;
+            out.write_all(input.key.as_bytes())?;
+            write_varint(&mut out, input.bytes.len() as u64)?;
+            out.write_all(&input.bytes)?;
+        }
+        out.flush()
+    }
+
+    pub fn read_from(mut input: impl Read) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut header = [0; 5];
+        input.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(invalid("not a capture tape"));
+        }
+        let mut inputs = Vec::new();
+        loop {
+            let mut tag = [0];
+            if input.read(&mut tag)? == 0 {
+                return Ok(Self { inputs });
+            }
+            let kind = InputKind::from_tag(tag[0]).ok_or(invalid("unknown input kind"))?;
+            let key = String::from_utf8(read_bytes(&mut input)?)
+                .map_err(|_| invalid("input key is not UTF-8"))?;
+            let bytes = read_bytes(&mut input)?;
+            inputs.push(Input { kind, key, bytes });
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    /// Performing the operation itself failed while recording.
+    Io(io::Error),
+    /// The replay asked for a different input than the recording had next.
+    Diverged {
+        index: usize,
+        recorded: String,
+        requested: String,
+    },
+    /// The replay asked for more inputs than the recording had.
+    Exhausted { index: usize, requested: String },
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Diverged {
+                index,
+                recorded,
+                requested,
+            } => write!(
+                f,
+                "replay diverged at input {}: recorded {}, requested {}",
+                index, recorded, requested
+            ),
+            Self::Exhausted { index, requested } => write!(
+                f,
+                "replay requested {} as input {}, past the end of the tape",
+                requested, index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<io::Error> for CaptureError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+enum Mode {
+    Record,
+    /// The position of the next input to hand back.
+    Replay(usize),
+}
+
+struct State {
+    mode: Mode,
+    tape: Tape,
+}
+
+/// Records or replays external inputs. Clones share the tape, so the inputs
+/// of every task holding one end up on it in the order they were asked for.
+#[derive(Clone)]
+pub struct Capture {
+    state: Arc<Mutex<State>>,
+}
+
+impl Capture {
+    /// Perform every operation and tape its result.
+    pub fn recording() -> Self {
+        Self::with(Mode::Record, Tape::default())
+    }
+
+    /// Hand back the results on `tape` instead of performing anything.
+    pub fn replaying(tape: Tape) -> Self {
+        Self::with(Mode::Replay(0), tape)
+    }
+
+    fn with(mode: Mode, tape: Tape) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State { mode, tape })),
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.state.lock().unwrap().mode, Mode::Replay(_))
+    }
+
+    /// The inputs taped so far.
+    pub fn tape(&self) -> Tape {
+        self.state.lock().unwrap().tape.clone()
+    }
+
+    /// The contents of the file at `path`.
+    pub fn read_file(&self, path: impl AsRef<Path>) -> Result<Vec<u8>, CaptureError> {
+        let path = path.as_ref();
+        self.input(InputKind::File, &path.display().to_string(), || {
+            fs::read(path)
+        })
+    }
+
+    /// A payload from outside the simulation, such as a message from a real
+    /// peer, labelled `source`; `receive` gets it while recording.
+    pub fn payload(
+        &self,
+        source: &str,
+        receive: impl FnOnce() -> io::Result<Vec<u8>>,
+    ) -> Result<Vec<u8>, CaptureError> {
+        self.input(InputKind::Payload, source, receive)
+    }
+
+    /// The next number from `rng`, a stream labelled `stream`. Replaying
+    /// leaves `rng` alone.
+    pub fn draw(&self, stream: &str, rng: &mut impl RngCore) -> Result<u64, CaptureError> {
+        let bytes = self.input(InputKind::Draw, stream, || {
+            Ok(rng.next_u64().to_le_bytes().to_vec())
+        })?;
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "taped draw is not 8 bytes"))?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn input(
+        &self,
+        kind: InputKind,
+        key: &str,
+        perform: impl FnOnce() -> io::Result<Vec<u8>>,
+    ) -> Result<Vec<u8>, CaptureError> {
+        let mut state = self.state.lock().unwrap();
+        let State { mode, tape } = &mut *state;
+        match mode {
+            Mode::Record => {
+                let bytes = perform()?;
+                tape.inputs.push(Input {
+                    kind,
+                    key: key.to_string(),
+                    bytes: bytes.clone(),
+                });
+                Ok(bytes)
+            }
+            Mode::Replay(next) => {
+                let index = *next;
+                let requested = Input::describe(kind, key);
+                let Some(input) = tape.inputs.get(index) else {
+                    return Err(CaptureError::Exhausted { index, requested });
+                };
+                if input.kind != kind || input.key != key {
+                    return Err(CaptureError::Diverged {
+                        index,
+                        recorded: Input::describe(input.kind, &input.key),
+                        requested,
+                    });
+                }
+                *next += 1;
+                Ok(input.bytes.clone())
+            }
+        }
+    }
+}
+
+/// Read the text at `path` through `capture` and pick `count` of its words
+/// at positions drawn from `rng`, with replacement.
+pub fn sample_words(
+    capture: &Capture,
+    path: impl AsRef<Path>,
+    count: usize,
+    rng: &mut impl RngCore,
+) -> Result<Vec<String>, CaptureError> {
+    let text = capture.read_file(path)?;
+    let text = String::from_utf8_lossy(&text);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut sample = Vec::with_capacity(count);
+    for _ in 0..count {
+        let position = capture.draw("sample", rng)? as usize % words.len().max(1);
+        sample.extend(words.get(position).map(|word| word.to_string()));
+    }
+    Ok(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    /// A generator seeded from the wall clock, standing in for OS
+    /// randomness that a later run cannot reproduce.
+    fn unseeded() -> StdRng {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        StdRng::seed_from_u64(nanos as u64)
+    }
+
+    /// A replay from the saved tape returns what the recording saw after
+    /// the file is deleted and with different randomness.
+    #[test]
+    fn test_replay_without_the_inputs() {
+        let path = std::env::temp_dir().join(format!("capture-{}.txt", std::process::id()));
+        fs::write(&path, "the quick brown fox jumps over the lazy dog").unwrap();
+
+        let recording = Capture::recording();
+        let sample = sample_words(&recording, &path, 5, &mut unseeded()).unwrap();
+        let mut saved = Vec::new();
+        recording.tape().write_to(&mut saved).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let tape = Tape::read_from(saved.as_slice()).unwrap();
+        assert_eq!(tape, recording.tape());
+        assert_eq!(tape.inputs.len(), 1 + 5);
+        let replay = Capture::replaying(tape);
+        assert!(replay.is_replaying());
+        assert_eq!(
+            sample_words(&replay, &path, 5, &mut unseeded()).unwrap(),
+            sample
+        );
+        assert!(matches!(
+            sample_words(&Capture::recording(), &path, 5, &mut unseeded()),
+            Err(CaptureError::Io(_))
+        ));
+    }
+
+    /// A tape whose length runs past its end is cut off, not a reason to
+    /// allocate what the length claims.
+    #[test]
+    fn test_corrupt_length() {
+        let recording = Capture::recording();
+        recording.payload("node 1", || Ok(b"hi".to_vec())).unwrap();
+        let mut saved = Vec::new();
+        recording.tape().write_to(&mut saved).unwrap();
+
+        let mut corrupt = saved[..6].to_vec();
+        write_varint(&mut corrupt, u64::MAX).unwrap();
+        corrupt.extend_from_slice(b"node 1");
+        let error = Tape::read_from(corrupt.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    /// Asking for inputs in another order, or more of them, than were
+    /// recorded is reported, not papered over.
+    #[test]
+    fn test_divergence_reported() {
+        let recording = Capture::recording();
+        recording
+            .payload("node 1", || Ok(b"hello".to_vec()))
+            .unwrap();
+
+        let replay = Capture::replaying(recording.tape());
+        let error = replay.draw("sample", &mut unseeded()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "replay diverged at input 0: recorded payload node 1, requested draw sample"
+        );
+        assert_eq!(
+            replay.payload("node 1", || unreachable!()).unwrap(),
+            b"hello"
+        );
+        assert!(matches!(
+            replay.payload("node 1", || unreachable!()),
+            Err(CaptureError::Exhausted { index: 1, .. })
+        ));
+    }
+}
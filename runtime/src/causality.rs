@@ -0,0 +1,243 @@
+//! The happens-before order of a run, recovered from its trace.
+//!
+//! An [`EventLog`] is a total order, but most of it is incidental: two tasks
+//! that never interacted could have had their events interleaved any other
+//! way. A [`CausalityGraph`] keeps only the order that could not have been
+//! different. Each task's events follow one another, and a wake recorded by
+//! a [`WakeTracer`](crate::wake::WakeTracer) follows the last event of the
+//! task that issued it. Event `a` causally precedes event `b` when a path of
+//! those edges leads from one to the other; when neither reaches the other
+//! they were concurrent, and the log's order between them was the
+//! scheduler's choice.
+//!
+//! Events are referred to by their index in the log.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    trace::{EventLog, TraceEvent},
+    wake::waker_of,
+};
+
+/// Why one event must come before another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Both are the same task's, in the order it recorded them.
+    Program,
+    /// The later event is a wake issued by the earlier event's task.
+    Wake,
+}
+
+/// How two events are ordered by causality.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Same,
+    Before,
+    After,
+    Concurrent,
+}
+
+pub struct CausalityGraph {
+    events: Vec<TraceEvent>,
+    /// For each event, the events that directly precede it.
+    causes: Vec<Vec<(usize, EdgeKind)>>,
+}
+
+impl CausalityGraph {
+    pub fn new(events: Vec<TraceEvent>) -> Self {
+        let mut last: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut causes = Vec::with_capacity(events.len());
+        for (index, event) in events.iter().enumerate() {
+            let mut direct = Vec::new();
+            if let Some(&previous) = last.get(event.task.as_str()) {
+                direct.push((previous, EdgeKind::Program));
+            }
+            if let Some(waker) = waker_of(event)
+                && waker != event.task
+                && let Some(&cause) = last.get(waker)
+            {
+                direct.push((cause, EdgeKind::Wake));
+            }
+            last.insert(event.task.as_str(), index);
+            causes.push(direct);
+        }
+        Self { events, causes }
+    }
+
+    pub fn from_log(log: &EventLog) -> Self {
+        Self::new(log.events())
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Every edge as `(from, to, kind)`, ordered by `to`.
+    pub fn edges(&self) -> Vec<(usize, usize, EdgeKind)> {
+        self.causes
+            .iter()
+            .enumerate()
+            .flat_map(|(to, causes)| causes.iter().map(move |&(from, kind)| (from, to, kind)))
+            .collect()
+    }
+
+    /// Whether event `a` causally precedes event `b`. Edges only point
+    /// forward in the log, so the search never looks before `a`.
+    pub fn happens_before(&self, a: usize, b: usize) -> bool {
+        if a >= b {
+            return false;
+        }
+        let mut seen = vec![false; b + 1];
+        let mut stack = vec![b];
+        while let Some(event) = stack.pop() {
+            for &(cause, _) in &self.causes[event] {
+                if cause == a {
+                    return true;
+                }
+                if cause > a && !seen[cause] {
+                    seen[cause] = true;
+                    stack.push(cause);
+                }
+            }
+        }
+        false
+    }
+
+    pub fn relation(&self, a: usize, b: usize) -> Relation {
+        if a == b {
+            Relation::Same
+        } else if self.happens_before(a, b) {
+            Relation::Before
+        } else if self.happens_before(b, a) {
+            Relation::After
+        } else {
+            Relation::Concurrent
+        }
+    }
+
+    /// The first event recorded by `task` with `message`, for looking
+    /// events up without counting through the log.
+    pub fn position(&self, task: &str, message: &str) -> Option<usize> {
+        self.events
+            .iter()
+            .position(|event| event.task == task && event.message == message)
+    }
+
+    /// The graph in Graphviz DOT, one column per task with its events top
+    /// to bottom, and wakes drawn as dashed edges between columns.
+    pub fn to_dot(&self) -> String {
+        let mut tasks: Vec<&str> = Vec::new();
+        for event in &self.events {
+            if !tasks.contains(&event.task.as_str()) {
+                tasks.push(&event.task);
+            }
+        }
+        let mut dot = String::from("digraph causality {\n    rankdir=TB;\n");
+        dot.push_str("    node [shape=box];\n");
+        for (lane, task) in tasks.iter().enumerate() {
+            dot.push_str(&format!(
+                "    subgraph cluster_{} {{\n        label=\"{}\";\n",
+                lane,
+                task.replace('"', "\\\"")
+            ));
+            for (index, event) in self.events.iter().enumerate() {
+                if event.task == *task {
+                    dot.push_str(&format!(
+                        "        e{} [label=\"{}\"];\n",
+                        index,
+                        event.message.replace('"', "\\\"")
+                    ));
+                }
+            }
+            dot.push_str("    }\n");
+        }
+        for (from, to, kind) in self.edges() {
+            let style = match kind {
+                EdgeKind::Program => "",
+                EdgeKind::Wake => " [style=dashed]",
+            };
+            dot.push_str(&format!("    e{} -> e{}{};\n", from, to, style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::wake::wake_causality;
+
+    fn event(task: &str, message: &str) -> TraceEvent {
+        TraceEvent {
+            task: task.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Program order and wakes are the only edges; events of tasks that
+    /// never woke each other stay concurrent whatever the log order.
+    #[test]
+    fn test_relations() {
+        let graph = CausalityGraph::new(vec![
+            event("a", "start"),
+            event("b", "start"),
+            event("a", "send"),
+            event("b", "woken by channel from a"),
+            event("c", "start"),
+            event("b", "done"),
+        ]);
+
+        assert_eq!(
+            graph.edges(),
+            [
+                (0, 2, EdgeKind::Program),
+                (1, 3, EdgeKind::Program),
+                (2, 3, EdgeKind::Wake),
+                (3, 5, EdgeKind::Program),
+            ]
+        );
+        assert_eq!(graph.relation(0, 5), Relation::Before);
+        assert_eq!(graph.relation(5, 2), Relation::After);
+        assert_eq!(graph.relation(0, 1), Relation::Concurrent);
+        assert_eq!(graph.relation(4, 5), Relation::Concurrent);
+        assert_eq!(graph.relation(3, 3), Relation::Same);
+    }
+
+    /// In the wake demo the consumer's progress follows the producer's
+    /// through both wakes, while their first polls were concurrent.
+    #[test]
+    fn test_wake_causality_graph() {
+        let log = EventLog::new();
+        DeterministicRunner::new(Config::default().with_seed(0)).start(|context| {
+            let log = log.clone();
+            async move { wake_causality(&context, &log).await }
+        });
+        let graph = CausalityGraph::from_log(&log);
+        let at = |task, message| graph.position(task, message).unwrap();
+
+        assert_eq!(
+            graph.relation(at("producer", "sending"), at("consumer", "received")),
+            Relation::Before
+        );
+        assert_eq!(
+            graph.relation(
+                at("consumer", "acquired the lock"),
+                at("producer", "releasing the lock")
+            ),
+            Relation::After
+        );
+        assert_eq!(
+            graph.relation(at("producer", "polled"), at("consumer", "polled")),
+            Relation::Concurrent
+        );
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph causality {"));
+        assert_eq!(dot.matches("[style=dashed]").count(), 2);
+    }
+}
@@ -0,0 +1,205 @@
+//! Batch processing that survives a crash by checkpointing.
+//!
+//! [`count_in_batches`] counts the words of a [`Corpus`] in batches of
+//! consecutive words. After each batch it stages the updated counts of the
+//! words it saw, plus the number of batches done, and commits them to a
+//! [`Storage`] in one step, so a checkpoint is either wholly on the simulated
+//! disk or not at all. A run can be told to crash partway through a batch,
+//! after counting but before committing, which loses that batch's work the
+//! way a real crash would. Running again against the same storage picks up
+//! at the last checkpoint, redoes the lost batch, and ends with the same
+//! counts as a run that never crashed. With [`LatencyStorage`] every
+//! checkpoint costs virtual time, so under the deterministic runtime the
+//! crash, the restart and every checkpoint land at the same instants on every
+//! run.
+//!
+//! [`LatencyStorage`]: crate::parallel_determinism::state::LatencyStorage
+
+use std::collections::BTreeMap;
+
+use commonware_runtime::Clock;
+
+use crate::{
+    corpus::Corpus,
+    parallel_determinism::state::{Storage, Value},
+    trace::EventLog,
+};
+
+/// Key holding the number of batches committed so far.
+pub const PROGRESS_KEY: &str = "checkpoint/batches";
+/// Prefix of the keys holding each word's count so far.
+pub const COUNT_PREFIX: &str = "count/";
+
+/// The counts committed in `storage`, by word.
+pub fn checkpointed_counts(storage: &impl Storage) -> BTreeMap<String, Value> {
+    storage
+        .snapshot()
+        .into_iter()
+        .filter_map(|(key, count)| Some((key.strip_prefix(COUNT_PREFIX)?.to_string(), count)))
+        .collect()
+}
+
+/// Count every word of `corpus` in batches of `batch_words` words,
+/// checkpointing to `storage` after each batch and resuming from whatever
+/// checkpoint `storage` already holds. With `crash_in` set, stop in the
+/// middle of that batch, before its checkpoint, and return `None`.
+/// Otherwise return the finished counts.
+///
+/// Records `resumed at batch N`, `checkpoint N` and `crashed in batch N` in
+/// `log` under `batcher`.
+pub async fn count_in_batches(
+    context: &impl Clock,
+    corpus: &Corpus,
+    batch_words: usize,
+    storage: &impl Storage,
+    crash_in: Option<usize>,
+    log: &EventLog,
+) -> Option<BTreeMap<String, Value>> {
+    assert!(batch_words > 0, "Batches must hold at least one word");
+    let batches = corpus.len().div_ceil(batch_words);
+    let resume = storage.get(context, PROGRESS_KEY).await.unwrap_or(0) as usize;
+    if resume > 0 {
+        log.record("batcher", format!("resumed at batch {}", resume));
+    }
+
+    let mut counts = checkpointed_counts(storage);
+    for batch in resume..batches {
+        let start = batch * batch_words;
+        let words = corpus.words_in(start..(start + batch_words).min(corpus.len()));
+        let mut seen = Vec::new();
+        for word in words {
+            *counts.entry(word.to_string()).or_default() += 1;
+            seen.push(word);
+        }
+        if crash_in == Some(batch) {
+            log.record("batcher", format!("crashed in batch {}", batch));
+            return None;
+        }
+
+        seen.sort_unstable();
+        seen.dedup();
+        for word in seen {
+            storage.put(format!("{}{}", COUNT_PREFIX, word), counts[word]);
+        }
+        storage.put(PROGRESS_KEY.to_string(), batch as Value + 1);
+        storage.commit(context).await;
+        log.record("batcher", format!("checkpoint {}", batch + 1));
+    }
+    Some(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::{
+        parallel_determinism::state::{LatencyStorage, MemoryStorage},
+        tasks::read_file,
+        trace::TraceEvent,
+        verify::verify_word_counts,
+    };
+
+    const BATCH_WORDS: usize = 100;
+
+    /// Crash in batch `crash_in`, then restart against the same disk. Returns
+    /// the finished counts, the events and the virtual time spent.
+    fn crash_and_restart(
+        seed: u64,
+        crash_in: usize,
+    ) -> (BTreeMap<String, Value>, Vec<TraceEvent>, Duration) {
+        let corpus = read_file();
+        let log = EventLog::new();
+        let disk = LatencyStorage::new(MemoryStorage::new(), Duration::from_millis(2));
+        let (counts, elapsed) =
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+                let log = log.clone();
+                async move {
+                    let start = context.current();
+                    let crashed = count_in_batches(
+                        &context,
+                        &corpus,
+                        BATCH_WORDS,
+                        &disk,
+                        Some(crash_in),
+                        &log,
+                    )
+                    .await;
+                    assert_eq!(crashed, None);
+                    let counts =
+                        count_in_batches(&context, &corpus, BATCH_WORDS, &disk, None, &log)
+                            .await
+                            .expect("Restart should finish");
+                    (counts, context.current().duration_since(start).unwrap())
+                }
+            });
+        (counts, log.events(), elapsed)
+    }
+
+    /// A run that crashed and restarted ends with the counts of a run that
+    /// never crashed, and those are the corpus's real counts.
+    #[test]
+    fn test_restart_matches_uninterrupted() {
+        let uninterrupted =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                count_in_batches(
+                    &context,
+                    &read_file(),
+                    BATCH_WORDS,
+                    &MemoryStorage::new(),
+                    None,
+                    &EventLog::new(),
+                )
+                .await
+                .unwrap()
+            });
+        let (restarted, _, _) = crash_and_restart(0, 3);
+
+        assert_eq!(restarted, uninterrupted);
+        assert_eq!(
+            verify_word_counts(
+                &read_file(),
+                restarted
+                    .iter()
+                    .map(|(word, &count)| (word, count as usize))
+            ),
+            Ok(())
+        );
+    }
+
+    /// The restart picks up at the last checkpoint and redoes only the batch
+    /// the crash lost.
+    #[test]
+    fn test_restart_resumes_from_checkpoint() {
+        let (_, events, _) = crash_and_restart(0, 3);
+        let messages: Vec<_> = events.iter().map(|event| event.message.as_str()).collect();
+
+        assert_eq!(
+            messages[..6],
+            [
+                "checkpoint 1",
+                "checkpoint 2",
+                "checkpoint 3",
+                "crashed in batch 3",
+                "resumed at batch 3",
+                "checkpoint 4",
+            ]
+        );
+        let batches = read_file().len().div_ceil(BATCH_WORDS);
+        assert_eq!(
+            messages.last(),
+            Some(&format!("checkpoint {}", batches).as_str())
+        );
+    }
+
+    /// Crash, restart and every checkpoint happen identically on every run.
+    #[test]
+    fn test_crash_and_restart_repeat() {
+        assert_eq!(crash_and_restart(5, 2), crash_and_restart(5, 2));
+    }
+}
@@ -0,0 +1,154 @@
+//! Map and set types whose iteration order is part of their contract.
+//!
+//! `HashMap` and `HashSet` iterate in an order that depends on a per-process
+//! random hash seed, so any workload that loops over one can take a different
+//! path on every run, behind the deterministic scheduler's back. [`DMap`] and
+//! [`DSet`] are B-tree backed: iteration is always in key order, on every
+//! machine and every run. Use them for any state a workload iterates.
+//!
+//! Both dereference to the underlying `BTreeMap`/`BTreeSet`, so the full std
+//! API is available; the wrapper exists to make the ordering guarantee
+//! visible in type signatures.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, btree_map, btree_set},
+    ops::{Deref, DerefMut},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DMap<K: Ord, V>(BTreeMap<K, V>);
+
+impl<K: Ord, V> DMap<K, V> {
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    pub fn into_inner(self) -> BTreeMap<K, V> {
+        self.0
+    }
+}
+
+impl<K: Ord, V> Default for DMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> Deref for DMap<K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K: Ord, V> DerefMut for DMap<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for DMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<K: Ord, V> IntoIterator for DMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = btree_map::IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a DMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = btree_map::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DSet<T: Ord>(BTreeSet<T>);
+
+impl<T: Ord> DSet<T> {
+    pub fn new() -> Self {
+        Self(BTreeSet::new())
+    }
+
+    pub fn into_inner(self) -> BTreeSet<T> {
+        self.0
+    }
+}
+
+impl<T: Ord> Default for DSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Deref for DSet<T> {
+    type Target = BTreeSet<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Ord> DerefMut for DSet<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T: Ord> FromIterator<T> for DSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<T: Ord> IntoIterator for DSet<T> {
+    type Item = T;
+    type IntoIter = btree_set::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a DSet<T> {
+    type Item = &'a T;
+    type IntoIter = btree_set::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Iteration follows key order regardless of insertion order.
+    #[test]
+    fn test_iteration_is_key_ordered() {
+        let map: DMap<_, _> = [("carol", 3), ("alice", 1), ("bob", 2)]
+            .into_iter()
+            .collect();
+        let mut set = DSet::new();
+        for id in [5, 1, 3] {
+            set.insert(id);
+        }
+
+        assert_eq!(
+            map.keys().copied().collect::<Vec<_>>(),
+            ["alice", "bob", "carol"]
+        );
+        assert_eq!((&set).into_iter().copied().collect::<Vec<_>>(), [1, 3, 5]);
+        assert_eq!(map["bob"], 2);
+    }
+}
@@ -0,0 +1,105 @@
+//! tokio-console support for the Tokio-backed demos.
+//!
+//! Built with the `console` feature. [`init`] installs `console-subscriber`,
+//! which serves task instrumentation to `tokio-console` on its default
+//! address (`127.0.0.1:6669`). Tokio only emits that instrumentation when
+//! compiled with `--cfg tokio_unstable`, so run with
+//! `RUSTFLAGS="--cfg tokio_unstable"` or the console will list no tasks.
+//!
+//! [`instrumented`] is the demo mode: it runs a [`Workload`] on Tokio round
+//! after round, pausing in between so the console has something to watch,
+//! then runs it once on the deterministic runtime. The console shows what
+//! Tokio did with the tasks: when they were polled, how long they sat idle,
+//! which worker woke them. The returned [`ConsoleRun`] holds the crate's own
+//! trace of every round next to the deterministic one, so the two views of
+//! the same workload can be read side by side.
+
+use std::{sync::Once, thread, time::Duration};
+
+use commonware_runtime::{
+    Runner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+
+use crate::{
+    shadow::Workload,
+    trace::{EventLog, TraceEvent},
+};
+
+static INIT: Once = Once::new();
+
+/// Install the console subscriber. Later calls do nothing.
+pub fn init() {
+    INIT.call_once(console_subscriber::init);
+}
+
+/// The traces of an [`instrumented`] run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsoleRun {
+    /// One trace per Tokio round, in round order.
+    pub tokio: Vec<Vec<TraceEvent>>,
+    /// The trace of the single deterministic run.
+    pub deterministic: Vec<TraceEvent>,
+}
+
+impl ConsoleRun {
+    /// Number of different interleavings Tokio produced across the rounds.
+    pub fn tokio_schedules(&self) -> usize {
+        let mut schedules = self.tokio.clone();
+        schedules.sort();
+        schedules.dedup();
+        schedules.len()
+    }
+}
+
+/// Run `workload` on a Tokio runtime with `worker_threads` workers for
+/// `rounds` rounds, sleeping `pause` after each, then once on the
+/// deterministic runtime with `seed`. Call [`init`] first for the Tokio
+/// rounds to appear in tokio-console.
+pub fn instrumented<W: Workload>(
+    workload: W,
+    seed: u64,
+    worker_threads: usize,
+    rounds: usize,
+    pause: Duration,
+) -> ConsoleRun {
+    let tokio = (0..rounds)
+        .map(|_| {
+            let log = EventLog::new();
+            TokioRunner::new(TokioConfig::default().with_worker_threads(worker_threads))
+                .start(|context| workload.clone().run(context, log.clone()));
+            thread::sleep(pause);
+            log.events()
+        })
+        .collect();
+
+    let log = EventLog::new();
+    DeterministicRunner::new(Config::default().with_seed(seed))
+        .start(|context| workload.run(context, log.clone()));
+    ConsoleRun {
+        tokio,
+        deterministic: log.events(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shadow::SleepTasks;
+
+    /// Every round records the whole workload, and the deterministic trace is
+    /// the one the seed always produces.
+    #[test]
+    fn test_instrumented_rounds() {
+        let run = instrumented(SleepTasks, 7, 4, 5, Duration::ZERO);
+
+        assert_eq!(run.tokio.len(), 5);
+        assert!(run.tokio.iter().all(|round| round.len() == 6));
+        assert!((1..=5).contains(&run.tokio_schedules()));
+        assert_eq!(
+            run.deterministic,
+            instrumented(SleepTasks, 7, 4, 0, Duration::ZERO).deterministic
+        );
+    }
+}
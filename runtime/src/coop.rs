@@ -0,0 +1,112 @@
+//! Cooperative yielding for CPU-bound loops.
+//!
+//! A task that computes without awaiting holds its worker until it is done;
+//! on a single-threaded executor nothing else runs meanwhile. The fix is to
+//! yield every so often, and "every so often" should be one tunable number
+//! rather than a modulus written into each loop. A [`Budget`] counts the
+//! iterations of a loop and [`Budget::maybe_yield`] yields once every
+//! `every` of them.
+//!
+//! Yielding means a short sleep on the runtime's clock, [`yield_now`]. A
+//! zero-length sleep would not do: a timer that is already due completes
+//! without giving up the worker.
+
+use std::time::Duration;
+
+use commonware_runtime::Clock;
+
+/// How long a yielding task sleeps, enough for the scheduler to run others
+/// first.
+pub const YIELD_PAUSE: Duration = Duration::from_micros(10);
+
+/// Give the scheduler a chance to run other tasks.
+pub async fn yield_now(context: &impl Clock) {
+    context.sleep(YIELD_PAUSE).await;
+}
+
+/// Yields once per `every` iterations of a loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Budget {
+    every: u64,
+    spent: u64,
+    yields: u64,
+}
+
+impl Default for Budget {
+    /// Once per ten million iterations.
+    fn default() -> Self {
+        Self::new(10_000_000)
+    }
+}
+
+impl Budget {
+    pub fn new(every: u64) -> Self {
+        assert!(every > 0, "A budget should allow some work between yields");
+        Self {
+            every,
+            spent: 0,
+            yields: 0,
+        }
+    }
+
+    /// Count one iteration, yielding if it used up the budget. Returns
+    /// whether it yielded.
+    pub async fn maybe_yield(&mut self, context: &impl Clock) -> bool {
+        self.spent += 1;
+        if self.spent < self.every {
+            return false;
+        }
+        self.spent = 0;
+        self.yields += 1;
+        yield_now(context).await;
+        true
+    }
+
+    /// How many times the budget has yielded so far.
+    pub fn yields(&self) -> u64 {
+        self.yields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner, Spawner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::trace::EventLog;
+
+    /// A loop of 25 iterations on a budget of 10 yields twice, letting a
+    /// sibling task run before the loop finishes.
+    #[test]
+    fn test_budget_yields_to_siblings() {
+        let log = EventLog::new();
+        let yields = DeterministicRunner::new(Config::default().with_seed(0)).start(|context| {
+            let log = log.clone();
+            async move {
+                let looping = context.clone().spawn({
+                    let log = log.clone();
+                    move |context| async move {
+                        let mut budget = Budget::new(10);
+                        for _ in 0..25 {
+                            budget.maybe_yield(&context).await;
+                        }
+                        log.record("loop", "done");
+                        budget.yields()
+                    }
+                });
+                let sibling = context.clone().spawn(move |_| async move {
+                    log.record("sibling", "done");
+                });
+                let yields = looping.await.unwrap();
+                sibling.await.unwrap();
+                yields
+            }
+        });
+
+        assert_eq!(yields, 2);
+        assert_eq!(log.events()[0].task, "sibling");
+    }
+}
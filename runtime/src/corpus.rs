@@ -0,0 +1,318 @@
+//! A word corpus stored as one string plus word boundaries.
+//!
+//! Tokenizing into a `Vec<String>` costs one allocation per word. A [`Corpus`]
+//! keeps the text in a single buffer and records where each word starts and
+//! ends, so iterating, picking, or counting words hands out `&str` slices into
+//! that buffer. Word order is the order in the file, so anything indexed by
+//! position (like a seeded pick) is the same as with the owned representation.
+//!
+//! With the `mmap` feature, [`Corpus::open_mmap`] maps the file instead of
+//! reading it, so a multi-hundred-MB corpus lives in the page cache rather
+//! than on the heap. Word boundaries are only computed the first time a word
+//! is looked up by position; streaming through [`Corpus::words`] never needs
+//! them. Either way words come out in file order.
+//!
+//! [`Corpus::count`] counts a word without tokenizing at all: it searches the
+//! raw bytes with `memchr`'s vectorized substring finder and keeps only the
+//! matches that stand alone between whitespace. When the same corpus is
+//! queried over and over, build a [`CorpusIndex`] once instead: afterwards
+//! every count is a single lookup.
+
+use std::{fs, io, ops::Range, path::Path, sync::OnceLock};
+
+use memchr::memmem;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::collections::DMap;
+
+enum Text {
+    Owned(String),
+    /// Validated as UTF-8 when mapped.
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+}
+
+pub struct Corpus {
+    text: Text,
+    spans: OnceLock<Vec<Range<usize>>>,
+}
+
+impl Corpus {
+    /// Split `text` on whitespace without copying any word.
+    pub fn new(text: String) -> Self {
+        Self {
+            text: Text::Owned(text),
+            spans: OnceLock::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(fs::read_to_string(path)?))
+    }
+
+    /// Map `path` into memory instead of reading it. The file is checked to be
+    /// UTF-8 in one streaming pass; nothing is copied onto the heap.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        // SAFETY: the corpus is treated as read-only input. Truncating or
+        // editing the file while it is mapped is outside the supported use.
+        let map = unsafe { Mmap::map(&file)? };
+        std::str::from_utf8(&map).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self {
+            text: Text::Mapped(map),
+            spans: OnceLock::new(),
+        })
+    }
+
+    fn text(&self) -> &str {
+        match &self.text {
+            Text::Owned(text) => text,
+            // SAFETY: validated in `open_mmap`, and the mapping is read-only.
+            #[cfg(feature = "mmap")]
+            Text::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+        }
+    }
+
+    fn spans(&self) -> &[Range<usize>] {
+        self.spans.get_or_init(|| {
+            let text = self.text();
+            let base = text.as_ptr() as usize;
+            text.split_whitespace()
+                .map(|word| {
+                    let start = word.as_ptr() as usize - base;
+                    start..start + word.len()
+                })
+                .collect()
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans().is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.spans()
+            .get(index)
+            .map(|span| &self.text()[span.clone()])
+    }
+
+    /// The words at positions `range`, in file order.
+    pub fn words_in(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        self.spans()[range]
+            .iter()
+            .map(|span| &self.text()[span.clone()])
+    }
+
+    /// Every word in file order, straight from the text.
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.text().split_whitespace()
+    }
+
+    /// Pick a word uniformly at random. Draws from `rng` exactly like
+    /// choosing from a `Vec<String>` of the same words would.
+    pub fn choose(&self, rng: &mut impl Rng) -> Option<&str> {
+        self.spans()
+            .choose(rng)
+            .map(|span| &self.text()[span.clone()])
+    }
+
+    /// How many words equal `word`, the same as filtering [`Corpus::words`]
+    /// but without splitting the text. Matches of `word` inside a longer
+    /// word are skipped by checking that the characters on either side are
+    /// whitespace. A `word` that is empty or contains whitespace can never
+    /// equal a single word, so it counts zero.
+    pub fn count(&self, word: &str) -> usize {
+        if word.is_empty() || word.contains(char::is_whitespace) {
+            return 0;
+        }
+        let text = self.text();
+        // Matches of valid UTF-8 in valid UTF-8 start and end on character
+        // boundaries, so the slices below cannot panic.
+        memmem::find_iter(text.as_bytes(), word)
+            .filter(|&start| {
+                let before = text[..start].chars().next_back();
+                let after = text[start + word.len()..].chars().next();
+                before.is_none_or(char::is_whitespace) && after.is_none_or(char::is_whitespace)
+            })
+            .count()
+    }
+
+    /// Build an index of every distinct word's positions, in one pass.
+    pub fn index(&self) -> CorpusIndex {
+        let mut positions: DMap<String, Vec<usize>> = DMap::new();
+        for (position, word) in self.words().enumerate() {
+            match positions.get_mut(word) {
+                Some(seen) => seen.push(position),
+                None => {
+                    positions.insert(word.to_string(), vec![position]);
+                }
+            }
+        }
+        CorpusIndex { positions }
+    }
+
+    /// Owned copies of every word, for callers that need `Vec<String>`.
+    pub fn to_owned_words(&self) -> Vec<String> {
+        self.words().map(str::to_string).collect()
+    }
+}
+
+/// Where each distinct word of a [`Corpus`] occurs, built by
+/// [`Corpus::index`]. Positions are word positions, as for [`Corpus::get`].
+#[derive(Clone, Debug, Default)]
+pub struct CorpusIndex {
+    positions: DMap<String, Vec<usize>>,
+}
+
+impl CorpusIndex {
+    /// How many times `word` occurs.
+    pub fn count(&self, word: &str) -> usize {
+        self.positions(word).len()
+    }
+
+    /// The positions of `word`, ascending.
+    pub fn positions(&self, word: &str) -> &[usize] {
+        self.positions.get(word).map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of distinct words.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    /// Slices cover every word in order, whatever the whitespace between them.
+    #[test]
+    fn test_tokenization() {
+        let corpus = Corpus::new("  once upon\ta\n\ntime ".to_string());
+
+        assert_eq!(corpus.len(), 4);
+        assert_eq!(
+            corpus.words().collect::<Vec<_>>(),
+            ["once", "upon", "a", "time"]
+        );
+        assert_eq!(corpus.get(3), Some("time"));
+        assert_eq!(corpus.get(4), None);
+        assert_eq!(corpus.to_owned_words(), ["once", "upon", "a", "time"]);
+    }
+
+    /// A seeded pick matches the pick from the owned representation.
+    #[test]
+    fn test_choose_matches_owned() {
+        let corpus = Corpus::new("the quick brown fox jumps over the lazy dog".to_string());
+        let owned = corpus.to_owned_words();
+
+        for seed in 0..20 {
+            let from_corpus = corpus.choose(&mut StdRng::seed_from_u64(seed));
+            let from_owned = owned.choose(&mut StdRng::seed_from_u64(seed));
+            assert_eq!(from_corpus, from_owned.map(String::as_str));
+        }
+    }
+
+    /// The substring search agrees with counting tokens, including for
+    /// prefixes, suffixes, Unicode whitespace and words at either end.
+    #[test]
+    fn test_count_matches_scan() {
+        let corpus =
+            Corpus::new("the\u{3000}theme of the\tother: the bathe  the\nthe thé the".to_string());
+        for word in [
+            "the",
+            "theme",
+            "th",
+            "he",
+            "thé",
+            "other:",
+            "missing",
+            "",
+            "the bathe",
+        ] {
+            assert_eq!(
+                corpus.count(word),
+                corpus.words().filter(|&w| w == word).count(),
+                "{:?}",
+                word
+            );
+        }
+        assert_eq!(corpus.count("the"), 6);
+
+        let grimm = crate::tasks::read_file();
+        for word in grimm.words().take(200) {
+            assert_eq!(
+                grimm.count(word),
+                grimm.words().filter(|&w| w == word).count()
+            );
+        }
+    }
+
+    /// The index agrees with scanning for every word, and its positions
+    /// point back at the word.
+    #[test]
+    fn test_index_matches_scan() {
+        let corpus = crate::tasks::read_file();
+        let index = corpus.index();
+
+        for word in corpus.words().chain(["missing", ""]) {
+            assert_eq!(index.count(word), corpus.count(word));
+            assert!(
+                index
+                    .positions(word)
+                    .iter()
+                    .all(|&position| corpus.get(position) == Some(word))
+            );
+        }
+        assert_eq!(
+            index.len(),
+            corpus
+                .words()
+                .collect::<std::collections::BTreeSet<_>>()
+                .len()
+        );
+    }
+
+    /// A mapped corpus yields the same words, in the same order, as a loaded
+    /// one.
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_matches_load() {
+        let path = std::env::temp_dir().join(format!("corpus-{}.txt", std::process::id()));
+        fs::write(&path, "far  away\nin a\tkingdom ").unwrap();
+
+        let loaded = Corpus::load(&path).unwrap();
+        let mapped = Corpus::open_mmap(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.words().eq(mapped.words()));
+        assert_eq!(mapped.len(), 5);
+        assert_eq!(mapped.get(4), Some("kingdom"));
+    }
+
+    /// Mapping a file that is not UTF-8 fails instead of yielding garbage.
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_rejects_invalid_utf8() {
+        let path = std::env::temp_dir().join(format!("corpus-bad-{}.txt", std::process::id()));
+        fs::write(&path, [b'o', b'k', b' ', 0xff]).unwrap();
+
+        let error = Corpus::open_mmap(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
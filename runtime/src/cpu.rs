@@ -0,0 +1,260 @@
+//! Virtual CPU accounting per task, with optional preemption.
+//!
+//! Async runtimes cannot interrupt a task mid-poll: a task that computes for
+//! a long time between awaits holds its worker until it chooses to yield,
+//! and under the deterministic runtime its computation does not even show up
+//! on the (virtual) clock. So CPU is accounted in units the task reports
+//! itself: iterations, hashes, bytes scanned, or an estimate where counting
+//! exactly is impractical. Each task charges a [`CpuMeter`] as it works; the
+//! shared [`CpuAccount`] keeps totals and the length of every slice, the
+//! units charged between two yields, which is the work done in one poll.
+//!
+//! A [`CpuBudget`] caps the slice. With [`Enforcement::Account`] an overrun
+//! is only counted. With [`Enforcement::Preempt`] the meter yields before
+//! any charge that would take the slice past the cap, so no task holds the
+//! scheduler for more than one budget of work at a time. Yield points then
+//! depend only on the units charged, never on how fast the machine is, and
+//! a deterministic run interleaves its tasks the same way on every replay.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+use commonware_runtime::Clock;
+
+use crate::coop;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Enforcement {
+    /// Record slices that exceed the budget, but never interrupt a task.
+    #[default]
+    Account,
+    /// Yield before a charge would exceed the budget.
+    Preempt,
+}
+
+/// At most `slice` units of work between yields.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuBudget {
+    pub slice: u64,
+    pub enforcement: Enforcement,
+}
+
+/// What one task consumed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuUsage {
+    pub units: u64,
+    /// Stretches of work between yields.
+    pub slices: usize,
+    pub longest_slice: u64,
+    /// Slices longer than the budget.
+    pub overruns: usize,
+    /// Yields the meter forced.
+    pub preemptions: usize,
+}
+
+/// Usage of every metered task in a run. Clones share the totals.
+#[derive(Clone)]
+pub struct CpuAccount {
+    budget: CpuBudget,
+    usage: Arc<Mutex<BTreeMap<String, CpuUsage>>>,
+}
+
+impl CpuAccount {
+    pub fn new(budget: CpuBudget) -> Self {
+        assert!(budget.slice > 0, "A slice should allow some work");
+        Self {
+            budget,
+            usage: Arc::default(),
+        }
+    }
+
+    /// The meter `task` charges its work to, yielding on `context`.
+    pub fn meter<C: Clock>(&self, context: C, task: impl Into<String>) -> CpuMeter<C> {
+        CpuMeter {
+            account: self.clone(),
+            context,
+            task: task.into(),
+            slice: 0,
+        }
+    }
+
+    /// Usage per task, by task name.
+    pub fn usage(&self) -> BTreeMap<String, CpuUsage> {
+        self.usage.lock().unwrap().clone()
+    }
+
+    /// One line per task: units, slices, the longest slice, overruns and
+    /// preemptions.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (task, usage) in self.usage() {
+            writeln!(
+                report,
+                "{}: {} units in {} slices (longest {}), {} overruns, {} preemptions",
+                task,
+                usage.units,
+                usage.slices,
+                usage.longest_slice,
+                usage.overruns,
+                usage.preemptions
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    fn update(&self, task: &str, f: impl FnOnce(&mut CpuUsage)) {
+        f(self
+            .usage
+            .lock()
+            .unwrap()
+            .entry(task.to_string())
+            .or_default());
+    }
+}
+
+/// One task's handle on a [`CpuAccount`]. Dropping it ends the task's last
+/// slice.
+pub struct CpuMeter<C: Clock> {
+    account: CpuAccount,
+    context: C,
+    task: String,
+    slice: u64,
+}
+
+impl<C: Clock> CpuMeter<C> {
+    /// Charge `units` of work to the task, first yielding if the budget
+    /// preempts and the charge would overrun the current slice.
+    pub async fn charge(&mut self, units: u64) {
+        let budget = self.account.budget;
+        if budget.enforcement == Enforcement::Preempt
+            && self.slice > 0
+            && self.slice + units > budget.slice
+        {
+            self.account
+                .update(&self.task, |usage| usage.preemptions += 1);
+            self.yield_now().await;
+        }
+        self.slice += units;
+        self.account
+            .update(&self.task, |usage| usage.units += units);
+    }
+
+    /// Yield to the scheduler, ending the current slice.
+    pub async fn yield_now(&mut self) {
+        self.end_slice();
+        coop::yield_now(&self.context).await;
+    }
+
+    fn end_slice(&mut self) {
+        let (slice, budget) = (std::mem::take(&mut self.slice), self.account.budget.slice);
+        if slice == 0 {
+            return;
+        }
+        self.account.update(&self.task, |usage| {
+            usage.slices += 1;
+            usage.longest_slice = usage.longest_slice.max(slice);
+            if slice > budget {
+                usage.overruns += 1;
+            }
+        });
+    }
+}
+
+impl<C: Clock> Drop for CpuMeter<C> {
+    fn drop(&mut self) {
+        self.end_slice();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner, Spawner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::trace::{EventLog, TraceEvent};
+
+    /// A hog charging 100 units ten times beside a task charging 10 once,
+    /// under a 250-unit budget.
+    fn run(seed: u64, enforcement: Enforcement) -> (CpuAccount, Vec<TraceEvent>) {
+        let account = CpuAccount::new(CpuBudget {
+            slice: 250,
+            enforcement,
+        });
+        let log = EventLog::new();
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+            let (account, log) = (account.clone(), log.clone());
+            async move {
+                let hog = context.clone().spawn({
+                    let (account, log) = (account.clone(), log.clone());
+                    move |context| async move {
+                        let mut meter = account.meter(context, "hog");
+                        for step in 0..10 {
+                            meter.charge(100).await;
+                            log.record("hog", format!("step {}", step));
+                        }
+                    }
+                });
+                let light = context.clone().spawn(move |context| async move {
+                    account.meter(context, "light").charge(10).await;
+                    log.record("light", "done");
+                });
+                hog.await.unwrap();
+                light.await.unwrap();
+            }
+        });
+        (account, log.events())
+    }
+
+    /// Accounting alone lets the hog run its whole computation in one
+    /// slice, and counts the overrun.
+    #[test]
+    fn test_account_counts_overruns() {
+        let (account, _) = run(0, Enforcement::Account);
+        let hog = account.usage()["hog"];
+
+        assert_eq!(hog.units, 1000);
+        assert_eq!((hog.slices, hog.longest_slice), (1, 1000));
+        assert_eq!((hog.overruns, hog.preemptions), (1, 0));
+    }
+
+    /// Preemption splits the hog into slices within the budget, so the light
+    /// task finishes before the hog on every seed, and each seed replays
+    /// exactly.
+    #[test]
+    fn test_preempt_keeps_slices_within_budget() {
+        for seed in 0..10 {
+            let (account, events) = run(seed, Enforcement::Preempt);
+            let hog = account.usage()["hog"];
+
+            assert_eq!((hog.slices, hog.longest_slice), (5, 200));
+            assert_eq!((hog.overruns, hog.preemptions), (0, 4));
+            let position = |task: &str, message: &str| {
+                events
+                    .iter()
+                    .position(|event| event.task == task && event.message == message)
+                    .unwrap()
+            };
+            assert!(position("light", "done") < position("hog", "step 9"));
+            assert_eq!(run(seed, Enforcement::Preempt).1, events);
+        }
+    }
+
+    /// The report lists each task on one line.
+    #[test]
+    fn test_report() {
+        let (account, _) = run(0, Enforcement::Preempt);
+
+        assert_eq!(
+            account.report(),
+            "hog: 1000 units in 5 slices (longest 200), 0 overruns, 4 preemptions\n\
+             light: 10 units in 1 slices (longest 10), 0 overruns, 0 preemptions\n"
+        );
+    }
+}
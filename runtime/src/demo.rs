@@ -0,0 +1,197 @@
+//! The word demos on both runtimes, side by side.
+//!
+//! The demos' comments claim that under Tokio the counter may count a word
+//! the selector has since replaced, or find nothing selected at all, and
+//! that under the deterministic runtime whatever happens happens the same
+//! way every time. A [`DemoTrace`] runs the word workflow on both runtimes
+//! with the same [`WorkflowConfig`], so both select the same words, and
+//! classifies each of the counter's rounds by what it saw:
+//!
+//! - **fresh**: the word selected in the same round;
+//! - **stale**: a word from an earlier round;
+//! - **ahead**: a word from a later round, the selector having run twice
+//!   before the counter ran once;
+//! - **missing**: nothing, because no word had been selected yet.
+//!
+//! The two outcomes, lock traces included, are kept for anything the
+//! classification does not cover.
+
+use std::fmt;
+
+use crate::{WorkflowConfig, WorkflowOutcome, commonware_word_workflow, tokio_word_workflow};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Observation {
+    Fresh,
+    /// Saw the selection of round `seen`.
+    Stale {
+        seen: usize,
+    },
+    /// Saw the selection of round `seen`.
+    Ahead {
+        seen: usize,
+    },
+    Missing,
+}
+
+impl fmt::Display for Observation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Observation::Fresh => f.write_str("fresh"),
+            Observation::Stale { seen } => write!(f, "stale (round {})", seen),
+            Observation::Ahead { seen } => write!(f, "ahead (round {})", seen),
+            Observation::Missing => f.write_str("missing"),
+        }
+    }
+}
+
+/// What the counter saw in each round of `outcome`.
+pub fn observations(outcome: &WorkflowOutcome) -> Vec<Observation> {
+    outcome
+        .visible
+        .iter()
+        .enumerate()
+        .map(|(round, &visible)| match visible.checked_sub(1) {
+            None => Observation::Missing,
+            Some(seen) if seen == round => Observation::Fresh,
+            Some(seen) if seen < round => Observation::Stale { seen },
+            Some(seen) => Observation::Ahead { seen },
+        })
+        .collect()
+}
+
+/// The word workflow's outcome on each runtime, from the same config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DemoTrace {
+    pub config: WorkflowConfig,
+    pub tokio: WorkflowOutcome,
+    pub deterministic: WorkflowOutcome,
+}
+
+impl DemoTrace {
+    /// Run the workflow as `config` describes, on Tokio with
+    /// `worker_threads` workers and on the deterministic runtime.
+    pub fn run(worker_threads: usize, config: WorkflowConfig) -> Self {
+        Self {
+            config,
+            tokio: tokio_word_workflow(worker_threads, config),
+            deterministic: commonware_word_workflow(config),
+        }
+    }
+
+    /// Rounds where the counter did not see that round's word, on either
+    /// runtime, with what each runtime's counter saw.
+    pub fn off_rounds(&self) -> Vec<(usize, Observation, Observation)> {
+        observations(&self.tokio)
+            .into_iter()
+            .zip(observations(&self.deterministic))
+            .enumerate()
+            .filter(|(_, (tokio, deterministic))| {
+                *tokio != Observation::Fresh || *deterministic != Observation::Fresh
+            })
+            .map(|(round, (tokio, deterministic))| (round, tokio, deterministic))
+            .collect()
+    }
+}
+
+impl fmt::Display for DemoTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (tokio, deterministic) = (observations(&self.tokio), observations(&self.deterministic));
+        writeln!(
+            f,
+            "round | tokio                | deterministic        | selected"
+        )?;
+        for (round, selected) in self.deterministic.selections.iter().enumerate() {
+            let cell = |observations: &[Observation]| {
+                observations
+                    .get(round)
+                    .map_or("-".to_string(), |observation| observation.to_string())
+            };
+            writeln!(
+                f,
+                "{:>5} | {:<20} | {:<20} | {}",
+                round,
+                cell(&tokio),
+                cell(&deterministic),
+                selected
+            )?;
+        }
+        let fresh = |observations: &[Observation]| {
+            observations
+                .iter()
+                .filter(|observation| **observation == Observation::Fresh)
+                .count()
+        };
+        write!(
+            f,
+            "fresh rounds: tokio {}/{}, deterministic {}/{}",
+            fresh(&tokio),
+            tokio.len(),
+            fresh(&deterministic),
+            deterministic.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::tasks::{Coordination, CountStrategy};
+
+    fn outcome(visible: Vec<usize>) -> WorkflowOutcome {
+        WorkflowOutcome {
+            selections: Vec::new(),
+            counts: Vec::new(),
+            visible,
+            lock_events: Vec::new(),
+        }
+    }
+
+    /// Each round is classified by which round's selection it saw.
+    #[test]
+    fn test_observations() {
+        assert_eq!(
+            observations(&outcome(vec![0, 2, 2, 4, 6])),
+            [
+                Observation::Missing,
+                Observation::Fresh,
+                Observation::Stale { seen: 1 },
+                Observation::Fresh,
+                Observation::Ahead { seen: 5 },
+            ]
+        );
+    }
+
+    /// Both runtimes select the same words; the deterministic side of the
+    /// trace repeats exactly.
+    #[test]
+    fn test_demo_trace() {
+        let config = WorkflowConfig::default()
+            .with_rounds(4)
+            .with_pause(Duration::from_millis(2))
+            .with_strategy(CountStrategy::Indexed);
+        let trace = DemoTrace::run(2, config);
+
+        assert_eq!(trace.tokio.selections, trace.deterministic.selections);
+        assert_eq!(trace.deterministic.visible.len(), 4);
+        assert_eq!(DemoTrace::run(2, config).deterministic, trace.deterministic);
+        let report = trace.to_string();
+        assert!(report.starts_with("round | tokio"));
+        assert_eq!(report.lines().count(), 6);
+    }
+
+    /// With the handshake, the demos on both runtimes see every round's own
+    /// word.
+    #[test]
+    fn test_demo_trace_handshake() {
+        let config = WorkflowConfig::default()
+            .with_rounds(4)
+            .with_coordination(Coordination::Handshake);
+        let trace = DemoTrace::run(2, config);
+
+        assert!(trace.off_rounds().is_empty());
+        assert_eq!(trace.tokio.counts, trace.deterministic.counts);
+    }
+}
@@ -0,0 +1,147 @@
+//! Finding a seed that produces a particular interleaving.
+//!
+//! Some schedules matter more than others: the one where a consumer runs
+//! before anything was produced, or where a task finishes before its sibling
+//! even starts. Under the deterministic runtime each such schedule belongs to
+//! some set of seeds, and once one is known the schedule can be replayed at
+//! will. [`find_seed`] sweeps seeds, runs the workload under each, and stops
+//! at the first whose event log satisfies a predicate. The seed it returns
+//! can then be pinned in a regression test, so the edge case is exercised on
+//! every run rather than whenever the scheduler happens to produce it.
+//!
+//! [`occurs_before`] covers the most common predicate: some event of one
+//! kind happens before any event of another.
+
+use std::future::Future;
+
+use commonware_runtime::{
+    Runner,
+    deterministic::{Config, Context, Runner as DeterministicRunner},
+};
+
+use crate::trace::{EventLog, TraceEvent};
+
+/// A seed whose run matched, and the events that run recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Discovery {
+    pub seed: u64,
+    pub events: Vec<TraceEvent>,
+    /// Seeds run before this one matched, this one included.
+    pub tried: usize,
+}
+
+/// Run `workload` on the deterministic runtime with each of `seeds` in turn
+/// and return the first seed whose events satisfy `predicate`.
+pub fn find_seed<F, Fut>(
+    seeds: impl IntoIterator<Item = u64>,
+    workload: F,
+    predicate: impl Fn(&[TraceEvent]) -> bool,
+) -> Option<Discovery>
+where
+    F: Fn(Context, EventLog) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    seeds.into_iter().enumerate().find_map(|(index, seed)| {
+        let log = EventLog::new();
+        DeterministicRunner::new(Config::default().with_seed(seed))
+            .start(|context| workload(context, log.clone()));
+        let events = log.events();
+        predicate(&events).then_some(Discovery {
+            seed,
+            events,
+            tried: index + 1,
+        })
+    })
+}
+
+/// Whether an event matching `first` happens before every event matching
+/// `then`. Also true if `first` happens and `then` never does.
+pub fn occurs_before(
+    events: &[TraceEvent],
+    first: impl Fn(&TraceEvent) -> bool,
+    then: impl Fn(&TraceEvent) -> bool,
+) -> bool {
+    let Some(first) = events.iter().position(first) else {
+        return false;
+    };
+    events.iter().position(then).is_none_or(|then| first < then)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use commonware_runtime::{Clock, Spawner};
+
+    use super::*;
+
+    /// A selector and a counter started together: the counter records
+    /// `nothing selected` if it runs first.
+    async fn select_and_count(context: Context, log: EventLog) {
+        let selected = Arc::new(Mutex::new(None));
+        let selector = context.clone().spawn({
+            let (selected, log) = (selected.clone(), log.clone());
+            move |_| async move {
+                *selected.lock().unwrap() = Some("word");
+                log.record("selector", "selected word");
+            }
+        });
+        let counter = context.clone().spawn(move |context| async move {
+            context.sleep(Duration::ZERO).await;
+            match *selected.lock().unwrap() {
+                Some(word) => log.record("counter", format!("counted {}", word)),
+                None => log.record("counter", "nothing selected"),
+            }
+        });
+        selector.await.unwrap();
+        counter.await.unwrap();
+    }
+
+    fn counter_first(events: &[TraceEvent]) -> bool {
+        occurs_before(
+            events,
+            |event| event.task == "counter",
+            |event| event.task == "selector",
+        )
+    }
+
+    /// The search finds a seed where the counter beats the selector, and that
+    /// seed reproduces the schedule on every later run.
+    #[test]
+    fn test_find_seed_pins_schedule() {
+        let discovery = find_seed(0..500, select_and_count, counter_first)
+            .expect("Some seed should run the counter first");
+
+        assert_eq!(discovery.events[0].message, "nothing selected");
+        let again = find_seed([discovery.seed], select_and_count, counter_first).unwrap();
+        assert_eq!(again.events, discovery.events);
+        assert_eq!(again.tried, 1);
+    }
+
+    /// A schedule that cannot happen is reported as not found.
+    #[test]
+    fn test_impossible_schedule_not_found() {
+        assert_eq!(
+            find_seed(0..20, select_and_count, |events| events.len() > 2),
+            None
+        );
+    }
+
+    /// `occurs_before` needs the first event to happen, not the second.
+    #[test]
+    fn test_occurs_before() {
+        let events = [("a", "x"), ("b", "y")].map(|(task, message)| TraceEvent {
+            task: task.into(),
+            message: message.into(),
+        });
+        let task = |name: &'static str| move |event: &TraceEvent| event.task == name;
+
+        assert!(occurs_before(&events, task("a"), task("b")));
+        assert!(!occurs_before(&events, task("b"), task("a")));
+        assert!(occurs_before(&events, task("a"), task("c")));
+        assert!(!occurs_before(&events, task("c"), task("a")));
+    }
+}
@@ -0,0 +1,69 @@
+//! How task failures are reported and what happens after one.
+//!
+//! A panic inside a task used to unwind in whatever way the runtime chose:
+//! Tokio reports it through the join handle, the deterministic runtime can
+//! abort the whole run. The [`ParallelExecutor`](crate::parallel_determinism::executor::ParallelExecutor)
+//! and the [`TaskSpawner`](crate::spawn::TaskSpawner) both catch panics at the
+//! task boundary instead and report them as a [`TaskError`], the same way on
+//! every runtime.
+
+use std::{any::Any, fmt};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskError {
+    /// The task panicked. `task_id` is the executor's task id, or the number
+    /// of the spawner's [`TaskId`](crate::spawn::TaskId).
+    Panicked { task_id: u64, message: String },
+    /// The task was aborted through its [`TaskHandle`](crate::spawn::TaskHandle)
+    /// before it finished.
+    Aborted { task_id: u64 },
+}
+
+impl TaskError {
+    pub fn task_id(&self) -> u64 {
+        match self {
+            Self::Panicked { task_id, .. } | Self::Aborted { task_id } => *task_id,
+        }
+    }
+
+    #[cfg(feature = "runtime")]
+    pub(crate) fn panicked(task_id: u64, payload: Box<dyn Any + Send>) -> Self {
+        Self::Panicked {
+            task_id,
+            message: panic_message(payload.as_ref()),
+        }
+    }
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Panicked { task_id, message } => {
+                write!(f, "task {} panicked: {}", task_id, message)
+            }
+            Self::Aborted { task_id } => write!(f, "task {} was aborted", task_id),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+/// What an executor does once a task has failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Record the failure and keep executing.
+    #[default]
+    Continue,
+    /// Finish the level the failure happened in, then stop.
+    Halt,
+}
+
+/// The message a panic was raised with, for the common `&str` and `String`
+/// payloads.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
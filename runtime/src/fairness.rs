@@ -0,0 +1,218 @@
+//! Quantifying scheduler fairness for a workload mix.
+//!
+//! Every task of a [`WorkloadMix`] is wrapped in a future that reads the
+//! runtime's clock around each poll. From that we derive, per task:
+//!
+//! - **time to first poll**: spawn until the scheduler first ran the task;
+//! - **wait time**: time the task existed but was not inside `poll` (queued
+//!   behind other tasks, or sleeping);
+//! - **completion latency**: spawn until the task finished.
+//!
+//! Percentiles of those three numbers, side by side for Tokio and the
+//! deterministic runtime, turn "Tokio for throughput, deterministic for
+//! replay" into a table instead of a claim. Note that under the deterministic
+//! runtime the clock is virtual: CPU work inside a poll costs no time, only
+//! sleeps and scheduling cycles do.
+
+use std::{
+    fmt::Write,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use commonware_runtime::{
+    Clock, Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+
+use crate::{
+    mix::{WorkloadKind, WorkloadMix},
+    stats::{LatencyHistogram, Percentiles, elapsed_since},
+};
+
+#[derive(Clone, Debug)]
+pub struct TaskTiming {
+    pub index: usize,
+    pub kind: WorkloadKind,
+    pub time_to_first_poll: Duration,
+    pub wait_time: Duration,
+    pub completion_latency: Duration,
+}
+
+/// Timings of every task of one run, labelled with the runtime that ran it.
+pub struct FairnessReport {
+    pub runtime: &'static str,
+    pub timings: Vec<TaskTiming>,
+}
+
+impl FairnessReport {
+    fn percentiles(&self, metric: impl Fn(&TaskTiming) -> Duration) -> Percentiles {
+        self.timings
+            .iter()
+            .map(metric)
+            .collect::<LatencyHistogram>()
+            .percentiles()
+    }
+
+    pub fn time_to_first_poll(&self) -> Percentiles {
+        self.percentiles(|t| t.time_to_first_poll)
+    }
+
+    pub fn wait_time(&self) -> Percentiles {
+        self.percentiles(|t| t.wait_time)
+    }
+
+    pub fn completion_latency(&self) -> Percentiles {
+        self.percentiles(|t| t.completion_latency)
+    }
+}
+
+/// Wraps a future and accumulates how long it spent inside `poll`.
+struct Timed<C: Clock, F: Future> {
+    context: C,
+    inner: Pin<Box<F>>,
+    first_poll: Option<SystemTime>,
+    in_poll: Duration,
+}
+
+// Nothing is ever pinned in place: the inner future lives in its own box.
+impl<C: Clock, F: Future> Unpin for Timed<C, F> {}
+
+impl<C: Clock, F: Future> Future for Timed<C, F> {
+    type Output = (SystemTime, Duration);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let before = this.context.current();
+        let first_poll = *this.first_poll.get_or_insert(before);
+        let result = this.inner.as_mut().poll(cx);
+        this.in_poll += elapsed_since(&this.context, before);
+        match result {
+            Poll::Ready(_) => Poll::Ready((first_poll, this.in_poll)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Spawn every task of the mix as a sibling and time each one.
+pub async fn measure_mix<C: Spawner + Clock>(
+    context: &C,
+    kinds: &[WorkloadKind],
+) -> Vec<TaskTiming> {
+    let timings = Arc::new(Mutex::new(Vec::with_capacity(kinds.len())));
+    let handles: Vec<_> = kinds
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(index, kind)| {
+            let timings = timings.clone();
+            let spawned = context.current();
+            context.clone().spawn(move |context| async move {
+                let timed = Timed {
+                    context: context.clone(),
+                    inner: Box::pin(async move { kind.run(&context).await }),
+                    first_poll: None,
+                    in_poll: Duration::ZERO,
+                };
+                let clock = timed.context.clone();
+                let (first_poll, in_poll) = timed.await;
+                let latency = elapsed_since(&clock, spawned);
+                timings.lock().unwrap().push(TaskTiming {
+                    index,
+                    kind,
+                    time_to_first_poll: first_poll
+                        .duration_since(spawned)
+                        .unwrap_or(Duration::ZERO),
+                    wait_time: latency.saturating_sub(in_poll),
+                    completion_latency: latency,
+                });
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle
+            .await
+            .expect("Workload task should run to completion");
+    }
+    let mut timings = std::mem::take(&mut *timings.lock().unwrap());
+    timings.sort_by_key(|timing| timing.index);
+    timings
+}
+
+/// Run the same generated mix on Tokio (with `worker_threads` workers) and on
+/// the deterministic runtime.
+pub fn compare_runtimes(
+    mix: WorkloadMix,
+    count: usize,
+    seed: u64,
+    worker_threads: usize,
+) -> Vec<FairnessReport> {
+    let kinds = mix.generate(count, seed);
+
+    let tokio =
+        TokioRunner::new(TokioConfig::default().with_worker_threads(worker_threads)).start({
+            let kinds = kinds.clone();
+            |context| async move { measure_mix(&context, &kinds).await }
+        });
+    let deterministic = DeterministicRunner::new(Config::default().with_seed(seed))
+        .start(|context| async move { measure_mix(&context, &kinds).await });
+
+    vec![
+        FairnessReport {
+            runtime: "tokio",
+            timings: tokio,
+        },
+        FairnessReport {
+            runtime: "deterministic",
+            timings: deterministic,
+        },
+    ]
+}
+
+/// Render the percentile comparison as a Markdown table.
+pub fn comparison_table(reports: &[FairnessReport]) -> String {
+    let mut table = String::new();
+    writeln!(table, "| runtime | metric | p50 | p95 | p99 |").unwrap();
+    writeln!(table, "|---|---|---|---|---|").unwrap();
+    for report in reports {
+        for (metric, percentiles) in [
+            ("time to first poll", report.time_to_first_poll()),
+            ("wait time", report.wait_time()),
+            ("completion latency", report.completion_latency()),
+        ] {
+            writeln!(
+                table,
+                "| {} | {} | {:?} | {:?} | {:?} |",
+                report.runtime, metric, percentiles.p50, percentiles.p95, percentiles.p99
+            )
+            .unwrap();
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// I/O tasks spend nearly all of their latency waiting, and the table
+    /// has a row per runtime and metric.
+    #[test]
+    fn test_compare_io_mix() {
+        let reports = compare_runtimes(WorkloadMix::new(0, 100, 0), 4, 1, 2);
+
+        for report in &reports {
+            assert_eq!(report.timings.len(), 4);
+            assert!(report.completion_latency().p50 >= Duration::from_millis(250));
+            assert!(report.wait_time().p50 >= Duration::from_millis(200));
+        }
+        let table = comparison_table(&reports);
+        assert_eq!(table.lines().count(), 2 + 2 * 3);
+        assert!(table.contains("| deterministic | wait time |"));
+    }
+}
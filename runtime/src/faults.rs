@@ -0,0 +1,179 @@
+//! Injecting failures at seed-determined points.
+//!
+//! A [`FaultInjector`] decides, from a [`DeterministicRng`], whether each
+//! attempt of each operation fails. [`flaky_operations`] runs a batch of
+//! operations concurrently, each through [`retry`], with the injector in the
+//! way of every attempt, and records every failure, retry and outcome. The
+//! failure-handling paths (which attempts fail, how long each backoff
+//! sleeps, which operations give up) are then as much a part of the run as
+//! its results: under the deterministic runtime, the same seed fails the same
+//! attempts and retries them at the same instants on every run.
+
+use std::{fmt, time::Duration};
+
+use commonware_runtime::{Clock, Spawner};
+use rand::Rng;
+
+use crate::{
+    retry::{Backoff, RetryError, retry},
+    rng::DeterministicRng,
+    trace::EventLog,
+};
+
+/// A failure put there by a [`FaultInjector`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InjectedFault {
+    pub operation: usize,
+    pub attempt: usize,
+}
+
+impl fmt::Display for InjectedFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "injected fault in operation {} attempt {}",
+            self.operation, self.attempt
+        )
+    }
+}
+
+impl std::error::Error for InjectedFault {}
+
+/// Fails each attempt with probability `failure_rate`. Clones draw from the
+/// same stream, so the order attempts are checked in decides which fail.
+#[derive(Clone)]
+pub struct FaultInjector {
+    rng: DeterministicRng,
+    failure_rate: f64,
+}
+
+impl FaultInjector {
+    pub fn new(rng: DeterministicRng, failure_rate: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&failure_rate),
+            "Failure rate should be a probability"
+        );
+        Self { rng, failure_rate }
+    }
+
+    /// Whether `attempt` of `operation` goes through.
+    pub fn check(&mut self, operation: usize, attempt: usize) -> Result<(), InjectedFault> {
+        if self.rng.random_bool(self.failure_rate) {
+            Err(InjectedFault { operation, attempt })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Run `operations` operations concurrently, each taking `latency` per
+/// attempt and failing where `injector` says, retried as `backoff` says.
+/// Records `attempt N failed`, `succeeded on attempt N` and `gave up after N
+/// attempts` in `log` under `op K`. Returns each operation's outcome: the
+/// attempt it succeeded on, or why it gave up.
+pub async fn flaky_operations<S: Spawner + Clock>(
+    context: &S,
+    operations: usize,
+    latency: Duration,
+    injector: FaultInjector,
+    backoff: Backoff,
+    log: &EventLog,
+) -> Vec<Result<usize, RetryError<InjectedFault>>> {
+    let handles: Vec<_> = (0..operations)
+        .map(|operation| {
+            let (injector, log) = (injector.clone(), log.clone());
+            context.clone().spawn(move |context| async move {
+                let name = format!("op {}", operation);
+                let outcome = retry(&context, &backoff, |attempt| {
+                    let (context, mut injector, log, name) =
+                        (context.clone(), injector.clone(), log.clone(), name.clone());
+                    async move {
+                        context.sleep(latency).await;
+                        injector.check(operation, attempt).inspect_err(|_| {
+                            log.record(name.as_str(), format!("attempt {} failed", attempt));
+                        })?;
+                        Ok(attempt)
+                    }
+                })
+                .await;
+                match &outcome {
+                    Ok(attempt) => {
+                        log.record(name.as_str(), format!("succeeded on attempt {}", attempt))
+                    }
+                    Err(error) => log.record(
+                        name.as_str(),
+                        format!("gave up after {} attempts", error.attempts),
+                    ),
+                }
+                outcome
+            })
+        })
+        .collect();
+
+    let mut outcomes = Vec::with_capacity(operations);
+    for handle in handles {
+        outcomes.push(handle.await.expect("Operation should run to completion"));
+    }
+    outcomes
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::trace::TraceEvent;
+
+    type Outcomes = Vec<Result<usize, RetryError<InjectedFault>>>;
+
+    fn run(seed: u64, failure_rate: f64) -> (Outcomes, Vec<TraceEvent>) {
+        let log = EventLog::new();
+        let outcomes =
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(|mut context| {
+                let log = log.clone();
+                async move {
+                    let injector = FaultInjector::new(
+                        DeterministicRng::from_runtime(&mut context),
+                        failure_rate,
+                    );
+                    let backoff = Backoff::new(Duration::from_millis(5), 4);
+                    flaky_operations(
+                        &context,
+                        8,
+                        Duration::from_millis(1),
+                        injector,
+                        backoff,
+                        &log,
+                    )
+                    .await
+                }
+            });
+        (outcomes, log.events())
+    }
+
+    /// The same seed fails the same attempts, retries them in the same order
+    /// and reaches the same outcomes.
+    #[test]
+    fn test_failures_repeat_with_seed() {
+        let (outcomes, events) = run(9, 0.5);
+
+        assert!(outcomes.iter().any(|outcome| *outcome != Ok(1)));
+        assert!(events.iter().any(|event| event.message.ends_with("failed")));
+        assert_eq!(run(9, 0.5), (outcomes, events));
+    }
+
+    /// With no faults every operation succeeds first time; with nothing but
+    /// faults every one gives up after the last attempt.
+    #[test]
+    fn test_failure_rate_extremes() {
+        assert!(run(0, 0.0).0.iter().all(|outcome| *outcome == Ok(1)));
+        for outcome in run(0, 1.0).0 {
+            let error = outcome.unwrap_err();
+            assert_eq!(error.attempts, 4);
+            assert_eq!(error.last.attempt, 4);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Callbacks for instrumenting execution without touching workload code.
+//!
+//! Both the [`ParallelExecutor`](crate::parallel_determinism::executor::ParallelExecutor)
+//! and the [`TaskSpawner`](crate::spawn::TaskSpawner) accept hooks that run
+//! when a task starts, when it ends, and (for the executor) when a level
+//! completes. Metrics, logging and assertions can all be attached this way.
+//! Hooks run inline on the task that triggered them, so they should be cheap
+//! and must not block.
+
+use std::sync::Arc;
+
+pub type Hook<T> = Arc<dyn Fn(&T) + Send + Sync>;
+
+/// An ordered list of hooks for one kind of event. Hooks run in the order
+/// they were registered. Clones share the registered hooks.
+pub struct Hooks<T: ?Sized> {
+    hooks: Vec<Hook<T>>,
+}
+
+impl<T: ?Sized> Hooks<T> {
+    pub fn new() -> Self {
+        Self { hooks: Vec::new() }
+    }
+
+    pub fn push(&mut self, hook: impl Fn(&T) + Send + Sync + 'static) {
+        self.hooks.push(Arc::new(hook));
+    }
+
+    pub fn call(&self, value: &T) {
+        for hook in &self.hooks {
+            hook(value);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+}
+
+impl<T: ?Sized> Default for Hooks<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Clone for Hooks<T> {
+    fn clone(&self) -> Self {
+        Self {
+            hooks: self.hooks.clone(),
+        }
+    }
+}
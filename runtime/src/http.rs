@@ -0,0 +1,239 @@
+//! A small HTTP service for scheduling analysis.
+//!
+//! Built with the `http` feature. [`serve`] answers three endpoints, all
+//! taking and returning JSON:
+//!
+//! - `GET /health` returns `{"status": "ok"}`.
+//! - `POST /analyze` takes `{"tasks": [...]}` and returns the execution
+//!   levels, the per-level cost and the graph metrics.
+//! - `POST /execute` takes `{"tasks": [...], "seed": 0}` and runs the block
+//!   with the parallel executor on the deterministic runtime, returning the
+//!   levels, the order tasks actually completed in and the virtual time it
+//!   took. The same seed always returns the same answer.
+//!
+//! A task is `{"id", "name", "reads", "writes"}` with optional `"priority"`
+//! and `"cost"`. Ids must be `0..n` in order. Submitted tasks carry no code,
+//! so they execute as no-ops: the service is for looking at schedules, not
+//! at state. The server handles one request at a time on the calling thread,
+//! which is plenty for demos and classrooms.
+
+use std::io;
+
+use commonware_runtime::{
+    Runner,
+    deterministic::{Config, Runner as DeterministicRunner},
+};
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::parallel_determinism::{
+    dep_graph::DependencyGraph,
+    executor::ParallelExecutor,
+    types::{Task, TaskContext, TaskId},
+};
+
+fn no_work(_: &mut TaskContext) -> Result<String, String> {
+    Ok(String::new())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaskSpec {
+    pub id: TaskId,
+    pub name: String,
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    #[serde(default)]
+    pub priority: u8,
+    #[serde(default = "default_cost")]
+    pub cost: u64,
+}
+
+fn default_cost() -> u64 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskSet {
+    tasks: Vec<TaskSpec>,
+    #[serde(default)]
+    seed: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Metrics {
+    tasks: usize,
+    edges: usize,
+    average_out_degree: f64,
+    depth: usize,
+    max_width: usize,
+    conflict_density: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Analysis {
+    levels: Vec<Vec<TaskId>>,
+    level_costs: Vec<u64>,
+    metrics: Metrics,
+}
+
+#[derive(Debug, Serialize)]
+struct Execution {
+    seed: u64,
+    levels: Vec<Vec<TaskId>>,
+    completion_order: Vec<TaskId>,
+    elapsed_ms: u128,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+fn graph(tasks: Vec<TaskSpec>) -> Result<DependencyGraph, String> {
+    if let Some((position, task)) = tasks.iter().enumerate().find(|(i, t)| t.id != *i) {
+        return Err(format!(
+            "task at position {} has id {}; ids must be 0..n in order",
+            position, task.id
+        ));
+    }
+    Ok(DependencyGraph::from_tasks(tasks.into_iter().map(|spec| {
+        Task {
+            id: spec.id,
+            name: spec.name,
+            reads: spec.reads,
+            writes: spec.writes,
+            priority: spec.priority,
+            cost: spec.cost,
+            work: &no_work,
+        }
+    })))
+}
+
+fn analyze(graph: &DependencyGraph) -> Analysis {
+    let metrics = graph.metrics();
+    Analysis {
+        levels: graph.execution_levels(),
+        level_costs: graph.level_costs().iter().map(|level| level.max).collect(),
+        metrics: Metrics {
+            tasks: metrics.tasks,
+            edges: metrics.edges,
+            average_out_degree: metrics.average_out_degree,
+            depth: metrics.depth,
+            max_width: metrics.max_width,
+            conflict_density: metrics.conflict_density,
+        },
+    }
+}
+
+fn execute(graph: DependencyGraph, seed: u64) -> Execution {
+    let levels = graph.execution_levels();
+    let report = DeterministicRunner::new(Config::default().with_seed(seed))
+        .start(|context| async move { ParallelExecutor::new().execute(&context, &graph).await });
+    Execution {
+        seed,
+        levels,
+        completion_order: report.completion_order,
+        elapsed_ms: report.elapsed.as_millis(),
+    }
+}
+
+fn json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).expect("Response types always serialize")
+}
+
+fn error(status: u16, error: String) -> (u16, String) {
+    (status, json(&ErrorBody { error }))
+}
+
+/// Answer one request: the status code and the JSON body.
+pub fn handle(method: &str, path: &str, body: &str) -> (u16, String) {
+    match (method, path) {
+        ("GET", "/health") => (200, r#"{"status":"ok"}"#.to_string()),
+        ("POST", "/analyze" | "/execute") => {
+            let set: TaskSet = match serde_json::from_str(body) {
+                Ok(set) => set,
+                Err(e) => return error(400, format!("invalid task set: {}", e)),
+            };
+            let graph = match graph(set.tasks) {
+                Ok(graph) => graph,
+                Err(e) => return error(400, e),
+            };
+            if path == "/analyze" {
+                (200, json(&analyze(&graph)))
+            } else {
+                (200, json(&execute(graph, set.seed)))
+            }
+        }
+        (_, "/health" | "/analyze" | "/execute") => error(405, "method not allowed".to_string()),
+        _ => error(404, format!("no such endpoint: {}", path)),
+    }
+}
+
+/// Serve requests on `addr` (for example `"127.0.0.1:8080"`) until the
+/// process exits.
+pub fn serve(addr: &str) -> io::Result<()> {
+    let server = Server::http(addr).map_err(io::Error::other)?;
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let (status, response) = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => {
+                let method = match request.method() {
+                    Method::Get => "GET",
+                    Method::Post => "POST",
+                    _ => "OTHER",
+                };
+                handle(method, request.url(), &body)
+            }
+            Err(e) => error(400, format!("unreadable body: {}", e)),
+        };
+        let header =
+            Header::from_bytes("Content-Type", "application/json").expect("Static header is valid");
+        request.respond(
+            Response::from_string(response)
+                .with_status_code(status)
+                .with_header(header),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAIN: &str = r#"{"tasks": [
+        {"id": 0, "name": "a", "reads": [], "writes": ["x"], "cost": 3},
+        {"id": 1, "name": "b", "reads": [], "writes": ["y"]},
+        {"id": 2, "name": "c", "reads": ["x", "y"], "writes": []}
+    ], "seed": 4}"#;
+
+    /// Analysis returns levels, costs and metrics for a submitted task set.
+    #[test]
+    fn test_analyze() {
+        let (status, body) = handle("POST", "/analyze", CHAIN);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status, 200);
+        assert_eq!(body["levels"], serde_json::json!([[0, 1], [2]]));
+        assert_eq!(body["level_costs"], serde_json::json!([3, 1]));
+        assert_eq!(body["metrics"]["edges"], 2);
+    }
+
+    /// Execution is reproducible for a seed, and bad input is a client error.
+    #[test]
+    fn test_execute_and_errors() {
+        let (status, first) = handle("POST", "/execute", CHAIN);
+        assert_eq!(status, 200);
+        assert_eq!(handle("POST", "/execute", CHAIN).1, first);
+
+        let swapped = CHAIN.replacen(r#""id": 0"#, r#""id": 5"#, 1);
+        assert_eq!(handle("POST", "/execute", &swapped).0, 400);
+        assert_eq!(handle("POST", "/analyze", "not json").0, 400);
+        assert_eq!(handle("GET", "/execute", "").0, 405);
+        assert_eq!(handle("GET", "/nope", "").0, 404);
+        assert_eq!(
+            handle("GET", "/health", ""),
+            (200, r#"{"status":"ok"}"#.to_string())
+        );
+    }
+}
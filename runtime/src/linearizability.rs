@@ -0,0 +1,289 @@
+//! Checking concurrent histories against a sequential model.
+//!
+//! A history is linearizable if every operation can be placed at a single
+//! point between its invocation and its response such that, replayed in that
+//! order against the sequential [`Model`], each operation returns what it
+//! actually returned. That turns "the counter ended up right" into something
+//! stronger and machine-checked: every value every task observed is
+//! explainable by some serial order that respects real time.
+//!
+//! The checker is the Wing & Gong backtracking search with memoization on
+//! (operations linearized so far, model state). It is exponential in the
+//! worst case and meant for the short histories the demo workloads produce.
+
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+/// A sequential specification: applying an operation to the state yields its
+/// return value.
+pub trait Model: Clone + Eq + Hash {
+    type Op: Clone + Debug;
+    type Ret: Clone + PartialEq + Debug;
+
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret;
+}
+
+/// A counter starting at zero. `Increment` returns the new value.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Counter(pub u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterOp {
+    Increment,
+    Get,
+}
+
+impl Model for Counter {
+    type Op = CounterOp;
+    type Ret = u64;
+
+    fn apply(&mut self, op: &CounterOp) -> u64 {
+        if let CounterOp::Increment = op {
+            self.0 += 1;
+        }
+        self.0
+    }
+}
+
+/// A single read/write register. Reads return `Some(value)`, writes `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Register<T>(pub T);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegisterOp<T> {
+    Read,
+    Write(T),
+}
+
+impl<T: Clone + Debug + Eq + Hash> Model for Register<T> {
+    type Op = RegisterOp<T>;
+    type Ret = Option<T>;
+
+    fn apply(&mut self, op: &RegisterOp<T>) -> Option<T> {
+        match op {
+            RegisterOp::Read => Some(self.0.clone()),
+            RegisterOp::Write(value) => {
+                self.0 = value.clone();
+                None
+            }
+        }
+    }
+}
+
+/// One operation in a history. Times are positions in the history's own
+/// logical clock, so only their order matters.
+#[derive(Clone, Debug)]
+pub struct Entry<Op, Ret> {
+    pub client: usize,
+    pub op: Op,
+    pub invoked: u64,
+    /// `None` while the operation is still pending.
+    pub response: Option<(u64, Ret)>,
+}
+
+struct Recorded<Op, Ret> {
+    clock: u64,
+    entries: Vec<Entry<Op, Ret>>,
+}
+
+/// A shared recorder of invocations and responses. Clones record into the
+/// same history.
+pub struct History<Op, Ret> {
+    inner: Arc<Mutex<Recorded<Op, Ret>>>,
+}
+
+impl<Op, Ret> Clone for History<Op, Ret> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Op, Ret> Default for History<Op, Ret> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Recorded {
+                clock: 0,
+                entries: Vec::new(),
+            })),
+        }
+    }
+}
+
+impl<Op: Clone, Ret: Clone> History<Op, Ret> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `client` invoked `op`. Returns the id to respond to.
+    pub fn invoke(&self, client: usize, op: Op) -> usize {
+        let mut recorded = self.inner.lock().unwrap();
+        let invoked = recorded.clock;
+        recorded.clock += 1;
+        recorded.entries.push(Entry {
+            client,
+            op,
+            invoked,
+            response: None,
+        });
+        recorded.entries.len() - 1
+    }
+
+    pub fn respond(&self, id: usize, ret: Ret) {
+        let mut recorded = self.inner.lock().unwrap();
+        let responded = recorded.clock;
+        recorded.clock += 1;
+        recorded.entries[id].response = Some((responded, ret));
+    }
+
+    pub fn entries(&self) -> Vec<Entry<Op, Ret>> {
+        self.inner.lock().unwrap().entries.clone()
+    }
+}
+
+/// No serial order explains the history. `longest_prefix` is the deepest
+/// partial linearization the search reached, as entry ids; the next operation
+/// after it is usually where the anomaly is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NotLinearizable {
+    pub longest_prefix: Vec<usize>,
+}
+
+/// Check `history` against `initial`. On success, returns the entry ids in a
+/// valid linearization order. Pending operations may be placed anywhere after
+/// their invocation, with any return value, or left out entirely.
+pub fn check<M: Model>(
+    initial: M,
+    history: &History<M::Op, M::Ret>,
+) -> Result<Vec<usize>, NotLinearizable> {
+    let entries = history.entries();
+    let mut search = Search {
+        entries: &entries,
+        linearized: vec![false; entries.len()],
+        order: Vec::new(),
+        longest_prefix: Vec::new(),
+        seen: HashSet::new(),
+    };
+    if search.extend(initial) {
+        Ok(search.order)
+    } else {
+        Err(NotLinearizable {
+            longest_prefix: search.longest_prefix,
+        })
+    }
+}
+
+struct Search<'a, M: Model> {
+    entries: &'a [Entry<M::Op, M::Ret>],
+    linearized: Vec<bool>,
+    order: Vec<usize>,
+    longest_prefix: Vec<usize>,
+    /// Configurations already shown to be dead ends.
+    seen: HashSet<(Vec<bool>, M)>,
+}
+
+impl<M: Model> Search<'_, M> {
+    fn extend(&mut self, state: M) -> bool {
+        if self.order.len() > self.longest_prefix.len() {
+            self.longest_prefix = self.order.clone();
+        }
+        let completed_remaining = self
+            .entries
+            .iter()
+            .enumerate()
+            .any(|(id, entry)| !self.linearized[id] && entry.response.is_some());
+        if !completed_remaining {
+            return true;
+        }
+        if !self.seen.insert((self.linearized.clone(), state.clone())) {
+            return false;
+        }
+
+        // An operation can go next only if no other remaining operation
+        // responded before it was invoked.
+        let earliest_response = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| !self.linearized[*id])
+            .filter_map(|(_, entry)| entry.response.as_ref().map(|(at, _)| *at))
+            .min()
+            .unwrap_or(u64::MAX);
+
+        for id in 0..self.entries.len() {
+            let entry = &self.entries[id];
+            if self.linearized[id] || entry.invoked > earliest_response {
+                continue;
+            }
+            let mut next = state.clone();
+            let ret = next.apply(&entry.op);
+            if let Some((_, expected)) = &entry.response
+                && ret != *expected
+            {
+                continue;
+            }
+            self.linearized[id] = true;
+            self.order.push(id);
+            if self.extend(next) {
+                return true;
+            }
+            self.order.pop();
+            self.linearized[id] = false;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Overlapping increments may be ordered either way, but two increments
+    /// that both returned 1 cannot both have happened.
+    #[test]
+    fn test_counter_histories() {
+        let ok = History::new();
+        let a = ok.invoke(0, CounterOp::Increment);
+        let b = ok.invoke(1, CounterOp::Increment);
+        ok.respond(a, 2);
+        ok.respond(b, 1);
+        assert_eq!(check(Counter::default(), &ok), Ok(vec![b, a]));
+
+        let lost = History::new();
+        let a = lost.invoke(0, CounterOp::Increment);
+        let b = lost.invoke(1, CounterOp::Increment);
+        lost.respond(a, 1);
+        lost.respond(b, 1);
+        let get = lost.invoke(0, CounterOp::Get);
+        lost.respond(get, 1);
+        assert!(check(Counter::default(), &lost).is_err());
+    }
+
+    /// A read that returns a value written strictly after it completed is a
+    /// stale-future read; a pending write may or may not have taken effect.
+    #[test]
+    fn test_register_histories() {
+        let future_read = History::new();
+        let read = future_read.invoke(0, RegisterOp::Read);
+        future_read.respond(read, Some(7));
+        let write = future_read.invoke(1, RegisterOp::Write(7));
+        future_read.respond(write, None);
+        assert_eq!(
+            check(Register(0), &future_read),
+            Err(NotLinearizable {
+                longest_prefix: vec![]
+            })
+        );
+
+        let pending = History::new();
+        pending.invoke(1, RegisterOp::Write(7));
+        let read = pending.invoke(0, RegisterOp::Read);
+        pending.respond(read, Some(7));
+        assert!(check(Register(0), &pending).is_ok());
+    }
+}
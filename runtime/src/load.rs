@@ -0,0 +1,320 @@
+//! A client node that puts reproducible load on a simulated cluster.
+//!
+//! A [`LoadGenerator`] drives one [`SimNode`] as an open-loop client: it
+//! sends requests at times drawn from a Poisson process of the configured
+//! rate, whether or not earlier ones have been answered, the way independent
+//! users would. The gaps between requests and the server each goes to come
+//! from a [`DeterministicRng`], so under the deterministic runtime a seed
+//! fixes the whole arrival schedule, and with it the latency every request
+//! sees in virtual time. Two versions of a protocol can then be compared
+//! under exactly the same load.
+//!
+//! Requests are [`RpcMessage`]s, so servers built on [`RpcNode`] answer
+//! them unchanged. Latency is measured on the generator node's clock, which
+//! is virtual time unless the node was given a [`Skew`](crate::skew::Skew).
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use commonware_runtime::{Clock, Spawner};
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::{
+    parallel_determinism::types::Value,
+    rng::DeterministicRng,
+    rpc::{RpcMessage, RpcNode},
+    simnet::{NodeId, SimNet, SimNode},
+    stats::{LatencyHistogram, Percentiles},
+    trace::EventLog,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoadConfig {
+    pub requests: usize,
+    /// Mean requests per second.
+    pub rate: f64,
+    /// How long a request may go unanswered before it counts as timed out.
+    pub timeout: Duration,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            requests: 100,
+            rate: 100.0,
+            timeout: Duration::from_millis(100),
+        }
+    }
+}
+
+impl LoadConfig {
+    pub fn with_requests(mut self, requests: usize) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    pub fn with_rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// One request the generator sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestRecord {
+    pub id: u64,
+    pub to: NodeId,
+    /// Time since the generator started.
+    pub sent_at: Duration,
+    /// How long the response took, if it came within the timeout.
+    pub latency: Option<Duration>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Every request, in the order sent.
+    pub requests: Vec<RequestRecord>,
+}
+
+impl LoadReport {
+    pub fn completed(&self) -> usize {
+        self.requests
+            .iter()
+            .filter(|request| request.latency.is_some())
+            .count()
+    }
+
+    pub fn timed_out(&self) -> usize {
+        self.requests.len() - self.completed()
+    }
+
+    /// Latency percentiles of the completed requests.
+    pub fn percentiles(&self) -> Percentiles {
+        let mut histogram = LatencyHistogram::new();
+        for latency in self.requests.iter().filter_map(|request| request.latency) {
+            histogram.record(latency);
+        }
+        histogram.percentiles()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests, {} completed, {} timed out, {}",
+            self.requests.len(),
+            self.completed(),
+            self.timed_out(),
+            self.percentiles()
+        )
+    }
+}
+
+pub struct LoadGenerator<C, Req, Resp> {
+    node: SimNode<C, RpcMessage<Req, Resp>>,
+    targets: Vec<NodeId>,
+    rng: DeterministicRng,
+    config: LoadConfig,
+}
+
+impl<C, Req, Resp> LoadGenerator<C, Req, Resp>
+where
+    C: Spawner + Clock,
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    /// Send requests through `node` to servers picked from `targets`,
+    /// drawing arrival times and targets from `rng`.
+    pub fn new(
+        node: SimNode<C, RpcMessage<Req, Resp>>,
+        targets: Vec<NodeId>,
+        rng: DeterministicRng,
+    ) -> Self {
+        assert!(!targets.is_empty(), "Load should have somewhere to go");
+        Self {
+            node,
+            targets,
+            rng,
+            config: LoadConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: LoadConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Send every request, built by `request` from its id, then wait out
+    /// the timeout of the last one or until every request is answered.
+    pub async fn run(mut self, mut request: impl FnMut(u64) -> Req) -> LoadReport {
+        let start = self.node.local_time();
+        let mut requests: Vec<RequestRecord> = Vec::with_capacity(self.config.requests);
+        let mut outstanding = BTreeMap::new();
+        let mut next = Duration::ZERO;
+        for id in 0..self.config.requests as u64 {
+            self.collect(start, start + next, &mut requests, &mut outstanding)
+                .await;
+            let to = *self.targets.choose(&mut self.rng).unwrap();
+            self.node.send(
+                to,
+                RpcMessage::Request {
+                    id,
+                    body: request(id),
+                },
+            );
+            outstanding.insert(id, requests.len());
+            requests.push(RequestRecord {
+                id,
+                to,
+                sent_at: self.node.local_time() - start,
+                latency: None,
+            });
+            next += self.interarrival();
+        }
+        if let Some(last) = requests.last() {
+            let deadline = start + last.sent_at + self.config.timeout;
+            self.collect(start, deadline, &mut requests, &mut outstanding)
+                .await;
+        }
+        LoadReport { requests }
+    }
+
+    /// Record responses until `deadline` on the node's clock, or until none
+    /// are outstanding once every request is sent. Both are measured like
+    /// `start`, the node's time when the run began.
+    async fn collect(
+        &mut self,
+        start: Duration,
+        deadline: Duration,
+        requests: &mut [RequestRecord],
+        outstanding: &mut BTreeMap<u64, usize>,
+    ) {
+        loop {
+            let now = self.node.local_time();
+            let all_sent = requests.len() == self.config.requests;
+            if now >= deadline || (all_sent && outstanding.is_empty()) {
+                return;
+            }
+            let Some(envelope) = self.node.recv_timeout(deadline - now).await else {
+                return;
+            };
+            let RpcMessage::Response { id, .. } = envelope.message else {
+                continue;
+            };
+            if let Some(index) = outstanding.remove(&id) {
+                let record = &mut requests[index];
+                let latency = self.node.local_time() - start - record.sent_at;
+                if latency <= self.config.timeout {
+                    record.latency = Some(latency);
+                }
+            }
+        }
+    }
+
+    /// The gap before the next request: exponential with mean `1 / rate`.
+    fn interarrival(&mut self) -> Duration {
+        let uniform: f64 = self.rng.random();
+        Duration::from_secs_f64(-(1.0 - uniform).ln() / self.config.rate)
+    }
+}
+
+/// Node 0 loads `servers` servers, nodes 1 and up, with `config`'s
+/// requests. Each server answers one request at a time, taking
+/// `service_time` per request, so a rate the servers cannot keep up with
+/// builds queues and latency.
+pub async fn load_test<C: Spawner + Clock>(
+    context: &C,
+    config: LoadConfig,
+    servers: usize,
+    service_time: Duration,
+    seed: u64,
+    log: &EventLog,
+) -> LoadReport {
+    let net = SimNet::new(
+        context.clone(),
+        servers + 1,
+        DeterministicRng::derived(seed, "load-network", 0),
+        log.clone(),
+    );
+    let serving: Vec<_> = (1..=servers)
+        .map(|id| {
+            let mut server: RpcNode<C, Value, Value> = RpcNode::new(net.node(id));
+            context.clone().spawn(move |context| async move {
+                loop {
+                    let request = server.next_request().await;
+                    context.sleep(service_time).await;
+                    server.respond(&request, request.body);
+                }
+            })
+        })
+        .collect();
+    let generator = LoadGenerator::new(
+        net.node(0),
+        (1..=servers).collect(),
+        DeterministicRng::derived(seed, "load-arrivals", 0),
+    )
+    .with_config(config);
+    let report = generator.run(|id| id as Value).await;
+    for handle in serving {
+        handle.abort();
+    }
+    report
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    fn run(config: LoadConfig, seed: u64) -> LoadReport {
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            load_test(
+                &context,
+                config,
+                2,
+                Duration::from_millis(5),
+                seed,
+                &EventLog::discarding(),
+            )
+            .await
+        })
+    }
+
+    /// A seed fixes when every request goes out, where, and how long it
+    /// takes; another seed draws another schedule.
+    #[test]
+    fn test_load_replays() {
+        let report = run(LoadConfig::default(), 1);
+
+        assert_eq!(report.requests.len(), 100);
+        assert!(report.requests.is_sorted_by_key(|request| request.sent_at));
+        assert!(report.requests.iter().any(|request| request.to == 1));
+        assert!(report.requests.iter().any(|request| request.to == 2));
+        assert_eq!(run(LoadConfig::default(), 1), report);
+        assert_ne!(run(LoadConfig::default(), 2), report);
+    }
+
+    /// Below capacity a typical request takes the service time plus two
+    /// trips across the network; well above it queues build until requests
+    /// time out.
+    #[test]
+    fn test_latency_grows_with_rate() {
+        let light = run(LoadConfig::default().with_rate(50.0), 1);
+        let heavy = run(LoadConfig::default().with_rate(1_000.0), 1);
+
+        assert_eq!(light.timed_out(), 0);
+        assert!(light.percentiles().p50 < Duration::from_millis(25));
+        assert!(heavy.percentiles().p99 > light.percentiles().p99);
+        assert!(heavy.timed_out() > 0);
+        assert!(heavy.to_string().starts_with("100 requests"));
+    }
+}
@@ -0,0 +1,170 @@
+//! A Merkle root over a corpus, computed in parallel.
+//!
+//! [`merkle_root`] splits a [`Corpus`] into chunks of consecutive words and
+//! spawns one task per chunk. Each task sorts its chunk's words and hashes
+//! them into a leaf. The leaves are then folded pairwise, left to right, one
+//! level at a time, with an odd node carried up unchanged. The tasks may
+//! finish in any order on any runtime, but leaves are collected by chunk
+//! position and the fold order is fixed, so the root depends only on the
+//! corpus and the chunk size. A whole parallel run can be checked for
+//! determinism by comparing one `u64`.
+//!
+//! The leaf work is all CPU, so the same call doubles as a benchmark of how
+//! well a runtime spreads compute across its workers (see
+//! `benches/merkle.rs`). [`merkle_root_sequential`] computes the same root
+//! on the calling thread, as the reference.
+
+use std::sync::Arc;
+
+use commonware_runtime::Spawner;
+
+use crate::{corpus::Corpus, parallel_determinism::hash::Fnv};
+
+/// Domain tags, so a leaf can never be mistaken for an interior node.
+const LEAF: u8 = 0;
+const NODE: u8 = 1;
+
+fn leaf<'a>(words: impl Iterator<Item = &'a str>) -> u64 {
+    let mut words: Vec<&str> = words.collect();
+    words.sort_unstable();
+    let mut hasher = Fnv::new();
+    hasher.update(&[LEAF]);
+    for word in words {
+        hasher.update(&(word.len() as u64).to_le_bytes());
+        hasher.update(word.as_bytes());
+    }
+    hasher.finish()
+}
+
+fn node(left: u64, right: u64) -> u64 {
+    let mut hasher = Fnv::new();
+    hasher.update(&[NODE]);
+    hasher.update(&left.to_le_bytes());
+    hasher.update(&right.to_le_bytes());
+    hasher.finish()
+}
+
+/// Fold `leaves` into a root. An empty tree has the root of an empty leaf.
+fn fold(mut level: Vec<u64>) -> u64 {
+    if level.is_empty() {
+        return leaf(std::iter::empty());
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match *pair {
+                [left, right] => node(left, right),
+                [odd] => odd,
+                _ => unreachable!("chunks(2) yields one or two nodes"),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+fn chunks(corpus: &Corpus, chunk_words: usize) -> impl Iterator<Item = std::ops::Range<usize>> {
+    assert!(chunk_words > 0, "Chunks must hold at least one word");
+    let len = corpus.len();
+    (0..len)
+        .step_by(chunk_words)
+        .map(move |start| start..(start + chunk_words).min(len))
+}
+
+/// The Merkle root of `corpus` in chunks of `chunk_words` words, with one
+/// task per chunk spawned on `context`.
+pub async fn merkle_root<S: Spawner>(context: &S, corpus: Arc<Corpus>, chunk_words: usize) -> u64 {
+    let handles: Vec<_> = chunks(&corpus, chunk_words)
+        .map(|range| {
+            let corpus = corpus.clone();
+            context
+                .clone()
+                .spawn(move |_| async move { leaf(corpus.words_in(range)) })
+        })
+        .collect();
+
+    let mut leaves = Vec::with_capacity(handles.len());
+    for handle in handles {
+        leaves.push(handle.await.expect("Leaf task should run to completion"));
+    }
+    fold(leaves)
+}
+
+/// The same root as [`merkle_root`], computed on the calling thread.
+pub fn merkle_root_sequential(corpus: &Corpus, chunk_words: usize) -> u64 {
+    fold(
+        chunks(corpus, chunk_words)
+            .map(|range| leaf(corpus.words_in(range)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::tasks::read_file;
+
+    fn deterministic_root(seed: u64, corpus: Arc<Corpus>, chunk_words: usize) -> u64 {
+        DeterministicRunner::new(Config::default().with_seed(seed))
+            .start(|context| async move { merkle_root(&context, corpus, chunk_words).await })
+    }
+
+    /// Every seed yields the sequential root, for chunk sizes that leave odd
+    /// levels and a short last chunk.
+    #[test]
+    fn test_parallel_root_matches_sequential() {
+        let corpus = Arc::new(read_file());
+        for chunk_words in [1, 7, 64, corpus.len()] {
+            let expected = merkle_root_sequential(&corpus, chunk_words);
+            for seed in 0..5 {
+                assert_eq!(
+                    deterministic_root(seed, corpus.clone(), chunk_words),
+                    expected
+                );
+            }
+        }
+    }
+
+    /// Tokio's worker pool finishes leaves in whatever order it likes and
+    /// still produces the same root.
+    #[cfg(feature = "tokio-backend")]
+    #[test]
+    fn test_tokio_root_matches_sequential() {
+        use commonware_runtime::tokio::{Config as TokioConfig, Runner as TokioRunner};
+
+        let corpus = Arc::new(read_file());
+        let expected = merkle_root_sequential(&corpus, 16);
+        let root = TokioRunner::new(TokioConfig::default().with_worker_threads(4))
+            .start(|context| async move { merkle_root(&context, corpus, 16).await });
+
+        assert_eq!(root, expected);
+    }
+
+    /// The root commits to which words fall in which chunk, but not to their
+    /// order inside a chunk.
+    #[test]
+    fn test_root_commits_to_chunks() {
+        let corpus = Corpus::new("a b c d".into());
+
+        assert_ne!(
+            merkle_root_sequential(&corpus, 2),
+            merkle_root_sequential(&corpus, 4)
+        );
+        assert_eq!(
+            merkle_root_sequential(&corpus, 2),
+            merkle_root_sequential(&Corpus::new("b a d c".into()), 2)
+        );
+        assert_ne!(
+            merkle_root_sequential(&corpus, 2),
+            merkle_root_sequential(&Corpus::new("c d a b".into()), 2)
+        );
+        assert_eq!(
+            merkle_root_sequential(&Corpus::new(String::new()), 3),
+            leaf(std::iter::empty())
+        );
+    }
+}
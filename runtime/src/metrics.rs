@@ -0,0 +1,313 @@
+//! Prometheus metrics for long-running simulations.
+//!
+//! Built with the `metrics` feature. Metrics are registered through the
+//! Commonware runtime's own [`Metrics`] context, so they are encoded
+//! alongside whatever the runtime exports itself and prefixed with the
+//! context's label. [`ExecutorMetrics`] counts task starts, completions and
+//! failures, levels and their widths, and optimistic re-executions;
+//! [`SpawnerMetrics`] counts tasks spawned through a [`TaskSpawner`], their
+//! completions and every poll. Both attach through the existing hooks, so
+//! instrumented code runs exactly as it would without them.
+//!
+//! [`serve`] answers `GET /metrics` with the encoded registry, for a
+//! Prometheus server to scrape while a simulation runs on another thread.
+
+use std::io;
+
+use commonware_runtime::{Metrics, Spawner};
+use prometheus_client::metrics::{
+    counter::Counter,
+    histogram::{Histogram, exponential_buckets},
+};
+use tiny_http::{Header, Response, Server};
+
+use crate::{
+    parallel_determinism::{executor::ParallelExecutor, optimistic::OptimisticReport},
+    spawn::TaskSpawner,
+};
+
+/// Counters and the level-width histogram for the graph executors.
+#[derive(Clone)]
+pub struct ExecutorMetrics {
+    tasks_started: Counter,
+    tasks_completed: Counter,
+    tasks_failed: Counter,
+    levels: Counter,
+    level_width: Histogram,
+    reexecutions: Counter,
+}
+
+impl ExecutorMetrics {
+    /// Create the metrics and register them with `context`.
+    pub fn register(context: &impl Metrics) -> Self {
+        let metrics = Self {
+            tasks_started: Counter::default(),
+            tasks_completed: Counter::default(),
+            tasks_failed: Counter::default(),
+            levels: Counter::default(),
+            level_width: Histogram::new(exponential_buckets(1.0, 2.0, 10)),
+            reexecutions: Counter::default(),
+        };
+        context.register(
+            "tasks_started",
+            "Tasks whose work has started",
+            metrics.tasks_started.clone(),
+        );
+        context.register(
+            "tasks_completed",
+            "Tasks that produced a receipt",
+            metrics.tasks_completed.clone(),
+        );
+        context.register(
+            "tasks_failed",
+            "Tasks whose work returned an error",
+            metrics.tasks_failed.clone(),
+        );
+        context.register(
+            "levels",
+            "Execution levels committed",
+            metrics.levels.clone(),
+        );
+        context.register(
+            "level_width",
+            "Tasks per committed level",
+            metrics.level_width.clone(),
+        );
+        context.register(
+            "reexecutions",
+            "Tasks the optimistic executor ran a second time",
+            metrics.reexecutions.clone(),
+        );
+        metrics
+    }
+
+    /// Attach hooks to `executor` that update these metrics.
+    pub fn instrument(&self, executor: ParallelExecutor) -> ParallelExecutor {
+        let (started, completed, failed) = (
+            self.tasks_started.clone(),
+            self.tasks_completed.clone(),
+            self.tasks_failed.clone(),
+        );
+        let (levels, level_width) = (self.levels.clone(), self.level_width.clone());
+        executor
+            .on_task_start(move |_| {
+                started.inc();
+            })
+            .on_task_end(move |receipt| {
+                completed.inc();
+                if receipt.output.is_err() {
+                    failed.inc();
+                }
+            })
+            .on_level_complete(move |level| {
+                levels.inc();
+                level_width.observe(level.tasks.len() as f64);
+            })
+    }
+
+    /// Count the re-executions of an optimistic run.
+    pub fn record_reexecutions(&self, report: &OptimisticReport) {
+        self.reexecutions.inc_by(report.reexecuted.len() as u64);
+    }
+}
+
+/// Counters for tasks spawned through a [`TaskSpawner`].
+#[derive(Clone)]
+pub struct SpawnerMetrics {
+    spawned: Counter,
+    finished: Counter,
+    polls: Counter,
+}
+
+impl SpawnerMetrics {
+    /// Create the metrics and register them with `context`.
+    pub fn register(context: &impl Metrics) -> Self {
+        let metrics = Self {
+            spawned: Counter::default(),
+            finished: Counter::default(),
+            polls: Counter::default(),
+        };
+        context.register(
+            "tasks_spawned",
+            "Tasks that started running",
+            metrics.spawned.clone(),
+        );
+        context.register(
+            "tasks_finished",
+            "Tasks whose future completed",
+            metrics.finished.clone(),
+        );
+        context.register("polls", "Polls of spawned tasks", metrics.polls.clone());
+        metrics
+    }
+
+    /// Attach hooks to `spawner` that update these metrics, for its tasks
+    /// and all their children.
+    pub fn instrument<S: Spawner>(&self, spawner: TaskSpawner<S>) -> TaskSpawner<S> {
+        let (spawned, finished, polls) = (
+            self.spawned.clone(),
+            self.finished.clone(),
+            self.polls.clone(),
+        );
+        spawner
+            .on_task_start(move |_| {
+                spawned.inc();
+            })
+            .on_task_poll(move |_| {
+                polls.inc();
+            })
+            .on_task_end(move |_| {
+                finished.inc();
+            })
+    }
+}
+
+/// Answer one scrape: the status code and the body.
+fn respond(method: &str, path: &str, encode: impl Fn() -> String) -> (u16, String) {
+    match (method, path) {
+        ("GET", "/metrics") => (200, encode()),
+        (_, "/metrics") => (405, "method not allowed\n".to_string()),
+        _ => (404, format!("no such endpoint: {}\n", path)),
+    }
+}
+
+/// Serve `GET /metrics` on `addr` until the process exits, encoding the
+/// registry afresh for every scrape. Pass the runtime context's
+/// [`Metrics::encode`], for example `move || context.encode()`.
+pub fn serve(addr: &str, encode: impl Fn() -> String) -> io::Result<()> {
+    let server = Server::http(addr).map_err(io::Error::other)?;
+    for request in server.incoming_requests() {
+        let (status, body) = respond(request.method().as_str(), request.url(), &encode);
+        let header = Header::from_bytes(
+            "Content-Type",
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )
+        .expect("Static header is valid");
+        request.respond(
+            Response::from_string(body)
+                .with_status_code(status)
+                .with_header(header),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use commonware_runtime::{
+        Clock, Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::{
+        parallel_determinism::{
+            dep_graph::DependencyGraph,
+            generator::{BlockSpec, generate_tasks},
+            optimistic::OptimisticExecutor,
+            state::MemoryStorage,
+        },
+        trace::EventLog,
+    };
+
+    /// Lines of `encoded` for the metric `name`, without comments.
+    fn samples<'a>(encoded: &'a str, name: &str) -> Vec<&'a str> {
+        encoded
+            .lines()
+            .filter(|line| line.starts_with(name))
+            .collect()
+    }
+
+    /// Executor metrics count every task and level of a block, and
+    /// re-executions of an optimistic run.
+    #[test]
+    fn test_executor_metrics() {
+        let graph = DependencyGraph::from_tasks(generate_tasks(&BlockSpec {
+            size: 12,
+            conflict_rate: 0.6,
+            seed: 5,
+        }));
+        let levels = graph.execution_levels().len();
+        let encoded =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let metrics = ExecutorMetrics::register(&context.with_label("executor"));
+                metrics
+                    .instrument(ParallelExecutor::new())
+                    .execute(&context, &graph)
+                    .await;
+                let optimistic = OptimisticExecutor::new()
+                    .execute_with_state(&context, &graph, &MemoryStorage::new())
+                    .await;
+                metrics.record_reexecutions(&optimistic);
+                context.encode()
+            });
+
+        assert_eq!(
+            samples(&encoded, "executor_tasks_started_total"),
+            ["executor_tasks_started_total 12"]
+        );
+        assert_eq!(
+            samples(&encoded, "executor_tasks_completed_total"),
+            ["executor_tasks_completed_total 12"]
+        );
+        assert_eq!(
+            samples(&encoded, "executor_levels_total"),
+            [format!("executor_levels_total {}", levels)]
+        );
+        assert_eq!(
+            samples(&encoded, "executor_level_width_count"),
+            [format!("executor_level_width_count {}", levels)]
+        );
+        assert!(
+            samples(&encoded, "executor_reexecutions_total")[0] != "executor_reexecutions_total 0"
+        );
+    }
+
+    /// Spawner metrics count children too, and every task is polled at
+    /// least once per suspension.
+    #[test]
+    fn test_spawner_metrics() {
+        let encoded =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let metrics = SpawnerMetrics::register(&context.with_label("spawner"));
+                let spawner =
+                    metrics.instrument(TaskSpawner::new(context.clone(), EventLog::new()));
+                let parent = spawner.spawn(|scope| async move {
+                    let child = scope.spawner().spawn(|scope| async move {
+                        scope.context().sleep(Duration::from_millis(1)).await;
+                    });
+                    child.await.unwrap();
+                });
+                parent.await.unwrap();
+                context.encode()
+            });
+
+        assert_eq!(
+            samples(&encoded, "spawner_tasks_spawned_total"),
+            ["spawner_tasks_spawned_total 2"]
+        );
+        assert_eq!(
+            samples(&encoded, "spawner_tasks_finished_total"),
+            ["spawner_tasks_finished_total 2"]
+        );
+        let polls: u64 = samples(&encoded, "spawner_polls_total")[0]
+            .rsplit(' ')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(polls >= 4);
+    }
+
+    /// Only `GET /metrics` is served.
+    #[test]
+    fn test_respond() {
+        let encode = || "up 1\n".to_string();
+
+        assert_eq!(respond("GET", "/metrics", encode), (200, "up 1\n".into()));
+        assert_eq!(respond("POST", "/metrics", encode).0, 405);
+        assert_eq!(respond("GET", "/", encode).0, 404);
+    }
+}
@@ -0,0 +1,198 @@
+//! Reproducible mixes of the task types in [`crate::tasks`].
+//!
+//! Fairness comparisons between runtimes are only meaningful if both run the
+//! same work. A [`WorkloadMix`] fixes the proportions of CPU-bound, I/O-bound
+//! and greedy tasks, and [`WorkloadMix::generate`] turns it into a concrete,
+//! seed-determined list that can be spawned on either runtime.
+
+use std::sync::{Arc, Mutex};
+
+use commonware_runtime::{Clock, Spawner};
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::tasks::{cpu_cooperative, greedy_task, io_bound};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkloadKind {
+    /// Long computation that yields periodically.
+    CpuBound,
+    /// Mostly waiting on (simulated) I/O.
+    IoBound,
+    /// Long computation that never yields.
+    Greedy,
+}
+
+impl WorkloadKind {
+    pub async fn run(&self, context: &impl Clock) {
+        match self {
+            WorkloadKind::CpuBound => cpu_cooperative(context).await,
+            WorkloadKind::IoBound => io_bound(context).await,
+            WorkloadKind::Greedy => greedy_task(),
+        }
+    }
+}
+
+/// Percentages of each task type, adding up to 100. The fields are private
+/// so every mix goes through [`WorkloadMix::new`]'s check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorkloadMix {
+    cpu_bound: u8,
+    io_bound: u8,
+    greedy: u8,
+}
+
+impl WorkloadMix {
+    pub fn new(cpu_bound: u8, io_bound: u8, greedy: u8) -> Self {
+        assert_eq!(
+            cpu_bound as u32 + io_bound as u32 + greedy as u32,
+            100,
+            "Workload percentages should add up to 100"
+        );
+        Self {
+            cpu_bound,
+            io_bound,
+            greedy,
+        }
+    }
+
+    pub fn cpu_bound(&self) -> u8 {
+        self.cpu_bound
+    }
+
+    pub fn io_bound(&self) -> u8 {
+        self.io_bound
+    }
+
+    pub fn greedy(&self) -> u8 {
+        self.greedy
+    }
+
+    /// Expand the mix into `count` tasks in a seed-determined order.
+    ///
+    /// Counts are exact: each type gets its share rounded down, and the tasks
+    /// left over go one each to the types with the largest fractional
+    /// shares, so a type at 0% never gets one. Two runs with the same mix
+    /// always contain the same tasks; only the seed decides the order they
+    /// are spawned in.
+    pub fn generate(&self, count: usize, seed: u64) -> Vec<WorkloadKind> {
+        let shares = [
+            (WorkloadKind::CpuBound, self.cpu_bound as usize),
+            (WorkloadKind::IoBound, self.io_bound as usize),
+            (WorkloadKind::Greedy, self.greedy as usize),
+        ];
+        let mut counts = shares.map(|(_, percent)| count * percent / 100);
+        let left_over = count - counts.iter().sum::<usize>();
+        // A 0% type has no fractional share, so it is never picked here.
+        let mut by_fraction: Vec<_> = (0..shares.len()).collect();
+        by_fraction.sort_by_key(|&i| std::cmp::Reverse(count * shares[i].1 % 100));
+        for &i in &by_fraction[..left_over] {
+            counts[i] += 1;
+        }
+
+        let mut kinds: Vec<_> = shares
+            .iter()
+            .zip(counts)
+            .flat_map(|(&(kind, _), count)| std::iter::repeat_n(kind, count))
+            .collect();
+        kinds.shuffle(&mut StdRng::seed_from_u64(seed));
+        kinds
+    }
+}
+
+/// Spawn every task as a sibling, wait for all of them, and return the task
+/// indices in the order they finished.
+pub async fn spawn_mix<C: Spawner + Clock>(context: &C, kinds: &[WorkloadKind]) -> Vec<usize> {
+    let finished = Arc::new(Mutex::new(Vec::with_capacity(kinds.len())));
+    let handles: Vec<_> = kinds
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(index, kind)| {
+            let finished = finished.clone();
+            context.clone().spawn(move |context| async move {
+                kind.run(&context).await;
+                finished.lock().unwrap().push(index);
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle
+            .await
+            .expect("Workload task should run to completion");
+    }
+    std::mem::take(&mut *finished.lock().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    /// The generated list honors the percentages exactly.
+    #[test]
+    fn test_generate_counts() {
+        let kinds = WorkloadMix::new(50, 30, 20).generate(10, 1);
+        let count = |kind| kinds.iter().filter(|k| **k == kind).count();
+
+        assert_eq!(count(WorkloadKind::CpuBound), 5);
+        assert_eq!(count(WorkloadKind::IoBound), 3);
+        assert_eq!(count(WorkloadKind::Greedy), 2);
+    }
+
+    /// Left-over tasks go to the largest fractional shares, never to a type
+    /// at 0%.
+    #[test]
+    fn test_generate_remainder() {
+        let count = |kinds: &[WorkloadKind], kind| kinds.iter().filter(|k| **k == kind).count();
+
+        let kinds = WorkloadMix::new(50, 50, 0).generate(3, 1);
+        assert_eq!(kinds.len(), 3);
+        assert_eq!(count(&kinds, WorkloadKind::Greedy), 0);
+
+        let kinds = WorkloadMix::new(33, 0, 67).generate(7, 1);
+        assert_eq!(count(&kinds, WorkloadKind::CpuBound), 2);
+        assert_eq!(count(&kinds, WorkloadKind::IoBound), 0);
+        assert_eq!(count(&kinds, WorkloadKind::Greedy), 5);
+
+        for total in [1, 2, 3, 7, 99, 101, 250] {
+            let kinds = WorkloadMix::new(0, 100, 0).generate(total, 1);
+            assert_eq!(count(&kinds, WorkloadKind::IoBound), total);
+        }
+    }
+
+    /// A mix that does not add up to 100 cannot be built.
+    #[test]
+    #[should_panic(expected = "add up to 100")]
+    fn test_new_rejects_bad_percentages() {
+        WorkloadMix::new(50, 30, 30);
+    }
+
+    /// Same seed, same order; different seed, (very likely) different order.
+    #[test]
+    fn test_generate_is_seeded() {
+        let mix = WorkloadMix::new(40, 40, 20);
+
+        assert_eq!(mix.generate(20, 7), mix.generate(20, 7));
+        assert_ne!(mix.generate(20, 7), mix.generate(20, 8));
+    }
+
+    /// An I/O-only mix completes in the same order on every deterministic run.
+    #[test]
+    fn test_spawn_mix_replays() {
+        let run = || {
+            DeterministicRunner::new(Config::default().with_seed(3)).start(|context| async move {
+                let kinds = WorkloadMix::new(0, 100, 0).generate(4, 3);
+                spawn_mix(&context, &kinds).await
+            })
+        };
+
+        let first = run();
+        assert_eq!(first.len(), 4);
+        assert_eq!(first, run());
+    }
+}
@@ -0,0 +1,285 @@
+//! A notify primitive whose wakeup order is part of the schedule.
+//!
+//! Tokio's `Notify` wakes waiters in an order it does not promise, so a demo
+//! that coordinates through it can replay differently even on a
+//! deterministic runtime. A [`DeterministicNotify`] keeps its waiters in
+//! line: first by when they registered on the runtime's clock, then by task
+//! id, so waiters that registered at the same instant are woken in id order
+//! whichever of them the scheduler happened to poll first. Every
+//! registration and wakeup is recorded, and [`DeterministicNotify::wake_order`]
+//! lists who was woken, in order.
+//!
+//! As with Tokio's `Notify`, [`notify_one`](DeterministicNotify::notify_one)
+//! with nobody waiting stores a single permit that the next waiter consumes
+//! without waiting; [`notify_all`](DeterministicNotify::notify_all) only
+//! wakes those already waiting.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, SystemTime},
+};
+
+use commonware_runtime::Clock;
+
+use crate::{
+    spawn::{TaskId, TaskInfo},
+    trace::EventLog,
+};
+
+type Now = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+/// Registration time, task id, then registration number, which only
+/// separates repeated registrations by the same task at the same instant.
+type Key = (Duration, TaskId, u64);
+
+#[derive(Default)]
+struct Slot {
+    woken: bool,
+    waker: Option<Waker>,
+}
+
+struct Waiter {
+    task: TaskInfo,
+    slot: Arc<Mutex<Slot>>,
+}
+
+#[derive(Default)]
+struct State {
+    waiters: BTreeMap<Key, Waiter>,
+    registrations: u64,
+    permit: bool,
+    woken: Vec<TaskInfo>,
+}
+
+pub struct DeterministicNotify {
+    now: Now,
+    origin: SystemTime,
+    state: Mutex<State>,
+    log: Option<EventLog>,
+}
+
+impl DeterministicNotify {
+    /// A notify timing registrations on `clock`.
+    pub fn new(clock: impl Clock) -> Self {
+        let now: Now = Arc::new(move || clock.current());
+        Self {
+            origin: now(),
+            now,
+            state: Mutex::default(),
+            log: None,
+        }
+    }
+
+    /// Also record registrations and wakeups into `log`, under the task's
+    /// label.
+    pub fn with_log(mut self, log: EventLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Wait to be notified. `task` joins the line when this is called, not
+    /// when the future is first polled; dropping the future before it
+    /// completes leaves the line.
+    pub fn notified(&self, task: &TaskInfo) -> Notified<'_> {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let mut state = self.state.lock().unwrap();
+        if std::mem::take(&mut state.permit) {
+            slot.lock().unwrap().woken = true;
+            self.record(task, "took the stored notification");
+            state.woken.push(task.clone());
+            return Notified {
+                notify: self,
+                key: None,
+                slot,
+            };
+        }
+        let at = (self.now)()
+            .duration_since(self.origin)
+            .unwrap_or(Duration::ZERO);
+        let key = (at, task.id, state.registrations);
+        state.registrations += 1;
+        state.waiters.insert(
+            key,
+            Waiter {
+                task: task.clone(),
+                slot: slot.clone(),
+            },
+        );
+        self.record(task, "waiting");
+        Notified {
+            notify: self,
+            key: Some(key),
+            slot,
+        }
+    }
+
+    /// Wake the first waiter in line and return who it was. With nobody
+    /// waiting, store a permit for the next one instead.
+    pub fn notify_one(&self) -> Option<TaskInfo> {
+        let mut state = self.state.lock().unwrap();
+        match state.waiters.pop_first() {
+            Some((_, waiter)) => Some(self.wake(&mut state, waiter)),
+            None => {
+                state.permit = true;
+                None
+            }
+        }
+    }
+
+    /// Wake everyone waiting, in line order, and return them in that order.
+    pub fn notify_all(&self) -> Vec<TaskInfo> {
+        let mut state = self.state.lock().unwrap();
+        let waiters = std::mem::take(&mut state.waiters);
+        waiters
+            .into_values()
+            .map(|waiter| self.wake(&mut state, waiter))
+            .collect()
+    }
+
+    /// Every task woken so far, in the order they were woken.
+    pub fn wake_order(&self) -> Vec<TaskInfo> {
+        self.state.lock().unwrap().woken.clone()
+    }
+
+    fn wake(&self, state: &mut State, waiter: Waiter) -> TaskInfo {
+        let mut slot = waiter.slot.lock().unwrap();
+        slot.woken = true;
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+        self.record(&waiter.task, "woken");
+        state.woken.push(waiter.task.clone());
+        waiter.task
+    }
+
+    fn record(&self, task: &TaskInfo, message: &str) {
+        if let Some(log) = &self.log {
+            log.record(task.label(), message);
+        }
+    }
+}
+
+/// The future returned by [`DeterministicNotify::notified`].
+pub struct Notified<'a> {
+    notify: &'a DeterministicNotify,
+    key: Option<Key>,
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut slot = self.slot.lock().unwrap();
+        if slot.woken {
+            return Poll::Ready(());
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = &self.key {
+            self.notify.state.lock().unwrap().waiters.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner, Spawner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    fn task(id: u64) -> TaskInfo {
+        TaskInfo {
+            id: TaskId(id),
+            name: Some(format!("waiter-{}", id)),
+        }
+    }
+
+    /// Waiters 2 and 1 register at once, waiter 0 a millisecond later, and
+    /// all are woken together. Returns who was woken and the log.
+    fn run(seed: u64) -> (Vec<TaskId>, Vec<String>) {
+        let log = EventLog::new();
+        let woken = DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+            let log = log.clone();
+            async move {
+                let notify = Arc::new(DeterministicNotify::new(context.clone()).with_log(log));
+                let handles: Vec<_> = [(2, 0), (1, 0), (0, 1)]
+                    .into_iter()
+                    .map(|(id, delay)| {
+                        let notify = notify.clone();
+                        context.clone().spawn(move |context| async move {
+                            context.sleep(Duration::from_millis(delay)).await;
+                            notify.notified(&task(id)).await;
+                        })
+                    })
+                    .collect();
+                context.sleep(Duration::from_millis(10)).await;
+                let woken: Vec<_> = notify.notify_all().iter().map(|task| task.id).collect();
+                for handle in handles {
+                    handle.await.expect("Waiter should run to completion");
+                }
+                assert_eq!(
+                    notify
+                        .wake_order()
+                        .iter()
+                        .map(|task| task.id)
+                        .collect::<Vec<_>>(),
+                    woken
+                );
+                woken
+            }
+        });
+        (
+            woken,
+            log.events().iter().map(|event| event.to_string()).collect(),
+        )
+    }
+
+    /// Same-instant waiters are woken by task id, later ones after them,
+    /// and every seed replays the same log.
+    #[test]
+    fn test_wake_order_is_fixed() {
+        let (woken, events) = run(0);
+
+        assert_eq!(woken, [TaskId(1), TaskId(2), TaskId(0)]);
+        assert_eq!(
+            &events[3..],
+            ["waiter-1: woken", "waiter-2: woken", "waiter-0: woken"]
+        );
+        for seed in 1..5 {
+            assert_eq!(run(seed).0, woken);
+        }
+    }
+
+    /// `notify_one` wakes the head of the line; with nobody waiting it
+    /// leaves a permit, which only one waiter can take.
+    #[test]
+    fn test_notify_one_and_permit() {
+        DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+            let notify = Arc::new(DeterministicNotify::new(context.clone()));
+            assert_eq!(notify.notify_one(), None);
+            notify.notified(&task(0)).await;
+
+            let waiter = context.clone().spawn({
+                let notify = notify.clone();
+                move |_| async move { notify.notified(&task(1)).await }
+            });
+            context.sleep(Duration::from_millis(1)).await;
+            assert_eq!(notify.notify_one(), Some(task(1)));
+            waiter.await.unwrap();
+            assert_eq!(notify.wake_order(), [task(0), task(1)]);
+        });
+    }
+}
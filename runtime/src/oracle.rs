@@ -0,0 +1,219 @@
+//! Checking global invariants while a simulation runs.
+//!
+//! Each node of a [`SimNet`] only knows its own state, but a test usually
+//! cares about properties of all of them together: at most one leader per
+//! term, no balance below zero. An [`Invariant`] is such a property, a check
+//! against the [`SimState`] of every node at once. Invariants registered
+//! with [`SimNet::with_invariant`] are checked each time a message is
+//! delivered, which is when one node's actions become visible to another.
+//!
+//! A check replays the trace to find the first event since the previous
+//! check after which the invariant stopped holding, and records a
+//! [`Violation`] with that event, the trace up to it and the state it left
+//! behind. Under the deterministic runtime a seed that breaks an invariant
+//! breaks it at the same event on every run, so the report is a reproducer.
+//!
+//! [`overdraft`] shows one: a bank that approves withdrawals without
+//! checking the balance, caught as soon as its second approval is delivered.
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{
+    parallel_determinism::types::Value,
+    rng::DeterministicRng,
+    simnet::{NodeEvent, SimNet, SimState, Update},
+    trace::EventLog,
+};
+
+/// A named property of the nodes' combined state.
+#[derive(Clone)]
+pub struct Invariant {
+    name: String,
+    check: Arc<dyn Fn(&SimState) -> bool + Send + Sync>,
+}
+
+impl Invariant {
+    pub fn new(
+        name: impl Into<String>,
+        check: impl Fn(&SimState) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            check: Arc::new(check),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn holds(&self, state: &SimState) -> bool {
+        (self.check)(state)
+    }
+
+    /// Replay `updates` over `events` and return the first event from index
+    /// `from` on after which this invariant fails, if any does.
+    pub(crate) fn first_violation(
+        &self,
+        nodes: usize,
+        events: &[NodeEvent],
+        updates: &[Update],
+        from: usize,
+    ) -> Option<Violation> {
+        // An update is stamped with the clock of the event that made it.
+        let made_by: HashMap<_, _> = updates
+            .iter()
+            .map(|update| ((update.node, &update.clock), update))
+            .collect();
+        let mut state = SimState::new(nodes);
+        for (index, event) in events.iter().enumerate() {
+            if let Some(update) = made_by.get(&(event.node, &event.clock)) {
+                state.apply(update);
+            }
+            if index >= from && !self.holds(&state) {
+                return Some(Violation {
+                    invariant: self.name.clone(),
+                    event: event.clone(),
+                    trace: events[..=index].to_vec(),
+                    state,
+                });
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Debug for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Invariant")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An invariant that stopped holding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub invariant: String,
+    /// The first event after which it failed.
+    pub event: NodeEvent,
+    /// Every event up to and including `event`, in log order.
+    pub trace: Vec<NodeEvent>,
+    /// The state just after `event`.
+    pub state: SimState,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} broken by node {}: {} at {}",
+            self.invariant, self.event.node, self.event.message, self.event.clock
+        )?;
+        for event in &self.trace {
+            writeln!(
+                f,
+                "  node {}: {} at {}",
+                event.node, event.message, event.clock
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Node 0 is a bank holding 100; nodes 1 and 2 each ask it to withdraw 60.
+/// A bank that does not `check_funds` approves both and overdraws. Returns
+/// the violations of "balances never negative" the oracle found.
+pub async fn overdraft<C: Spawner + Clock>(
+    context: &C,
+    check_funds: bool,
+    seed: u64,
+    log: &EventLog,
+) -> Vec<Violation> {
+    let net: SimNet<C, Value> =
+        SimNet::new(context.clone(), 3, DeterministicRng::new(seed), log.clone())
+            .with_invariant("balances never negative", |state| {
+                state.values_of("balance").all(|(_, balance)| balance >= 0)
+            });
+    let mut bank = net.node(0);
+    let mut handles = vec![context.clone().spawn(move |_| async move {
+        let mut balance = 100;
+        bank.update("balance", balance);
+        for _ in 0..2 {
+            let request = bank.recv().await;
+            let amount = request.message;
+            if check_funds && balance < amount {
+                bank.record("refused the withdrawal");
+                bank.send(request.from, 0);
+            } else {
+                balance -= amount;
+                bank.update("balance", balance);
+                bank.send(request.from, amount);
+            }
+        }
+    })];
+    for id in [1, 2] {
+        let mut client = net.node(id);
+        handles.push(context.clone().spawn(move |_| async move {
+            client.send(0, 60);
+            let reply = client.recv().await;
+            client.record(if reply.message > 0 {
+                "withdrawal approved"
+            } else {
+                "withdrawal refused"
+            });
+        }));
+    }
+    for handle in handles {
+        handle.await.expect("Node should run to completion");
+    }
+    net.violations()
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    fn run(check_funds: bool, seed: u64) -> (Vec<Violation>, u64) {
+        let log = EventLog::new();
+        let violations =
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+                let log = log.clone();
+                async move { overdraft(&context, check_funds, seed, &log).await }
+            });
+        (violations, log.fingerprint())
+    }
+
+    /// The oracle names the update that overdrew the account, with the
+    /// trace that led to it, and finds it at the same event on every run.
+    #[test]
+    fn test_overdraft_caught() {
+        let (violations, fingerprint) = run(false, 2);
+
+        assert_eq!(violations.len(), 1);
+        let violation = &violations[0];
+        assert_eq!(violation.invariant, "balances never negative");
+        assert_eq!(violation.event.node, 0);
+        assert_eq!(violation.event.message, "set balance to -20");
+        assert_eq!(violation.trace.last(), Some(&violation.event));
+        assert_eq!(violation.state.value(0, "balance"), Some(-20));
+        assert!(violation.to_string().contains("node 1: send to node 0"));
+        assert_eq!(run(false, 2), (violations, fingerprint));
+    }
+
+    /// A bank that checks funds refuses the second withdrawal and never
+    /// breaks the invariant.
+    #[test]
+    fn test_checked_funds_hold() {
+        for seed in 0..4 {
+            assert!(run(true, seed).0.is_empty());
+        }
+    }
+}
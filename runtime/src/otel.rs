@@ -0,0 +1,263 @@
+//! Exporting execution traces as OpenTelemetry spans.
+//!
+//! Built with the `otel` feature. A [`SpanRecorder`] hooks into a
+//! [`ParallelExecutor`] and notes, on the runtime's clock, when each task and
+//! level starts and ends. [`SpanRecorder::export`] then emits one span for
+//! the block, one per level as its children, and one per task under its
+//! level, through any OpenTelemetry [`Tracer`]. With an OTLP exporter behind
+//! the tracer, runs show up in Jaeger or Tempo next to the rest of a system's
+//! traces.
+//!
+//! A task's span covers its work, from the hook that runs after its reads to
+//! its receipt; a level's span also covers the reads and the commit.
+//! Span timestamps are the runtime's clock readings, so under the
+//! deterministic runtime they are virtual time counted from the Unix epoch:
+//! the shape of a run and its durations are exact and repeat with the seed,
+//! but the spans are dated 1970, and task spans there are instantaneous
+//! because work takes no virtual time.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use commonware_runtime::Clock;
+use opentelemetry::{
+    Context, KeyValue,
+    trace::{Span, Status, TraceContextExt, Tracer},
+};
+
+use crate::parallel_determinism::{executor::ParallelExecutor, types::TaskId};
+
+struct TaskTiming {
+    name: String,
+    start: SystemTime,
+    end: Option<SystemTime>,
+    error: Option<String>,
+}
+
+struct LevelTiming {
+    level: usize,
+    tasks: Vec<TaskId>,
+    start: SystemTime,
+    end: SystemTime,
+}
+
+#[derive(Default)]
+struct Recording {
+    tasks: BTreeMap<TaskId, TaskTiming>,
+    levels: Vec<LevelTiming>,
+}
+
+/// Records task and level timings from an executor's hooks for export as
+/// spans. Clones share the recording.
+#[derive(Clone)]
+pub struct SpanRecorder<C> {
+    clock: C,
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl<C: Clock> SpanRecorder<C> {
+    /// A recorder that reads time from `clock`, normally the context the
+    /// executor runs on.
+    pub fn new(clock: &C) -> Self {
+        Self {
+            clock: clock.clone(),
+            recording: Arc::new(Mutex::new(Recording::default())),
+        }
+    }
+
+    /// Attach hooks to `executor` that record into this recorder.
+    pub fn instrument(&self, executor: ParallelExecutor) -> ParallelExecutor {
+        let (on_start, on_end, on_level) = (self.clone(), self.clone(), self.clone());
+        executor
+            .on_task_start(move |task| {
+                let start = on_start.clock.current();
+                on_start.recording.lock().unwrap().tasks.insert(
+                    task.id,
+                    TaskTiming {
+                        name: task.name.clone(),
+                        start,
+                        end: None,
+                        error: None,
+                    },
+                );
+            })
+            .on_task_end(move |receipt| {
+                let end = on_end.clock.current();
+                if let Some(timing) = on_end
+                    .recording
+                    .lock()
+                    .unwrap()
+                    .tasks
+                    .get_mut(&receipt.task_id)
+                {
+                    timing.end = Some(end);
+                    timing.error = receipt.output.clone().err();
+                }
+            })
+            .on_level_complete(move |level| {
+                let end = on_level.clock.current();
+                on_level.recording.lock().unwrap().levels.push(LevelTiming {
+                    level: level.level,
+                    tasks: level.tasks.clone(),
+                    start: end - level.elapsed,
+                    end,
+                });
+            })
+    }
+
+    /// Emit everything recorded so far through `tracer` as one span named
+    /// `block`, and clear the recording for the next block.
+    pub fn export<T: Tracer>(&self, tracer: &T, block: &str)
+    where
+        T::Span: Send + Sync + 'static,
+    {
+        let Recording { tasks, levels } = std::mem::take(&mut *self.recording.lock().unwrap());
+        let Some(start) = levels.iter().map(|level| level.start).min() else {
+            return;
+        };
+        let end = levels
+            .iter()
+            .map(|level| level.end)
+            .max()
+            .expect("There is at least one level");
+
+        let block_span = tracer
+            .span_builder(block.to_string())
+            .with_start_time(start)
+            .with_attributes([
+                KeyValue::new("block.tasks", tasks.len() as i64),
+                KeyValue::new("block.levels", levels.len() as i64),
+            ])
+            .start_with_context(tracer, &Context::new());
+        let block_cx = Context::new().with_span(block_span);
+
+        for level in &levels {
+            let level_span = tracer
+                .span_builder(format!("level {}", level.level))
+                .with_start_time(level.start)
+                .with_attributes([
+                    KeyValue::new("level.index", level.level as i64),
+                    KeyValue::new("level.width", level.tasks.len() as i64),
+                ])
+                .start_with_context(tracer, &block_cx);
+            let level_cx = block_cx.with_span(level_span);
+            for id in &level.tasks {
+                if let Some(timing) = tasks.get(id) {
+                    let mut span = tracer
+                        .span_builder(timing.name.clone())
+                        .with_start_time(timing.start)
+                        .with_attributes([
+                            KeyValue::new("task.id", *id as i64),
+                            KeyValue::new("task.level", level.level as i64),
+                        ])
+                        .start_with_context(tracer, &level_cx);
+                    if let Some(error) = &timing.error {
+                        span.set_status(Status::error(error.clone()));
+                    }
+                    span.end_with_timestamp(timing.end.unwrap_or(level.end));
+                }
+            }
+            level_cx.span().end_with_timestamp(level.end);
+        }
+        block_cx.span().end_with_timestamp(end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+    use opentelemetry::trace::{SpanId, TracerProvider as _};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+
+    use super::*;
+    use crate::parallel_determinism::{
+        dep_graph::DependencyGraph,
+        generator::{BlockSpec, generate_tasks},
+        state::{LatencyStorage, MemoryStorage},
+    };
+
+    fn spans(seed: u64) -> Vec<SpanData> {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("runtime");
+        let graph = DependencyGraph::from_tasks(generate_tasks(&BlockSpec {
+            size: 10,
+            conflict_rate: 0.5,
+            seed: 2,
+        }));
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            let recorder = SpanRecorder::new(&context);
+            let store = LatencyStorage::new(MemoryStorage::new(), Duration::from_millis(1));
+            recorder
+                .instrument(ParallelExecutor::new())
+                .execute_with_state(&context, &graph, &store)
+                .await;
+            recorder.export(&tracer, "block");
+        });
+        exporter.get_finished_spans().unwrap()
+    }
+
+    fn span<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+        spans
+            .iter()
+            .find(|span| span.name == name)
+            .expect("Span should be exported")
+    }
+
+    /// One span for the block, one per level under it and one per task under
+    /// its level, each nested in time inside its parent.
+    #[test]
+    fn test_span_hierarchy() {
+        let spans = spans(0);
+        let block = span(&spans, "block");
+        let levels: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name.starts_with("level "))
+            .collect();
+        let tasks: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name.starts_with("tx"))
+            .collect();
+
+        assert_eq!(spans.len(), 1 + levels.len() + 10);
+        assert_eq!(block.parent_span_id, SpanId::INVALID);
+        for level in &levels {
+            assert_eq!(level.parent_span_id, block.span_context.span_id());
+            assert!(level.start_time >= block.start_time && level.end_time <= block.end_time);
+        }
+        for task in &tasks {
+            let level = levels
+                .iter()
+                .find(|level| level.span_context.span_id() == task.parent_span_id)
+                .expect("Every task span sits under a level span");
+            assert!(task.start_time >= level.start_time && task.end_time <= level.end_time);
+            assert!(task.end_time >= task.start_time);
+        }
+    }
+
+    /// Under the deterministic runtime the exported timings repeat with the
+    /// seed.
+    #[test]
+    fn test_timings_are_reproducible() {
+        let timings = |spans: Vec<SpanData>| -> Vec<(String, SystemTime, SystemTime)> {
+            let mut timings: Vec<_> = spans
+                .into_iter()
+                .map(|span| (span.name.into_owned(), span.start_time, span.end_time))
+                .collect();
+            timings.sort();
+            timings
+        };
+
+        assert_eq!(timings(spans(4)), timings(spans(4)));
+    }
+}
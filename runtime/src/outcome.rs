@@ -0,0 +1,324 @@
+//! The final state of a run, in a form two machines can compare byte for byte.
+//!
+//! Replicas agree when they reach the same state from the same inputs, and
+//! the same goes for two people running one demo with one seed: the check is
+//! only as good as the way the state is written down. An [`Outcome`] holds a
+//! run's final values under string keys in a `BTreeMap`, so it never depends
+//! on hash-map order, thread count or the platform's formatting of anything
+//! richer than a string. [`to_text`](Outcome::to_text) renders it in a fixed
+//! layout:
+//!
+//! ```text
+//! outcome v1
+//! workload relay
+//! seed 7
+//! node 0/held    12
+//! node 1/held    12
+//! ```
+//!
+//! a version line, the workload and seed, then one line per value in key
+//! order with a tab between key and value, and backslash, tab, newline and
+//! carriage return escaped in both. The [`digest`](Outcome::digest) is the
+//! FNV-1a hash of exactly that text, short enough to paste into a chat; when
+//! two digests differ, [`parse`](Outcome::parse) the other side's text and
+//! [`differences`](Outcome::differences) names the keys that disagree.
+//!
+//! The layout only ever changes together with [`FORMAT_VERSION`], so a
+//! digest always identifies one text.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    parallel_determinism::{
+        hash::Fnv,
+        types::{ResourceId, Value},
+    },
+    trace::EventLog,
+};
+
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub workload: String,
+    pub seed: u64,
+    pub values: BTreeMap<String, String>,
+}
+
+impl Outcome {
+    pub fn new(workload: impl Into<String>, seed: u64) -> Self {
+        Self {
+            workload: workload.into(),
+            seed,
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.values.insert(key.into(), value.to_string());
+        self
+    }
+
+    /// Every key of `state` under `prefix/`, such as a block's storage.
+    pub fn with_storage(mut self, prefix: &str, state: &BTreeMap<ResourceId, Value>) -> Self {
+        for (key, value) in state {
+            self = self.with(format!("{}/{}", prefix, key), value);
+        }
+        self
+    }
+
+    /// The number of events `log` holds and its fingerprint, so the outcome
+    /// also pins down the path the run took.
+    pub fn with_log(self, log: &EventLog) -> Self {
+        self.with("log/events", log.events().len())
+            .with("log/fingerprint", format!("{:016x}", log.fingerprint()))
+    }
+
+    /// Every value a simulated node set, under `node N/`.
+    #[cfg(feature = "runtime")]
+    pub fn with_sim_state(mut self, state: &crate::simnet::SimState) -> Self {
+        for (node, values) in state.values.iter().enumerate() {
+            for (key, value) in values {
+                self = self.with(format!("node {}/{}", node, key), value);
+            }
+        }
+        self
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "outcome v{}\nworkload {}\nseed {}\n",
+            FORMAT_VERSION,
+            escape(&self.workload),
+            self.seed
+        );
+        for (key, value) in &self.values {
+            text.push_str(&format!("{}\t{}\n", escape(key), escape(value)));
+        }
+        text
+    }
+
+    pub fn digest(&self) -> u64 {
+        let mut hasher = Fnv::new();
+        hasher.update(self.to_text().as_bytes());
+        hasher.finish()
+    }
+
+    /// Read back the text of [`to_text`](Self::to_text).
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line));
+        let mut header = |prefix: &str| {
+            let (line, content) = lines.next().ok_or(ParseError {
+                line: 0,
+                reason: format!("missing {} line", prefix.trim()),
+            })?;
+            let rest = content.strip_prefix(prefix).ok_or_else(|| ParseError {
+                line,
+                reason: format!("expected {} line", prefix.trim()),
+            })?;
+            Ok((line, rest.to_string()))
+        };
+
+        let (line, version) = header("outcome v")?;
+        if version != FORMAT_VERSION.to_string() {
+            return Err(ParseError {
+                line,
+                reason: format!("unsupported format version {}", version),
+            });
+        }
+        let (_, workload) = header("workload ")?;
+        let (line, seed) = header("seed ")?;
+        let seed = seed.parse().map_err(|_| ParseError {
+            line,
+            reason: format!("seed {} is not a number", seed),
+        })?;
+
+        let mut outcome = Self::new(unescape(&workload), seed);
+        for (line, content) in lines {
+            let (key, value) = content.split_once('\t').ok_or(ParseError {
+                line,
+                reason: "expected key and value separated by a tab".to_string(),
+            })?;
+            let key = unescape(key);
+            if outcome.values.contains_key(&key) {
+                return Err(ParseError {
+                    line,
+                    reason: format!("duplicate key {}", key),
+                });
+            }
+            outcome.values.insert(key, unescape(value));
+        }
+        Ok(outcome)
+    }
+
+    /// Every key whose value differs from `other`'s or that only one side
+    /// has, in key order.
+    pub fn differences<'a>(&'a self, other: &'a Self) -> Vec<&'a str> {
+        let mut keys: Vec<&str> = self
+            .values
+            .keys()
+            .chain(other.values.keys())
+            .map(String::as_str)
+            .filter(|key| self.values.get(*key) != other.values.get(*key))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_text())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based; 0 when the text ended early.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome() -> Outcome {
+        Outcome::new("bank", 7)
+            .with_storage(
+                "storage",
+                &BTreeMap::from([("b".to_string(), 2), ("a".to_string(), -1)]),
+            )
+            .with("note", "two\tcolumns\nand a \\ line")
+    }
+
+    /// The text and digest are fixed by the values alone, here down to the
+    /// byte, so any machine that reaches these values prints this digest.
+    #[test]
+    fn test_canonical_text() {
+        let outcome = outcome();
+        assert_eq!(
+            outcome.to_text(),
+            "outcome v1\nworkload bank\nseed 7\n\
+             note\ttwo\\tcolumns\\nand a \\\\ line\n\
+             storage/a\t-1\n\
+             storage/b\t2\n"
+        );
+        assert_eq!(outcome.digest(), 0x0ef7_72aa_f844_d59a);
+        let reordered = Outcome::new("bank", 7)
+            .with("note", "two\tcolumns\nand a \\ line")
+            .with("storage/b", 2)
+            .with("storage/a", -1);
+        assert_eq!(reordered.digest(), outcome.digest());
+    }
+
+    /// Text read back is the same outcome; another machine's text that
+    /// disagrees says where.
+    #[test]
+    fn test_parse_and_differences() {
+        let outcome = outcome();
+        assert_eq!(Outcome::parse(&outcome.to_text()).unwrap(), outcome);
+
+        let theirs = outcome.clone().with("storage/a", 0).with("storage/c", 1);
+        let theirs = Outcome::parse(&theirs.to_text()).unwrap();
+        assert_ne!(theirs.digest(), outcome.digest());
+        assert_eq!(outcome.differences(&theirs), ["storage/a", "storage/c"]);
+
+        let error = Outcome::parse("outcome v2\nworkload bank\nseed 7\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: unsupported format version 2");
+        assert_eq!(Outcome::parse("outcome v1\n").unwrap_err().line, 0);
+    }
+
+    /// A carriage return at the end of a value survives the round trip
+    /// instead of being taken for a line ending.
+    #[test]
+    fn test_parse_carriage_return() {
+        let outcome = Outcome::new("bank\r", 7).with("line\r", "ends\r");
+        assert_eq!(
+            outcome.to_text(),
+            "outcome v1\nworkload bank\\r\nseed 7\nline\\r\tends\\r\n"
+        );
+        assert_eq!(Outcome::parse(&outcome.to_text()).unwrap(), outcome);
+    }
+
+    /// A key listed twice is an error rather than the last value winning.
+    #[test]
+    fn test_parse_duplicate_key() {
+        let error =
+            Outcome::parse("outcome v1\nworkload bank\nseed 7\na\t1\nb\t2\na\t3\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 6: duplicate key a");
+    }
+
+    /// The same seed reaches the same outcome, log included; another seed
+    /// takes another path.
+    #[cfg(feature = "deterministic-backend")]
+    #[test]
+    fn test_simulation_outcome() {
+        use std::time::Duration;
+
+        use commonware_runtime::{
+            Runner,
+            deterministic::{Config, Runner as DeterministicRunner},
+        };
+
+        use crate::{rng::DeterministicRng, simnet::SimNet, snapshot::relay};
+
+        let run = |seed: u64| {
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(
+                |context| async move {
+                    let log = EventLog::new();
+                    let net =
+                        SimNet::new(context.clone(), 4, DeterministicRng::new(seed), log.clone());
+                    let snapshot = relay(&context, net, Duration::from_millis(80)).await;
+                    Outcome::new("relay", seed)
+                        .with_sim_state(&snapshot.state())
+                        .with_log(&log)
+                },
+            )
+        };
+
+        let outcome = run(3);
+        assert!(outcome.values.contains_key("node 0/held"));
+        assert!(outcome.values.contains_key("log/fingerprint"));
+        assert_eq!(run(3).to_text(), outcome.to_text());
+        assert_ne!(run(4).digest(), outcome.digest());
+    }
+}
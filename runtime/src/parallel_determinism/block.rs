@@ -0,0 +1,322 @@
+//! A block is a list of signed tasks (transactions) executed as a unit.
+//!
+//! Block execution is a two-phase pipeline:
+//!
+//! 1. A stateless pre-stage verifies every transaction's signature. No check
+//!    depends on any other, so all of them run in parallel with no graph.
+//! 2. The stateful stage builds the dependency graph from the verified tasks
+//!    and hands it to the level-parallel executor.
+//!
+//! Rejected transactions stay in the block with a failing receipt, so task
+//! ids (and therefore receipt positions) never shift because of a bad input.
+//! They shift only if an [`OrderingPolicy`] reorders the verified block, in
+//! which case [`BlockOutcome::order`] maps them back.
+
+use std::sync::Arc;
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::parallel_determinism::{
+    dep_graph::DependencyGraph,
+    executor::{ExecutionReport, ParallelExecutor},
+    hash::Fnv,
+    ordering::OrderingPolicy,
+    state::Storage,
+    types::{Task, TaskContext, TaskId},
+};
+
+/// How many hashing rounds a signature check costs. Large enough that the
+/// pre-stage is real CPU work worth spreading across threads.
+const VERIFY_ROUNDS: usize = 2_000;
+
+/// A task plus a signature over its declared contents. Cloning shares the
+/// task rather than copying it.
+#[derive(Clone)]
+pub struct SignedTask {
+    pub task: Arc<Task>,
+    pub signature: u64,
+}
+
+impl SignedTask {
+    /// Sign `task` with the demo signature scheme.
+    pub fn sign(task: Task) -> Self {
+        let signature = signature_of(&task);
+        Self {
+            task: Arc::new(task),
+            signature,
+        }
+    }
+
+    pub fn verify(&self) -> bool {
+        signature_of(&self.task) == self.signature
+    }
+}
+
+/// FNV-1a over the task's identity and declared access sets, iterated to
+/// stand in for an expensive public-key check. Deterministic on every machine.
+fn signature_of(task: &Task) -> u64 {
+    let mut hasher = Fnv::new();
+    hasher.update(&task.id.to_le_bytes());
+    hasher.update(task.name.as_bytes());
+    for read in &task.reads {
+        hasher.update(b"r");
+        hasher.update(read.as_bytes());
+    }
+    for write in &task.writes {
+        hasher.update(b"w");
+        hasher.update(write.as_bytes());
+    }
+    let mut hash = hasher.finish();
+    for _ in 0..VERIFY_ROUNDS {
+        hash = (hash ^ (hash >> 29)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    }
+    hash
+}
+
+fn rejected(_: &mut TaskContext) -> Result<String, String> {
+    Err("signature verification failed".to_string())
+}
+
+#[derive(Clone)]
+pub struct Block {
+    pub transactions: Vec<SignedTask>,
+}
+
+/// The result of executing a block.
+pub struct BlockOutcome {
+    /// Transactions that failed verification, by position in the block.
+    pub rejected: Vec<TaskId>,
+    /// Block positions in execution order: receipt `i` belongs to the
+    /// transaction at position `order[i]`. The identity unless an ordering
+    /// policy is set.
+    pub order: Vec<TaskId>,
+    pub report: ExecutionReport,
+}
+
+/// Runs the verify-then-execute pipeline over a block.
+#[derive(Default)]
+pub struct BlockExecutor {
+    executor: ParallelExecutor,
+    ordering: Option<Box<dyn OrderingPolicy>>,
+}
+
+impl BlockExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reorder verified transactions with `policy` before building the
+    /// graph, instead of executing them in block order.
+    pub fn with_ordering(mut self, policy: impl OrderingPolicy + 'static) -> Self {
+        self.ordering = Some(Box::new(policy));
+        self
+    }
+
+    /// Phase 1: check every signature in parallel. Results come back indexed
+    /// by position, so the outcome does not depend on completion order.
+    pub async fn verify<S: Spawner>(&self, context: &S, block: &Block) -> Vec<bool> {
+        let handles: Vec<_> = block
+            .transactions
+            .iter()
+            .map(|transaction| {
+                let transaction = transaction.clone();
+                context
+                    .clone()
+                    .spawn(move |_| async move { transaction.verify() })
+            })
+            .collect();
+
+        let mut verified = Vec::with_capacity(handles.len());
+        for handle in handles {
+            verified.push(handle.await.expect("Verification should run to completion"));
+        }
+        verified
+    }
+
+    /// Verify the block, then execute it level by level.
+    pub async fn execute<S: Spawner + Clock>(&self, context: &S, block: Block) -> BlockOutcome {
+        let (graph, rejected, order) = self.prepare(context, block).await;
+        let report = self.executor.execute(context, &graph).await;
+        BlockOutcome {
+            rejected,
+            order,
+            report,
+        }
+    }
+
+    /// Verify the block, then execute it against `storage`.
+    pub async fn execute_with_state<S: Spawner + Clock, St: Storage>(
+        &self,
+        context: &S,
+        block: Block,
+        storage: &St,
+    ) -> BlockOutcome {
+        let (graph, rejected, order) = self.prepare(context, block).await;
+        let report = self
+            .executor
+            .execute_with_state(context, &graph, storage)
+            .await;
+        BlockOutcome {
+            rejected,
+            order,
+            report,
+        }
+    }
+
+    /// Run the verification pre-stage, apply the ordering policy, and build
+    /// the graph for phase 2.
+    async fn prepare<S: Spawner + Clock>(
+        &self,
+        context: &S,
+        block: Block,
+    ) -> (DependencyGraph, Vec<TaskId>, Vec<TaskId>) {
+        let verified = self.verify(context, &block).await;
+
+        // Phase 2: a rejected transaction keeps its slot but touches nothing,
+        // so it cannot create edges in the dependency graph.
+        let mut rejected_ids = vec![];
+        let tasks = block
+            .transactions
+            .into_iter()
+            .zip(verified)
+            .map(|(transaction, ok)| {
+                if ok {
+                    return transaction.task;
+                }
+                let task = transaction.task;
+                rejected_ids.push(task.id);
+                Arc::new(Task {
+                    id: task.id,
+                    name: task.name.clone(),
+                    reads: vec![],
+                    writes: vec![],
+                    priority: task.priority,
+                    cost: 1,
+                    work: &rejected,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let Some(policy) = &self.ordering else {
+            let order = (0..tasks.len()).collect();
+            return (DependencyGraph::from_tasks(tasks), rejected_ids, order);
+        };
+        let order = policy.order(&tasks);
+        let reordered = order
+            .iter()
+            .enumerate()
+            .map(|(id, &position)| tasks[position].renumbered(id));
+        (DependencyGraph::from_tasks(reordered), rejected_ids, order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+        tokio::{Config as TokioConfig, Runner as TokioRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::state::{LatencyStorage, MemoryStorage};
+    use std::time::Duration;
+
+    fn transfer(id: TaskId, from: &str, to: &str) -> Task {
+        Task {
+            id,
+            name: format!("transfer {} -> {}", from, to),
+            reads: vec![from.to_string()],
+            writes: vec![from.to_string(), to.to_string()],
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok("ok".to_string())),
+        }
+    }
+
+    fn block() -> Block {
+        Block {
+            transactions: vec![
+                SignedTask::sign(transfer(0, "alice", "bob")),
+                SignedTask::sign(transfer(1, "carol", "dave")),
+                SignedTask::sign(transfer(2, "bob", "carol")),
+            ],
+        }
+    }
+
+    /// A freshly signed block verifies and executes every transaction.
+    #[test]
+    fn test_valid_block_executes() {
+        let outcome = DeterministicRunner::new(Config::default().with_seed(1))
+            .start(|context| async move { BlockExecutor::new().execute(&context, block()).await });
+
+        assert!(outcome.rejected.is_empty());
+        assert!(outcome.report.receipts.iter().all(|r| r.output.is_ok()));
+    }
+
+    /// Tampering with a transaction after signing gets it rejected in the
+    /// pre-stage, while its receipt stays at the same position.
+    #[test]
+    fn test_tampered_transaction_rejected() {
+        let mut block = block();
+        Arc::get_mut(&mut block.transactions[1].task)
+            .expect("Freshly signed task should not be shared")
+            .writes
+            .push("mallory".to_string());
+
+        let outcome = TokioRunner::new(TokioConfig::default().with_worker_threads(4))
+            .start(|context| async move { BlockExecutor::new().execute(&context, block).await });
+
+        assert_eq!(outcome.rejected, vec![1]);
+        assert_eq!(outcome.report.receipts[1].task_id, 1);
+        assert!(outcome.report.receipts[1].output.is_err());
+        assert!(outcome.report.receipts[0].output.is_ok());
+        assert!(outcome.report.receipts[2].output.is_ok());
+    }
+
+    /// The verification verdicts do not depend on the seed.
+    #[test]
+    fn test_verification_is_order_independent() {
+        let mut verdicts = vec![];
+        for seed in 0..5 {
+            let mut block = block();
+            block.transactions[0].signature ^= 1;
+            verdicts.push(
+                DeterministicRunner::new(Config::default().with_seed(seed)).start(
+                    |context| async move { BlockExecutor::new().verify(&context, &block).await },
+                ),
+            );
+        }
+        verdicts.dedup();
+        assert_eq!(verdicts, vec![vec![false, true, true]]);
+    }
+
+    /// The same block runs unchanged on the fast and the slow backend; only
+    /// the elapsed (virtual) time differs.
+    #[test]
+    fn test_same_block_on_fast_and_slow_storage() {
+        let genesis = || {
+            ["alice", "bob", "carol", "dave"]
+                .into_iter()
+                .map(|name| (name, 100))
+                .collect::<MemoryStorage>()
+        };
+
+        let fast =
+            DeterministicRunner::new(Config::default().with_seed(2)).start(|context| async move {
+                BlockExecutor::new()
+                    .execute_with_state(&context, block(), &genesis())
+                    .await
+            });
+        let slow =
+            DeterministicRunner::new(Config::default().with_seed(2)).start(|context| async move {
+                let storage = LatencyStorage::new(genesis(), Duration::from_millis(20));
+                BlockExecutor::new()
+                    .execute_with_state(&context, block(), &storage)
+                    .await
+            });
+
+        assert_eq!(fast.report.receipts, slow.report.receipts);
+        assert!(slow.report.elapsed >= fast.report.elapsed + Duration::from_millis(40));
+    }
+}
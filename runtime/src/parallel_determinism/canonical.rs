@@ -0,0 +1,150 @@
+//! What a dependency graph owes to the order its tasks came in.
+//!
+//! Whether two tasks conflict depends only on their access sets, so the set
+//! of conflicting pairs is the same however the block is ordered. Everything
+//! built on top of it is not: an edge points from the later task to the
+//! earlier one, so swapping two conflicting tasks reverses their edge, and
+//! the execution levels, the depth and the final state all follow from those
+//! directions.
+//!
+//! [`DependencyGraph::permuted`] rebuilds a graph in another order, and
+//! [`DependencyGraph::conflict_pairs`] exposes the part that survives.
+//! [`DependencyGraph::from_tasks_canonical`] removes the input order from the
+//! picture altogether by sorting tasks on a caller-chosen key before any edge
+//! is built, so every permutation of the same tasks yields the same graph.
+
+use std::sync::Arc;
+
+use crate::{
+    collections::DSet,
+    parallel_determinism::{
+        dep_graph::DependencyGraph,
+        types::{Task, TaskId},
+    },
+};
+
+impl DependencyGraph {
+    /// Build the graph with tasks sorted by `key` and renumbered in that
+    /// order. Tasks with equal keys keep their input order, so the result is
+    /// independent of the input order only when `key` tells every task apart.
+    pub fn from_tasks_canonical<K: Ord>(
+        tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>,
+        key: impl Fn(&Task) -> K,
+    ) -> Self {
+        let mut tasks: Vec<Arc<Task>> = tasks.into_iter().map(Into::into).collect();
+        tasks.sort_by_key(|task| key(task));
+        Self::from_tasks(
+            tasks
+                .iter()
+                .enumerate()
+                .map(|(id, task)| task.renumbered(id)),
+        )
+    }
+
+    /// The same tasks rebuilt in the order `order` lists them: task
+    /// `order[i]` of this graph becomes task `i` of the new one.
+    pub fn permuted(&self, order: &[TaskId]) -> Self {
+        assert_eq!(order.len(), self.tasks.len(), "order must list every task");
+        Self::from_tasks(
+            order
+                .iter()
+                .enumerate()
+                .map(|(id, &old)| self.tasks[old].renumbered(id)),
+        )
+    }
+
+    /// Every conflicting pair of tasks as `(lower id, higher id)`: the edges
+    /// with their direction forgotten.
+    pub fn conflict_pairs(&self) -> DSet<(TaskId, TaskId)> {
+        self.dependencies
+            .iter()
+            .flat_map(|(&task, deps)| deps.iter().map(move |&dep| (dep.min(task), dep.max(task))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::SliceRandom;
+
+    use super::*;
+    use crate::{
+        parallel_determinism::generator::{BlockSpec, generate_tasks},
+        rng::DeterministicRng,
+    };
+
+    fn block() -> DependencyGraph {
+        DependencyGraph::from_tasks(generate_tasks(&BlockSpec {
+            size: 24,
+            conflict_rate: 0.5,
+            seed: 3,
+        }))
+    }
+
+    /// Random orders of the same block: the conflict relation never changes,
+    /// while edge directions and execution levels do.
+    #[test]
+    fn test_conflicts_invariant_directions_not() {
+        let original = block();
+        let edges = |graph: &DependencyGraph| -> DSet<(TaskId, TaskId)> {
+            graph
+                .dependencies
+                .iter()
+                .flat_map(|(&task, deps)| deps.iter().map(move |&dep| (task, dep)))
+                .collect()
+        };
+        let mut flipped = false;
+        let mut relevelled = false;
+        let mut rng = DeterministicRng::new(17);
+        for _ in 0..50 {
+            let mut order: Vec<TaskId> = (0..original.tasks.len()).collect();
+            order.shuffle(&mut rng);
+            let permuted = original.permuted(&order);
+
+            let back = |(a, b): (TaskId, TaskId)| (order[a].min(order[b]), order[a].max(order[b]));
+            let conflicts: DSet<_> = permuted.conflict_pairs().into_iter().map(back).collect();
+            assert_eq!(conflicts, original.conflict_pairs());
+
+            let directed: DSet<_> = edges(&permuted)
+                .into_iter()
+                .map(|(task, dep)| (order[task], order[dep]))
+                .collect();
+            flipped |= directed != edges(&original);
+            let levels: Vec<Vec<TaskId>> = permuted
+                .execution_levels()
+                .into_iter()
+                .map(|level| {
+                    let mut level: Vec<TaskId> =
+                        level.into_iter().map(|task| order[task]).collect();
+                    level.sort();
+                    level
+                })
+                .collect();
+            relevelled |= levels != original.execution_levels();
+        }
+        assert!(flipped);
+        assert!(relevelled);
+    }
+
+    /// Canonicalizing by name rebuilds the identical graph from any order.
+    #[test]
+    fn test_canonical_order_removes_permutation() {
+        let original = block();
+        let canonical = |graph: &DependencyGraph| {
+            DependencyGraph::from_tasks_canonical(graph.tasks.clone(), |task| task.name.clone())
+        };
+        let expected = canonical(&original);
+        let mut rng = DeterministicRng::new(4);
+        for _ in 0..20 {
+            let mut order: Vec<TaskId> = (0..original.tasks.len()).collect();
+            order.shuffle(&mut rng);
+            let graph = canonical(&original.permuted(&order));
+
+            let names = |graph: &DependencyGraph| -> Vec<String> {
+                graph.tasks.iter().map(|task| task.name.clone()).collect()
+            };
+            assert_eq!(names(&graph), names(&expected));
+            assert_eq!(graph.dependencies, expected.dependencies);
+        }
+    }
+}
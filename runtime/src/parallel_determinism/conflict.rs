@@ -0,0 +1,344 @@
+//! Strategies for deciding whether two tasks conflict.
+//!
+//! The dependency graph only needs a yes/no answer for each pair of tasks.
+//! Behind [`ConflictDetector`] that answer can come from exact access sets,
+//! from a hierarchy of keys, or from knowledge about which writes commute, and
+//! different strategies can be compared on the same block by the level
+//! structure they produce.
+//!
+//! [`BloomPrefilter`] sits in front of an exact detector: each task's read
+//! and write sets are summarised once as small bloom filters, and a pair whose
+//! filters share no bits cannot share a key, so the exact comparison is
+//! skipped. Filters give false positives, never false negatives, so the
+//! resulting graph is the same as without the prefilter.
+
+use std::sync::Arc;
+
+use crate::{
+    collections::DSet,
+    parallel_determinism::{
+        hash::Fnv,
+        types::{ResourceId, Task},
+    },
+};
+
+/// Decides whether `a` and `b` must be ordered. A detector may report false
+/// conflicts, which only cost parallelism, but never miss a real one under
+/// the access model it claims to implement.
+pub trait ConflictDetector: Sync {
+    fn conflicts(&self, a: &Task, b: &Task) -> bool;
+}
+
+/// The default: two tasks conflict if one writes a key the other reads or
+/// writes, comparing keys for exact equality.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExactSets;
+
+impl ConflictDetector for ExactSets {
+    fn conflicts(&self, a: &Task, b: &Task) -> bool {
+        a.conflicts_with(b)
+    }
+}
+
+/// Keys name nodes in a hierarchy, such as `account/7/balance`. Touching a
+/// node touches everything under it, so `account/7` overlaps
+/// `account/7/balance` but not `account/70`.
+#[derive(Clone, Debug)]
+pub struct PrefixHierarchy {
+    separator: char,
+}
+
+impl PrefixHierarchy {
+    pub fn new(separator: char) -> Self {
+        Self { separator }
+    }
+
+    fn overlaps(&self, a: &str, b: &str) -> bool {
+        let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+        long.strip_prefix(short)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(self.separator))
+    }
+
+    fn any_overlap(&self, a: &[ResourceId], b: &[ResourceId]) -> bool {
+        a.iter().any(|x| b.iter().any(|y| self.overlaps(x, y)))
+    }
+}
+
+impl Default for PrefixHierarchy {
+    fn default() -> Self {
+        Self::new('/')
+    }
+}
+
+impl ConflictDetector for PrefixHierarchy {
+    fn conflicts(&self, a: &Task, b: &Task) -> bool {
+        self.any_overlap(&a.reads, &b.writes)
+            || self.any_overlap(&a.writes, &b.reads)
+            || self.any_overlap(&a.writes, &b.writes)
+    }
+}
+
+/// Exact sets, except that two blind writes to a commutative key, such as a
+/// counter both tasks only add to, do not conflict. A read of such a key
+/// still orders against every write to it.
+///
+/// The executors merge writes by overwrite, not by combining them, so this is
+/// for measuring how much parallelism commutativity would unlock, not for
+/// executing blocks.
+#[derive(Clone, Debug, Default)]
+pub struct Commutative {
+    keys: DSet<ResourceId>,
+}
+
+impl Commutative {
+    pub fn new(keys: impl IntoIterator<Item = impl Into<ResourceId>>) -> Self {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl ConflictDetector for Commutative {
+    fn conflicts(&self, a: &Task, b: &Task) -> bool {
+        let read_write = |reader: &Task, writer: &Task| {
+            reader.reads.iter().any(|key| writer.writes.contains(key))
+        };
+        let write_write = a
+            .writes
+            .iter()
+            .any(|key| !self.keys.contains(key) && b.writes.contains(key));
+        read_write(a, b) || read_write(b, a) || write_write
+    }
+}
+
+/// Bits each key sets in a [`Bloom`].
+const BLOOM_HASHES: u32 = 2;
+
+/// The distinct bits `key` sets. Each is ten bits of the key's hash; when
+/// they coincide the second moves to the next bit, so every key sets exactly
+/// [`BLOOM_HASHES`] bits and [`Bloom::may_intersect`] can rely on that.
+fn bloom_bits(key: &ResourceId) -> [usize; BLOOM_HASHES as usize] {
+    let mut hasher = Fnv::new();
+    hasher.update(key.as_bytes());
+    let hash = hasher.finish();
+    let first = hash as usize & 1023;
+    let mut second = (hash >> 10) as usize & 1023;
+    if second == first {
+        second = (first + 1) & 1023;
+    }
+    [first, second]
+}
+
+/// A 1024-bit bloom filter over a set of keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bloom([u64; 16]);
+
+impl Bloom {
+    pub fn from_keys<'a>(keys: impl IntoIterator<Item = &'a ResourceId>) -> Self {
+        let mut bloom = Self([0; 16]);
+        for key in keys {
+            for bit in bloom_bits(key) {
+                bloom.0[bit >> 6] |= 1 << (bit & 63);
+            }
+        }
+        bloom
+    }
+
+    /// False only if the two key sets are certainly disjoint. A shared key
+    /// sets all of its bits in both filters, so fewer common bits than one
+    /// key's worth rules it out.
+    pub fn may_intersect(&self, other: &Bloom) -> bool {
+        let common: u32 = self
+            .0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a & b).count_ones())
+            .sum();
+        common >= BLOOM_HASHES
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct AccessFilters {
+    reads: Bloom,
+    writes: Bloom,
+}
+
+/// Rules out pairs whose access sets certainly do not overlap before asking
+/// `inner`. Only sound for detectors that compare keys for equality, such as
+/// [`ExactSets`] and [`Commutative`]; a [`PrefixHierarchy`] relates keys that
+/// hash differently.
+pub struct BloomPrefilter<D> {
+    inner: D,
+    /// Indexed by task id, so a lookup costs less than the comparison it
+    /// saves.
+    filters: Vec<Option<AccessFilters>>,
+}
+
+impl<D: ConflictDetector> BloomPrefilter<D> {
+    /// Summarise the access sets of `tasks`. Tasks not seen here always fall
+    /// through to `inner`.
+    pub fn new(inner: D, tasks: &[Arc<Task>]) -> Self {
+        let mut filters = vec![None; tasks.iter().map(|task| task.id + 1).max().unwrap_or(0)];
+        for task in tasks {
+            filters[task.id] = Some(AccessFilters {
+                reads: Bloom::from_keys(&task.reads),
+                writes: Bloom::from_keys(&task.writes),
+            });
+        }
+        Self { inner, filters }
+    }
+}
+
+impl<D: ConflictDetector> ConflictDetector for BloomPrefilter<D> {
+    fn conflicts(&self, a: &Task, b: &Task) -> bool {
+        let filters = |task: &Task| self.filters.get(task.id).copied().flatten();
+        if let (Some(fa), Some(fb)) = (filters(a), filters(b))
+            && !fa.writes.may_intersect(&fb.reads)
+            && !fa.writes.may_intersect(&fb.writes)
+            && !fa.reads.may_intersect(&fb.writes)
+        {
+            return false;
+        }
+        self.inner.conflicts(a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel_determinism::dep_graph::DependencyGraph;
+
+    fn task(id: usize, reads: &[&str], writes: &[&str]) -> Task {
+        Task {
+            id,
+            name: format!("T{}", id),
+            reads: reads.iter().map(|key| key.to_string()).collect(),
+            writes: writes.iter().map(|key| key.to_string()).collect(),
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok(String::new())),
+        }
+    }
+
+    /// A parent key overlaps its children but not a sibling that merely
+    /// shares its spelling as a prefix.
+    #[test]
+    fn test_prefix_hierarchy() {
+        let detector = PrefixHierarchy::default();
+        let parent = task(0, &[], &["account/7"]);
+
+        assert!(detector.conflicts(&parent, &task(1, &["account/7/balance"], &[])));
+        assert!(!detector.conflicts(&parent, &task(1, &["account/70"], &[])));
+        assert!(!ExactSets.conflicts(&parent, &task(1, &["account/7/balance"], &[])));
+    }
+
+    /// Keys in both sets always share bits; disjoint sets mostly do not.
+    #[test]
+    fn test_bloom_filters() {
+        let keys = |range: std::ops::Range<usize>| -> Vec<ResourceId> {
+            range.map(|i| format!("account_{}", i)).collect()
+        };
+        let low = Bloom::from_keys(&keys(0..4));
+
+        assert!(low.may_intersect(&Bloom::from_keys(&keys(3..7))));
+        let disjoint = (0..100)
+            .filter(|i| !low.may_intersect(&Bloom::from_keys(&keys(100 + i..101 + i))))
+            .count();
+        assert!(disjoint > 80, "only {} of 100 pairs ruled out", disjoint);
+    }
+
+    /// The prefilter never hides a real conflict: graphs built with and
+    /// without it are identical across conflict rates.
+    #[test]
+    fn test_prefilter_has_no_false_negatives() {
+        use crate::parallel_determinism::generator::{BlockSpec, generate_tasks};
+
+        for (seed, conflict_rate) in [(1, 0.0), (2, 0.1), (3, 0.5), (4, 1.0)] {
+            let spec = BlockSpec {
+                size: 300,
+                conflict_rate,
+                seed,
+            };
+            let exact = DependencyGraph::from_tasks(generate_tasks(&spec));
+            let prefiltered = DependencyGraph::from_tasks_prefiltered(generate_tasks(&spec));
+
+            assert_eq!(exact.dependencies, prefiltered.dependencies);
+        }
+    }
+
+    /// A key whose two hash positions coincide still sets two bits, so a
+    /// shared one is never taken for disjoint sets.
+    #[test]
+    fn test_bloom_colliding_key() {
+        let colliding = (0..10_000)
+            .map(|i| format!("account_{}", i))
+            .find(|key| {
+                let mut hasher = Fnv::new();
+                hasher.update(key.as_bytes());
+                let hash = hasher.finish();
+                hash & 1023 == (hash >> 10) & 1023
+            })
+            .expect("Some key should hash both positions alike");
+        let tasks = || vec![task(0, &[], &[&colliding]), task(1, &[], &[&colliding])];
+
+        let bloom = Bloom::from_keys([&colliding]);
+        assert!(bloom.may_intersect(&bloom));
+        assert_eq!(
+            DependencyGraph::from_tasks_prefiltered(tasks()).execution_levels(),
+            [[0], [1]]
+        );
+    }
+
+    /// On random access sets over a small pool of keys, the prefiltered graph
+    /// is the exact one.
+    #[test]
+    fn test_prefilter_matches_exact_on_random_sets() {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let keys = |rng: &mut StdRng| -> Vec<ResourceId> {
+                (0..rng.random_range(0..4))
+                    .map(|_| format!("account_{}", rng.random_range(0..2_000)))
+                    .collect()
+            };
+            let tasks: Vec<Arc<Task>> = (0..40)
+                .map(|id| {
+                    Arc::new(Task {
+                        reads: keys(&mut rng),
+                        writes: keys(&mut rng),
+                        ..task(id, &[], &[])
+                    })
+                })
+                .collect();
+            let exact = DependencyGraph::from_tasks(tasks.clone());
+            let prefiltered = DependencyGraph::from_tasks_prefiltered(tasks);
+
+            assert_eq!(exact.dependencies, prefiltered.dependencies);
+        }
+    }
+
+    /// On the same block, commutative fees collapse the levels exact sets
+    /// force, and the hierarchy adds the edges hidden behind a parent key.
+    #[test]
+    fn test_detectors_compared() {
+        let tasks = || {
+            vec![
+                task(0, &[], &["fees", "a/1"]),
+                task(1, &[], &["fees", "a/2"]),
+                task(2, &[], &["fees", "a/3"]),
+                task(3, &["a"], &["audit"]),
+            ]
+        };
+        let levels = |detector: &dyn ConflictDetector| {
+            DependencyGraph::from_tasks_with(tasks(), detector)
+                .execution_levels()
+                .len()
+        };
+
+        assert_eq!(levels(&ExactSets), 3);
+        assert_eq!(levels(&Commutative::new(["fees"])), 1);
+        assert_eq!(levels(&PrefixHierarchy::default()), 4);
+    }
+}
@@ -0,0 +1,235 @@
+//! Duplicate-task detection.
+//!
+//! Two tasks with the same name, the same declared reads and the same
+//! declared writes are assumed to run the same work. If nothing between them
+//! writes any of those reads, the later one sees exactly the inputs the
+//! earlier one saw and so produces the same writes; if nothing between them
+//! writes any of those writes either, re-applying them changes nothing. The
+//! later task is then a duplicate: it can be dropped and handed the earlier
+//! task's receipt instead.
+//!
+//! Like the rest of this module, this trusts declarations. A task whose work
+//! depends on its own id, rather than only on what it reads, is not safe to
+//! deduplicate.
+
+use std::{collections::BTreeSet, sync::Arc};
+
+use crate::parallel_determinism::{
+    dep_graph::DependencyGraph,
+    types::{Receipt, Task, TaskId},
+};
+
+/// A task that repeats an earlier one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Duplicate {
+    /// The task whose result is shared.
+    pub original: TaskId,
+    /// The later task that would recompute it.
+    pub duplicate: TaskId,
+}
+
+pub struct Deduplicated {
+    /// The graph without its duplicates, renumbered densely in original order.
+    pub graph: DependencyGraph,
+    /// For each task in `graph`, its id before deduplication.
+    pub original_ids: Vec<TaskId>,
+    /// Every duplicate removed, by ascending duplicate id.
+    pub duplicates: Vec<Duplicate>,
+}
+
+impl Deduplicated {
+    /// Receipts for the original graph, given receipts for the deduplicated
+    /// one: survivors get their original ids back, and each duplicate gets a
+    /// copy of its original's receipt.
+    pub fn share(&self, receipts: &[Receipt]) -> Vec<Receipt> {
+        let total = self.original_ids.len() + self.duplicates.len();
+        let mut shared: Vec<Option<Receipt>> = vec![None; total];
+        for (receipt, &id) in receipts.iter().zip(&self.original_ids) {
+            shared[id] = Some(renumber(receipt.clone(), id));
+        }
+        for duplicate in &self.duplicates {
+            let receipt = shared[duplicate.original]
+                .clone()
+                .expect("Originals precede their duplicates");
+            shared[duplicate.duplicate] = Some(renumber(receipt, duplicate.duplicate));
+        }
+        shared.into_iter().flatten().collect()
+    }
+}
+
+fn renumber(mut receipt: Receipt, id: TaskId) -> Receipt {
+    receipt.task_id = id;
+    for event in &mut receipt.events {
+        event.task_id = id;
+    }
+    receipt
+}
+
+impl DependencyGraph {
+    /// Every task that repeats an earlier one, by ascending duplicate id. A
+    /// chain of repeats all point at the first task of the chain.
+    pub fn duplicates(&self) -> Vec<Duplicate> {
+        let mut duplicates = vec![];
+        let mut is_duplicate = vec![false; self.tasks.len()];
+        for later in &self.tasks {
+            let original = self.tasks[..later.id]
+                .iter()
+                .rev()
+                .filter(|earlier| !is_duplicate[earlier.id])
+                .find(|earlier| self.repeats(earlier, later));
+            if let Some(original) = original {
+                is_duplicate[later.id] = true;
+                duplicates.push(Duplicate {
+                    original: original.id,
+                    duplicate: later.id,
+                });
+            }
+        }
+        duplicates
+    }
+
+    /// Drop every duplicate, keeping the first task of each run of repeats.
+    pub fn deduplicate(&self) -> Deduplicated {
+        let duplicates = self.duplicates();
+        let dropped: BTreeSet<TaskId> = duplicates.iter().map(|d| d.duplicate).collect();
+        let survivors: Vec<&Arc<Task>> = self
+            .tasks
+            .iter()
+            .filter(|task| !dropped.contains(&task.id))
+            .collect();
+        let original_ids = survivors.iter().map(|task| task.id).collect();
+        let renumbered: Vec<Arc<Task>> = survivors
+            .into_iter()
+            .enumerate()
+            .map(|(id, task)| Arc::new(task.renumbered(id)))
+            .collect();
+
+        Deduplicated {
+            graph: DependencyGraph::from_tasks(renumbered),
+            original_ids,
+            duplicates,
+        }
+    }
+
+    /// Whether `later` would recompute exactly what `earlier` computed.
+    fn repeats(&self, earlier: &Task, later: &Task) -> bool {
+        let same = |a: &[String], b: &[String]| {
+            a.iter().collect::<BTreeSet<_>>() == b.iter().collect::<BTreeSet<_>>()
+        };
+        if earlier.name != later.name
+            || !same(&earlier.reads, &later.reads)
+            || !same(&earlier.writes, &later.writes)
+        {
+            return false;
+        }
+        // The earlier task's own writes count against the reads: if it
+        // updates a key it read, the later task sees the new value.
+        let clobbers_reads = self.tasks[earlier.id..later.id]
+            .iter()
+            .any(|task| task.writes.iter().any(|key| later.reads.contains(key)));
+        let clobbers_writes = self.tasks[earlier.id + 1..later.id]
+            .iter()
+            .any(|task| task.writes.iter().any(|key| later.writes.contains(key)));
+        !clobbers_reads && !clobbers_writes
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        sequential::SequentialExecutor,
+        state::{MemoryStorage, Storage},
+        types::TaskContext,
+    };
+
+    type Work = &'static (dyn Fn(&mut TaskContext) -> Result<String, String> + Sync);
+
+    fn task(id: TaskId, name: &str, reads: &[&str], writes: &[&str], work: Work) -> Task {
+        Task {
+            id,
+            name: name.to_string(),
+            reads: reads.iter().map(|key| key.to_string()).collect(),
+            writes: writes.iter().map(|key| key.to_string()).collect(),
+            priority: 0,
+            cost: 1,
+            work,
+        }
+    }
+
+    fn double_a(context: &mut TaskContext) -> Result<String, String> {
+        let doubled = context.read("a").unwrap_or(0) * 2;
+        context.write("double", doubled);
+        context.emit(format!("doubled to {}", doubled));
+        Ok(doubled.to_string())
+    }
+
+    fn bump_a(context: &mut TaskContext) -> Result<String, String> {
+        context.write("a", context.read("a").unwrap_or(0) + 1);
+        Ok("bumped".to_string())
+    }
+
+    fn copy_double(context: &mut TaskContext) -> Result<String, String> {
+        context.write("copy", context.read("double").unwrap_or(0));
+        Ok("copied".to_string())
+    }
+
+    /// T0 and T2 are the same doubling with nothing in between touching `a`
+    /// or `double`. T4 repeats it after T3 bumps `a`, so it is not a
+    /// duplicate.
+    fn graph() -> DependencyGraph {
+        DependencyGraph::from_tasks([
+            task(0, "double", &["a"], &["double"], &double_a),
+            task(1, "copy", &["double"], &["copy"], &copy_double),
+            task(2, "double", &["a"], &["double"], &double_a),
+            task(3, "bump", &["a"], &["a"], &bump_a),
+            task(4, "double", &["a"], &["double"], &double_a),
+        ])
+    }
+
+    /// Only the repeat with unchanged inputs is reported.
+    #[test]
+    fn test_detects_repeat_with_unchanged_inputs() {
+        assert_eq!(
+            graph().duplicates(),
+            [Duplicate {
+                original: 0,
+                duplicate: 2
+            }]
+        );
+    }
+
+    /// Running the deduplicated graph leaves the same final state, and the
+    /// shared receipts match the ones the full graph produced.
+    #[test]
+    fn test_deduplicated_final_state_unchanged() {
+        let deduplicated = graph().deduplicate();
+        assert_eq!(deduplicated.original_ids, [0, 1, 3, 4]);
+
+        let ((full_state, full_receipts), (state, receipts)) =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let genesis = || [("a", 5)].into_iter().collect::<MemoryStorage>();
+                let full = genesis();
+                let full_report = SequentialExecutor::new()
+                    .execute_with_state(&context, &graph(), &full)
+                    .await;
+                let store = genesis();
+                let report = SequentialExecutor::new()
+                    .execute_with_state(&context, &deduplicated.graph, &store)
+                    .await;
+                (
+                    (full.snapshot(), full_report.receipts),
+                    (store.snapshot(), deduplicated.share(&report.receipts)),
+                )
+            });
+
+        assert_eq!(state, full_state);
+        assert_eq!(state["double"], 12);
+        assert_eq!(receipts, full_receipts);
+    }
+}
@@ -1,27 +1,98 @@
-use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    collections::{DMap, DSet},
+    parallel_determinism::{
+        conflict::{BloomPrefilter, ConflictDetector, ExactSets},
+        types::{Task, TaskId},
+    },
+};
+
+/// Every earlier task that `tasks[i]` conflicts with under `detector`.
+fn dependencies_of(
+    tasks: &[Arc<Task>],
+    i: usize,
+    detector: &(impl ConflictDetector + ?Sized),
+) -> DSet<TaskId> {
+    tasks[..i]
+        .iter()
+        .enumerate()
+        .filter(|(_, other_task)| detector.conflicts(&tasks[i], other_task))
+        .map(|(j, _)| j)
+        .collect()
+}
 
-use crate::parallel_determinism::types::{Task, TaskId};
 pub struct DependencyGraph {
-    pub tasks: Vec<Task>,
-    pub dependencies: HashMap<TaskId, HashSet<TaskId>>, // (task_id, depends_on_task_id)
+    pub tasks: Vec<Arc<Task>>,
+    pub dependencies: DMap<TaskId, DSet<TaskId>>, // (task_id, depends_on_task_id)
 }
 
 impl DependencyGraph {
-    pub fn from_tasks(tasks: Vec<Task>) -> Self {
-        let mut dependencies: HashMap<TaskId, HashSet<TaskId>> = HashMap::new();
+    /// Build the graph with [`ExactSets`] conflict detection.
+    pub fn from_tasks(tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>) -> Self {
+        Self::from_tasks_with(tasks, &ExactSets)
+    }
 
-        // For each task, find all tasks before it that it conflicts with
-        for (i, task) in tasks.iter().enumerate() {
-            let mut deps = HashSet::new();
+    /// [`Self::from_tasks`] with a [`BloomPrefilter`] in front of the exact
+    /// comparison. Produces the same graph; on large blocks with wide access
+    /// sets most pairs are ruled out without comparing keys.
+    pub fn from_tasks_prefiltered(tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>) -> Self {
+        let tasks: Vec<Arc<Task>> = tasks.into_iter().map(Into::into).collect();
+        let detector = BloomPrefilter::new(ExactSets, &tasks);
+        Self::from_tasks_with(tasks, &detector)
+    }
 
-            for (j, other_task) in tasks[..i].iter().enumerate() {
-                if task.conflicts_with(other_task) {
-                    deps.insert(j);
-                }
-            }
+    /// Build the graph, deciding conflicts with `detector` and computing each
+    /// task's edges on the rayon pool when the `rayon` feature is enabled.
+    /// Each task's edges depend only on the tasks before it, so the result is
+    /// identical to building on one thread.
+    #[cfg(feature = "rayon")]
+    pub fn from_tasks_with(
+        tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>,
+        detector: &(impl ConflictDetector + ?Sized),
+    ) -> Self {
+        let tasks: Vec<Arc<Task>> = tasks.into_iter().map(Into::into).collect();
+        let dependencies = (0..tasks.len())
+            .into_par_iter()
+            .map(|i| (i, dependencies_of(&tasks, i, detector)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect();
 
-            dependencies.insert(i, deps);
+        Self {
+            tasks,
+            dependencies,
         }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    pub fn from_tasks_with(
+        tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>,
+        detector: &(impl ConflictDetector + ?Sized),
+    ) -> Self {
+        Self::from_tasks_sequential_with(tasks, detector)
+    }
+
+    /// Build the graph on the current thread.
+    ///
+    /// Accepts owned tasks or already-shared `Arc<Task>`s; either way the
+    /// graph only holds reference-counted handles.
+    pub fn from_tasks_sequential(tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>) -> Self {
+        Self::from_tasks_sequential_with(tasks, &ExactSets)
+    }
+
+    pub fn from_tasks_sequential_with(
+        tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>,
+        detector: &(impl ConflictDetector + ?Sized),
+    ) -> Self {
+        let tasks: Vec<Arc<Task>> = tasks.into_iter().map(Into::into).collect();
+        // For each task, find all tasks before it that it conflicts with
+        let dependencies = (0..tasks.len())
+            .map(|i| (i, dependencies_of(&tasks, i, detector)))
+            .collect();
 
         Self {
             tasks,
@@ -30,35 +101,71 @@ impl DependencyGraph {
     }
 
     pub fn execution_levels(&self) -> Vec<Vec<TaskId>> {
-        let mut levels = vec![];
-        let mut completed = HashSet::new();
-        let mut remaining: HashSet<TaskId> = self.tasks.iter().map(|t| t.id).collect();
-
-        while !remaining.is_empty() {
-            let mut current_level = vec![];
-
-            // Find tasks whose dependencies are all completed
-            for &task_id in &remaining {
-                let deps = &self.dependencies[&task_id];
-                if deps.iter().all(|dep| completed.contains(dep)) {
-                    current_level.push(task_id);
-                }
-            }
+        self.levels().collect()
+    }
 
-            if current_level.is_empty() {
-                panic!("Circular dependency detected!");
-            }
+    /// The execution levels, computed one at a time as they are consumed.
+    pub fn levels(&self) -> Levels<'_> {
+        Levels {
+            graph: self,
+            completed: DSet::new(),
+            remaining: self.tasks.iter().map(|t| t.id).collect(),
+        }
+    }
 
-            // Mark current level as completed
-            for &task_id in &current_level {
-                completed.insert(task_id);
-                remaining.remove(&task_id);
-            }
+    /// [`Self::levels`] as an async `Stream`, so a consumer can interleave
+    /// executing one level with other async work, such as fetching the next
+    /// block, on either runtime. Each level is computed when it is polled
+    /// for; the stream never waits.
+    #[cfg(feature = "runtime")]
+    pub fn levels_stream(&self) -> Levels<'_> {
+        self.levels()
+    }
 
-            levels.push(current_level);
+    /// Summary statistics of the graph's shape.
+    pub fn metrics(&self) -> GraphMetrics {
+        let tasks = self.tasks.len();
+        let edges: usize = self.dependencies.values().map(|deps| deps.len()).sum();
+        let levels = self.execution_levels();
+        let pairs = tasks * tasks.saturating_sub(1) / 2;
+        GraphMetrics {
+            tasks,
+            edges,
+            average_out_degree: if tasks == 0 {
+                0.0
+            } else {
+                edges as f64 / tasks as f64
+            },
+            depth: levels.len(),
+            max_width: levels.iter().map(Vec::len).max().unwrap_or(0),
+            conflict_density: if pairs == 0 {
+                0.0
+            } else {
+                edges as f64 / pairs as f64
+            },
         }
+    }
 
-        levels
+    /// Each level's estimated cost. Tasks in a level run in parallel, so a
+    /// level takes about as long as its heaviest task.
+    pub fn level_costs(&self) -> Vec<LevelCost> {
+        self.execution_levels()
+            .into_iter()
+            .map(|level| {
+                let total = level.iter().map(|&id| self.tasks[id].cost).sum();
+                let heaviest = level
+                    .iter()
+                    .copied()
+                    .max_by_key(|&id| (self.tasks[id].cost, std::cmp::Reverse(id)))
+                    .expect("Levels are never empty");
+                LevelCost {
+                    tasks: level,
+                    total,
+                    heaviest,
+                    max: self.tasks[heaviest].cost,
+                }
+            })
+            .collect()
     }
 
     pub fn visualize(&self) {
@@ -77,16 +184,160 @@ impl DependencyGraph {
         }
 
         println!("\n=== Execution Levels ===");
-        for (level_num, level) in self.execution_levels().iter().enumerate() {
+        for (level_num, level) in self.level_costs().iter().enumerate() {
             let task_names: Vec<_> = level
+                .tasks
                 .iter()
-                .map(|id| self.tasks[*id].name.as_str())
+                .map(|id| format!("{} ({})", self.tasks[*id].name, self.tasks[*id].cost))
                 .collect();
-            println!(
-                "Level {}: {:?} (can run in parallel)",
-                level_num, task_names
+            print!(
+                "Level {}: {:?} (can run in parallel), cost {}",
+                level_num, task_names, level.max
             );
+            if level.is_dominated() {
+                print!(", dominated by {}", self.tasks[level.heaviest].name);
+            }
+            println!();
+        }
+    }
+
+    /// The graph in Graphviz DOT, one row per level, with tasks shaded from
+    /// light to dark by their share of the heaviest task's cost and the
+    /// heaviest task of a dominated level outlined in bold.
+    pub fn to_dot(&self) -> String {
+        let levels = self.level_costs();
+        let max_cost = levels
+            .iter()
+            .map(|level| level.max)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let mut dot = String::from("digraph dependencies {\n    rankdir=TB;\n");
+        dot.push_str("    node [shape=box, style=filled, colorscheme=reds5];\n");
+        for (level_num, level) in levels.iter().enumerate() {
+            dot.push_str(&format!(
+                "    subgraph level_{} {{\n        rank=same;\n",
+                level_num
+            ));
+            for &id in &level.tasks {
+                let task = &self.tasks[id];
+                // Shades 1..=5 of the color scheme, proportional to cost.
+                let shade = 1 + task.cost * 4 / max_cost;
+                let bold = if level.is_dominated() && id == level.heaviest {
+                    ", penwidth=3"
+                } else {
+                    ""
+                };
+                dot.push_str(&format!(
+                    "        t{} [label=\"{}\\ncost {}\", fillcolor={}{}];\n",
+                    id,
+                    task.name.replace('"', "\\\""),
+                    task.cost,
+                    shade,
+                    bold
+                ));
+            }
+            dot.push_str("    }\n");
         }
+        for (task_id, deps) in &self.dependencies {
+            for dep in deps.iter() {
+                dot.push_str(&format!("    t{} -> t{};\n", dep, task_id));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Iterator, and with the `runtime` feature `Stream`, over a graph's
+/// execution levels in order.
+pub struct Levels<'a> {
+    graph: &'a DependencyGraph,
+    completed: DSet<TaskId>,
+    remaining: DSet<TaskId>,
+}
+
+impl Iterator for Levels<'_> {
+    type Item = Vec<TaskId>;
+
+    fn next(&mut self) -> Option<Vec<TaskId>> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+
+        // Find tasks whose dependencies are all completed
+        let level: Vec<TaskId> = self
+            .remaining
+            .iter()
+            .copied()
+            .filter(|task_id| {
+                self.graph.dependencies[task_id]
+                    .iter()
+                    .all(|dep| self.completed.contains(dep))
+            })
+            .collect();
+
+        if level.is_empty() {
+            panic!("Circular dependency detected!");
+        }
+
+        // Mark current level as completed
+        for &task_id in &level {
+            self.completed.insert(task_id);
+            self.remaining.remove(&task_id);
+        }
+
+        Some(level)
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl futures_core::Stream for Levels<'_> {
+    type Item = Vec<TaskId>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Vec<TaskId>>> {
+        std::task::Poll::Ready(self.next())
+    }
+}
+
+/// The shape of a dependency graph, for characterizing workloads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphMetrics {
+    pub tasks: usize,
+    /// Conflicting pairs. Every conflict is an edge, including ones implied
+    /// by others, so this is not the size of a transitive reduction.
+    pub edges: usize,
+    /// Edges per task: how many earlier tasks a task waits on, on average.
+    pub average_out_degree: f64,
+    /// Number of execution levels, the length of the critical path.
+    pub depth: usize,
+    /// Tasks in the widest level, the most parallelism ever available.
+    pub max_width: usize,
+    /// Fraction of task pairs that conflict, from 0 (all independent) to 1
+    /// (a total order).
+    pub conflict_density: f64,
+}
+
+/// One execution level's estimated cost.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LevelCost {
+    pub tasks: Vec<TaskId>,
+    /// Sum of the level's task costs: the work, not the time.
+    pub total: u64,
+    /// The costliest task, lowest id on ties.
+    pub heaviest: TaskId,
+    /// Its cost, which bounds the level's runtime from below.
+    pub max: u64,
+}
+
+impl LevelCost {
+    /// The heaviest task costs more than all the others together, so the
+    /// level's runtime is mostly waiting on it.
+    pub fn is_dominated(&self) -> bool {
+        self.tasks.len() > 1 && self.max * 2 > self.total
     }
 }
 
@@ -96,20 +347,24 @@ mod tests {
 
     #[test]
     fn test_no_conflicts() {
-        let tasks = vec![
+        let tasks = [
             Task {
                 id: 0,
                 name: "A".to_string(),
                 reads: vec!["account_1".to_string()],
                 writes: vec!["account_2".to_string()],
-                work: &(|| Ok("A done".to_string())),
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("A done".to_string())),
             },
             Task {
                 id: 1,
                 name: "B".to_string(),
                 reads: vec!["account_3".to_string()],
                 writes: vec!["account_4".to_string()],
-                work: &(|| Ok("B done".to_string())),
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("B done".to_string())),
             },
         ];
 
@@ -124,7 +379,9 @@ mod tests {
             name: "A".to_string(),
             reads: vec![],
             writes: vec!["account_1".to_string()],
-            work: &(|| Ok("A".to_string())),
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok("A".to_string())),
         };
 
         let task_b = Task {
@@ -132,7 +389,9 @@ mod tests {
             name: "B".to_string(),
             reads: vec![],
             writes: vec!["account_1".to_string()],
-            work: &(|| Ok("B".to_string())),
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok("B".to_string())),
         };
 
         assert!(task_a.conflicts_with(&task_b));
@@ -146,7 +405,9 @@ mod tests {
             name: "A".to_string(),
             reads: vec![],
             writes: vec!["account_1".to_string()],
-            work: &(|| Ok("A".to_string())),
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok("A".to_string())),
         };
 
         let task_b = Task {
@@ -154,7 +415,9 @@ mod tests {
             name: "B".to_string(),
             reads: vec!["account_1".to_string()],
             writes: vec![],
-            work: &(|| Ok("B".to_string())),
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok("B".to_string())),
         };
 
         assert!(task_b.conflicts_with(&task_a));
@@ -168,21 +431,27 @@ mod tests {
                 name: "A".to_string(),
                 reads: vec![],
                 writes: vec!["x".to_string()],
-                work: &(|| Ok("A".to_string())),
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("A".to_string())),
             },
             Task {
                 id: 1,
                 name: "B".to_string(),
                 reads: vec![],
                 writes: vec!["y".to_string()],
-                work: &(|| Ok("B".to_string())),
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("B".to_string())),
             },
             Task {
                 id: 2,
                 name: "C".to_string(),
                 reads: vec!["x".to_string()],
                 writes: vec!["z".to_string()],
-                work: &(|| Ok("C".to_string())),
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("C".to_string())),
             },
         ];
 
@@ -193,4 +462,178 @@ mod tests {
         assert_eq!(levels[0].len(), 2); // A and B
         assert_eq!(levels[1].len(), 1); // C
     }
+
+    #[test]
+    fn test_parallel_build_matches_sequential() {
+        use crate::parallel_determinism::generator::{BlockSpec, generate_tasks};
+
+        let spec = BlockSpec {
+            size: 512,
+            conflict_rate: 0.3,
+            seed: 17,
+        };
+        let parallel = DependencyGraph::from_tasks(generate_tasks(&spec));
+        let sequential = DependencyGraph::from_tasks_sequential(generate_tasks(&spec));
+
+        assert_eq!(parallel.dependencies, sequential.dependencies);
+        assert_eq!(parallel.execution_levels(), sequential.execution_levels());
+    }
+
+    /// Metrics of a small fan-in graph, and of uncontended and fully
+    /// contended generated blocks.
+    #[test]
+    fn test_metrics() {
+        use crate::parallel_determinism::generator::{BlockSpec, generate_tasks};
+
+        let tasks = vec![
+            Task {
+                id: 0,
+                name: "A".to_string(),
+                reads: vec![],
+                writes: vec!["x".to_string()],
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("A".to_string())),
+            },
+            Task {
+                id: 1,
+                name: "B".to_string(),
+                reads: vec![],
+                writes: vec!["y".to_string()],
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("B".to_string())),
+            },
+            Task {
+                id: 2,
+                name: "C".to_string(),
+                reads: vec!["x".to_string(), "y".to_string()],
+                writes: vec![],
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("C".to_string())),
+            },
+        ];
+        let metrics = DependencyGraph::from_tasks(tasks).metrics();
+        assert_eq!(
+            metrics,
+            GraphMetrics {
+                tasks: 3,
+                edges: 2,
+                average_out_degree: 2.0 / 3.0,
+                depth: 2,
+                max_width: 2,
+                conflict_density: 2.0 / 3.0,
+            }
+        );
+
+        let spec = |conflict_rate| BlockSpec {
+            size: 40,
+            conflict_rate,
+            seed: 5,
+        };
+        let free = DependencyGraph::from_tasks(generate_tasks(&spec(0.0))).metrics();
+        assert_eq!((free.edges, free.depth, free.max_width), (0, 1, 40));
+        assert_eq!(free.conflict_density, 0.0);
+        let contended = DependencyGraph::from_tasks(generate_tasks(&spec(1.0))).metrics();
+        assert!(contended.conflict_density > 0.2);
+        assert!(contended.depth > free.depth);
+    }
+
+    /// A level whose heaviest task outweighs the rest is flagged, and the
+    /// DOT export shades tasks by cost and outlines the culprit.
+    #[test]
+    fn test_cost_annotations() {
+        let task = |id: TaskId, writes: &str, cost: u64| Task {
+            id,
+            name: format!("T{}", id),
+            reads: vec![],
+            writes: vec![writes.to_string()],
+            priority: 0,
+            cost,
+            work: &(|_| Ok(String::new())),
+        };
+        let graph = DependencyGraph::from_tasks([
+            task(0, "x", 1),
+            task(1, "y", 8),
+            task(2, "z", 2),
+            task(3, "x", 1),
+        ]);
+
+        let levels = graph.level_costs();
+        assert_eq!(levels.len(), 2);
+        assert_eq!(
+            (levels[0].total, levels[0].heaviest, levels[0].max),
+            (11, 1, 8)
+        );
+        assert!(levels[0].is_dominated());
+        assert!(!levels[1].is_dominated());
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("t1 [label=\"T1\\ncost 8\", fillcolor=5, penwidth=3];"));
+        assert!(dot.contains("t0 [label=\"T0\\ncost 1\", fillcolor=1];"));
+        assert!(dot.contains("t0 -> t3;"));
+    }
+
+    #[test]
+    fn test_shared_tasks_are_not_copied() {
+        let task = Arc::new(Task {
+            id: 0,
+            name: "A".to_string(),
+            reads: vec![],
+            writes: vec!["x".to_string()],
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok("A".to_string())),
+        });
+
+        let graph = DependencyGraph::from_tasks([task.clone()]);
+
+        assert!(Arc::ptr_eq(&graph.tasks[0], &task));
+    }
+
+    /// The stream yields the same levels as `execution_levels`, consumed
+    /// between other awaits, on both runtimes.
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_levels_stream() {
+        use std::{future::poll_fn, pin::Pin, time::Duration};
+
+        use commonware_runtime::{
+            Clock, Runner,
+            deterministic::{Config, Runner as DeterministicRunner},
+            tokio::Runner as TokioRunner,
+        };
+        use futures_core::Stream;
+
+        use crate::parallel_determinism::generator::{BlockSpec, generate_tasks};
+
+        async fn consume(context: impl Clock, graph: &DependencyGraph) -> Vec<Vec<TaskId>> {
+            let mut levels = graph.levels_stream();
+            let mut consumed = Vec::new();
+            while let Some(level) = poll_fn(|cx| Pin::new(&mut levels).poll_next(cx)).await {
+                // Stands in for executing the level while the next block loads.
+                context.sleep(Duration::from_millis(1)).await;
+                consumed.push(level);
+            }
+            consumed
+        }
+
+        let graph = DependencyGraph::from_tasks(generate_tasks(&BlockSpec {
+            size: 40,
+            conflict_rate: 0.5,
+            seed: 1,
+        }));
+        let expected = graph.execution_levels();
+        assert!(expected.len() > 1);
+        assert_eq!(
+            DeterministicRunner::new(Config::default())
+                .start(|context| async { consume(context, &graph).await }),
+            expected
+        );
+        assert_eq!(
+            TokioRunner::default().start(|context| async { consume(context, &graph).await }),
+            expected
+        );
+    }
 }
@@ -0,0 +1,189 @@
+//! Comparing the dependency structure of two task sets.
+//!
+//! Reordering a block, or changing what one transaction touches, changes
+//! which tasks wait on which, and so how much of the block can run in
+//! parallel. [`DependencyGraph::diff`] lines the two graphs up and reports
+//! what changed.
+//!
+//! Ids differ between the two sides after a reorder, so tasks are matched by
+//! what they are instead: the same name with the same reads and writes. When
+//! several tasks share all three, the first on one side matches the first on
+//! the other, and so on. A task whose accesses were modified therefore shows
+//! up as removed and re-added, and an edge whose direction flipped because
+//! two tasks swapped places shows up as removed and added.
+
+use std::collections::BTreeMap;
+
+use crate::parallel_determinism::{
+    dep_graph::{DependencyGraph, GraphMetrics},
+    types::{Task, TaskId},
+};
+
+/// What changed from one graph to another. Tasks and edges on the `before`
+/// side carry ids in that graph, those on the `after` side ids in the other.
+/// Edges are `(dependent, dependency)` pairs. Every list is sorted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphDiff {
+    /// Matched tasks as `(before, after)` id pairs.
+    pub matched: Vec<(TaskId, TaskId)>,
+    pub removed_tasks: Vec<TaskId>,
+    pub added_tasks: Vec<TaskId>,
+    pub removed_edges: Vec<(TaskId, TaskId)>,
+    pub added_edges: Vec<(TaskId, TaskId)>,
+    pub before: GraphMetrics,
+    pub after: GraphMetrics,
+}
+
+impl GraphDiff {
+    /// Neither tasks nor edges changed, though ids may have.
+    pub fn is_empty(&self) -> bool {
+        self.removed_tasks.is_empty()
+            && self.added_tasks.is_empty()
+            && self.removed_edges.is_empty()
+            && self.added_edges.is_empty()
+    }
+}
+
+/// What a task is, independent of where it sits in the block.
+type Signature = (String, Vec<String>, Vec<String>);
+
+fn signature(task: &Task) -> Signature {
+    let mut reads = task.reads.clone();
+    reads.sort();
+    let mut writes = task.writes.clone();
+    writes.sort();
+    (task.name.clone(), reads, writes)
+}
+
+fn edges(graph: &DependencyGraph) -> Vec<(TaskId, TaskId)> {
+    let mut edges: Vec<_> = graph
+        .dependencies
+        .iter()
+        .flat_map(|(&task, deps)| deps.iter().map(move |&dep| (task, dep)))
+        .collect();
+    edges.sort();
+    edges
+}
+
+impl DependencyGraph {
+    /// How `other` differs from this graph.
+    pub fn diff(&self, other: &DependencyGraph) -> GraphDiff {
+        let mut unmatched: BTreeMap<Signature, Vec<TaskId>> = BTreeMap::new();
+        for task in other.tasks.iter().rev() {
+            unmatched.entry(signature(task)).or_default().push(task.id);
+        }
+
+        let mut matched = vec![];
+        let mut forward = vec![None; self.tasks.len()];
+        let mut removed_tasks = vec![];
+        for task in &self.tasks {
+            match unmatched.get_mut(&signature(task)).and_then(Vec::pop) {
+                Some(id) => {
+                    forward[task.id] = Some(id);
+                    matched.push((task.id, id));
+                }
+                None => removed_tasks.push(task.id),
+            }
+        }
+        let mut added_tasks: Vec<TaskId> = unmatched.into_values().flatten().collect();
+        added_tasks.sort();
+
+        let before_edges = edges(self);
+        let after_edges = edges(other);
+        let mapped: Vec<_> = before_edges
+            .iter()
+            .map(|&(task, dep)| forward[task].zip(forward[dep]))
+            .collect();
+        let removed_edges = before_edges
+            .iter()
+            .zip(&mapped)
+            .filter(|(_, mapped)| {
+                mapped.is_none_or(|edge| after_edges.binary_search(&edge).is_err())
+            })
+            .map(|(&edge, _)| edge)
+            .collect();
+        let mut kept: Vec<_> = mapped.into_iter().flatten().collect();
+        kept.sort();
+        let added_edges = after_edges
+            .iter()
+            .filter(|edge| kept.binary_search(edge).is_err())
+            .copied()
+            .collect();
+
+        GraphDiff {
+            matched,
+            removed_tasks,
+            added_tasks,
+            removed_edges,
+            added_edges,
+            before: self.metrics(),
+            after: other.metrics(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: TaskId, name: &str, reads: &[&str], writes: &[&str]) -> Task {
+        Task {
+            id,
+            name: name.to_string(),
+            reads: reads.iter().map(|key| key.to_string()).collect(),
+            writes: writes.iter().map(|key| key.to_string()).collect(),
+            priority: 0,
+            cost: 1,
+            work: &(|_| Ok(String::new())),
+        }
+    }
+
+    fn graph(tasks: &[(&str, &[&str], &[&str])]) -> DependencyGraph {
+        DependencyGraph::from_tasks(
+            tasks
+                .iter()
+                .enumerate()
+                .map(|(id, &(name, reads, writes))| task(id, name, reads, writes)),
+        )
+    }
+
+    /// A graph differs from itself in nothing.
+    #[test]
+    fn test_identical_graphs() {
+        let tasks: &[(&str, &[&str], &[&str])] = &[
+            ("A", &[], &["x"]),
+            ("B", &["x"], &["y"]),
+            ("C", &[], &["z"]),
+        ];
+        let diff = graph(tasks).diff(&graph(tasks));
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.matched, [(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(diff.before, diff.after);
+    }
+
+    /// Moving a writer behind its reader flips their edge, and making a task
+    /// read a contended key replaces it, adds its edge, and serializes the
+    /// block.
+    #[test]
+    fn test_reorder_and_modify() {
+        let before = graph(&[
+            ("A", &[], &["x"]),
+            ("B", &["x"], &["y"]),
+            ("C", &[], &["z"]),
+        ]);
+        let after = graph(&[
+            ("B", &["x"], &["y"]),
+            ("A", &[], &["x"]),
+            ("C", &["x"], &["z"]),
+        ]);
+        let diff = before.diff(&after);
+
+        assert_eq!(diff.matched, [(0, 1), (1, 0)]);
+        assert_eq!(diff.removed_tasks, [2]);
+        assert_eq!(diff.added_tasks, [2]);
+        assert_eq!(diff.removed_edges, [(1, 0)]);
+        assert_eq!(diff.added_edges, [(1, 0), (2, 1)]);
+        assert_eq!((diff.before.max_width, diff.after.max_width), (2, 1));
+    }
+}
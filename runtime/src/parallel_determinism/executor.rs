@@ -0,0 +1,948 @@
+//! Level-by-level parallel execution of a dependency graph.
+//!
+//! Tasks in the same level do not conflict, so they are spawned together and
+//! may finish in any order. What must *not* depend on that order is the
+//! output of the block: receipts and events are always reported in canonical
+//! task-id order, the same way every validator must produce the same receipts
+//! for a block no matter how many cores it used to execute it.
+//!
+//! When tasks read from a [`Storage`] backend, the executor can either let each
+//! task fetch its reads on demand or prefetch the whole level's declared
+//! read set concurrently before any task starts. Declared read sets are what
+//! make the second option possible.
+//!
+//! Writes are never applied while a level is running. Each level's writes are
+//! gathered into a [`WriteBatch`], which can be inspected, then staged and
+//! committed to storage in one step before the next level starts: a two-phase
+//! commit at every level boundary.
+//!
+//! [`Hooks`] can be attached for task start, task end and level completion,
+//! to instrument a run without changing task code, and a [`Middleware`] stack
+//! wraps every task's work the same way.
+//!
+//! [`ParallelExecutor::with_workers`] caps how many tasks run at once. A wide
+//! level is dealt onto a fixed number of worker lanes by position, so the
+//! assignment is part of the configuration, not of the timing.
+//!
+//! A task that panics fails on its own: the panic is caught at the task
+//! boundary, the task gets an error receipt, and the report lists a
+//! [`TaskError`]. The [`ErrorPolicy`] decides whether later levels still run.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{
+    error::{ErrorPolicy, TaskError, panic_message},
+    hooks::Hooks,
+    parallel_determinism::{
+        dep_graph::DependencyGraph,
+        middleware::{Middleware, Next},
+        state::{MemoryStorage, Storage, Value, WriteBatch},
+        tie_break::TieBreak,
+        types::{Event, Receipt, ResourceId, Task, TaskContext, TaskId},
+    },
+    stats::elapsed_since,
+    trace::EventLog,
+};
+
+/// The trace label worker assignments are filed under.
+const TRACE_LABEL: &str = "scheduler";
+
+/// Everything observable about one execution of a graph.
+pub struct ExecutionReport {
+    /// One receipt per task that ran, in task-id order. Unless execution
+    /// halted early, that is every task, indexed by task id.
+    pub receipts: Vec<Receipt>,
+    /// The order in which tasks actually finished. This is scheduler-dependent
+    /// and kept only for inspection; nothing canonical is derived from it.
+    pub completion_order: Vec<TaskId>,
+    /// The write batch committed at the end of each level, in level order.
+    pub batches: Vec<WriteBatch>,
+    /// Tasks that panicked, in task-id order.
+    pub errors: Vec<TaskError>,
+    /// Time from the first level starting to the last one finishing, on the
+    /// runtime's clock (virtual time under the deterministic runtime).
+    pub elapsed: Duration,
+}
+
+impl ExecutionReport {
+    /// All events of the block in canonical order: by task id, then by the
+    /// order the task emitted them.
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.receipts
+            .iter()
+            .flat_map(|receipt| receipt.events.iter())
+    }
+}
+
+/// How tasks obtain the values in their read sets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Each task fetches its own reads, one after another, once it starts.
+    #[default]
+    OnDemand,
+    /// All distinct reads of a level are fetched concurrently before the
+    /// level's tasks are spawned.
+    Prefetch,
+}
+
+/// Called with each level's pending writes before they are committed.
+pub type WriteInspector = Arc<dyn Fn(&WriteBatch) + Send + Sync>;
+
+/// Passed to level-completion hooks once a level's batch is committed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LevelComplete {
+    pub level: usize,
+    /// The level's tasks, in the order they were started.
+    pub tasks: Vec<TaskId>,
+    /// Time the level took, including its reads and commit.
+    pub elapsed: Duration,
+}
+
+/// Runs each execution level in parallel on any Commonware-style runtime.
+#[derive(Default)]
+pub struct ParallelExecutor {
+    read_mode: ReadMode,
+    error_policy: ErrorPolicy,
+    tie_break: TieBreak,
+    workers: Option<usize>,
+    trace: Option<EventLog>,
+    inspector: Option<WriteInspector>,
+    task_start: Hooks<Task>,
+    task_end: Hooks<Receipt>,
+    level_complete: Hooks<LevelComplete>,
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl ParallelExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_read_mode(mut self, read_mode: ReadMode) -> Self {
+        self.read_mode = read_mode;
+        self
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// The order in which each level's tasks are started. Defaults to
+    /// declared priority, then task id.
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Run at most `workers` tasks at once. Each level's tasks are dealt
+    /// round-robin, in start order, onto `workers` lanes that each run their
+    /// tasks one after another, so which worker runs which task depends only
+    /// on `workers` and the level, never on timing.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        assert!(workers > 0, "need at least one worker");
+        self.workers = Some(workers);
+        self
+    }
+
+    /// Record the tie-break rule and every level's worker assignment in `log`.
+    pub fn with_trace(mut self, log: EventLog) -> Self {
+        self.trace = Some(log);
+        self
+    }
+
+    /// Observe every pending write batch before it reaches storage.
+    pub fn with_write_inspector(
+        mut self,
+        inspector: impl Fn(&WriteBatch) + Send + Sync + 'static,
+    ) -> Self {
+        self.inspector = Some(Arc::new(inspector));
+        self
+    }
+
+    /// Wrap every task's work in `middleware`. The first one added is the
+    /// outermost.
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Run `hook` on the worker just before each task's work starts.
+    pub fn on_task_start(mut self, hook: impl Fn(&Task) + Send + Sync + 'static) -> Self {
+        self.task_start.push(hook);
+        self
+    }
+
+    /// Run `hook` on the worker with each task's receipt as soon as it has one.
+    pub fn on_task_end(mut self, hook: impl Fn(&Receipt) + Send + Sync + 'static) -> Self {
+        self.task_end.push(hook);
+        self
+    }
+
+    /// Run `hook` after each level's writes are committed.
+    pub fn on_level_complete(
+        mut self,
+        hook: impl Fn(&LevelComplete) + Send + Sync + 'static,
+    ) -> Self {
+        self.level_complete.push(hook);
+        self
+    }
+
+    /// Execute every task of `graph`, one level at a time.
+    ///
+    /// Each level acts as a barrier: all of its tasks are spawned as siblings
+    /// and awaited before the next level starts.
+    pub async fn execute<S: Spawner + Clock>(
+        &self,
+        context: &S,
+        graph: &DependencyGraph,
+    ) -> ExecutionReport {
+        self.run::<S, MemoryStorage>(context, graph, None).await
+    }
+
+    /// Like [`execute`](Self::execute), but tasks read their declared keys
+    /// from `store` according to the configured [`ReadMode`].
+    pub async fn execute_with_state<S: Spawner + Clock, St: Storage>(
+        &self,
+        context: &S,
+        graph: &DependencyGraph,
+        store: &St,
+    ) -> ExecutionReport {
+        self.run(context, graph, Some(store)).await
+    }
+
+    async fn run<S: Spawner + Clock, St: Storage>(
+        &self,
+        context: &S,
+        graph: &DependencyGraph,
+        store: Option<&St>,
+    ) -> ExecutionReport {
+        let start = context.current();
+        if let Some(log) = &self.trace {
+            self.tie_break.record(log);
+        }
+        let completion_order = Arc::new(Mutex::new(Vec::new()));
+        let mut receipts: Vec<Option<Receipt>> = vec![None; graph.tasks.len()];
+        let mut batches = vec![];
+        let mut errors = vec![];
+
+        for (level_num, mut level) in graph.execution_levels().into_iter().enumerate() {
+            let level_start = context.current();
+            level.sort_by_key(|&task_id| self.tie_break.key(&graph.tasks[task_id]));
+            let prefetched = match (store, self.read_mode) {
+                (Some(store), ReadMode::Prefetch) => {
+                    let keys = level
+                        .iter()
+                        .flat_map(|&task_id| graph.tasks[task_id].reads.iter().cloned())
+                        .collect();
+                    Some(Arc::new(prefetch(context, store, keys).await))
+                }
+                _ => None,
+            };
+
+            let lanes = assign_workers(&level, self.workers);
+            if let Some(log) = &self.trace {
+                for (worker, lane) in lanes.iter().enumerate() {
+                    log.record(
+                        TRACE_LABEL,
+                        format!("level {} worker {}: {:?}", level_num, worker, lane),
+                    );
+                }
+            }
+            let handles: Vec<_> = lanes
+                .into_iter()
+                .map(|lane| {
+                    let tasks: Vec<_> = lane
+                        .iter()
+                        .map(|&task_id| graph.tasks[task_id].clone())
+                        .collect();
+                    let completion_order = completion_order.clone();
+                    let prefetched = prefetched.clone();
+                    let store = store.cloned();
+                    let task_start = self.task_start.clone();
+                    let task_end = self.task_end.clone();
+                    let middleware = self.middleware.clone();
+                    context.clone().spawn(move |context| async move {
+                        let mut results = Vec::with_capacity(tasks.len());
+                        for task in tasks {
+                            let reads = match (&prefetched, &store) {
+                                (Some(prefetched), _) => task
+                                    .reads
+                                    .iter()
+                                    .map(|key| (key.clone(), prefetched[key]))
+                                    .collect(),
+                                (None, Some(store)) => {
+                                    let mut reads = BTreeMap::new();
+                                    for key in &task.reads {
+                                        reads.insert(key.clone(), store.get(&context, key).await);
+                                    }
+                                    reads
+                                }
+                                (None, None) => BTreeMap::new(),
+                            };
+                            task_start.call(&task);
+                            let (receipt, error) = run_task_with(&task, reads, &middleware);
+                            task_end.call(&receipt);
+                            completion_order.lock().unwrap().push(task.id);
+                            results.push((receipt, error));
+                        }
+                        results
+                    })
+                })
+                .collect();
+
+            let mut batch = WriteBatch {
+                level: level_num,
+                writes: BTreeMap::new(),
+            };
+            for handle in handles {
+                for (receipt, error) in handle.await.expect("Worker should run to completion") {
+                    errors.extend(error);
+                    batch.writes.extend(receipt.writes.clone());
+                    let task_id = receipt.task_id;
+                    receipts[task_id] = Some(receipt);
+                }
+            }
+
+            // Phase 1: expose and stage the whole batch. Phase 2: commit it,
+            // so the next level reads this level's writes and nothing partial.
+            if let Some(inspector) = &self.inspector {
+                inspector(&batch);
+            }
+            if let Some(store) = store {
+                for (key, value) in &batch.writes {
+                    store.put(key.clone(), *value);
+                }
+                store.commit(context).await;
+            }
+            batches.push(batch);
+            self.level_complete.call(&LevelComplete {
+                level: level_num,
+                tasks: level,
+                elapsed: elapsed_since(context, level_start),
+            });
+            if self.error_policy == ErrorPolicy::Halt && !errors.is_empty() {
+                break;
+            }
+        }
+        errors.sort_by_key(TaskError::task_id);
+
+        let completion_order = completion_order.lock().unwrap().clone();
+        ExecutionReport {
+            receipts: receipts.into_iter().flatten().collect(),
+            completion_order,
+            batches,
+            errors,
+            elapsed: elapsed_since(context, start),
+        }
+    }
+}
+
+/// Deal a level's tasks, already in start order, round-robin onto at most
+/// `workers` lanes. Without a limit every task gets its own lane.
+fn assign_workers(level: &[TaskId], workers: Option<usize>) -> Vec<Vec<TaskId>> {
+    let lanes = workers.map_or(level.len(), |workers| workers.min(level.len()));
+    let mut assigned = vec![vec![]; lanes];
+    for (position, &task_id) in level.iter().enumerate() {
+        assigned[position % lanes].push(task_id);
+    }
+    assigned
+}
+
+/// Fetch every key concurrently, one spawned read per distinct key.
+pub(crate) async fn prefetch<S: Spawner + Clock, St: Storage>(
+    context: &S,
+    store: &St,
+    keys: BTreeSet<ResourceId>,
+) -> BTreeMap<ResourceId, Option<Value>> {
+    let handles: Vec<_> = keys
+        .into_iter()
+        .map(|key| {
+            let store = store.clone();
+            context.clone().spawn(move |context| async move {
+                let value = store.get(&context, &key).await;
+                (key, value)
+            })
+        })
+        .collect();
+
+    let mut values = BTreeMap::new();
+    for handle in handles {
+        let (key, value) = handle.await.expect("Prefetch should run to completion");
+        values.insert(key, value);
+    }
+    values
+}
+
+/// Run a single task's work and collect what it emitted.
+///
+/// A failed task, or one that wrote outside its declared write set, keeps its
+/// events but contributes no writes. A task that panicked fails with the
+/// panic message and is also reported as a [`TaskError`].
+pub(crate) fn run_task(
+    task: &Task,
+    reads: BTreeMap<ResourceId, Option<Value>>,
+) -> (Receipt, Option<TaskError>) {
+    run_task_with(task, reads, &[])
+}
+
+/// [`run_task`] with the work wrapped in `middleware`.
+pub(crate) fn run_task_with(
+    task: &Task,
+    reads: BTreeMap<ResourceId, Option<Value>>,
+    middleware: &[Arc<dyn Middleware>],
+) -> (Receipt, Option<TaskError>) {
+    let mut context = TaskContext::with_reads(task.id, reads);
+    let (mut output, error) = match panic::catch_unwind(AssertUnwindSafe(|| {
+        Next::new(task, middleware).run(&mut context)
+    })) {
+        Ok(output) => (output, None),
+        Err(payload) => {
+            let message = format!("panicked: {}", panic_message(payload.as_ref()));
+            (
+                Err(message),
+                Some(TaskError::panicked(task.id as u64, payload)),
+            )
+        }
+    };
+    let (mut writes, events) = context.into_parts();
+    if let Some(key) = writes.keys().find(|key| !task.writes.contains(key)) {
+        output = Err(format!("undeclared write to {}", key));
+    }
+    if output.is_err() {
+        writes.clear();
+    }
+    let receipt = Receipt {
+        task_id: task.id,
+        name: task.name.clone(),
+        output,
+        writes,
+        events,
+    };
+    (receipt, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+        tokio::{Config as TokioConfig, Runner as TokioRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        middleware::{CatchPanic, Logging, Timing},
+        state::LatencyStorage,
+    };
+
+    /// Emits a few events with a task-dependent amount of busy work in between,
+    /// so parallel tasks finish in different orders.
+    fn noisy_work(context: &mut TaskContext) -> Result<String, String> {
+        let spins = (8 - context.task_id() as u64) * 100_000;
+        for step in 0..3 {
+            let mut acc = 0u64;
+            for i in 0..spins {
+                acc = std::hint::black_box(acc.wrapping_add(i));
+            }
+            context.emit(format!("step {} ({})", step, acc % 7));
+        }
+        Ok(format!("task {} done", context.task_id()))
+    }
+
+    fn independent_tasks(n: usize) -> Vec<Task> {
+        (0..n)
+            .map(|id| Task {
+                id,
+                name: format!("T{}", id),
+                reads: vec![],
+                writes: vec![format!("account_{}", id)],
+                priority: 0,
+                cost: 1,
+                work: &noisy_work,
+            })
+            .collect()
+    }
+
+    fn expected_events(n: usize) -> Vec<(TaskId, usize)> {
+        (0..n)
+            .flat_map(|task_id| (0..3).map(move |seq| (task_id, seq)))
+            .collect()
+    }
+
+    fn event_keys(report: &ExecutionReport) -> Vec<(TaskId, usize)> {
+        report.events().map(|e| (e.task_id, e.seq)).collect()
+    }
+
+    /// Events come out in task-id order on Tokio's multi-threaded scheduler.
+    #[test]
+    fn test_events_canonical_on_tokio() {
+        for _ in 0..5 {
+            let graph = DependencyGraph::from_tasks(independent_tasks(8));
+            let report = TokioRunner::new(TokioConfig::default().with_worker_threads(4)).start(
+                |context| async move { ParallelExecutor::new().execute(&context, &graph).await },
+            );
+
+            assert_eq!(event_keys(&report), expected_events(8));
+            assert_eq!(report.completion_order.len(), 8);
+        }
+    }
+
+    /// Events come out in task-id order for every seed, even though the
+    /// deterministic runtime polls siblings in a seed-dependent order.
+    #[test]
+    fn test_events_canonical_on_deterministic() {
+        let mut orders = vec![];
+        for seed in 0..10 {
+            let graph = DependencyGraph::from_tasks(independent_tasks(8));
+            let report = DeterministicRunner::new(Config::default().with_seed(seed)).start(
+                |context| async move { ParallelExecutor::new().execute(&context, &graph).await },
+            );
+
+            assert_eq!(event_keys(&report), expected_events(8));
+            orders.push(report.completion_order);
+        }
+        orders.dedup();
+        assert!(
+            orders.len() > 1,
+            "seeds should produce different completion orders"
+        );
+    }
+
+    /// Dependent tasks still see their dependencies' receipts first.
+    #[test]
+    fn test_receipts_follow_levels() {
+        let tasks = vec![
+            Task {
+                id: 0,
+                name: "A".to_string(),
+                reads: vec![],
+                writes: vec!["x".to_string()],
+                priority: 0,
+                cost: 1,
+                work: &(|_| Ok("A".to_string())),
+            },
+            Task {
+                id: 1,
+                name: "B".to_string(),
+                reads: vec!["x".to_string()],
+                writes: vec![],
+                priority: 0,
+                cost: 1,
+                work: &(|_| Err("B failed".to_string())),
+            },
+        ];
+        let graph = DependencyGraph::from_tasks(tasks);
+        let report =
+            DeterministicRunner::new(Config::default().with_seed(7)).start(|context| async move {
+                ParallelExecutor::new().execute(&context, &graph).await
+            });
+
+        assert_eq!(report.completion_order, vec![0, 1]);
+        assert_eq!(report.receipts[0].output, Ok("A".to_string()));
+        assert_eq!(report.receipts[1].output, Err("B failed".to_string()));
+    }
+
+    /// Sums the task's three declared reads.
+    fn sum_reads(context: &mut TaskContext) -> Result<String, String> {
+        let id = context.task_id();
+        let sum: Value = (0..3)
+            .map(|i| context.read(&format!("key_{}_{}", id, i)).unwrap_or(0))
+            .sum();
+        Ok(sum.to_string())
+    }
+
+    fn run_with_store(read_mode: ReadMode) -> ExecutionReport {
+        let tasks = (0..4)
+            .map(|id| Task {
+                id,
+                name: format!("T{}", id),
+                reads: (0..3).map(|i| format!("key_{}_{}", id, i)).collect(),
+                writes: vec![],
+                priority: 0,
+                cost: 1,
+                work: &sum_reads,
+            })
+            .collect::<Vec<_>>();
+        let graph = DependencyGraph::from_tasks(tasks);
+        let store = LatencyStorage::new(
+            (0..4)
+                .flat_map(|id| (0..3).map(move |i| (format!("key_{}_{}", id, i), id * 10 + i)))
+                .collect::<MemoryStorage>(),
+            Duration::from_millis(10),
+        );
+
+        DeterministicRunner::new(Config::default().with_seed(3)).start(|context| async move {
+            ParallelExecutor::new()
+                .with_read_mode(read_mode)
+                .execute_with_state(&context, &graph, &store)
+                .await
+        })
+    }
+
+    /// Prefetching a level's reads concurrently beats each task reading its
+    /// keys one by one, and both modes see the same values.
+    #[test]
+    fn test_prefetch_reduces_read_latency() {
+        let on_demand = run_with_store(ReadMode::OnDemand);
+        let prefetch = run_with_store(ReadMode::Prefetch);
+
+        // Three serial reads vs one concurrent round; both pay one commit.
+        assert!(on_demand.elapsed >= Duration::from_millis(40));
+        assert!(prefetch.elapsed < Duration::from_millis(30));
+        assert_eq!(on_demand.receipts, prefetch.receipts);
+        assert_eq!(prefetch.receipts[2].output, Ok("63".to_string()));
+    }
+
+    /// Moves 10 from the first declared read to the second.
+    fn transfer_ten(context: &mut TaskContext) -> Result<String, String> {
+        let (from, to) = match context.task_id() {
+            0 => ("alice", "bob"),
+            1 => ("bob", "carol"),
+            _ => ("carol", "mallory"),
+        };
+        let balance = context.read(from).ok_or("missing sender")?;
+        context.write(from, balance - 10);
+        context.write(to, context.read(to).unwrap_or(0) + 10);
+        Ok(format!("{} -> {}", from, to))
+    }
+
+    fn transfers() -> DependencyGraph {
+        let tasks = [("alice", "bob"), ("bob", "carol"), ("carol", "dave")]
+            .into_iter()
+            .enumerate()
+            .map(|(id, (from, to))| Task {
+                id,
+                name: format!("{} -> {}", from, to),
+                reads: vec![from.to_string(), to.to_string()],
+                writes: vec![from.to_string(), to.to_string()],
+                priority: 0,
+                cost: 1,
+                work: &transfer_ten,
+            })
+            .collect::<Vec<_>>();
+        DependencyGraph::from_tasks(tasks)
+    }
+
+    /// Middleware wraps every task: a panicking task fails alone, its
+    /// writes are dropped, and logging and timing see every task.
+    #[test]
+    fn test_middleware_stack() {
+        let tasks = vec![
+            Task {
+                id: 0,
+                name: "ok".to_string(),
+                reads: vec![],
+                writes: vec!["x".to_string()],
+                priority: 0,
+                cost: 1,
+                work: &(|context| {
+                    context.write("x", 1);
+                    Ok("done".to_string())
+                }),
+            },
+            Task {
+                id: 1,
+                name: "boom".to_string(),
+                reads: vec![],
+                writes: vec!["y".to_string()],
+                priority: 0,
+                cost: 1,
+                work: &(|context| {
+                    context.write("y", 1);
+                    panic!("bad input")
+                }),
+            },
+        ];
+        let graph = DependencyGraph::from_tasks(tasks);
+        let log = EventLog::new();
+        let timing = Timing::new();
+
+        let report = DeterministicRunner::new(Config::default().with_seed(5)).start({
+            let (log, timing) = (log.clone(), timing.clone());
+            |context| async move {
+                ParallelExecutor::new()
+                    .with_middleware(Logging::new(log))
+                    .with_middleware(timing)
+                    .with_middleware(CatchPanic)
+                    .execute(&context, &graph)
+                    .await
+            }
+        });
+
+        assert_eq!(report.receipts[0].output, Ok("done".to_string()));
+        assert_eq!(
+            report.receipts[1].output,
+            Err("panicked: bad input".to_string())
+        );
+        assert!(report.receipts[1].writes.is_empty());
+        assert_eq!(timing.histogram().len(), 2);
+        let mut events: Vec<_> = log.events().iter().map(|e| e.to_string()).collect();
+        events.sort();
+        assert_eq!(
+            events,
+            [
+                "boom: failed: panicked: bad input",
+                "boom: started",
+                "ok: ok: done",
+                "ok: started"
+            ]
+        );
+    }
+
+    /// A panic fails only its own task. Under `Continue` later levels still
+    /// run; under `Halt` execution stops after the panicking level.
+    #[test]
+    fn test_error_policy() {
+        let run = |policy| {
+            let tasks = vec![
+                Task {
+                    id: 0,
+                    name: "boom".to_string(),
+                    reads: vec![],
+                    writes: vec![],
+                    priority: 0,
+                    cost: 1,
+                    work: &(|_| panic!("bad input")),
+                },
+                Task {
+                    id: 1,
+                    name: "producer".to_string(),
+                    reads: vec![],
+                    writes: vec!["x".to_string()],
+                    priority: 0,
+                    cost: 1,
+                    work: &(|_| Ok("produced".to_string())),
+                },
+                Task {
+                    id: 2,
+                    name: "consumer".to_string(),
+                    reads: vec!["x".to_string()],
+                    writes: vec![],
+                    priority: 0,
+                    cost: 1,
+                    work: &(|_| Ok("consumed".to_string())),
+                },
+            ];
+            let graph = DependencyGraph::from_tasks(tasks);
+            DeterministicRunner::new(Config::default().with_seed(1)).start(|context| async move {
+                ParallelExecutor::new()
+                    .with_error_policy(policy)
+                    .execute(&context, &graph)
+                    .await
+            })
+        };
+        let panicked = vec![TaskError::Panicked {
+            task_id: 0,
+            message: "bad input".to_string(),
+        }];
+
+        let continued = run(ErrorPolicy::Continue);
+        assert_eq!(continued.errors, panicked);
+        assert_eq!(continued.receipts.len(), 3);
+        assert_eq!(continued.receipts[2].output, Ok("consumed".to_string()));
+
+        let halted = run(ErrorPolicy::Halt);
+        assert_eq!(halted.errors, panicked);
+        assert_eq!(
+            halted
+                .receipts
+                .iter()
+                .map(|r| r.task_id)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            halted.receipts[0].output,
+            Err("panicked: bad input".to_string())
+        );
+    }
+
+    /// Hooks see every task start and end, and each level completes only
+    /// after all of its tasks have ended.
+    #[test]
+    fn test_hooks_observe_execution() {
+        let graph = transfers();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (on_start, on_end, on_level) = (seen.clone(), seen.clone(), seen.clone());
+
+        DeterministicRunner::new(Config::default().with_seed(2)).start(|context| async move {
+            ParallelExecutor::new()
+                .on_task_start(move |task| {
+                    on_start.lock().unwrap().push(format!("start {}", task.id))
+                })
+                .on_task_end(move |receipt| {
+                    on_end
+                        .lock()
+                        .unwrap()
+                        .push(format!("end {}", receipt.task_id))
+                })
+                .on_level_complete(move |level| {
+                    on_level
+                        .lock()
+                        .unwrap()
+                        .push(format!("level {} {:?}", level.level, level.tasks))
+                })
+                .execute(&context, &graph)
+                .await
+        });
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "start 0",
+                "end 0",
+                "level 0 [0]",
+                "start 1",
+                "end 1",
+                "level 1 [1]",
+                "start 2",
+                "end 2",
+                "level 2 [2]",
+            ]
+        );
+    }
+
+    /// A worker limit serialises a wide level onto that many lanes, and the
+    /// assignment in the trace is the same on every run and every runtime.
+    #[test]
+    fn test_workers_bound_concurrency() {
+        fn run<S: Spawner + Clock>(
+            context: S,
+            workers: Option<usize>,
+        ) -> impl Future<Output = (Duration, Vec<String>)> {
+            let log = EventLog::new();
+            let mut executor = ParallelExecutor::new().with_trace(log.clone());
+            if let Some(workers) = workers {
+                executor = executor.with_workers(workers);
+            }
+            async move {
+                let graph =
+                    DependencyGraph::from_tasks(independent_tasks(8).into_iter().map(|task| {
+                        Task {
+                            reads: vec![format!("account_{}", task.id)],
+                            ..task
+                        }
+                    }));
+                let store = LatencyStorage::new(MemoryStorage::new(), Duration::from_millis(10));
+                let report = executor.execute_with_state(&context, &graph, &store).await;
+                let trace = log.events().iter().map(|e| e.to_string()).collect();
+                (report.elapsed, trace)
+            }
+        }
+
+        let deterministic = |seed, workers| {
+            DeterministicRunner::new(Config::default().with_seed(seed))
+                .start(|context| run(context, workers))
+        };
+        let (unbounded, _) = deterministic(0, None);
+        let (bounded, trace) = deterministic(0, Some(3));
+
+        assert!(bounded >= unbounded + Duration::from_millis(20));
+        assert_eq!(
+            trace,
+            [
+                "scheduler: tie-break: priority",
+                "scheduler: level 0 worker 0: [0, 3, 6]",
+                "scheduler: level 0 worker 1: [1, 4, 7]",
+                "scheduler: level 0 worker 2: [2, 5]",
+            ]
+        );
+        assert_eq!(deterministic(9, Some(3)).1, trace);
+        assert_eq!(
+            TokioRunner::default()
+                .start(|context| run(context, Some(3)))
+                .1,
+            trace
+        );
+    }
+
+    /// Within a level, tasks start by declared priority, then id, unless the
+    /// tie-break says to ignore priorities.
+    #[test]
+    fn test_levels_start_by_priority() {
+        let started = |tie_break: TieBreak| {
+            let levels = Arc::new(Mutex::new(vec![]));
+            let on_level = levels.clone();
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let graph =
+                    DependencyGraph::from_tasks(independent_tasks(4).into_iter().map(|task| {
+                        Task {
+                            priority: 3 - task.id as u8,
+                            ..task
+                        }
+                    }));
+                ParallelExecutor::new()
+                    .with_tie_break(tie_break)
+                    .on_level_complete(move |level| {
+                        on_level.lock().unwrap().push(level.tasks.clone())
+                    })
+                    .execute(&context, &graph)
+                    .await
+            });
+            levels.lock().unwrap().clone()
+        };
+
+        assert_eq!(started(TieBreak::Priority), [[3, 2, 1, 0]]);
+        assert_eq!(started(TieBreak::LowestId), [[0, 1, 2, 3]]);
+    }
+
+    /// Each level's writes are committed as one batch before the next level
+    /// reads them, and the inspector sees every batch before commit.
+    #[test]
+    fn test_writes_committed_per_level() {
+        let graph = transfers();
+        let storage = [("alice", 100), ("bob", 0), ("carol", 0)]
+            .into_iter()
+            .collect::<MemoryStorage>();
+        let inspected = Arc::new(Mutex::new(vec![]));
+
+        let report = DeterministicRunner::new(Config::default().with_seed(4)).start({
+            let storage = storage.clone();
+            let inspected = inspected.clone();
+            |context| async move {
+                let committed = storage.clone();
+                ParallelExecutor::new()
+                    .with_read_mode(ReadMode::Prefetch)
+                    .with_write_inspector(move |batch| {
+                        // Nothing from this batch is visible yet.
+                        assert!(
+                            batch
+                                .writes
+                                .iter()
+                                .all(|(key, value)| committed.snapshot().get(key) != Some(value))
+                        );
+                        inspected.lock().unwrap().push(batch.clone());
+                    })
+                    .execute_with_state(&context, &graph, &storage)
+                    .await
+            }
+        });
+
+        assert_eq!(*inspected.lock().unwrap(), report.batches);
+        assert_eq!(report.batches.len(), 3);
+        assert_eq!(
+            report.batches[1].writes,
+            BTreeMap::from([("bob".to_string(), 0), ("carol".to_string(), 10)])
+        );
+        // The last transfer writes outside its declared set and is rejected.
+        assert!(report.receipts[2].output.is_err());
+        assert!(report.receipts[2].writes.is_empty());
+        assert!(report.batches[2].writes.is_empty());
+        assert_eq!(
+            storage.snapshot(),
+            BTreeMap::from([
+                ("alice".to_string(), 90),
+                ("bob".to_string(), 0),
+                ("carol".to_string(), 10),
+            ])
+        );
+    }
+}
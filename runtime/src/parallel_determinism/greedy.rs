@@ -0,0 +1,343 @@
+//! Greedy list scheduling: start each task the moment its dependencies are
+//! done.
+//!
+//! Level-by-level execution puts a barrier after every level, so a task whose
+//! only dependency finished early still waits for the slowest task of that
+//! level. [`GreedyExecutor`] drops the barriers. It tracks how many of each
+//! task's dependencies are still running, and as soon as that count reaches
+//! zero the task is spawned. With a worker limit, ready tasks queue until a
+//! worker frees up, and the configured [`TieBreak`] decides which goes first,
+//! by default declared priority and then task id, so under the deterministic
+//! runtime the whole schedule is still a function of the seed.
+//!
+//! Each task's writes are committed as soon as it finishes. That is safe
+//! because a task that conflicts with it depends on it, directly or through
+//! another task, and so cannot have started yet.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use commonware_runtime::{Clock, Handle, Spawner};
+
+use crate::{
+    error::TaskError,
+    parallel_determinism::{
+        dep_graph::DependencyGraph,
+        executor::{ExecutionReport, run_task},
+        state::{MemoryStorage, Storage, WriteBatch},
+        tie_break::TieBreak,
+        types::{Receipt, TaskId},
+    },
+    stats::elapsed_since,
+    trace::EventLog,
+};
+
+type TaskHandle = Pin<Box<Handle<(Receipt, Option<TaskError>)>>>;
+
+/// Resolves to the position and output of the first handle in the list that
+/// has finished, checking them in list order.
+pub(crate) struct FirstCompleted<'a, T: Send + 'static> {
+    handles: &'a mut [Pin<Box<Handle<T>>>],
+}
+
+impl<T: Send + 'static> Future for FirstCompleted<'_, T> {
+    type Output = (usize, T);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        for (index, handle) in this.handles.iter_mut().enumerate() {
+            if let Poll::Ready(output) = handle.as_mut().poll(cx) {
+                return Poll::Ready((index, output.expect("Task should run to completion")));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[derive(Default)]
+pub struct GreedyExecutor {
+    tie_break: TieBreak,
+    workers: Option<usize>,
+    trace: Option<EventLog>,
+}
+
+impl GreedyExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Run at most `workers` tasks at once. Without a limit every ready task
+    /// starts immediately and the tie-break only decides spawn order.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        assert!(workers > 0, "need at least one worker");
+        self.workers = Some(workers);
+        self
+    }
+
+    /// Record the tie-break rule in `log` at the start of every run.
+    pub fn with_trace(mut self, log: EventLog) -> Self {
+        self.trace = Some(log);
+        self
+    }
+
+    pub async fn execute<S: Spawner + Clock>(
+        &self,
+        context: &S,
+        graph: &DependencyGraph,
+    ) -> ExecutionReport {
+        self.run::<S, MemoryStorage>(context, graph, None).await
+    }
+
+    /// Execute against `store`, each task fetching its reads when it starts
+    /// and committing its writes when it finishes. The report has one batch
+    /// per task, in completion order.
+    pub async fn execute_with_state<S: Spawner + Clock, St: Storage>(
+        &self,
+        context: &S,
+        graph: &DependencyGraph,
+        store: &St,
+    ) -> ExecutionReport {
+        self.run(context, graph, Some(store)).await
+    }
+
+    async fn run<S: Spawner + Clock, St: Storage>(
+        &self,
+        context: &S,
+        graph: &DependencyGraph,
+        store: Option<&St>,
+    ) -> ExecutionReport {
+        let start = context.current();
+        if let Some(log) = &self.trace {
+            self.tie_break.record(log);
+        }
+        let mut waiting_on: Vec<usize> = (0..graph.tasks.len())
+            .map(|task_id| graph.dependencies[&task_id].len())
+            .collect();
+        let mut dependents: Vec<Vec<TaskId>> = vec![vec![]; graph.tasks.len()];
+        for (&task_id, deps) in graph.dependencies.iter() {
+            for &dep in deps.iter() {
+                dependents[dep].push(task_id);
+            }
+        }
+        let key = |task_id: TaskId| self.tie_break.key(&graph.tasks[task_id]);
+        let mut ready: BTreeSet<_> = (0..graph.tasks.len())
+            .filter(|&task_id| waiting_on[task_id] == 0)
+            .map(key)
+            .collect();
+        let workers = self.workers.unwrap_or(usize::MAX);
+
+        let mut running: Vec<TaskHandle> = vec![];
+        let mut receipts: Vec<Option<Receipt>> = vec![None; graph.tasks.len()];
+        let mut completion_order = vec![];
+        let mut batches = vec![];
+        let mut errors = vec![];
+        loop {
+            while running.len() < workers
+                && let Some((_, task_id)) = ready.pop_first()
+            {
+                let task = graph.tasks[task_id].clone();
+                let store = store.cloned();
+                running.push(Box::pin(context.clone().spawn(move |context| async move {
+                    let mut reads = BTreeMap::new();
+                    if let Some(store) = store {
+                        for key in &task.reads {
+                            reads.insert(key.clone(), store.get(&context, key).await);
+                        }
+                    }
+                    run_task(&task, reads)
+                })));
+            }
+            if running.is_empty() {
+                break;
+            }
+
+            let (index, (receipt, error)) = FirstCompleted {
+                handles: &mut running,
+            }
+            .await;
+            drop(running.remove(index));
+            errors.extend(error);
+            if let Some(store) = store {
+                for (key, value) in &receipt.writes {
+                    store.put(key.clone(), *value);
+                }
+                store.commit(context).await;
+            }
+            let task_id = receipt.task_id;
+            for &dependent in &dependents[task_id] {
+                waiting_on[dependent] -= 1;
+                if waiting_on[dependent] == 0 {
+                    ready.insert(key(dependent));
+                }
+            }
+            batches.push(WriteBatch {
+                level: task_id,
+                writes: receipt.writes.clone(),
+            });
+            completion_order.push(task_id);
+            receipts[task_id] = Some(receipt);
+        }
+        errors.sort_by_key(TaskError::task_id);
+
+        ExecutionReport {
+            receipts: receipts.into_iter().flatten().collect(),
+            completion_order,
+            batches,
+            errors,
+            elapsed: elapsed_since(context, start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        executor::ParallelExecutor,
+        generator::{BlockSpec, generate_tasks},
+        sequential::SequentialExecutor,
+        state::LatencyStorage,
+        types::{Task, TaskContext},
+    };
+
+    fn copy_first_read(context: &mut TaskContext) -> Result<String, String> {
+        let value = context.read_set().find_map(|key| context.read(key));
+        let target = format!("out_{}", context.task_id());
+        context.write(target, value.unwrap_or(0) + 1);
+        Ok("copied".to_string())
+    }
+
+    fn task(id: TaskId, reads: &[&str]) -> Task {
+        Task {
+            id,
+            name: format!("T{}", id),
+            reads: reads.iter().map(|key| key.to_string()).collect(),
+            writes: vec![format!("out_{}", id)],
+            priority: 0,
+            cost: 1,
+            work: &copy_first_read,
+        }
+    }
+
+    /// A slow task shares a level with a fast one whose dependent could start
+    /// early. Strict levels make the dependent wait for the slow task; greedy
+    /// scheduling overlaps them.
+    #[test]
+    fn test_greedy_skips_level_barriers() {
+        let graph = || {
+            DependencyGraph::from_tasks([
+                task(0, &["a", "b", "c", "d", "e"]),
+                task(1, &["f"]),
+                task(2, &["out_1"]),
+            ])
+        };
+        let (levels, greedy) =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let latency = Duration::from_millis(10);
+                let store = LatencyStorage::new(MemoryStorage::new(), latency);
+                let levels = ParallelExecutor::new()
+                    .execute_with_state(&context, &graph(), &store)
+                    .await;
+                let store = LatencyStorage::new(MemoryStorage::new(), latency);
+                let greedy = GreedyExecutor::new()
+                    .execute_with_state(&context, &graph(), &store)
+                    .await;
+                (levels, greedy)
+            });
+
+        // Levels: 50ms of reads, a commit, then the dependent's read and
+        // commit. Greedy: the dependent finishes inside the slow task's 50ms.
+        assert!(levels.elapsed >= Duration::from_millis(80));
+        assert!(greedy.elapsed < Duration::from_millis(70));
+        assert_eq!(greedy.completion_order, [1, 2, 0]);
+        assert_eq!(greedy.receipts, levels.receipts);
+    }
+
+    /// With one worker the completion order is exactly the tie-break order,
+    /// and the rule is in the trace.
+    #[test]
+    fn test_tie_break_orders_ready_tasks() {
+        let run = |tie_break: TieBreak| {
+            let log = EventLog::new();
+            let executor = GreedyExecutor::new()
+                .with_workers(1)
+                .with_tie_break(tie_break)
+                .with_trace(log.clone());
+            let order = DeterministicRunner::new(Config::default().with_seed(0)).start(
+                |context| async move {
+                    let graph = DependencyGraph::from_tasks([
+                        Task {
+                            priority: 2,
+                            ..task(0, &["a", "b", "c"])
+                        },
+                        task(1, &["d", "e"]),
+                        Task {
+                            priority: 2,
+                            ..task(2, &[])
+                        },
+                        Task {
+                            priority: 1,
+                            ..task(3, &["f"])
+                        },
+                    ]);
+                    executor.execute(&context, &graph).await.completion_order
+                },
+            );
+            (order, TieBreak::from_trace(&log).unwrap())
+        };
+
+        assert_eq!(run(TieBreak::LowestId).0, [0, 1, 2, 3]);
+        assert_eq!(run(TieBreak::ShortestWork).0, [2, 3, 1, 0]);
+        let (order, recorded) = run(TieBreak::Priority);
+        assert_eq!(order, [1, 3, 0, 2]);
+        assert_eq!(recorded, TieBreak::Priority);
+    }
+
+    /// Dropping barriers does not change the result of a contended block.
+    #[test]
+    fn test_greedy_matches_sequential() {
+        let spec = BlockSpec {
+            size: 64,
+            conflict_rate: 0.5,
+            seed: 3,
+        };
+        let (greedy, sequential) =
+            DeterministicRunner::new(Config::default().with_seed(1)).start(|context| async move {
+                let greedy = MemoryStorage::new();
+                GreedyExecutor::new()
+                    .execute_with_state(
+                        &context,
+                        &DependencyGraph::from_tasks(generate_tasks(&spec)),
+                        &greedy,
+                    )
+                    .await;
+                let sequential = MemoryStorage::new();
+                SequentialExecutor::new()
+                    .execute_with_state(
+                        &context,
+                        &DependencyGraph::from_tasks(generate_tasks(&spec)),
+                        &sequential,
+                    )
+                    .await;
+                (greedy.snapshot(), sequential.snapshot())
+            });
+
+        assert_eq!(greedy, sequential);
+    }
+}
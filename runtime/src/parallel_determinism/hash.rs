@@ -0,0 +1,33 @@
+//! A tiny, dependency-free hash for digests that must match across machines.
+//!
+//! FNV-1a is not cryptographic, but it is fully specified, has no per-process
+//! keys (unlike `std`'s `DefaultHasher` contract), and is easy to read — enough
+//! for demo signatures and state roots.
+
+const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0100_0000_01b3;
+
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(OFFSET)
+    }
+}
+
+impl Fnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(PRIME);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,211 @@
+//! Lock-based scheduling: reads take shared locks, writes take exclusive ones.
+//!
+//! A block can be read as a sequence of lock requests. Each task asks for a
+//! lock on every key it touches, and two tasks can hold their locks at the
+//! same time only if every key they have in common is locked shared by both.
+//! Under [`LockSemantics::ReadWrite`] a read is a shared lock, so any number
+//! of readers of a price feed or a config key run side by side.
+//! [`LockSemantics::ExclusiveOnly`] is the conservative alternative of a
+//! plain mutex per resource, where readers queue up behind each other.
+//!
+//! Granting locks in task order gives the same levels as a dependency graph
+//! whose conflicts are "both lock the same key, at least one exclusively", so
+//! [`LockExecutor`] builds that graph and hands it to a [`ParallelExecutor`].
+
+use std::sync::Arc;
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{
+    collections::DMap,
+    parallel_determinism::{
+        conflict::ConflictDetector,
+        dep_graph::DependencyGraph,
+        executor::{ExecutionReport, ParallelExecutor},
+        state::Storage,
+        types::{ResourceId, Task},
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LockSemantics {
+    /// Every access locks its key exclusively, as if each resource were
+    /// behind its own mutex.
+    ExclusiveOnly,
+    /// Reads lock shared, writes lock exclusive.
+    #[default]
+    ReadWrite,
+}
+
+impl LockSemantics {
+    /// The lock `task` requests on each key it touches. A key that is both
+    /// read and written is locked exclusively.
+    pub fn requests(&self, task: &Task) -> DMap<ResourceId, LockMode> {
+        let read_mode = match self {
+            LockSemantics::ExclusiveOnly => LockMode::Exclusive,
+            LockSemantics::ReadWrite => LockMode::Shared,
+        };
+        let mut requests = DMap::new();
+        for key in &task.reads {
+            requests.insert(key.clone(), read_mode);
+        }
+        for key in &task.writes {
+            requests.insert(key.clone(), LockMode::Exclusive);
+        }
+        requests
+    }
+}
+
+impl ConflictDetector for LockSemantics {
+    fn conflicts(&self, a: &Task, b: &Task) -> bool {
+        let b_requests = self.requests(b);
+        self.requests(a).iter().any(|(key, a_mode)| {
+            b_requests
+                .get(key)
+                .is_some_and(|b_mode| (*a_mode).max(*b_mode) == LockMode::Exclusive)
+        })
+    }
+}
+
+/// Executes a block level by level, with levels decided by lock
+/// compatibility under its [`LockSemantics`].
+#[derive(Default)]
+pub struct LockExecutor {
+    semantics: LockSemantics,
+    executor: ParallelExecutor,
+}
+
+impl LockExecutor {
+    pub fn new(semantics: LockSemantics) -> Self {
+        Self {
+            semantics,
+            executor: ParallelExecutor::new(),
+        }
+    }
+
+    /// Run levels on `executor` instead of a default one, keeping its hooks,
+    /// middleware and policies.
+    pub fn with_executor(mut self, executor: ParallelExecutor) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    pub fn graph(&self, tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>) -> DependencyGraph {
+        DependencyGraph::from_tasks_with(tasks, &self.semantics)
+    }
+
+    /// The number of tasks in each level, the quantity read sharing
+    /// improves.
+    pub fn level_widths(
+        &self,
+        tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>,
+    ) -> Vec<usize> {
+        self.graph(tasks)
+            .execution_levels()
+            .iter()
+            .map(Vec::len)
+            .collect()
+    }
+
+    pub async fn execute_with_state<S: Spawner + Clock, St: Storage>(
+        &self,
+        context: &S,
+        tasks: impl IntoIterator<Item = impl Into<Arc<Task>>>,
+        store: &St,
+    ) -> ExecutionReport {
+        let graph = self.graph(tasks);
+        self.executor
+            .execute_with_state(context, &graph, store)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        sequential::SequentialExecutor, state::MemoryStorage, types::TaskContext,
+    };
+
+    fn quote(context: &mut TaskContext) -> Result<String, String> {
+        let price = context.read("price").unwrap_or(0);
+        let account = format!("account_{}", context.task_id());
+        context.write(account, price * 2);
+        Ok(format!("quoted {}", price))
+    }
+
+    fn set_price(context: &mut TaskContext) -> Result<String, String> {
+        context.write("price", 21);
+        Ok("price set".to_string())
+    }
+
+    /// One price update followed by readers of the price, each writing its
+    /// own account.
+    fn block(readers: usize) -> Vec<Task> {
+        let mut tasks = vec![Task {
+            id: 0,
+            name: "oracle".to_string(),
+            reads: vec![],
+            writes: vec!["price".to_string()],
+            priority: 0,
+            cost: 1,
+            work: &set_price,
+        }];
+        tasks.extend((1..=readers).map(|id| Task {
+            id,
+            name: format!("quote{}", id),
+            reads: vec!["price".to_string()],
+            writes: vec![format!("account_{}", id)],
+            priority: 0,
+            cost: 1,
+            work: &quote,
+        }));
+        tasks
+    }
+
+    /// Shared read locks put every reader in one level; exclusive locks
+    /// queue them one per level.
+    #[test]
+    fn test_read_sharing_widens_levels() {
+        let shared = LockExecutor::new(LockSemantics::ReadWrite).level_widths(block(6));
+        let exclusive = LockExecutor::new(LockSemantics::ExclusiveOnly).level_widths(block(6));
+
+        assert_eq!(shared, [1, 6]);
+        assert_eq!(exclusive, [1; 7]);
+    }
+
+    /// Sharing read locks changes the schedule, not the result.
+    #[test]
+    fn test_lock_execution_matches_sequential() {
+        let state =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let locked = MemoryStorage::new();
+                LockExecutor::new(LockSemantics::ReadWrite)
+                    .execute_with_state(&context, block(4), &locked)
+                    .await;
+                let sequential = MemoryStorage::new();
+                SequentialExecutor::new()
+                    .execute_with_state(
+                        &context,
+                        &DependencyGraph::from_tasks(block(4)),
+                        &sequential,
+                    )
+                    .await;
+                (locked.snapshot(), sequential.snapshot())
+            });
+
+        assert_eq!(state.0, state.1);
+        assert_eq!(state.0.get("account_3"), Some(&42));
+    }
+}
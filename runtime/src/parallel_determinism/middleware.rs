@@ -0,0 +1,125 @@
+//! Composable wrappers around a task's work.
+//!
+//! Cross-cutting concerns like timing, logging and panic handling belong in
+//! one place rather than in every task body. A [`Middleware`] wraps the call
+//! to a task's `work` and decides what happens before and after it; the
+//! executor applies its middleware stack to every task the same way, first
+//! registered outermost.
+//!
+//! Middleware runs on the worker that executes the task. It must not change
+//! what a task reads or writes, only observe it or turn a failure into an
+//! error, or the receipts would stop being a pure function of the block.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{
+    error::panic_message,
+    parallel_determinism::types::{Task, TaskContext},
+    stats::LatencyHistogram,
+    trace::EventLog,
+};
+
+pub type TaskOutput = Result<String, String>;
+
+pub trait Middleware: Send + Sync {
+    /// Handle one task. Call `next.run(context)` to continue down the stack
+    /// and eventually into the task's own work.
+    fn call(&self, task: &Task, context: &mut TaskContext, next: Next<'_>) -> TaskOutput;
+}
+
+/// The rest of the middleware stack, ending in the task's work.
+pub struct Next<'a> {
+    task: &'a Task,
+    rest: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub fn new(task: &'a Task, stack: &'a [Arc<dyn Middleware>]) -> Self {
+        Self { task, rest: stack }
+    }
+
+    pub fn run(self, context: &mut TaskContext) -> TaskOutput {
+        match self.rest.split_first() {
+            Some((first, rest)) => first.call(
+                self.task,
+                context,
+                Next {
+                    task: self.task,
+                    rest,
+                },
+            ),
+            None => (self.task.work)(context),
+        }
+    }
+}
+
+/// Records how long each task's work took into a shared histogram. Wall-clock
+/// time, so the numbers are for inspection only.
+#[derive(Clone, Default)]
+pub struct Timing {
+    histogram: Arc<Mutex<LatencyHistogram>>,
+}
+
+impl Timing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of everything recorded so far.
+    pub fn histogram(&self) -> LatencyHistogram {
+        self.histogram.lock().unwrap().clone()
+    }
+}
+
+impl Middleware for Timing {
+    fn call(&self, _: &Task, context: &mut TaskContext, next: Next<'_>) -> TaskOutput {
+        let start = Instant::now();
+        let output = next.run(context);
+        self.histogram.lock().unwrap().record(start.elapsed());
+        output
+    }
+}
+
+/// Records each task's start and result in an [`EventLog`] under the task's
+/// name.
+#[derive(Clone)]
+pub struct Logging {
+    log: EventLog,
+}
+
+impl Logging {
+    pub fn new(log: EventLog) -> Self {
+        Self { log }
+    }
+}
+
+impl Middleware for Logging {
+    fn call(&self, task: &Task, context: &mut TaskContext, next: Next<'_>) -> TaskOutput {
+        self.log.record(task.name.as_str(), "started");
+        let output = next.run(context);
+        let message = match &output {
+            Ok(output) => format!("ok: {}", output),
+            Err(error) => format!("failed: {}", error),
+        };
+        self.log.record(task.name.as_str(), message);
+        output
+    }
+}
+
+/// Turns a panic in the task's work into an ordinary task error, inside the
+/// middleware stack. The executor isolates panics on its own as well; this
+/// is for when middleware further out should see the failure as an `Err`
+/// rather than an unwind.
+#[derive(Clone, Copy, Default)]
+pub struct CatchPanic;
+
+impl Middleware for CatchPanic {
+    fn call(&self, _: &Task, context: &mut TaskContext, next: Next<'_>) -> TaskOutput {
+        panic::catch_unwind(AssertUnwindSafe(|| next.run(context)))
+            .unwrap_or_else(|payload| Err(format!("panicked: {}", panic_message(payload.as_ref()))))
+    }
+}
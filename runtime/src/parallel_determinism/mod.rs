@@ -1,2 +1,30 @@
+#[cfg(feature = "runtime")]
+pub mod block;
+pub mod canonical;
+#[cfg(feature = "runtime")]
+pub mod chain;
+pub mod conflict;
+pub mod dedup;
 pub mod dep_graph;
+pub mod diff;
+#[cfg(feature = "runtime")]
+pub mod executor;
+pub mod generator;
+#[cfg(feature = "runtime")]
+pub mod greedy;
+pub mod hash;
+#[cfg(feature = "runtime")]
+pub mod locks;
+pub mod middleware;
+#[cfg(feature = "runtime")]
+pub mod optimistic;
+pub mod ordering;
+pub mod prune;
+#[cfg(feature = "runtime")]
+pub mod sequential;
+#[cfg(feature = "runtime")]
+pub mod state;
+#[cfg(feature = "runtime")]
+pub mod stealing;
+pub mod tie_break;
 pub mod types;
@@ -0,0 +1,209 @@
+//! Optimistic execution: run everything in parallel first, fix conflicts after.
+//!
+//! Instead of building levels up front, every task is executed speculatively
+//! against the pre-block state. A validation pass then walks the tasks in id
+//! order and re-executes any task whose declared reads were written by an
+//! earlier task in the same block. The result is identical to sequential
+//! execution; the cost of a conflict is a re-execution rather than a barrier.
+//!
+//! Validation and re-execution happen in id order on a single task, so the
+//! final state never depends on which speculative run finished first.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{
+    error::TaskError,
+    parallel_determinism::{
+        dep_graph::DependencyGraph,
+        executor::{ExecutionReport, prefetch, run_task},
+        state::{Storage, Value, WriteBatch},
+        types::{Receipt, ResourceId, TaskId},
+    },
+    stats::elapsed_since,
+};
+
+/// An [`ExecutionReport`] plus which tasks had to be re-executed.
+pub struct OptimisticReport {
+    pub report: ExecutionReport,
+    pub reexecuted: Vec<TaskId>,
+}
+
+#[derive(Default)]
+pub struct OptimisticExecutor;
+
+impl OptimisticExecutor {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub async fn execute_with_state<S: Spawner + Clock, St: Storage>(
+        &self,
+        context: &S,
+        graph: &DependencyGraph,
+        store: &St,
+    ) -> OptimisticReport {
+        let start = context.current();
+
+        // Speculate: every task runs in parallel against the same snapshot.
+        let keys = graph
+            .tasks
+            .iter()
+            .flat_map(|task| task.reads.iter().cloned())
+            .collect();
+        let snapshot = Arc::new(prefetch(context, store, keys).await);
+        let completion_order = Arc::new(Mutex::new(Vec::new()));
+        let handles: Vec<_> = graph
+            .tasks
+            .iter()
+            .map(|task| {
+                let task = task.clone();
+                let snapshot = snapshot.clone();
+                let completion_order = completion_order.clone();
+                context.clone().spawn(move |_| async move {
+                    let reads = task
+                        .reads
+                        .iter()
+                        .map(|key| (key.clone(), snapshot[key]))
+                        .collect();
+                    let receipt = run_task(&task, reads);
+                    completion_order.lock().unwrap().push(task.id);
+                    receipt
+                })
+            })
+            .collect();
+        let mut speculative: Vec<(Receipt, Option<TaskError>)> = Vec::with_capacity(handles.len());
+        for handle in handles {
+            speculative.push(handle.await.expect("Task should run to completion"));
+        }
+
+        // Validate in id order against everything committed before each task.
+        let mut written: BTreeMap<ResourceId, Value> = BTreeMap::new();
+        let mut reexecuted = vec![];
+        let mut receipts = Vec::with_capacity(speculative.len());
+        let mut errors = vec![];
+        for (task, speculated) in graph.tasks.iter().zip(speculative) {
+            let stale = task.reads.iter().any(|key| written.contains_key(key));
+            let (receipt, error) = if !stale {
+                speculated
+            } else {
+                reexecuted.push(task.id);
+                let reads = task
+                    .reads
+                    .iter()
+                    .map(|key| {
+                        let value = written.get(key).copied().or(snapshot[key]);
+                        (key.clone(), value)
+                    })
+                    .collect();
+                run_task(task, reads)
+            };
+            for (key, value) in &receipt.writes {
+                written.insert(key.clone(), *value);
+            }
+            receipts.push(receipt);
+            errors.extend(error);
+        }
+
+        let batch = WriteBatch {
+            level: 0,
+            writes: written,
+        };
+        for (key, value) in &batch.writes {
+            store.put(key.clone(), *value);
+        }
+        store.commit(context).await;
+
+        let completion_order = completion_order.lock().unwrap().clone();
+        OptimisticReport {
+            report: ExecutionReport {
+                receipts,
+                completion_order,
+                batches: vec![batch],
+                errors,
+                elapsed: elapsed_since(context, start),
+            },
+            reexecuted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+        tokio::{Config as TokioConfig, Runner as TokioRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        executor::ParallelExecutor,
+        generator::{BlockSpec, generate_tasks},
+        sequential::SequentialExecutor,
+        state::MemoryStorage,
+    };
+
+    fn spec(conflict_rate: f64) -> BlockSpec {
+        BlockSpec {
+            size: 48,
+            conflict_rate,
+            seed: 11,
+        }
+    }
+
+    /// Optimistic, level-parallel, and sequential execution all end in the
+    /// same state with the same receipts.
+    #[test]
+    fn test_strategies_agree() {
+        let spec = spec(0.5);
+        let (sequential, optimistic, parallel) = TokioRunner::new(
+            TokioConfig::default().with_worker_threads(4),
+        )
+        .start(|context| async move {
+            let graph = DependencyGraph::from_tasks(generate_tasks(&spec));
+
+            let store = MemoryStorage::new();
+            let sequential = SequentialExecutor::new()
+                .execute_with_state(&context, &graph, &store)
+                .await;
+            let sequential = (sequential.receipts, store.snapshot());
+
+            let store = MemoryStorage::new();
+            let optimistic = OptimisticExecutor::new()
+                .execute_with_state(&context, &graph, &store)
+                .await;
+            assert!(!optimistic.reexecuted.is_empty());
+            let optimistic = (optimistic.report.receipts, store.snapshot());
+
+            let store = MemoryStorage::new();
+            let parallel = ParallelExecutor::new()
+                .execute_with_state(&context, &graph, &store)
+                .await;
+            let parallel = (parallel.receipts, store.snapshot());
+
+            (sequential, optimistic, parallel)
+        });
+
+        assert_eq!(sequential, optimistic);
+        assert_eq!(sequential, parallel);
+    }
+
+    /// Without conflicts nothing is re-executed.
+    #[test]
+    fn test_no_conflicts_no_reexecution() {
+        let report =
+            DeterministicRunner::new(Config::default().with_seed(1)).start(|context| async move {
+                let graph = DependencyGraph::from_tasks(generate_tasks(&spec(0.0)));
+                OptimisticExecutor::new()
+                    .execute_with_state(&context, &graph, &MemoryStorage::new())
+                    .await
+            });
+
+        assert!(report.reexecuted.is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+//! Policies that fix the order of a block's transactions before execution.
+//!
+//! The dependency graph orients every conflict from the earlier task to the
+//! later one, so whoever decides the order decides which of two conflicting
+//! transactions sees the other's writes. That changes the final state, and,
+//! through the shape of the graph, how much of the block runs in parallel.
+//! An [`OrderingPolicy`] makes that decision explicit and swappable: the
+//! [`BlockExecutor`] applies it to the verified transactions before building
+//! the graph.
+//!
+//! Tasks carry no fee, sender or nonce of their own, so [`ByFee`] and
+//! [`BySenderNonce`] read them through a caller-supplied function.
+//!
+//! [`BlockExecutor`]: crate::parallel_determinism::block::BlockExecutor
+
+use std::sync::Arc;
+
+use crate::parallel_determinism::{
+    hash::Fnv,
+    types::{Task, TaskId},
+};
+
+/// Decides the order in which a block's transactions are executed.
+pub trait OrderingPolicy: Send + Sync {
+    /// A permutation of `tasks`' ids: the id of the task to run first, then
+    /// the second, and so on.
+    fn order(&self, tasks: &[Arc<Task>]) -> Vec<TaskId>;
+}
+
+/// Ids of `tasks` sorted by `key`, ties broken by id.
+fn sorted_by<K: Ord>(tasks: &[Arc<Task>], key: impl Fn(&Task) -> K) -> Vec<TaskId> {
+    let mut order: Vec<TaskId> = tasks.iter().map(|task| task.id).collect();
+    order.sort_by_cached_key(|&id| (key(&tasks[id]), id));
+    order
+}
+
+/// Highest fee first.
+pub struct ByFee<F> {
+    fee: F,
+}
+
+impl<F: Fn(&Task) -> u64 + Send + Sync> ByFee<F> {
+    pub fn new(fee: F) -> Self {
+        Self { fee }
+    }
+}
+
+impl<F: Fn(&Task) -> u64 + Send + Sync> OrderingPolicy for ByFee<F> {
+    fn order(&self, tasks: &[Arc<Task>]) -> Vec<TaskId> {
+        sorted_by(tasks, |task| std::cmp::Reverse((self.fee)(task)))
+    }
+}
+
+/// Grouped by sender, each sender's transactions in nonce order.
+pub struct BySenderNonce<F> {
+    sender_nonce: F,
+}
+
+impl<F: Fn(&Task) -> (String, u64) + Send + Sync> BySenderNonce<F> {
+    pub fn new(sender_nonce: F) -> Self {
+        Self { sender_nonce }
+    }
+}
+
+impl<F: Fn(&Task) -> (String, u64) + Send + Sync> OrderingPolicy for BySenderNonce<F> {
+    fn order(&self, tasks: &[Arc<Task>]) -> Vec<TaskId> {
+        sorted_by(tasks, &self.sender_nonce)
+    }
+}
+
+/// By a hash of each transaction's name and declared accesses. The hash
+/// ignores the id, so the order depends only on what is in the block, not on
+/// the order it was assembled in.
+pub struct ByHash;
+
+impl OrderingPolicy for ByHash {
+    fn order(&self, tasks: &[Arc<Task>]) -> Vec<TaskId> {
+        sorted_by(tasks, |task| {
+            let mut hasher = Fnv::new();
+            hasher.update(task.name.as_bytes());
+            for read in &task.reads {
+                hasher.update(b"r");
+                hasher.update(read.as_bytes());
+            }
+            for write in &task.writes {
+                hasher.update(b"w");
+                hasher.update(write.as_bytes());
+            }
+            hasher.finish()
+        })
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        block::{Block, BlockExecutor, SignedTask},
+        state::{MemoryStorage, Storage},
+        types::TaskContext,
+    };
+
+    type Work = &'static (dyn Fn(&mut TaskContext) -> Result<String, String> + Sync);
+
+    /// Names are `sender/nonce`; the fee is carried in `priority`.
+    fn transaction(
+        id: TaskId,
+        name: &str,
+        fee: u8,
+        reads: &[&str],
+        write: &str,
+        work: Work,
+    ) -> Task {
+        Task {
+            id,
+            name: name.to_string(),
+            reads: reads.iter().map(|key| key.to_string()).collect(),
+            writes: vec![write.to_string()],
+            priority: fee,
+            cost: 1,
+            work,
+        }
+    }
+
+    fn transactions() -> Vec<Task> {
+        vec![
+            transaction(0, "alice/0", 1, &[], "x", &|c| {
+                c.write("x", 1);
+                Ok("x = 1".to_string())
+            }),
+            transaction(1, "bob/0", 3, &[], "x", &|c| {
+                c.write("x", 2);
+                Ok("x = 2".to_string())
+            }),
+            transaction(2, "alice/1", 2, &["x"], "y", &|c| {
+                let y = c.read("x").unwrap_or(0) * 10;
+                c.write("y", y);
+                Ok(format!("y = {}", y))
+            }),
+        ]
+    }
+
+    fn block(tasks: Vec<Task>) -> Block {
+        Block {
+            transactions: tasks.into_iter().map(SignedTask::sign).collect(),
+        }
+    }
+
+    fn sender_nonce(task: &Task) -> (String, u64) {
+        let (sender, nonce) = task.name.split_once('/').expect("Names are sender/nonce");
+        (
+            sender.to_string(),
+            nonce.parse().expect("Nonces are numbers"),
+        )
+    }
+
+    fn run(executor: BlockExecutor, block: Block) -> (Vec<TaskId>, Vec<(String, i64)>) {
+        DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+            let store = MemoryStorage::new();
+            let outcome = executor.execute_with_state(&context, block, &store).await;
+            let state = store.snapshot().into_iter().collect();
+            (outcome.order, state)
+        })
+    }
+
+    /// Each policy picks its own order, and with it a different final state.
+    #[test]
+    fn test_policies_change_order_and_state() {
+        let state = |x, y| vec![("x".to_string(), x), ("y".to_string(), y)];
+
+        assert_eq!(
+            run(BlockExecutor::new(), block(transactions())),
+            (vec![0, 1, 2], state(2, 20))
+        );
+        assert_eq!(
+            run(
+                BlockExecutor::new().with_ordering(ByFee::new(|task| task.priority as u64)),
+                block(transactions())
+            ),
+            (vec![1, 2, 0], state(1, 20))
+        );
+        assert_eq!(
+            run(
+                BlockExecutor::new().with_ordering(BySenderNonce::new(sender_nonce)),
+                block(transactions())
+            ),
+            (vec![0, 2, 1], state(2, 10))
+        );
+    }
+
+    /// Hash ordering executes the same sequence however the block was
+    /// assembled.
+    #[test]
+    fn test_hash_order_ignores_block_order() {
+        let reversed = || -> Vec<Task> {
+            let forward = transactions();
+            let count = forward.len();
+            (0..count)
+                .map(|id| forward[count - 1 - id].renumbered(id))
+                .collect()
+        };
+        let names = |tasks: &[Task], order: &[TaskId]| -> Vec<String> {
+            order.iter().map(|&id| tasks[id].name.clone()).collect()
+        };
+
+        let (order, state) = run(
+            BlockExecutor::new().with_ordering(ByHash),
+            block(transactions()),
+        );
+        let (reversed_order, reversed_state) = run(
+            BlockExecutor::new().with_ordering(ByHash),
+            block(reversed()),
+        );
+
+        assert_eq!(
+            names(&transactions(), &order),
+            names(&reversed(), &reversed_order)
+        );
+        assert_eq!(state, reversed_state);
+    }
+}
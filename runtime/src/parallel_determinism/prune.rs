@@ -0,0 +1,150 @@
+//! Dead-task elimination.
+//!
+//! A task whose writes nobody later reads, and which the caller does not want
+//! in the final state, does no useful work. [`DependencyGraph::prune`] finds
+//! such tasks in one backward pass: walking from the last task to the first,
+//! a task is live if it writes a kept key or a key some later live task
+//! reads, and only live tasks' reads keep earlier writers alive. Removing one
+//! dead task can therefore make the tasks feeding it dead as well.
+//!
+//! Outputs other than writes, such as events and receipts, do not keep a task
+//! alive; a task with no writes is always eliminated.
+
+use std::sync::Arc;
+
+use crate::{
+    collections::DSet,
+    parallel_determinism::{
+        dep_graph::DependencyGraph,
+        types::{ResourceId, Task, TaskId},
+    },
+};
+
+pub struct Pruned {
+    /// The surviving tasks, renumbered densely in their original order.
+    pub graph: DependencyGraph,
+    /// For each task in `graph`, its id before pruning.
+    pub original_ids: Vec<TaskId>,
+    /// Ids of the eliminated tasks, ascending.
+    pub eliminated: Vec<TaskId>,
+}
+
+impl DependencyGraph {
+    /// Drop every task whose writes are neither in `keep` nor read by a later
+    /// surviving task.
+    ///
+    /// Survivors get new, dense ids so the executors can index them; a task
+    /// whose work depends on its own id will see the new one.
+    pub fn prune(&self, keep: &DSet<ResourceId>) -> Pruned {
+        let mut live = vec![false; self.tasks.len()];
+        let mut needed: DSet<ResourceId> = keep.clone();
+        for task in self.tasks.iter().rev() {
+            if task.writes.iter().any(|key| needed.contains(key)) {
+                live[task.id] = true;
+                needed.extend(task.reads.iter().cloned());
+            }
+        }
+
+        let (survivors, eliminated): (Vec<_>, Vec<_>) =
+            self.tasks.iter().partition(|task| live[task.id]);
+        let original_ids = survivors.iter().map(|task| task.id).collect();
+        let renumbered: Vec<Arc<Task>> = survivors
+            .into_iter()
+            .enumerate()
+            .map(|(id, task)| Arc::new(task.renumbered(id)))
+            .collect();
+
+        Pruned {
+            graph: DependencyGraph::from_tasks(renumbered),
+            original_ids,
+            eliminated: eliminated.iter().map(|task| task.id).collect(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        sequential::SequentialExecutor,
+        state::{MemoryStorage, Storage},
+        types::TaskContext,
+    };
+
+    type Work = &'static (dyn Fn(&mut TaskContext) -> Result<String, String> + Sync);
+
+    /// Write one more than the sum of the task's reads to `key`.
+    fn sum_plus_one(context: &mut TaskContext, key: &str) -> Result<String, String> {
+        let sum: i64 = context
+            .read_set()
+            .map(|read| context.read(read).unwrap_or(0))
+            .sum();
+        context.write(key, sum + 1);
+        Ok(format!("wrote {}", sum + 1))
+    }
+
+    fn task(id: TaskId, reads: &[&str], write: &str, work: Work) -> Task {
+        Task {
+            id,
+            name: format!("T{}", id),
+            reads: reads.iter().map(|key| key.to_string()).collect(),
+            writes: vec![write.to_string()],
+            priority: 0,
+            cost: 1,
+            work,
+        }
+    }
+
+    fn graph() -> DependencyGraph {
+        DependencyGraph::from_tasks([
+            task(0, &[], "a", &|c| sum_plus_one(c, "a")),
+            task(1, &["a"], "b", &|c| sum_plus_one(c, "b")),
+            task(2, &[], "scratch", &|c| sum_plus_one(c, "scratch")),
+            task(3, &["b"], "result", &|c| sum_plus_one(c, "result")),
+            task(4, &["scratch"], "unused", &|c| sum_plus_one(c, "unused")),
+        ])
+    }
+
+    fn keep(keys: &[&str]) -> DSet<ResourceId> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    /// Only the chain feeding a kept key survives, and dropping the last
+    /// reader of a key kills its writer too.
+    #[test]
+    fn test_prune_keeps_what_feeds_kept_keys() {
+        let pruned = graph().prune(&keep(&["result"]));
+
+        assert_eq!(pruned.eliminated, [2, 4]);
+        assert_eq!(pruned.original_ids, [0, 1, 3]);
+        assert_eq!(pruned.graph.tasks[2].name, "T3");
+        assert_eq!(graph().prune(&keep(&[])).eliminated, [0, 1, 2, 3, 4]);
+    }
+
+    /// Kept keys end up with the same values with or without pruning.
+    #[test]
+    fn test_pruned_block_agrees_on_kept_keys() {
+        let pruned = graph().prune(&keep(&["result"]));
+        let (full, pruned) =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let full = MemoryStorage::new();
+                SequentialExecutor::new()
+                    .execute_with_state(&context, &graph(), &full)
+                    .await;
+                let store = MemoryStorage::new();
+                SequentialExecutor::new()
+                    .execute_with_state(&context, &pruned.graph, &store)
+                    .await;
+                (full.snapshot(), store.snapshot())
+            });
+
+        assert_eq!(full["result"], 3);
+        assert_eq!(pruned["result"], full["result"]);
+        assert!(!pruned.contains_key("scratch"));
+    }
+}
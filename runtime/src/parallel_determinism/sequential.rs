@@ -0,0 +1,65 @@
+//! The baseline every parallel strategy is measured against: run tasks one
+//! at a time in id order, committing each task's writes before the next one
+//! reads. This is the semantics parallel execution has to reproduce.
+
+use std::collections::BTreeMap;
+
+use commonware_runtime::Clock;
+
+use crate::{
+    parallel_determinism::{
+        dep_graph::DependencyGraph,
+        executor::{ExecutionReport, run_task},
+        state::{Storage, WriteBatch},
+    },
+    stats::elapsed_since,
+};
+
+#[derive(Default)]
+pub struct SequentialExecutor;
+
+impl SequentialExecutor {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Execute every task in id order against `store`. Each task gets its own
+    /// write batch, so the report has one batch per task.
+    pub async fn execute_with_state<C: Clock, St: Storage>(
+        &self,
+        context: &C,
+        graph: &DependencyGraph,
+        store: &St,
+    ) -> ExecutionReport {
+        let start = context.current();
+        let mut receipts = Vec::with_capacity(graph.tasks.len());
+        let mut batches = Vec::with_capacity(graph.tasks.len());
+        let mut errors = vec![];
+
+        for task in &graph.tasks {
+            let mut reads = BTreeMap::new();
+            for key in &task.reads {
+                reads.insert(key.clone(), store.get(context, key).await);
+            }
+            let (receipt, error) = run_task(task, reads);
+            errors.extend(error);
+            for (key, value) in &receipt.writes {
+                store.put(key.clone(), *value);
+            }
+            store.commit(context).await;
+            batches.push(WriteBatch {
+                level: task.id,
+                writes: receipt.writes.clone(),
+            });
+            receipts.push(receipt);
+        }
+
+        ExecutionReport {
+            completion_order: (0..graph.tasks.len()).collect(),
+            receipts,
+            batches,
+            errors,
+            elapsed: elapsed_since(context, start),
+        }
+    }
+}
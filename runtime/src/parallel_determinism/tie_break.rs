@@ -0,0 +1,106 @@
+//! Which ready task goes first.
+//!
+//! When more tasks are ready than there are workers to run them, a scheduler
+//! has to pick. Any rule is fine for replayability as long as it only looks at
+//! the tasks themselves, never at timing, and as long as the replay uses the
+//! same rule. [`TieBreak`] names the rule, orders tasks by it, and records
+//! itself in an [`EventLog`] so a replay can read it back with
+//! [`TieBreak::from_trace`] instead of relying on matching configuration.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    parallel_determinism::types::{Task, TaskId},
+    trace::EventLog,
+};
+
+/// The trace label tie-break records are filed under.
+const TRACE_LABEL: &str = "scheduler";
+const TRACE_PREFIX: &str = "tie-break: ";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Ignore declared priorities.
+    LowestId,
+    /// Fewest declared keys first, reads and writes together, as a cheap
+    /// estimate of how long a task will hold a worker.
+    ShortestWork,
+    /// Lowest [`Task::priority`] first.
+    #[default]
+    Priority,
+}
+
+impl TieBreak {
+    /// The sort key of `task` under this rule. Ties on the rule itself fall
+    /// back to the task id, so the order is total.
+    pub fn key(&self, task: &Task) -> (u64, TaskId) {
+        let rank = match self {
+            TieBreak::LowestId => 0,
+            TieBreak::ShortestWork => (task.reads.len() + task.writes.len()) as u64,
+            TieBreak::Priority => task.priority as u64,
+        };
+        (rank, task.id)
+    }
+
+    pub fn record(&self, log: &EventLog) {
+        log.record(TRACE_LABEL, format!("{}{}", TRACE_PREFIX, self));
+    }
+
+    /// The last rule recorded in `log`, if any.
+    pub fn from_trace(log: &EventLog) -> Option<Self> {
+        log.events().iter().rev().find_map(|event| {
+            if event.task != TRACE_LABEL {
+                return None;
+            }
+            event.message.strip_prefix(TRACE_PREFIX)?.parse().ok()
+        })
+    }
+}
+
+impl fmt::Display for TieBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TieBreak::LowestId => "lowest-id",
+            TieBreak::ShortestWork => "shortest-work",
+            TieBreak::Priority => "priority",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowest-id" => Ok(TieBreak::LowestId),
+            "shortest-work" => Ok(TieBreak::ShortestWork),
+            "priority" => Ok(TieBreak::Priority),
+            _ => Err(format!("unknown tie-break rule {:?}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every rule survives a trip through the trace.
+    #[test]
+    fn test_rules_round_trip_through_trace() {
+        let rules = [
+            TieBreak::LowestId,
+            TieBreak::ShortestWork,
+            TieBreak::Priority,
+        ];
+        for rule in rules {
+            let log = EventLog::new();
+            log.record("T0", "unrelated");
+            rule.record(&log);
+
+            assert_eq!(TieBreak::from_trace(&log), Some(rule));
+        }
+        assert_eq!(TieBreak::from_trace(&EventLog::new()), None);
+        assert!("fastest".parse::<TieBreak>().is_err());
+    }
+}
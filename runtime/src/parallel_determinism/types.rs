@@ -1,12 +1,25 @@
-type ResourceId = String;
+use std::collections::BTreeMap;
+
+pub type ResourceId = String;
+pub type Value = i64;
 pub type TaskId = usize;
-#[derive(Clone)]
+
+/// A unit of work with its declared access sets.
+///
+/// Deliberately not `Clone`: the graph and executors share tasks as
+/// `Arc<Task>`, so a large block is never deep-copied on its way to a worker.
 pub struct Task {
     pub id: TaskId,
     pub name: String,
     pub reads: Vec<ResourceId>,
     pub writes: Vec<ResourceId>,
-    pub work: &'static dyn Fn() -> Result<String, String>,
+    /// Among tasks ready at the same time, lower values start first, then
+    /// lower ids. 0 is the most urgent and the usual default.
+    pub priority: u8,
+    /// Estimated cost in arbitrary units, for visualization and planning.
+    /// Nothing enforces it; 1 is an ordinary task.
+    pub cost: u64,
+    pub work: &'static (dyn Fn(&mut TaskContext) -> Result<String, String> + Sync),
 }
 
 impl Task {
@@ -23,4 +36,98 @@ impl Task {
         }
         false
     }
+
+    /// A copy of this task under a new id, for rebuilding a graph from a
+    /// subset or reordering of its tasks.
+    pub fn renumbered(&self, id: TaskId) -> Task {
+        Task {
+            id,
+            name: self.name.clone(),
+            reads: self.reads.clone(),
+            writes: self.writes.clone(),
+            priority: self.priority,
+            cost: self.cost,
+            work: self.work,
+        }
+    }
+}
+
+/// A log line emitted by a task while it runs.
+///
+/// `seq` is the position of the event within its own task, so the pair
+/// `(task_id, seq)` gives every event a canonical place in the block output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub task_id: TaskId,
+    pub seq: usize,
+    pub message: String,
+}
+
+/// Handed to a task's `work` so it can read and write its declared state and
+/// emit events without touching shared state. Reads are resolved by the
+/// executor before the work runs; writes and events are buffered per task and
+/// merged afterwards.
+pub struct TaskContext {
+    task_id: TaskId,
+    reads: BTreeMap<ResourceId, Option<Value>>,
+    writes: BTreeMap<ResourceId, Value>,
+    events: Vec<Event>,
+}
+
+impl TaskContext {
+    pub fn new(task_id: TaskId) -> Self {
+        Self::with_reads(task_id, BTreeMap::new())
+    }
+
+    pub fn with_reads(task_id: TaskId, reads: BTreeMap<ResourceId, Option<Value>>) -> Self {
+        Self {
+            task_id,
+            reads,
+            writes: BTreeMap::new(),
+            events: vec![],
+        }
+    }
+
+    /// The value of a declared read, or `None` if the key is unset or was
+    /// never declared in the task's read set.
+    pub fn read(&self, key: &str) -> Option<Value> {
+        self.reads.get(key).copied().flatten()
+    }
+
+    /// The keys this task was given values for, in key order.
+    pub fn read_set(&self) -> impl Iterator<Item = &ResourceId> {
+        self.reads.keys()
+    }
+
+    /// Buffer a write. Writing outside the declared write set fails the task.
+    pub fn write(&mut self, key: impl Into<ResourceId>, value: Value) {
+        self.writes.insert(key.into(), value);
+    }
+
+    pub fn task_id(&self) -> TaskId {
+        self.task_id
+    }
+
+    pub fn emit(&mut self, message: impl Into<String>) {
+        self.events.push(Event {
+            task_id: self.task_id,
+            seq: self.events.len(),
+            message: message.into(),
+        });
+    }
+
+    pub fn into_parts(self) -> (BTreeMap<ResourceId, Value>, Vec<Event>) {
+        (self.writes, self.events)
+    }
+}
+
+/// The outcome of one task: its result, the writes it produced (empty if it
+/// failed), and every event it emitted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    pub task_id: TaskId,
+    pub name: String,
+    pub output: Result<String, String>,
+    pub writes: BTreeMap<ResourceId, Value>,
+    pub events: Vec<Event>,
 }
@@ -0,0 +1,77 @@
+//! The types most users of this crate need, in one import.
+//!
+//! `use runtime::prelude::*;` brings in tasks and the dependency graph, the
+//! executors and storage backends, the trace and RNG types every run threads
+//! through, and the shadow-execution harness. Everything here is also
+//! reachable through its own module; the prelude only saves spelling out
+//! the paths. What is available follows the crate features: without
+//! `runtime` only the graph analysis is exported, and the harness needs both
+//! backends.
+
+#[cfg(all(feature = "tokio-backend", feature = "deterministic-backend"))]
+pub use crate::shadow::{Divergence, ShadowReport, Workload, shadow};
+#[cfg(feature = "runtime")]
+pub use crate::{
+    error::{ErrorPolicy, TaskError},
+    parallel_determinism::{
+        block::{Block, BlockExecutor, BlockOutcome, SignedTask},
+        chain::Chain,
+        executor::{ExecutionReport, ParallelExecutor},
+        greedy::GreedyExecutor,
+        locks::LockExecutor,
+        optimistic::OptimisticExecutor,
+        sequential::SequentialExecutor,
+        state::{LatencyStorage, MemoryStorage, Storage},
+        stealing::WorkStealingExecutor,
+    },
+    spawn::{TaskHandle, TaskScope, TaskSpawner},
+};
+pub use crate::{
+    parallel_determinism::{
+        conflict::{ConflictDetector, ExactSets},
+        dep_graph::{DependencyGraph, GraphMetrics, LevelCost},
+        ordering::{ByFee, ByHash, BySenderNonce, OrderingPolicy},
+        tie_break::TieBreak,
+        types::{Event, Receipt, ResourceId, Task, TaskContext, TaskId, Value},
+    },
+    rng::DeterministicRng,
+    trace::{EventLog, TraceEvent},
+};
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use crate::prelude::*;
+
+    /// A downstream crate can build and execute a block through the prelude
+    /// alone.
+    #[test]
+    fn test_prelude_is_enough_to_run_a_block() {
+        let graph = DependencyGraph::from_tasks([Task {
+            id: 0,
+            name: "set".to_string(),
+            reads: vec![],
+            writes: vec!["x".to_string()],
+            priority: 0,
+            cost: 1,
+            work: &(|context: &mut TaskContext| {
+                context.write("x", 7);
+                Ok("set".to_string())
+            }),
+        }]);
+        let state =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let store = MemoryStorage::new();
+                ParallelExecutor::new()
+                    .execute_with_state(&context, &graph, &store)
+                    .await;
+                store.snapshot()
+            });
+
+        assert_eq!(state["x"], 7);
+    }
+}
@@ -0,0 +1,232 @@
+//! Python bindings for the dependency graph and the parallel executor.
+//!
+//! Built with the `python` feature, for example through
+//! `maturin develop --features python`. The module is importable as
+//! `runtime` and exposes:
+//!
+//! - `Task(id, name, reads, writes, priority=0, cost=1, work=None)`. `work`
+//!   is an optional callable taking a dict of the task's reads (missing keys
+//!   map to `None`) and returning a dict of writes.
+//! - `execution_levels(tasks)` and `metrics(tasks)`, the graph analysis.
+//! - `execute(tasks, state, seed=0)`, which runs the block with the parallel
+//!   executor on the deterministic runtime and returns the final state.
+//!
+//! Task ids must be `0..len(tasks)`, in order, as everywhere in this crate.
+//! A task's `work` is leaked when the task is created, since executors need
+//! `'static` work; that is fine for analysis scripts, not for long-lived
+//! services creating tasks in a loop.
+
+use std::collections::BTreeMap;
+
+use commonware_runtime::{
+    Runner,
+    deterministic::{Config, Runner as DeterministicRunner},
+};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::parallel_determinism::{
+    dep_graph::DependencyGraph,
+    executor::ParallelExecutor,
+    state::{MemoryStorage, Storage},
+    types::{Task, TaskContext, Value},
+};
+
+type Work = &'static (dyn Fn(&mut TaskContext) -> Result<String, String> + Sync);
+
+fn no_work(_: &mut TaskContext) -> Result<String, String> {
+    Ok(String::new())
+}
+
+/// Call a Python `work(reads) -> writes` and apply the writes.
+fn python_work(work: Py<PyAny>) -> Work {
+    Box::leak(Box::new(move |context: &mut TaskContext| {
+        let reads: BTreeMap<String, Option<Value>> = context
+            .read_set()
+            .map(|key| (key.clone(), context.read(key)))
+            .collect();
+        let writes = Python::attach(|py| {
+            work.call1(py, (reads,))?
+                .extract::<BTreeMap<String, Value>>(py)
+        })
+        .map_err(|error| error.to_string())?;
+        let count = writes.len();
+        for (key, value) in writes {
+            context.write(key, value);
+        }
+        Ok(format!("wrote {} keys", count))
+    }))
+}
+
+#[pyclass(name = "Task", frozen)]
+pub struct PyTask {
+    #[pyo3(get)]
+    id: usize,
+    #[pyo3(get)]
+    name: String,
+    #[pyo3(get)]
+    reads: Vec<String>,
+    #[pyo3(get)]
+    writes: Vec<String>,
+    #[pyo3(get)]
+    priority: u8,
+    #[pyo3(get)]
+    cost: u64,
+    work: Work,
+}
+
+#[pymethods]
+impl PyTask {
+    #[new]
+    #[pyo3(signature = (id, name, reads, writes, priority = 0, cost = 1, work = None))]
+    fn new(
+        id: usize,
+        name: String,
+        reads: Vec<String>,
+        writes: Vec<String>,
+        priority: u8,
+        cost: u64,
+        work: Option<Py<PyAny>>,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            reads,
+            writes,
+            priority,
+            cost,
+            work: work.map_or(&no_work, python_work),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Task({}, {:?}, reads={:?}, writes={:?})",
+            self.id, self.name, self.reads, self.writes
+        )
+    }
+}
+
+impl PyTask {
+    fn task(&self) -> Task {
+        Task {
+            id: self.id,
+            name: self.name.clone(),
+            reads: self.reads.clone(),
+            writes: self.writes.clone(),
+            priority: self.priority,
+            cost: self.cost,
+            work: self.work,
+        }
+    }
+}
+
+/// The graph's shape, as returned by `metrics`.
+#[pyclass(name = "GraphMetrics", frozen, get_all)]
+pub struct PyGraphMetrics {
+    tasks: usize,
+    edges: usize,
+    average_out_degree: f64,
+    depth: usize,
+    max_width: usize,
+    conflict_density: f64,
+}
+
+fn graph(tasks: &[PyRef<'_, PyTask>]) -> PyResult<DependencyGraph> {
+    if let Some((position, task)) = tasks.iter().enumerate().find(|(i, t)| t.id != *i) {
+        return Err(PyValueError::new_err(format!(
+            "task at position {} has id {}; ids must be 0..len(tasks) in order",
+            position, task.id
+        )));
+    }
+    Ok(DependencyGraph::from_tasks(
+        tasks.iter().map(|task| task.task()),
+    ))
+}
+
+#[pyfunction]
+fn execution_levels(tasks: Vec<PyRef<'_, PyTask>>) -> PyResult<Vec<Vec<usize>>> {
+    Ok(graph(&tasks)?.execution_levels())
+}
+
+#[pyfunction]
+fn metrics(tasks: Vec<PyRef<'_, PyTask>>) -> PyResult<PyGraphMetrics> {
+    let metrics = graph(&tasks)?.metrics();
+    Ok(PyGraphMetrics {
+        tasks: metrics.tasks,
+        edges: metrics.edges,
+        average_out_degree: metrics.average_out_degree,
+        depth: metrics.depth,
+        max_width: metrics.max_width,
+        conflict_density: metrics.conflict_density,
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (tasks, state, seed = 0))]
+fn execute(
+    tasks: Vec<PyRef<'_, PyTask>>,
+    state: BTreeMap<String, Value>,
+    seed: u64,
+) -> PyResult<BTreeMap<String, Value>> {
+    let graph = graph(&tasks)?;
+    let (state, errors) =
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            let store: MemoryStorage = state.into_iter().collect();
+            let report = ParallelExecutor::new()
+                .execute_with_state(&context, &graph, &store)
+                .await;
+            (store.snapshot(), report.errors)
+        });
+    match errors.first() {
+        Some(error) => Err(PyValueError::new_err(error.to_string())),
+        None => Ok(state),
+    }
+}
+
+#[pymodule]
+fn runtime(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyTask>()?;
+    module.add_class::<PyGraphMetrics>()?;
+    module.add_function(wrap_pyfunction!(execution_levels, module)?)?;
+    module.add_function(wrap_pyfunction!(metrics, module)?)?;
+    module.add_function(wrap_pyfunction!(execute, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Python-defined tasks are analyzed and executed like native ones, and
+    /// out-of-order ids are rejected.
+    #[test]
+    fn test_python_tasks_execute() {
+        Python::initialize();
+        Python::attach(|py| {
+            let double = py
+                .eval(c"lambda reads: {'y': reads['x'] * 2}", None, None)
+                .unwrap()
+                .unbind();
+            let tasks = [
+                PyTask::new(
+                    0,
+                    "double".into(),
+                    vec!["x".into()],
+                    vec!["y".into()],
+                    0,
+                    1,
+                    Some(double),
+                ),
+                PyTask::new(1, "observe".into(), vec!["y".into()], vec![], 0, 1, None),
+            ]
+            .map(|task| Py::new(py, task).unwrap());
+            let refs = || tasks.iter().map(|task| task.borrow(py)).collect::<Vec<_>>();
+
+            assert_eq!(execution_levels(refs()).unwrap(), [[0], [1]]);
+            assert_eq!(metrics(refs()).unwrap().edges, 1);
+            let state = execute(refs(), [("x".to_string(), 21)].into(), 0).unwrap();
+            assert_eq!(state["y"], 42);
+            assert!(execution_levels(refs().into_iter().rev().collect()).is_err());
+        });
+    }
+}
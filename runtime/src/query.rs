@@ -0,0 +1,231 @@
+//! Picking events out of a log without scanning it by hand.
+//!
+//! Assertions about a run usually care about a few events: what one task
+//! did, every send, everything that touched one key, whatever happened in
+//! the first ten milliseconds. A [`Query`] from [`EventLog::query`] narrows
+//! the log down by any combination of:
+//!
+//! - [`task`](Query::task): the task that recorded the event, exactly;
+//! - [`kind`](Query::kind): the first word of the message, such as `send`,
+//!   `received` or `polled`;
+//! - [`between`](Query::between): when the event was recorded, for a log with
+//!   a [clock](EventLog::with_clock);
+//! - [`touching`](Query::touching): a resource the message names, as whole
+//!   words, so `node 1` matches "send to node 1" but not "send to node 12".
+//!
+//! [`iter`](Query::iter) then yields the matching events in log order, each
+//! with its position in the log and its time, if it has one.
+//!
+//! [`EventLog::query`]: crate::trace::EventLog::query
+//! [`EventLog::with_clock`]: crate::trace::EventLog::with_clock
+
+use std::{ops::Range, time::Duration};
+
+use crate::trace::TraceEvent;
+
+/// An event with where and when it was recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Its position in the log.
+    pub index: usize,
+    /// Time since the log's clock started, if it has one.
+    pub at: Option<Duration>,
+    pub event: TraceEvent,
+}
+
+/// A selection of a log's events. Every filter set must match.
+#[derive(Clone, Debug)]
+pub struct Query {
+    entries: Vec<Entry>,
+    task: Option<String>,
+    kind: Option<String>,
+    window: Option<Range<Duration>>,
+    resource: Option<String>,
+}
+
+impl Query {
+    /// A query over `entries` that matches all of them.
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Self {
+            entries,
+            task: None,
+            kind: None,
+            window: None,
+            resource: None,
+        }
+    }
+
+    pub fn task(mut self, task: impl Into<String>) -> Self {
+        self.task = Some(task.into());
+        self
+    }
+
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    /// Only events recorded in `window`. Events without a time never match.
+    pub fn between(mut self, window: Range<Duration>) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    pub fn touching(mut self, resource: impl Into<String>) -> Self {
+        self.resource = Some(resource.into());
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(|entry| self.matches(entry))
+    }
+
+    /// Just the matching events, in log order.
+    pub fn events(&self) -> impl Iterator<Item = &TraceEvent> {
+        self.iter().map(|entry| &entry.event)
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        let event = &entry.event;
+        self.task.as_ref().is_none_or(|task| event.task == *task)
+            && self
+                .kind
+                .as_ref()
+                .is_none_or(|kind| event.message.split_whitespace().next() == Some(kind))
+            && self
+                .window
+                .as_ref()
+                .is_none_or(|window| entry.at.is_some_and(|at| window.contains(&at)))
+            && self
+                .resource
+                .as_ref()
+                .is_none_or(|resource| mentions(&event.message, resource))
+    }
+}
+
+/// Whether `message` contains `resource` with no letter, digit or
+/// underscore right before or after it.
+fn mentions(message: &str, resource: &str) -> bool {
+    let word = |c: char| c.is_alphanumeric() || c == '_';
+    message.match_indices(resource).any(|(start, _)| {
+        let before = message[..start].chars().next_back();
+        let after = message[start + resource.len()..].chars().next();
+        !before.is_some_and(word) && !after.is_some_and(word)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::EventLog;
+
+    fn log() -> EventLog {
+        let log = EventLog::new();
+        log.record("node 0", "set x to 1 at [1]");
+        log.record("node 0", "send to node 1 at [2]");
+        log.record("node 1", "received from node 0 at [2, 1]");
+        log.record("node 1", "set max to 2 at [2, 2]");
+        log.record("node 0", "send to node 12 at [3]");
+        log
+    }
+
+    /// Filters combine, and resources match as whole words only.
+    #[test]
+    fn test_filters() {
+        let log = log();
+        let messages = |query: Query| -> Vec<String> {
+            query.events().map(|event| event.message.clone()).collect()
+        };
+
+        assert_eq!(log.query().iter().count(), 5);
+        assert_eq!(
+            messages(log.query().task("node 0").kind("send")),
+            ["send to node 1 at [2]", "send to node 12 at [3]"]
+        );
+        assert_eq!(
+            messages(log.query().touching("node 1")),
+            ["send to node 1 at [2]"]
+        );
+        assert_eq!(messages(log.query().touching("x")), ["set x to 1 at [1]"]);
+        let indices: Vec<_> = log.query().kind("set").iter().map(|e| e.index).collect();
+        assert_eq!(indices, [0, 3]);
+        assert_eq!(
+            log.query()
+                .between(Duration::ZERO..Duration::MAX)
+                .iter()
+                .count(),
+            0
+        );
+    }
+
+    /// A log with a clock stamps events with virtual time, and a window
+    /// selects by it.
+    #[cfg(feature = "deterministic-backend")]
+    #[test]
+    fn test_time_window() {
+        use commonware_runtime::{
+            Clock, Runner,
+            deterministic::{Config, Runner as DeterministicRunner},
+        };
+
+        let query = DeterministicRunner::new(Config::default()).start(|context| async move {
+            let log = EventLog::new().with_clock(context.clone());
+            for step in 0..5 {
+                log.record("ticker", format!("tick {}", step));
+                context.sleep(Duration::from_millis(10)).await;
+            }
+            log.query()
+        });
+
+        let ticks: Vec<_> = query
+            .between(Duration::from_millis(10)..Duration::from_millis(30))
+            .iter()
+            .map(|entry| (entry.event.message.clone(), entry.at.unwrap()))
+            .collect();
+        assert_eq!(
+            ticks,
+            [
+                ("tick 1".to_string(), Duration::from_millis(10)),
+                ("tick 2".to_string(), Duration::from_millis(20)),
+            ]
+        );
+    }
+
+    /// Events recorded through a clone taken before the clock was attached
+    /// have no time, and every later event keeps its own.
+    #[cfg(feature = "deterministic-backend")]
+    #[test]
+    fn test_clone_before_clock() {
+        use commonware_runtime::{
+            Clock, Runner,
+            deterministic::{Config, Runner as DeterministicRunner},
+        };
+
+        let query = DeterministicRunner::new(Config::default()).start(|context| async move {
+            let early = EventLog::new();
+            let log = early.clone().with_clock(context.clone());
+            early.record("early", "untimed");
+            context.sleep(Duration::from_millis(10)).await;
+            log.record("late", "timed");
+            early.record("early", "untimed again");
+            log.query()
+        });
+
+        let entries: Vec<_> = query
+            .iter()
+            .map(|entry| (entry.event.message.clone(), entry.at))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("untimed".to_string(), None),
+                ("timed".to_string(), Some(Duration::from_millis(10))),
+                ("untimed again".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            query.between(Duration::ZERO..Duration::MAX).iter().count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Several consumers pulling jobs from one shared queue.
+//!
+//! [`work_queue`] fills a queue with jobs and spawns consumers that each take
+//! the next job, spend the job's cost sleeping, and come back for another
+//! until the queue is empty. Which consumer ends up with which job depends on
+//! who is free when a job reaches the front, so the assignment is a direct
+//! record of the schedule. Under the deterministic runtime it is the same on
+//! every run with the same seed; under Tokio only the fact that every job was
+//! processed exactly once is guaranteed.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use commonware_runtime::{Clock, Spawner};
+use rand::Rng;
+
+use crate::{rng::DeterministicRng, trace::EventLog};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Job {
+    pub id: usize,
+    /// How long processing the job takes.
+    pub cost: Duration,
+}
+
+/// `count` jobs with costs between one and `max_cost_ms` milliseconds, drawn
+/// from `rng`.
+pub fn jobs(count: usize, max_cost_ms: u64, rng: &mut DeterministicRng) -> Vec<Job> {
+    (0..count)
+        .map(|id| Job {
+            id,
+            cost: Duration::from_millis(rng.random_range(1..=max_cost_ms)),
+        })
+        .collect()
+}
+
+/// Run `jobs` through a queue drained by `consumers` consumer tasks, each
+/// recording `took job N` in `log` under `consumer C`. Returns the consumer
+/// that processed each job, indexed by job id.
+pub async fn work_queue<S: Spawner + Clock>(
+    context: &S,
+    jobs: Vec<Job>,
+    consumers: usize,
+    log: &EventLog,
+) -> Vec<usize> {
+    let count = jobs.len();
+    let queue = Arc::new(Mutex::new(VecDeque::from(jobs)));
+    let assignment = Arc::new(Mutex::new(vec![usize::MAX; count]));
+
+    let handles: Vec<_> = (0..consumers)
+        .map(|consumer| {
+            let (queue, assignment, log) = (queue.clone(), assignment.clone(), log.clone());
+            context.clone().spawn(move |context| async move {
+                let name = format!("consumer {}", consumer);
+                loop {
+                    // Take the lock only to pop, never across an await.
+                    let Some(job) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    log.record(name.as_str(), format!("took job {}", job.id));
+                    assignment.lock().unwrap()[job.id] = consumer;
+                    context.sleep(job.cost).await;
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.await.expect("Consumer should run to completion");
+    }
+    Arc::try_unwrap(assignment)
+        .expect("Consumers have finished")
+        .into_inner()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::trace::TraceEvent;
+
+    fn deterministic_queue(seed: u64) -> (Vec<usize>, Vec<TraceEvent>) {
+        let log = EventLog::new();
+        let jobs = jobs(40, 5, &mut DeterministicRng::new(1));
+        let assignment =
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+                let log = log.clone();
+                async move { work_queue(&context, jobs, 4, &log).await }
+            });
+        (assignment, log.events())
+    }
+
+    /// Every job is processed by exactly one consumer, and work is spread
+    /// across all of them.
+    #[test]
+    fn test_every_job_processed_once() {
+        let (assignment, events) = deterministic_queue(0);
+
+        assert_eq!(events.len(), 40);
+        assert!(assignment.iter().all(|&consumer| consumer < 4));
+        for consumer in 0..4 {
+            assert!(assignment.contains(&consumer));
+        }
+    }
+
+    /// The same seed assigns every job to the same consumer, in the same
+    /// order.
+    #[test]
+    fn test_assignment_repeats_with_seed() {
+        for seed in 0..5 {
+            assert_eq!(deterministic_queue(seed), deterministic_queue(seed));
+        }
+    }
+
+    /// Tokio also processes every job exactly once, whatever the assignment.
+    #[cfg(feature = "tokio-backend")]
+    #[test]
+    fn test_tokio_processes_every_job() {
+        use commonware_runtime::tokio::{Config as TokioConfig, Runner as TokioRunner};
+
+        let log = EventLog::new();
+        let jobs = jobs(40, 2, &mut DeterministicRng::new(1));
+        let assignment =
+            TokioRunner::new(TokioConfig::default().with_worker_threads(4)).start(|context| {
+                let log = log.clone();
+                async move { work_queue(&context, jobs, 4, &log).await }
+            });
+
+        assert!(assignment.iter().all(|&consumer| consumer < 4));
+        assert_eq!(log.events().len(), 40);
+    }
+}
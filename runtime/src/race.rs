@@ -0,0 +1,217 @@
+//! A deliberate lost-update race, and the tooling to catch and pin it.
+//!
+//! Several tasks increment one shared counter with a read-modify-write that
+//! may suspend between the read and the write. If another task writes in that
+//! window, one increment is lost. The invariant "final value equals the number
+//! of increments" catches it.
+//!
+//! On Tokio the race shows up some of the time and there is no way to ask for
+//! that run again. On the deterministic runtime, [`find_violating_seed`]
+//! sweeps seeds until one loses an update; from then on that seed loses
+//! exactly the same update on every run, which turns a flaky bug into a
+//! regression test.
+//!
+//! Each increment is also recorded in a [`History`], so the outcome can be
+//! checked for linearizability against a [`Counter`], not just by its final
+//! value: a lost update shows up as two increments that returned the same
+//! value.
+
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use commonware_runtime::{
+    Clock, Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+use rand::Rng;
+
+use crate::{
+    linearizability::{Counter, CounterOp, History, NotLinearizable, check},
+    rng::DeterministicRng,
+};
+
+pub const TASKS: usize = 4;
+pub const INCREMENTS: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RaceOutcome {
+    pub expected: u64,
+    pub observed: u64,
+}
+
+/// The invariant violation: some increments were overwritten.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LostUpdate {
+    pub expected: u64,
+    pub observed: u64,
+}
+
+impl LostUpdate {
+    pub fn lost(&self) -> u64 {
+        self.expected - self.observed
+    }
+}
+
+/// Every increment must be reflected in the final value.
+pub fn check_no_lost_updates(outcome: &RaceOutcome) -> Result<(), LostUpdate> {
+    if outcome.observed == outcome.expected {
+        Ok(())
+    } else {
+        Err(LostUpdate {
+            expected: outcome.expected,
+            observed: outcome.observed,
+        })
+    }
+}
+
+/// `tasks` tasks each increment a shared counter `increments` times. Before
+/// each increment a task waits a random jitter; half the time it then also
+/// waits between reading the counter and writing it back. That second wait is
+/// the race window. Every increment is recorded in `history`, returning the
+/// value it wrote.
+pub async fn racy_increments<S: Spawner + Clock>(
+    context: &S,
+    rng: DeterministicRng,
+    history: &History<CounterOp, u64>,
+    tasks: usize,
+    increments: usize,
+) -> RaceOutcome {
+    let counter = Arc::new(Mutex::new(0u64));
+    let handles: Vec<_> = (0..tasks)
+        .map(|task| {
+            let counter = counter.clone();
+            let mut rng = rng.clone();
+            let history = history.clone();
+            context.clone().spawn(move |context| async move {
+                for _ in 0..increments {
+                    let jitter = Duration::from_micros(rng.random_range(0..2_000));
+                    let suspend = rng.random_bool(0.5);
+                    context.sleep(jitter).await;
+
+                    let id = history.invoke(task, CounterOp::Increment);
+                    let value = *counter.lock().unwrap();
+                    if suspend {
+                        context.sleep(Duration::from_millis(1)).await;
+                    }
+                    *counter.lock().unwrap() = value + 1;
+                    history.respond(id, value + 1);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.await.expect("Racing task should run to completion");
+    }
+    let observed = *counter.lock().unwrap();
+    RaceOutcome {
+        expected: (tasks * increments) as u64,
+        observed,
+    }
+}
+
+/// The increments of a run must be linearizable as a [`Counter`].
+pub fn check_linearizable(history: &History<CounterOp, u64>) -> Result<(), NotLinearizable> {
+    check(Counter::default(), history).map(|_| ())
+}
+
+/// Run the race once on the deterministic runtime. The runtime seed drives
+/// both scheduling and the tasks' jitter.
+pub fn run_deterministic(seed: u64) -> RaceOutcome {
+    run_deterministic_recorded(seed).0
+}
+
+/// [`run_deterministic`], also returning the history of increments.
+pub fn run_deterministic_recorded(seed: u64) -> (RaceOutcome, History<CounterOp, u64>) {
+    let history = History::new();
+    let recorder = history.clone();
+    let outcome =
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            let rng = DeterministicRng::from_runtime(&mut context.clone());
+            racy_increments(&context, rng, &recorder, TASKS, INCREMENTS).await
+        });
+    (outcome, history)
+}
+
+/// Run the race on Tokio up to `attempts` times and return the first attempt
+/// (zero-based) that lost an update. Which attempt that is changes run to run.
+pub fn find_tokio_violation(attempts: usize, worker_threads: usize) -> Option<(usize, LostUpdate)> {
+    (0..attempts).find_map(|attempt| {
+        let outcome = TokioRunner::new(TokioConfig::default().with_worker_threads(worker_threads))
+            .start(|context| async move {
+                let rng = DeterministicRng::new(attempt as u64);
+                racy_increments(&context, rng, &History::new(), TASKS, INCREMENTS).await
+            });
+        check_no_lost_updates(&outcome)
+            .err()
+            .map(|lost| (attempt, lost))
+    })
+}
+
+/// The first seed in `seeds` whose deterministic run loses an update.
+pub fn find_violating_seed(seeds: Range<u64>) -> Option<(u64, LostUpdate)> {
+    seeds.into_iter().find_map(|seed| {
+        check_no_lost_updates(&run_deterministic(seed))
+            .err()
+            .map(|lost| (seed, lost))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The checker accepts a complete count and reports how much was lost
+    /// otherwise.
+    #[test]
+    fn test_check_no_lost_updates() {
+        let ok = RaceOutcome {
+            expected: 20,
+            observed: 20,
+        };
+        let lossy = RaceOutcome {
+            expected: 20,
+            observed: 17,
+        };
+
+        assert_eq!(check_no_lost_updates(&ok), Ok(()));
+        assert_eq!(check_no_lost_updates(&lossy).unwrap_err().lost(), 3);
+    }
+
+    /// Tokio exhibits the race within a handful of attempts.
+    #[test]
+    fn test_tokio_exhibits_race() {
+        let (_, lost) = find_tokio_violation(50, 4).expect("Race should show up on Tokio");
+        assert!(lost.lost() > 0);
+    }
+
+    /// A violating seed is found, and replaying it reproduces the exact same
+    /// lost update every time.
+    #[test]
+    fn test_violating_seed_reproduces() {
+        let (seed, lost) = find_violating_seed(0..100).expect("Some seed should lose an update");
+
+        for _ in 0..3 {
+            assert_eq!(check_no_lost_updates(&run_deterministic(seed)), Err(lost));
+        }
+    }
+
+    /// The linearizability checker agrees with the final-value invariant on
+    /// both racy and clean runs.
+    #[test]
+    fn test_histories_match_invariant() {
+        for seed in 0..20 {
+            let (outcome, history) = run_deterministic_recorded(seed);
+            assert_eq!(
+                check_no_lost_updates(&outcome).is_ok(),
+                check_linearizable(&history).is_ok(),
+                "seed {}",
+                seed
+            );
+        }
+    }
+}
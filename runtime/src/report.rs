@@ -0,0 +1,339 @@
+//! A readable summary of one run, as Markdown or HTML.
+//!
+//! The pieces of a run's story are spread over several types: the
+//! [`EventLog`] has the timeline, the dependency graph the level widths,
+//! [`FairnessReport`](crate::fairness::FairnessReport)s the scheduling
+//! percentiles, a [`LogDiff`] where a second run went another way. A
+//! [`RunReport`] collects whichever of them a run has and renders them as one
+//! document, for pasting into an issue ([`to_markdown`](RunReport::to_markdown))
+//! or opening in a browser ([`to_html`](RunReport::to_html)). Sections
+//! without data are left out.
+
+use std::fmt::Write;
+
+use crate::{
+    parallel_determinism::types::TaskId,
+    query::Entry,
+    stats::Percentiles,
+    trace::{EventLog, LogDiff},
+};
+
+/// Timeline rows shown before the rest is summarized.
+const DEFAULT_TIMELINE_LIMIT: usize = 50;
+
+/// One row of the percentile table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PercentileRow {
+    pub runtime: String,
+    pub metric: String,
+    pub percentiles: Percentiles,
+}
+
+#[derive(Clone, Debug)]
+pub struct RunReport {
+    pub title: String,
+    timeline: Vec<Entry>,
+    timeline_limit: usize,
+    metrics: Vec<(String, String)>,
+    level_widths: Vec<usize>,
+    percentiles: Vec<PercentileRow>,
+    divergence: Option<LogDiff>,
+}
+
+impl RunReport {
+    /// A report titled `title` whose timeline is `log` as it is now.
+    pub fn new(title: impl Into<String>, log: &EventLog) -> Self {
+        Self {
+            title: title.into(),
+            timeline: log.query().iter().cloned().collect(),
+            timeline_limit: DEFAULT_TIMELINE_LIMIT,
+            metrics: Vec::new(),
+            level_widths: Vec::new(),
+            percentiles: Vec::new(),
+            divergence: None,
+        }
+    }
+
+    /// Show at most `limit` events of the timeline.
+    pub fn with_timeline_limit(mut self, limit: usize) -> Self {
+        self.timeline_limit = limit;
+        self
+    }
+
+    /// Add a line to the summary, such as a seed or a result.
+    pub fn with_metric(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.metrics.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Show how many tasks each execution level had.
+    pub fn with_levels(mut self, levels: impl IntoIterator<Item = Vec<TaskId>>) -> Self {
+        self.level_widths = levels.into_iter().map(|level| level.len()).collect();
+        self
+    }
+
+    pub fn with_percentiles(
+        mut self,
+        runtime: impl Into<String>,
+        metric: impl Into<String>,
+        percentiles: Percentiles,
+    ) -> Self {
+        self.percentiles.push(PercentileRow {
+            runtime: runtime.into(),
+            metric: metric.into(),
+            percentiles,
+        });
+        self
+    }
+
+    /// Add the three fairness metrics of each report.
+    #[cfg(all(feature = "tokio-backend", feature = "deterministic-backend"))]
+    pub fn with_fairness(mut self, reports: &[crate::fairness::FairnessReport]) -> Self {
+        for report in reports {
+            for (metric, percentiles) in [
+                ("time to first poll", report.time_to_first_poll()),
+                ("wait time", report.wait_time()),
+                ("completion latency", report.completion_latency()),
+            ] {
+                self = self.with_percentiles(report.runtime, metric, percentiles);
+            }
+        }
+        self
+    }
+
+    /// Compare the timeline with another run's.
+    pub fn with_comparison(mut self, other: &EventLog) -> Self {
+        let timeline = self.timeline.iter().map(|entry| entry.event.clone());
+        self.divergence = Some(LogDiff::new(timeline.collect(), other.events()));
+        self
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "# {}\n", self.title).unwrap();
+
+        writeln!(out, "## Summary\n").unwrap();
+        writeln!(out, "- events: {}", self.timeline.len()).unwrap();
+        for (name, value) in &self.metrics {
+            writeln!(out, "- {}: {}", name, value).unwrap();
+        }
+
+        writeln!(out, "\n## Timeline\n").unwrap();
+        writeln!(out, "| step | time | task | event |").unwrap();
+        writeln!(out, "|---|---|---|---|").unwrap();
+        for entry in self.timeline.iter().take(self.timeline_limit) {
+            writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                entry.index,
+                time(entry),
+                entry.event.task,
+                entry.event.message.replace('|', "\\|")
+            )
+            .unwrap();
+        }
+        if let Some(hidden) = self.hidden_events() {
+            writeln!(out, "\n... {} more events", hidden).unwrap();
+        }
+
+        if !self.level_widths.is_empty() {
+            writeln!(out, "\n## Level widths\n").unwrap();
+            writeln!(out, "| level | tasks | |").unwrap();
+            writeln!(out, "|---|---|---|").unwrap();
+            for (level, &width) in self.level_widths.iter().enumerate() {
+                writeln!(out, "| {} | {} | {} |", level, width, "#".repeat(width)).unwrap();
+            }
+        }
+
+        if !self.percentiles.is_empty() {
+            writeln!(out, "\n## Fairness\n").unwrap();
+            writeln!(out, "| runtime | metric | p50 | p95 | p99 |").unwrap();
+            writeln!(out, "|---|---|---|---|---|").unwrap();
+            for row in &self.percentiles {
+                let p = row.percentiles;
+                writeln!(
+                    out,
+                    "| {} | {} | {:?} | {:?} | {:?} |",
+                    row.runtime, row.metric, p.p50, p.p95, p.p99
+                )
+                .unwrap();
+            }
+        }
+
+        if let Some(diff) = &self.divergence {
+            writeln!(out, "\n## Divergence\n").unwrap();
+            match diff.divergence() {
+                None => writeln!(out, "Identical to the comparison run.").unwrap(),
+                Some(step) => {
+                    writeln!(out, "Diverges from the comparison run at step {}.\n", step).unwrap();
+                    writeln!(out, "```\n{}```", diff).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>",
+            escape(&self.title)
+        )
+        .unwrap();
+        writeln!(out, "<h1>{}</h1>", escape(&self.title)).unwrap();
+
+        writeln!(out, "<h2>Summary</h2>\n<ul>").unwrap();
+        writeln!(out, "<li>events: {}</li>", self.timeline.len()).unwrap();
+        for (name, value) in &self.metrics {
+            writeln!(out, "<li>{}: {}</li>", escape(name), escape(value)).unwrap();
+        }
+        writeln!(out, "</ul>").unwrap();
+
+        writeln!(out, "<h2>Timeline</h2>").unwrap();
+        let rows = self.timeline.iter().take(self.timeline_limit).map(|entry| {
+            vec![
+                entry.index.to_string(),
+                time(entry),
+                entry.event.task.clone(),
+                entry.event.message.clone(),
+            ]
+        });
+        table(&mut out, &["step", "time", "task", "event"], rows);
+        if let Some(hidden) = self.hidden_events() {
+            writeln!(out, "<p>... {} more events</p>", hidden).unwrap();
+        }
+
+        if !self.level_widths.is_empty() {
+            writeln!(out, "<h2>Level widths</h2>").unwrap();
+            let rows = self.level_widths.iter().enumerate().map(|(level, &width)| {
+                vec![level.to_string(), width.to_string(), "#".repeat(width)]
+            });
+            table(&mut out, &["level", "tasks", ""], rows);
+        }
+
+        if !self.percentiles.is_empty() {
+            writeln!(out, "<h2>Fairness</h2>").unwrap();
+            let rows = self.percentiles.iter().map(|row| {
+                let p = row.percentiles;
+                vec![
+                    row.runtime.clone(),
+                    row.metric.clone(),
+                    format!("{:?}", p.p50),
+                    format!("{:?}", p.p95),
+                    format!("{:?}", p.p99),
+                ]
+            });
+            table(&mut out, &["runtime", "metric", "p50", "p95", "p99"], rows);
+        }
+
+        if let Some(diff) = &self.divergence {
+            writeln!(out, "<h2>Divergence</h2>").unwrap();
+            match diff.divergence() {
+                None => writeln!(out, "<p>Identical to the comparison run.</p>").unwrap(),
+                Some(step) => {
+                    writeln!(
+                        out,
+                        "<p>Diverges from the comparison run at step {}.</p>",
+                        step
+                    )
+                    .unwrap();
+                    writeln!(out, "<pre>{}</pre>", escape(&diff.to_string())).unwrap();
+                }
+            }
+        }
+        writeln!(out, "</body>\n</html>").unwrap();
+        out
+    }
+
+    fn hidden_events(&self) -> Option<usize> {
+        let hidden = self.timeline.len().saturating_sub(self.timeline_limit);
+        (hidden > 0).then_some(hidden)
+    }
+}
+
+fn time(entry: &Entry) -> String {
+    entry.at.map_or("-".to_string(), |at| format!("{:?}", at))
+}
+
+fn table(out: &mut String, headers: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    writeln!(out, "<table>\n<tr>").unwrap();
+    for header in headers {
+        writeln!(out, "<th>{}</th>", header).unwrap();
+    }
+    writeln!(out, "</tr>").unwrap();
+    for row in rows {
+        write!(out, "<tr>").unwrap();
+        for cell in row {
+            write!(out, "<td>{}</td>", escape(&cell)).unwrap();
+        }
+        writeln!(out, "</tr>").unwrap();
+    }
+    writeln!(out, "</table>").unwrap();
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn log(last: &str) -> EventLog {
+        let log = EventLog::new();
+        log.record("a", "start");
+        log.record("b", "start");
+        log.record("a", last);
+        log
+    }
+
+    /// Every section with data shows up, and the timeline stops at its
+    /// limit.
+    #[test]
+    fn test_markdown_sections() {
+        let markdown = RunReport::new("seed 7", &log("done"))
+            .with_timeline_limit(2)
+            .with_metric("seed", 7)
+            .with_levels(vec![vec![0, 1, 2], vec![3]])
+            .with_percentiles(
+                "deterministic",
+                "wait time",
+                Percentiles {
+                    p50: Duration::from_millis(1),
+                    p95: Duration::from_millis(2),
+                    p99: Duration::from_millis(3),
+                },
+            )
+            .with_comparison(&log("done"))
+            .to_markdown();
+
+        assert!(markdown.starts_with("# seed 7\n"));
+        assert!(markdown.contains("- seed: 7"));
+        assert!(markdown.contains("| 1 | - | b | start |"));
+        assert!(!markdown.contains("| 2 | - | a | done |"));
+        assert!(markdown.contains("... 1 more events"));
+        assert!(markdown.contains("| 0 | 3 | ### |"));
+        assert!(markdown.contains("| deterministic | wait time | 1ms | 2ms | 3ms |"));
+        assert!(markdown.contains("Identical to the comparison run."));
+    }
+
+    /// HTML escapes what the run recorded, and a divergence is reported
+    /// with the step it happened at.
+    #[test]
+    fn test_html_divergence() {
+        let html = RunReport::new("a <b> run", &log("done"))
+            .with_comparison(&log("failed"))
+            .to_html();
+
+        assert!(html.contains("<h1>a &lt;b&gt; run</h1>"));
+        assert!(html.contains("Diverges from the comparison run at step 2."));
+        assert!(!html.contains("Level widths"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+}
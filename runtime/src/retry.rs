@@ -0,0 +1,167 @@
+//! Retrying failed operations with exponential backoff.
+//!
+//! [`retry`] runs an operation until it succeeds or a [`Backoff`] runs out of
+//! attempts, sleeping on the runtime's clock between attempts. Delays double
+//! (or grow by any factor) from an initial value up to a cap. The sleeps are
+//! the runtime's, so under the deterministic runtime a retry schedule is
+//! virtual time: it costs nothing to wait out and lands at the same instants
+//! on every run.
+
+use std::{fmt, future::Future, time::Duration};
+
+use commonware_runtime::Clock;
+
+/// When to retry and how long to wait before each retry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    factor: u32,
+    max_delay: Duration,
+    max_attempts: usize,
+}
+
+impl Backoff {
+    /// Up to `max_attempts` attempts in all, waiting `initial` before the
+    /// first retry and twice as long before each one after.
+    pub fn new(initial: Duration, max_attempts: usize) -> Self {
+        assert!(max_attempts > 0, "An operation needs at least one attempt");
+        Self {
+            initial,
+            factor: 2,
+            max_delay: Duration::MAX,
+            max_attempts,
+        }
+    }
+
+    /// Grow the delay by `factor` after each retry instead of doubling it.
+    pub fn with_factor(mut self, factor: u32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Never wait longer than `max_delay` between attempts.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// The wait after failed attempt `attempt`, counting from 1.
+    pub fn delay(&self, attempt: usize) -> Duration {
+        u32::try_from(attempt.saturating_sub(1))
+            .ok()
+            .and_then(|retries| self.factor.checked_pow(retries))
+            .and_then(|growth| self.initial.checked_mul(growth))
+            .unwrap_or(Duration::MAX)
+            .min(self.max_delay)
+    }
+}
+
+/// Every attempt failed; `last` is the final attempt's error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryError<E> {
+    pub attempts: usize,
+    pub last: E,
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gave up after {} attempts: {}", self.attempts, self.last)
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for RetryError<E> {}
+
+/// Run `operation` until it returns `Ok`, waiting on `context`'s clock
+/// between attempts as `backoff` says. `operation` is handed the attempt
+/// number, counting from 1.
+pub async fn retry<C, T, E, F, Fut>(
+    context: &C,
+    backoff: &Backoff,
+    mut operation: F,
+) -> Result<T, RetryError<E>>
+where
+    C: Clock,
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation(attempt).await {
+            Ok(value) => return Ok(value),
+            Err(last) if attempt == backoff.max_attempts => {
+                return Err(RetryError {
+                    attempts: attempt,
+                    last,
+                });
+            }
+            Err(_) => {
+                context.sleep(backoff.delay(attempt)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::stats::elapsed_since;
+
+    /// Delays grow by the factor from the initial value and stop at the cap.
+    #[test]
+    fn test_delays() {
+        let backoff = Backoff::new(Duration::from_millis(10), 10)
+            .with_factor(3)
+            .with_max_delay(Duration::from_millis(200));
+        let delays: Vec<_> = (1..=5).map(|attempt| backoff.delay(attempt)).collect();
+
+        assert_eq!(
+            delays,
+            [10, 30, 90, 200, 200].map(Duration::from_millis).to_vec()
+        );
+        assert_eq!(
+            Backoff::new(Duration::from_secs(1), 100).delay(100),
+            Duration::MAX
+        );
+    }
+
+    /// An operation that fails twice succeeds on the third attempt, after
+    /// waiting out both delays; one that never succeeds gives up with its
+    /// last error.
+    #[test]
+    fn test_retry() {
+        DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+            let backoff = Backoff::new(Duration::from_millis(10), 4);
+            let start = context.current();
+            let result = retry(&context, &backoff, |attempt| async move {
+                if attempt < 3 {
+                    Err(attempt)
+                } else {
+                    Ok(attempt)
+                }
+            })
+            .await;
+            assert_eq!(result, Ok(3));
+            assert_eq!(elapsed_since(&context, start), Duration::from_millis(30));
+
+            let result: Result<(), _> =
+                retry(&context, &backoff, |attempt| async move { Err(attempt) }).await;
+            assert_eq!(
+                result,
+                Err(RetryError {
+                    attempts: 4,
+                    last: 4
+                })
+            );
+        });
+    }
+}
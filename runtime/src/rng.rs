@@ -0,0 +1,163 @@
+//! One seeded source of randomness per run.
+//!
+//! Building a fresh `StdRng` at every call site makes a run's randomness a
+//! collection of unrelated streams, each with its own magic seed. A
+//! [`DeterministicRng`] is created once from the run's seed (the same number
+//! handed to the deterministic runtime) and threaded through every workload,
+//! so all random choices in a run are one sequence that the seed fully
+//! determines.
+//!
+//! Inside a Commonware runtime, [`DeterministicRng::from_runtime`] takes the
+//! seed from the runtime's own RNG instead. Under the deterministic runtime
+//! that RNG is seeded by the runtime config, so workload randomness follows
+//! from the runtime seed with nothing else to keep in sync; under Tokio it is
+//! OS randomness, as it should be.
+//!
+//! Clones share the stream rather than copying it: two tasks holding clones
+//! draw alternately from the same sequence, in whatever order they run. Under
+//! the deterministic runtime that order is itself fixed by the seed.
+//!
+//! Where draws must not depend on that order at all, such as one selection
+//! per round of a workflow, [`derive_seed`] expands the master seed into an
+//! independent seed per purpose and index, and each gets its own stream.
+
+use std::sync::{Arc, Mutex};
+
+use rand::{RngCore, SeedableRng, rngs::StdRng};
+
+use crate::parallel_determinism::hash::Fnv;
+
+/// The seed for the `index`th use of `purpose` in a run seeded with
+/// `master`.
+///
+/// A small key-derivation step: the purpose label is hashed with FNV-1a,
+/// combined with the master seed and the index scaled by the golden-ratio
+/// constant, and the result mixed by the SplitMix64 finalizer. The same
+/// inputs always give the same seed; changing any of them gives an
+/// unrelated one, so changing the master seed changes every derived seed at
+/// once, and no two purposes or indices share a stream.
+pub fn derive_seed(master: u64, purpose: &str, index: u64) -> u64 {
+    let mut label = Fnv::new();
+    label.update(purpose.as_bytes());
+    let mut z = master
+        ^ label.finish().rotate_left(32)
+        ^ index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[derive(Clone)]
+pub struct DeterministicRng {
+    seed: u64,
+    inner: Arc<Mutex<StdRng>>,
+}
+
+impl DeterministicRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            inner: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// A stream of its own for the `index`th use of `purpose`; see
+    /// [`derive_seed`].
+    pub fn derived(master: u64, purpose: &str, index: u64) -> Self {
+        Self::new(derive_seed(master, purpose, index))
+    }
+
+    /// Seed a stream from a runtime context's RNG (any Commonware context).
+    pub fn from_runtime(context: &mut impl rand_core::RngCore) -> Self {
+        Self::new(context.next_u64())
+    }
+
+    /// The seed this stream was created from, for logging alongside results.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for DeterministicRng {
+    fn next_u32(&mut self) -> u32 {
+        self.inner.lock().unwrap().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner.lock().unwrap().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.inner.lock().unwrap().fill_bytes(dst)
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+    use rand::Rng;
+
+    use super::*;
+
+    /// The same seed always produces the same sequence.
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let draw = |seed| {
+            let mut rng = DeterministicRng::new(seed);
+            (0..8).map(|_| rng.random::<u64>()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(draw(3), draw(3));
+        assert_ne!(draw(3), draw(4));
+        assert_eq!(DeterministicRng::new(3).seed(), 3);
+    }
+
+    /// Derived seeds are stable, and differ across master seeds, purposes
+    /// and indices.
+    #[test]
+    fn test_derive_seed() {
+        let seeds: std::collections::BTreeSet<_> = (0..2)
+            .flat_map(|master| {
+                ["a", "b"].into_iter().flat_map(move |purpose| {
+                    (0..4).map(move |index| derive_seed(master, purpose, index))
+                })
+            })
+            .collect();
+
+        assert_eq!(seeds.len(), 16);
+        assert_eq!(derive_seed(7, "a", 3), derive_seed(7, "a", 3));
+        assert_eq!(
+            DeterministicRng::derived(7, "a", 3).seed(),
+            derive_seed(7, "a", 3)
+        );
+    }
+
+    /// Clones continue one shared stream instead of restarting it.
+    #[test]
+    fn test_clones_share_stream() {
+        let mut first = DeterministicRng::new(9);
+        let mut second = first.clone();
+        let mut reference = StdRng::seed_from_u64(9);
+
+        assert_eq!(first.next_u64(), reference.next_u64());
+        assert_eq!(second.next_u64(), reference.next_u64());
+        assert_eq!(first.next_u64(), reference.next_u64());
+    }
+
+    /// Under the deterministic runtime the stream follows from the runtime
+    /// seed alone.
+    #[test]
+    fn test_from_runtime_follows_runtime_seed() {
+        let seed_for = |runtime_seed| {
+            DeterministicRunner::new(Config::default().with_seed(runtime_seed)).start(
+                |mut context| async move { DeterministicRng::from_runtime(&mut context).seed() },
+            )
+        };
+
+        assert_eq!(seed_for(1), seed_for(1));
+        assert_ne!(seed_for(1), seed_for(2));
+    }
+}
@@ -0,0 +1,299 @@
+//! Request/response calls between simulated nodes.
+//!
+//! A [`SimNode`] only sends and receives messages, so a node that wants to
+//! ask another for something has to number its requests, match responses
+//! to them, wait with a timeout and resend. An [`RpcNode`] does that
+//! bookkeeping: [`call`](RpcNode::call) sends a request and resolves to its
+//! response, resending after each timeout with a [`Backoff`] between
+//! attempts, and [`next_request`](RpcNode::next_request) and
+//! [`respond`](RpcNode::respond) are the other side.
+//!
+//! Every attempt of a call carries the call's request id, so a response to
+//! any of them answers it, and a response that arrives after its call gave
+//! up is recognised as stale and dropped. Timeouts and backoff are waited
+//! out on the node's own clock, skewed or not, so under the deterministic
+//! runtime a seed fixes which messages are lost, which attempts time out and
+//! when each retry goes out.
+//!
+//! One task drives each node. Requests that arrive while that task is
+//! waiting on a call of its own are queued for its next
+//! [`next_request`](RpcNode::next_request).
+
+use std::{collections::VecDeque, fmt, time::Duration};
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{
+    parallel_determinism::types::Value,
+    retry::Backoff,
+    rng::DeterministicRng,
+    simnet::{NodeId, SimNet, SimNode},
+    trace::EventLog,
+};
+
+/// What travels over the network for an [`RpcNode`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcMessage<Req, Resp> {
+    Request { id: u64, body: Req },
+    Response { id: u64, body: Resp },
+}
+
+/// A request waiting to be answered with [`RpcNode::respond`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Incoming<Req> {
+    pub from: NodeId,
+    pub id: u64,
+    pub body: Req,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcError {
+    /// Every attempt timed out without a response.
+    TimedOut {
+        to: NodeId,
+        id: u64,
+        attempts: usize,
+    },
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TimedOut { to, id, attempts } => write!(
+                f,
+                "call {} to node {} timed out after {} attempts",
+                id, to, attempts
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+pub struct RpcNode<C, Req, Resp> {
+    node: SimNode<C, RpcMessage<Req, Resp>>,
+    next_id: u64,
+    timeout: Duration,
+    backoff: Backoff,
+    requests: VecDeque<Incoming<Req>>,
+}
+
+impl<C, Req, Resp> RpcNode<C, Req, Resp>
+where
+    C: Spawner + Clock,
+    Req: Clone + Send + 'static,
+    Resp: Send + 'static,
+{
+    /// Calls through `node` time out after 50 ms and are tried three times,
+    /// 10 ms apart and then 20.
+    pub fn new(node: SimNode<C, RpcMessage<Req, Resp>>) -> Self {
+        Self {
+            node,
+            next_id: 0,
+            timeout: Duration::from_millis(50),
+            backoff: Backoff::new(Duration::from_millis(10), 3),
+            requests: VecDeque::new(),
+        }
+    }
+
+    /// How long each attempt waits for a response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many attempts a call makes and how long it waits between them.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.node.id()
+    }
+
+    /// Send `body` to node `to` and wait for the response, resending after
+    /// each timeout until the backoff runs out of attempts.
+    pub async fn call(&mut self, to: NodeId, body: Req) -> Result<Resp, RpcError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        for attempt in 1..=self.backoff.max_attempts() {
+            if attempt > 1 {
+                self.node.sleep(self.backoff.delay(attempt - 1)).await;
+                self.node
+                    .record(format!("retrying call {}, attempt {}", id, attempt));
+            }
+            self.node.send(
+                to,
+                RpcMessage::Request {
+                    id,
+                    body: body.clone(),
+                },
+            );
+            if let Some(response) = self.await_response(id).await {
+                return Ok(response);
+            }
+            self.node
+                .record(format!("call {} attempt {} timed out", id, attempt));
+        }
+        Err(RpcError::TimedOut {
+            to,
+            id,
+            attempts: self.backoff.max_attempts(),
+        })
+    }
+
+    /// Wait for the next request to this node.
+    pub async fn next_request(&mut self) -> Incoming<Req> {
+        loop {
+            if let Some(request) = self.requests.pop_front() {
+                return request;
+            }
+            let envelope = self.node.recv().await;
+            self.dispatch(envelope.from, envelope.message, None);
+        }
+    }
+
+    /// Answer `request`.
+    pub fn respond(&mut self, request: &Incoming<Req>, body: Resp) {
+        self.node.send(
+            request.from,
+            RpcMessage::Response {
+                id: request.id,
+                body,
+            },
+        );
+    }
+
+    /// Receive until the response to call `id` arrives or the attempt times
+    /// out, queueing requests and dropping stale responses on the way.
+    async fn await_response(&mut self, id: u64) -> Option<Resp> {
+        let start = self.node.local_time();
+        loop {
+            let remaining = self.timeout.saturating_sub(self.node.local_time() - start);
+            let envelope = self.node.recv_timeout(remaining).await?;
+            if let Some(response) = self.dispatch(envelope.from, envelope.message, Some(id)) {
+                return Some(response);
+            }
+        }
+    }
+
+    /// Queue a request, or return a response if it answers call `awaiting`.
+    fn dispatch(
+        &mut self,
+        from: NodeId,
+        message: RpcMessage<Req, Resp>,
+        awaiting: Option<u64>,
+    ) -> Option<Resp> {
+        match message {
+            RpcMessage::Request { id, body } => {
+                self.requests.push_back(Incoming { from, id, body });
+                None
+            }
+            RpcMessage::Response { id, body } if awaiting == Some(id) => Some(body),
+            RpcMessage::Response { id, .. } => {
+                self.node
+                    .record(format!("dropped stale response to call {}", id));
+                None
+            }
+        }
+    }
+}
+
+/// Node 0 asks node 1 to double each of `values` over a network that loses
+/// messages with probability `loss`, and returns what each call came back
+/// with. Doubling is idempotent, so a request the server answers twice
+/// because the first response was lost does no harm.
+pub async fn lossy_calls<C: Spawner + Clock>(
+    context: &C,
+    values: &[Value],
+    loss: f64,
+    seed: u64,
+    log: &EventLog,
+) -> Vec<Result<Value, RpcError>> {
+    let net = SimNet::new(
+        context.clone(),
+        2,
+        DeterministicRng::derived(seed, "rpc-network", 0),
+        log.clone(),
+    )
+    .with_loss(loss);
+    let mut client = RpcNode::new(net.node(0));
+    let mut server: RpcNode<C, Value, Value> = RpcNode::new(net.node(1));
+
+    let serving = context.clone().spawn(move |_| async move {
+        loop {
+            let request = server.next_request().await;
+            server.respond(&request, request.body * 2);
+        }
+    });
+    let mut results = Vec::with_capacity(values.len());
+    for &value in values {
+        results.push(client.call(1, value).await);
+    }
+    serving.abort();
+    results
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    fn run(loss: f64, seed: u64) -> (Vec<Result<Value, RpcError>>, Vec<String>) {
+        let log = EventLog::new();
+        let results =
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+                let log = log.clone();
+                async move { lossy_calls(&context, &[1, 2, 3, 4, 5, 6], loss, seed, &log).await }
+            });
+        (
+            results,
+            log.events().iter().map(|event| event.to_string()).collect(),
+        )
+    }
+
+    /// Without loss every call succeeds on its first attempt.
+    #[test]
+    fn test_lossless_calls() {
+        let (results, events) = run(0.0, 0);
+
+        assert_eq!(results, [Ok(2), Ok(4), Ok(6), Ok(8), Ok(10), Ok(12)]);
+        assert!(!events.iter().any(|event| event.contains("retrying")));
+    }
+
+    /// Loss costs retries, and the same seed loses the same messages and
+    /// retries at the same instants.
+    #[test]
+    fn test_lossy_calls_replay() {
+        let (results, events) = run(0.3, 4);
+
+        assert!(events.iter().any(|event| event.contains("lost")));
+        assert!(events.iter().any(|event| event.contains("retrying")));
+        for (result, value) in results.iter().zip(1..) {
+            if let Ok(response) = result {
+                assert_eq!(*response, value * 2);
+            }
+        }
+        assert_eq!(run(0.3, 4), (results, events));
+    }
+
+    /// When nothing gets through, every call gives up after its last attempt.
+    #[test]
+    fn test_total_loss_times_out() {
+        let (results, _) = run(1.0, 0);
+
+        assert!(results.iter().enumerate().all(|(id, result)| {
+            *result
+                == Err(RpcError::TimedOut {
+                    to: 1,
+                    id: id as u64,
+                    attempts: 3,
+                })
+        }));
+    }
+}
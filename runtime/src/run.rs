@@ -0,0 +1,305 @@
+//! One place to configure a deterministic run.
+//!
+//! Commonware's `Config` covers the runtime itself; everything this crate
+//! layers on top, the trace, live sinks for it, the nondeterminism audit and
+//! a way to watch a run one event at a time, used to be wired up by hand in
+//! each caller. [`DeterministicRun::builder`] collects those options next to
+//! the seed, and [`DeterministicRun::run`] hands the workload its context,
+//! log and audit and returns the output with what was recorded:
+//!
+//! ```text
+//! let run = DeterministicRun::builder()
+//!     .with_seed(7)
+//!     .with_sink(|event| println!("{}", event))
+//!     .with_audit(AuditMode::ForbidWallClock)
+//!     .build();
+//! let outcome = run.run(|context, log, audit| workload(context, log, audit))?;
+//! ```
+//!
+//! A built run can be started any number of times; each start gets a fresh
+//! log and audit, and the same seed gives the same events.
+
+use std::{
+    future::Future,
+    sync::{Mutex, mpsc::Receiver},
+    thread,
+    time::Duration,
+};
+
+use commonware_runtime::{
+    Runner,
+    deterministic::{Config, Context, Runner as DeterministicRunner},
+};
+
+use crate::{
+    audit::{Finding, NondeterminismAudit, WallClockUsed},
+    hooks::Hooks,
+    trace::{EventLog, TraceEvent},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuditMode {
+    /// The audit's shims pass straight through and record nothing.
+    #[default]
+    Off,
+    /// Record every nondeterminism source the workload reaches.
+    Record,
+    /// Record, and fail the run if any task used real time.
+    ForbidWallClock,
+}
+
+/// How fast the run proceeds from one recorded event to the next.
+#[derive(Debug, Default)]
+pub enum StepMode {
+    /// As fast as it can.
+    #[default]
+    Free,
+    /// Pause for this long in real time after every event, slow enough to
+    /// watch. Virtual time is unaffected.
+    Paced(Duration),
+    /// Pause after every event until a step arrives on the receiver. Once
+    /// the sender is dropped the run continues freely.
+    Gated(Receiver<()>),
+}
+
+pub struct DeterministicRunBuilder {
+    seed: u64,
+    record: bool,
+    sinks: Hooks<TraceEvent>,
+    audit: AuditMode,
+    step: StepMode,
+}
+
+impl DeterministicRunBuilder {
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Whether the log keeps the events it records. Sinks see them either
+    /// way.
+    pub fn with_recording(mut self, record: bool) -> Self {
+        self.record = record;
+        self
+    }
+
+    /// Call `sink` on every event as it is recorded, in log order.
+    pub fn with_sink(mut self, sink: impl Fn(&TraceEvent) + Send + Sync + 'static) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    pub fn with_audit(mut self, audit: AuditMode) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn with_step_mode(mut self, step: StepMode) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn build(self) -> DeterministicRun {
+        let mut sinks = self.sinks;
+        match self.step {
+            StepMode::Free => {}
+            StepMode::Paced(pause) => sinks.push(move |_| thread::sleep(pause)),
+            StepMode::Gated(steps) => {
+                let steps = Mutex::new(steps);
+                sinks.push(move |_| {
+                    let _ = steps.lock().unwrap().recv();
+                });
+            }
+        }
+        DeterministicRun {
+            seed: self.seed,
+            record: self.record,
+            sinks,
+            audit: self.audit,
+        }
+    }
+}
+
+/// What a run returned and recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunOutput<T> {
+    pub output: T,
+    /// Empty when recording is off.
+    pub events: Vec<TraceEvent>,
+    /// Empty when the audit is off.
+    pub findings: Vec<Finding>,
+}
+
+pub struct DeterministicRun {
+    seed: u64,
+    record: bool,
+    sinks: Hooks<TraceEvent>,
+    audit: AuditMode,
+}
+
+impl DeterministicRun {
+    /// Seed 0, recording on, no sinks, no audit, free running.
+    pub fn builder() -> DeterministicRunBuilder {
+        DeterministicRunBuilder {
+            seed: 0,
+            record: true,
+            sinks: Hooks::new(),
+            audit: AuditMode::Off,
+            step: StepMode::Free,
+        }
+    }
+
+    /// The Commonware configuration the run starts with.
+    pub fn config(&self) -> Config {
+        Config::default().with_seed(self.seed)
+    }
+
+    /// Run `workload` to completion. Fails only under
+    /// [`AuditMode::ForbidWallClock`].
+    pub fn run<F, Fut>(&self, workload: F) -> Result<RunOutput<Fut::Output>, WallClockUsed>
+    where
+        F: FnOnce(Context, EventLog, NondeterminismAudit) -> Fut,
+        Fut: Future,
+    {
+        let log = if self.record {
+            EventLog::new()
+        } else {
+            EventLog::discarding()
+        };
+        let sinks = self.sinks.clone();
+        let log = log.with_hook(move |event| sinks.call(event));
+        let audit = match self.audit {
+            AuditMode::Off => NondeterminismAudit::disabled(),
+            AuditMode::Record | AuditMode::ForbidWallClock => NondeterminismAudit::enabled(),
+        };
+
+        let output = DeterministicRunner::new(self.config())
+            .start(|context| workload(context, log.clone(), audit.clone()));
+
+        let findings = audit.findings();
+        if self.audit == AuditMode::ForbidWallClock {
+            let wall_clock: Vec<_> = findings
+                .iter()
+                .filter(|finding| finding.source.is_wall_clock())
+                .cloned()
+                .collect();
+            if !wall_clock.is_empty() {
+                return Err(WallClockUsed {
+                    findings: wall_clock,
+                });
+            }
+        }
+        Ok(RunOutput {
+            output,
+            events: log.events(),
+            findings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, mpsc};
+
+    use commonware_runtime::{Clock, Spawner};
+
+    use super::*;
+    use crate::audit::Source;
+
+    /// Two tasks recording three events each.
+    async fn workload(context: Context, log: EventLog, audit: NondeterminismAudit) -> usize {
+        let handles: Vec<_> = (0..2)
+            .map(|task| {
+                let (log, audit) = (log.clone(), audit.for_task(task));
+                context.clone().spawn(move |context| async move {
+                    for step in 0..3 {
+                        context.sleep(Duration::from_millis(task as u64 + 1)).await;
+                        log.record(format!("task {}", task), format!("step {}", step));
+                    }
+                    audit.os_rng();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.expect("Task should run to completion");
+        }
+        log.len()
+    }
+
+    /// The same built run repeats its events exactly, and sinks see them
+    /// even with recording off.
+    #[test]
+    fn test_run_repeats_and_feeds_sinks() {
+        let run = DeterministicRun::builder().with_seed(3).build();
+        let first = run.run(workload).unwrap();
+        assert_eq!(first.output, 6);
+        assert_eq!(first.events, run.run(workload).unwrap().events);
+        assert!(first.findings.is_empty());
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let quiet = DeterministicRun::builder()
+            .with_seed(3)
+            .with_recording(false)
+            .with_sink({
+                let seen = seen.clone();
+                move |event| seen.lock().unwrap().push(event.clone())
+            })
+            .build()
+            .run(workload)
+            .unwrap();
+        assert_eq!(quiet.output, 0);
+        assert!(quiet.events.is_empty());
+        assert_eq!(*seen.lock().unwrap(), first.events);
+    }
+
+    /// Recording reports every source; forbidding wall-clock time fails
+    /// only when a task used it.
+    #[test]
+    fn test_audit_modes() {
+        let recorded = DeterministicRun::builder()
+            .with_audit(AuditMode::Record)
+            .build()
+            .run(workload)
+            .unwrap();
+        assert_eq!(recorded.findings.len(), 2);
+        assert!(
+            recorded
+                .findings
+                .iter()
+                .all(|finding| finding.source == Source::OsRng)
+        );
+
+        let forbid = DeterministicRun::builder()
+            .with_audit(AuditMode::ForbidWallClock)
+            .build();
+        assert!(forbid.run(workload).is_ok());
+        let error = forbid
+            .run(|_, _, audit| async move {
+                audit.for_task(0).wall_clock();
+            })
+            .unwrap_err();
+        assert_eq!(error.findings.len(), 1);
+    }
+
+    /// A gated run stops after each event until it is given a step.
+    #[test]
+    fn test_gated_steps() {
+        let (steps, gate) = mpsc::channel();
+        let (sink, events) = mpsc::channel();
+        let run = DeterministicRun::builder()
+            .with_sink(move |event| {
+                let _ = sink.send(event.clone());
+            })
+            .with_step_mode(StepMode::Gated(gate))
+            .build();
+        let runner = thread::spawn(move || run.run(workload).unwrap().output);
+
+        let first = events.recv().unwrap();
+        assert!(events.recv_timeout(Duration::from_millis(50)).is_err());
+        steps.send(()).unwrap();
+        let second = events.recv().unwrap();
+        assert_ne!(first, second);
+        drop(steps);
+        assert_eq!(runner.join().unwrap(), 6);
+    }
+}
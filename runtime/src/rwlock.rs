@@ -0,0 +1,291 @@
+//! A read-write lock that records who got it, and when.
+//!
+//! In the word-selection demos the two tasks interact only through a shared
+//! `RwLock`, so the order in which it was granted *is* the interleaving.
+//! Until now that order could only be inferred from prints. A
+//! [`TracedRwLock`] wraps Tokio's lock and records every acquisition and
+//! release with the task that made it and the time on the runtime's clock,
+//! virtual under the deterministic runtime. [`TracedRwLock::events`] is then
+//! something a test can compare between runs or seeds.
+//!
+//! Acquisitions are recorded once the lock is granted, releases just before
+//! the guard lets go, so the recorded order is the order the lock was
+//! actually held in.
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use commonware_runtime::Clock;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{spawn::TaskInfo, trace::EventLog};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockAction {
+    Acquired,
+    Released,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockEvent {
+    pub task: TaskInfo,
+    pub access: Access,
+    pub action: LockAction,
+    /// Time since the lock was created.
+    pub at: Duration,
+}
+
+impl fmt::Display for LockEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match self.access {
+            Access::Read => "read",
+            Access::Write => "write",
+        };
+        let action = match self.action {
+            LockAction::Acquired => "acquired",
+            LockAction::Released => "released",
+        };
+        write!(f, "{} {} at {:?}", access, action, self.at)
+    }
+}
+
+type Now = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+pub struct TracedRwLock<T> {
+    inner: RwLock<T>,
+    now: Now,
+    origin: SystemTime,
+    events: Mutex<Vec<LockEvent>>,
+    log: Option<EventLog>,
+}
+
+impl<T> TracedRwLock<T> {
+    /// A lock timing its events on `clock`.
+    pub fn new(value: T, clock: impl Clock) -> Self {
+        Self::with_now(value, Arc::new(move || clock.current()))
+    }
+
+    /// A lock timing its events on the wall clock, for code running on a
+    /// plain Tokio runtime with no Commonware context.
+    pub fn wall_clock(value: T) -> Self {
+        Self::with_now(value, Arc::new(SystemTime::now))
+    }
+
+    fn with_now(value: T, now: Now) -> Self {
+        Self {
+            inner: RwLock::new(value),
+            origin: now(),
+            now,
+            events: Mutex::new(Vec::new()),
+            log: None,
+        }
+    }
+
+    /// Also record every event into `log`, under the task's label.
+    pub fn with_log(mut self, log: EventLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    pub async fn read(&self, task: &TaskInfo) -> TracedReadGuard<'_, T> {
+        let guard = self.inner.read().await;
+        self.record(task, Access::Read, LockAction::Acquired);
+        TracedReadGuard {
+            guard,
+            lock: self,
+            task: task.clone(),
+        }
+    }
+
+    pub async fn write(&self, task: &TaskInfo) -> TracedWriteGuard<'_, T> {
+        let guard = self.inner.write().await;
+        self.record(task, Access::Write, LockAction::Acquired);
+        TracedWriteGuard {
+            guard,
+            lock: self,
+            task: task.clone(),
+        }
+    }
+
+    /// Every acquisition and release so far, in the order they happened.
+    pub fn events(&self) -> Vec<LockEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Who was granted the lock, in order, and for what.
+    pub fn grant_order(&self) -> Vec<(String, Access)> {
+        self.events()
+            .into_iter()
+            .filter(|event| event.action == LockAction::Acquired)
+            .map(|event| (event.task.label(), event.access))
+            .collect()
+    }
+
+    fn record(&self, task: &TaskInfo, access: Access, action: LockAction) {
+        let event = LockEvent {
+            task: task.clone(),
+            access,
+            action,
+            at: (self.now)()
+                .duration_since(self.origin)
+                .unwrap_or(Duration::ZERO),
+        };
+        if let Some(log) = &self.log {
+            log.record(task.label(), event.to_string());
+        }
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+pub struct TracedReadGuard<'a, T> {
+    guard: RwLockReadGuard<'a, T>,
+    lock: &'a TracedRwLock<T>,
+    task: TaskInfo,
+}
+
+impl<T> Deref for TracedReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> Drop for TracedReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock
+            .record(&self.task, Access::Read, LockAction::Released);
+    }
+}
+
+pub struct TracedWriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    lock: &'a TracedRwLock<T>,
+    task: TaskInfo,
+}
+
+impl<T> Deref for TracedWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for TracedWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for TracedWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock
+            .record(&self.task, Access::Write, LockAction::Released);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner, Spawner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::spawn::TaskId;
+
+    fn task(id: u64, name: &str) -> TaskInfo {
+        TaskInfo {
+            id: TaskId(id),
+            name: Some(name.into()),
+        }
+    }
+
+    /// A writer holding the lock for 10ms and a reader arriving after 5ms:
+    /// the read is granted only once the write is released, and the times
+    /// are virtual time.
+    #[test]
+    fn test_records_grants_and_releases() {
+        let events =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let lock = Arc::new(TracedRwLock::new(0, context.clone()));
+                let writer = context.clone().spawn({
+                    let lock = lock.clone();
+                    move |context| async move {
+                        let mut value = lock.write(&task(0, "writer")).await;
+                        context.sleep(Duration::from_millis(10)).await;
+                        *value += 1;
+                    }
+                });
+                let reader = context.clone().spawn({
+                    let lock = lock.clone();
+                    move |context| async move {
+                        context.sleep(Duration::from_millis(5)).await;
+                        *lock.read(&task(1, "reader")).await
+                    }
+                });
+                writer.await.unwrap();
+                assert_eq!(reader.await.unwrap(), 1);
+                lock.events()
+            });
+
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| {
+                format!(
+                    "{}: {:?} {:?}",
+                    event.task.label(),
+                    event.access,
+                    event.action
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "writer: Write Acquired",
+                "writer: Write Released",
+                "reader: Read Acquired",
+                "reader: Read Released",
+            ]
+        );
+        assert!(events[1].at >= events[0].at + Duration::from_millis(10));
+        assert!(events[2].at >= events[1].at);
+    }
+
+    /// Events also go to the log, and the grant order lists acquisitions
+    /// only.
+    #[test]
+    fn test_log_and_grant_order() {
+        let log = EventLog::new();
+        let lock = TracedRwLock::wall_clock(Vec::new()).with_log(log.clone());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            lock.write(&task(0, "a")).await.push(1);
+            assert_eq!(lock.read(&task(1, "b")).await.len(), 1);
+        });
+
+        assert_eq!(
+            lock.grant_order(),
+            [
+                ("a".to_string(), Access::Write),
+                ("b".to_string(), Access::Read)
+            ]
+        );
+        assert_eq!(log.len(), 4);
+        assert_eq!(log.events()[1].task, "a");
+    }
+}
@@ -0,0 +1,365 @@
+//! Experiments described as data.
+//!
+//! Built with the `scenario` feature. A [`Scenario`] names a runtime and a
+//! list of workloads, each with its own parameters and a start time in
+//! virtual (or, on Tokio, wall-clock) milliseconds. [`run`] starts the
+//! runtime, launches every workload at its start time, and reports when each
+//! started and finished, a one-line summary of its result and the events it
+//! recorded. A combination of workloads that would otherwise need a new Rust
+//! function becomes a JSON file:
+//!
+//! ```json
+//! {
+//!   "name": "walks under pressure",
+//!   "runtime": { "kind": "deterministic", "seed": 7 },
+//!   "workloads": [
+//!     { "name": "walkers", "kind": "random_walk", "walkers": 4, "steps": 20 },
+//!     { "name": "queue", "start_ms": 5, "kind": "work_queue",
+//!       "jobs": 30, "consumers": 3, "max_cost_ms": 4 }
+//!   ]
+//! }
+//! ```
+//!
+//! Every workload gets its own [`EventLog`], and any randomness it needs is
+//! seeded from the runtime's RNG, so a deterministic scenario repeats exactly
+//! with its seed.
+
+use std::{fmt, fs, io, path::Path, sync::Arc, time::Duration};
+
+use commonware_runtime::{
+    Clock, Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+use rand_core::RngCore;
+use serde::Deserialize;
+
+use crate::{
+    backend::Backend,
+    backpressure::backpressure,
+    faults::{FaultInjector, flaky_operations},
+    memory::{MemoryPressure, memory_pressure},
+    merkle::merkle_root,
+    queue::{jobs, work_queue},
+    retry::Backoff,
+    rng::DeterministicRng,
+    shadow::{SleepTasks, Workload},
+    stats::elapsed_since,
+    tasks::read_file,
+    trace::{EventLog, TraceEvent},
+    walk::{StateGraph, random_walks},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub runtime: Backend,
+    pub workloads: Vec<ScenarioWorkload>,
+}
+
+/// One workload of a scenario and when it starts.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ScenarioWorkload {
+    pub name: String,
+    #[serde(default)]
+    pub start_ms: u64,
+    #[serde(flatten)]
+    pub workload: WorkloadSpec,
+}
+
+/// The workloads a scenario can run, with their parameters.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorkloadSpec {
+    /// [`SleepTasks`].
+    SleepTasks,
+    /// [`random_walks`] over [`StateGraph::job_lifecycle`].
+    RandomWalk { walkers: usize, steps: usize },
+    /// [`work_queue`] over seeded [`jobs`].
+    WorkQueue {
+        jobs: usize,
+        consumers: usize,
+        max_cost_ms: u64,
+    },
+    /// [`backpressure`].
+    Backpressure {
+        capacity: usize,
+        items: usize,
+        produce_every_ms: u64,
+        consume_every_ms: u64,
+    },
+    /// [`merkle_root`] over the bundled corpus.
+    Merkle { chunk_words: usize },
+    /// [`flaky_operations`] with a doubling backoff from one millisecond.
+    FlakyOperations {
+        operations: usize,
+        failure_rate: f64,
+        max_attempts: usize,
+    },
+    /// [`memory_pressure`] with a one-millisecond period.
+    MemoryPressure {
+        allocators: usize,
+        bystanders: usize,
+        steps: usize,
+        max_bytes: usize,
+    },
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_json(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// What one workload of a scenario did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkloadReport {
+    pub name: String,
+    /// When it started and finished, measured from the start of the run.
+    pub started: Duration,
+    pub finished: Duration,
+    /// Its result in one line.
+    pub summary: String,
+    pub events: Vec<TraceEvent>,
+}
+
+impl fmt::Display for WorkloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{:?} .. {:?}]: {} ({} events)",
+            self.name,
+            self.started,
+            self.finished,
+            self.summary,
+            self.events.len()
+        )
+    }
+}
+
+impl WorkloadSpec {
+    async fn run<S: Spawner + Clock + RngCore>(&self, mut context: S, log: &EventLog) -> String {
+        match *self {
+            WorkloadSpec::SleepTasks => {
+                SleepTasks.run(context, log.clone()).await;
+                "done".to_string()
+            }
+            WorkloadSpec::RandomWalk { walkers, steps } => {
+                let graph = Arc::new(StateGraph::job_lifecycle());
+                let paths = random_walks(&context, graph.clone(), walkers, steps, log).await;
+                let ends: Vec<_> = paths
+                    .iter()
+                    .map(|path| graph.name(*path.last().unwrap_or(&0)))
+                    .collect();
+                format!("walkers ended in {}", ends.join(", "))
+            }
+            WorkloadSpec::WorkQueue {
+                jobs: count,
+                consumers,
+                max_cost_ms,
+            } => {
+                let mut rng = DeterministicRng::from_runtime(&mut context);
+                let jobs = jobs(count, max_cost_ms, &mut rng);
+                let assignment = work_queue(&context, jobs, consumers, log).await;
+                let per_consumer: Vec<_> = (0..consumers)
+                    .map(|consumer| {
+                        assignment
+                            .iter()
+                            .filter(|&&taken| taken == consumer)
+                            .count()
+                            .to_string()
+                    })
+                    .collect();
+                format!("jobs per consumer {}", per_consumer.join("/"))
+            }
+            WorkloadSpec::Backpressure {
+                capacity,
+                items,
+                produce_every_ms,
+                consume_every_ms,
+            } => {
+                let stalls = backpressure(
+                    &context,
+                    capacity,
+                    items,
+                    Duration::from_millis(produce_every_ms),
+                    Duration::from_millis(consume_every_ms),
+                    log,
+                )
+                .await;
+                format!("producer stalled {} times", stalls.len())
+            }
+            WorkloadSpec::Merkle { chunk_words } => {
+                let root = merkle_root(&context, Arc::new(read_file()), chunk_words).await;
+                format!("root {:016x}", root)
+            }
+            WorkloadSpec::FlakyOperations {
+                operations,
+                failure_rate,
+                max_attempts,
+            } => {
+                let injector =
+                    FaultInjector::new(DeterministicRng::from_runtime(&mut context), failure_rate);
+                let backoff = Backoff::new(Duration::from_millis(1), max_attempts);
+                let outcomes = flaky_operations(
+                    &context,
+                    operations,
+                    Duration::from_millis(1),
+                    injector,
+                    backoff,
+                    log,
+                )
+                .await;
+                let failed = outcomes.iter().filter(|outcome| outcome.is_err()).count();
+                format!("{} of {} operations gave up", failed, operations)
+            }
+            WorkloadSpec::MemoryPressure {
+                allocators,
+                bystanders,
+                steps,
+                max_bytes,
+            } => {
+                let spec = MemoryPressure {
+                    allocators,
+                    bystanders,
+                    steps,
+                    max_bytes,
+                    period: Duration::from_millis(1),
+                };
+                let rng = DeterministicRng::from_runtime(&mut context);
+                let report = memory_pressure(&context, spec, rng).await;
+                format!("peak {} bytes", report.peak_bytes)
+            }
+        }
+    }
+}
+
+async fn run_on<S: Spawner + Clock + RngCore>(
+    context: S,
+    workloads: Vec<ScenarioWorkload>,
+) -> Vec<WorkloadReport> {
+    let start = context.current();
+    let handles: Vec<_> = workloads
+        .into_iter()
+        .map(|entry| {
+            context.clone().spawn(move |context| async move {
+                context.sleep(Duration::from_millis(entry.start_ms)).await;
+                let log = EventLog::new();
+                let started = elapsed_since(&context, start);
+                let summary = entry.workload.run(context.clone(), &log).await;
+                WorkloadReport {
+                    name: entry.name,
+                    started,
+                    finished: elapsed_since(&context, start),
+                    summary,
+                    events: log.events(),
+                }
+            })
+        })
+        .collect();
+
+    let mut reports = Vec::with_capacity(handles.len());
+    for handle in handles {
+        reports.push(
+            handle
+                .await
+                .expect("Scenario workload should run to completion"),
+        );
+    }
+    reports
+}
+
+/// Run `scenario` to completion, returning one report per workload in the
+/// order the scenario lists them.
+pub fn run(scenario: &Scenario) -> Vec<WorkloadReport> {
+    let workloads = scenario.workloads.clone();
+    match scenario.runtime {
+        Backend::Deterministic { seed } => {
+            DeterministicRunner::new(Config::default().with_seed(seed))
+                .start(|context| run_on(context, workloads))
+        }
+        Backend::Tokio { worker_threads } => {
+            TokioRunner::new(TokioConfig::default().with_worker_threads(worker_threads))
+                .start(|context| run_on(context, workloads))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED: &str = include_str!("../scenarios/mixed.json");
+
+    /// The bundled scenario parses into every kind of workload it lists.
+    #[test]
+    fn test_parse_bundled_scenario() {
+        let scenario = Scenario::from_json(MIXED).unwrap();
+
+        assert_eq!(scenario.runtime, Backend::Deterministic { seed: 7 });
+        assert_eq!(scenario.workloads.len(), 7);
+        assert_eq!(
+            scenario.workloads[1],
+            ScenarioWorkload {
+                name: "walkers".into(),
+                start_ms: 0,
+                workload: WorkloadSpec::RandomWalk {
+                    walkers: 4,
+                    steps: 20
+                },
+            }
+        );
+    }
+
+    /// Workloads start no earlier than asked, and a deterministic scenario
+    /// repeats exactly.
+    #[test]
+    fn test_run_deterministic_scenario() {
+        let scenario = Scenario::from_json(MIXED).unwrap();
+        let reports = run(&scenario);
+
+        assert_eq!(reports.len(), scenario.workloads.len());
+        for (report, entry) in reports.iter().zip(&scenario.workloads) {
+            assert_eq!(report.name, entry.name);
+            assert!(report.started >= Duration::from_millis(entry.start_ms));
+            assert!(report.finished >= report.started);
+        }
+        assert_eq!(run(&scenario), reports);
+    }
+
+    /// The same workloads run on Tokio when the scenario asks for it.
+    #[test]
+    fn test_run_tokio_scenario() {
+        let scenario = Scenario::from_json(
+            r#"{
+                "name": "tokio",
+                "runtime": { "kind": "tokio", "worker_threads": 2 },
+                "workloads": [
+                    { "name": "sleepers", "kind": "sleep_tasks" },
+                    { "name": "root", "start_ms": 1, "kind": "merkle", "chunk_words": 64 }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let reports = run(&scenario);
+
+        assert_eq!(reports[0].events.len(), 6);
+        assert!(reports[1].summary.starts_with("root "));
+    }
+
+    /// An unknown workload kind is a parse error, not a silent no-op.
+    #[test]
+    fn test_unknown_workload_rejected() {
+        let json = r#"{
+            "name": "bad",
+            "runtime": { "kind": "deterministic", "seed": 0 },
+            "workloads": [{ "name": "x", "kind": "teleport" }]
+        }"#;
+
+        assert!(Scenario::from_json(json).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! The empirical distribution of schedules a demo produces.
+//!
+//! "Tokio ordering is not guaranteed" is easy to say and easy to doubt after a
+//! few runs that happen to look the same. This module runs a demo many times,
+//! fingerprints each run's [`EventLog`], and counts how often each distinct
+//! interleaving shows up: how many schedules exist in practice, and how
+//! heavily the most common one dominates.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    commonware_runtime_tasks_traced, tokio_tasks_traced,
+    trace::{EventLog, TraceEvent},
+};
+
+/// One distinct interleaving and how often it was observed.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    pub fingerprint: u64,
+    pub count: usize,
+    /// The events of the first run that produced this schedule.
+    pub example: Vec<TraceEvent>,
+}
+
+pub struct ScheduleDistribution {
+    pub runs: usize,
+    /// Most frequent first; ties broken by fingerprint.
+    pub schedules: Vec<Schedule>,
+}
+
+impl ScheduleDistribution {
+    pub fn distinct(&self) -> usize {
+        self.schedules.len()
+    }
+
+    /// Fraction of runs that produced the most common schedule. 1.0 means a
+    /// single schedule was observed; values near `1 / distinct` mean no
+    /// schedule is preferred.
+    pub fn dominant_share(&self) -> f64 {
+        match self.schedules.first() {
+            Some(schedule) if self.runs > 0 => schedule.count as f64 / self.runs as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Shannon entropy of the distribution, in bits. Zero for a single
+    /// schedule; `log2(distinct)` when all are equally likely.
+    pub fn entropy_bits(&self) -> f64 {
+        self.schedules
+            .iter()
+            .map(|schedule| schedule.count as f64 / self.runs as f64)
+            .map(|p| -p * p.log2())
+            .sum()
+    }
+
+    /// A summary line plus a table with one row per schedule and its events.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        writeln!(
+            report,
+            "{} runs, {} distinct schedules, dominant share {:.2}, entropy {:.2} bits",
+            self.runs,
+            self.distinct(),
+            self.dominant_share(),
+            self.entropy_bits()
+        )
+        .unwrap();
+        writeln!(report, "| fingerprint | runs | events |").unwrap();
+        writeln!(report, "|---|---|---|").unwrap();
+        for schedule in &self.schedules {
+            let events: Vec<_> = schedule.example.iter().map(|e| e.to_string()).collect();
+            writeln!(
+                report,
+                "| {:016x} | {} | {} |",
+                schedule.fingerprint,
+                schedule.count,
+                events.join(", ")
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+/// Run `run` `runs` times, each with a fresh log, and tally the schedules.
+pub fn schedule_distribution(runs: usize, mut run: impl FnMut(&EventLog)) -> ScheduleDistribution {
+    let mut seen: BTreeMap<u64, Schedule> = BTreeMap::new();
+    for _ in 0..runs {
+        let log = EventLog::new();
+        run(&log);
+        let fingerprint = log.fingerprint();
+        seen.entry(fingerprint)
+            .or_insert_with(|| Schedule {
+                fingerprint,
+                count: 0,
+                example: log.events(),
+            })
+            .count += 1;
+    }
+
+    let mut schedules: Vec<_> = seen.into_values().collect();
+    schedules.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(a.fingerprint.cmp(&b.fingerprint))
+    });
+    ScheduleDistribution { runs, schedules }
+}
+
+/// The schedules of [`tokio_tasks`](crate::tokio_tasks) over `runs` runs.
+pub fn tokio_tasks_distribution(runs: usize, worker_threads: usize) -> ScheduleDistribution {
+    schedule_distribution(runs, |log| tokio_tasks_traced(worker_threads, log))
+}
+
+/// The same demo on the deterministic runtime with a fixed seed: always
+/// exactly one schedule.
+pub fn commonware_tasks_distribution(runs: usize, seed: u64) -> ScheduleDistribution {
+    schedule_distribution(runs, |log| commonware_runtime_tasks_traced(seed, log))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every run is accounted for, and each observed schedule contains all
+    /// six events of the demo.
+    #[test]
+    fn test_tokio_distribution_accounts_for_every_run() {
+        let distribution = tokio_tasks_distribution(10, 4);
+
+        assert_eq!(distribution.runs, 10);
+        assert_eq!(
+            distribution
+                .schedules
+                .iter()
+                .map(|s| s.count)
+                .sum::<usize>(),
+            10
+        );
+        assert!(distribution.schedules.iter().all(|s| s.example.len() == 6));
+        assert!(distribution.dominant_share() > 0.0);
+        assert_eq!(
+            distribution.report().lines().count(),
+            3 + distribution.distinct()
+        );
+    }
+
+    /// A fixed seed yields a single schedule with zero entropy.
+    #[test]
+    fn test_deterministic_distribution_is_a_point() {
+        let distribution = commonware_tasks_distribution(5, 7);
+
+        assert_eq!(distribution.distinct(), 1);
+        assert_eq!(distribution.dominant_share(), 1.0);
+        assert_eq!(distribution.entropy_bits(), 0.0);
+    }
+}
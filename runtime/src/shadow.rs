@@ -0,0 +1,212 @@
+//! Shadow execution: one workload, two runtimes, compared as it runs.
+//!
+//! Comparing final outputs says whether two runs agreed, not where they
+//! stopped agreeing. [`shadow`] runs the same [`Workload`] on Tokio and on the
+//! deterministic runtime at the same time, each on its own thread, and streams
+//! every event both record into a comparator. The first step at which the two
+//! streams differ is reported as a [`Divergence`], with the event each runtime
+//! produced there; everything before it is known to match. Both runs are
+//! kept whole in the report's [`LogDiff`], which prints them side by side.
+
+use std::{
+    fmt,
+    future::Future,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use commonware_runtime::{
+    Clock, Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+
+use crate::trace::{EventLog, LogDiff, TraceEvent};
+
+/// A workload that can run on any runtime, recording its externally visible
+/// effects into `log`.
+pub trait Workload: Clone + Send + 'static {
+    fn run<S: Spawner + Clock>(self, context: S, log: EventLog) -> impl Future<Output = ()> + Send;
+}
+
+/// The three-task sleep demo from [`tokio_tasks`](crate::tokio_tasks) and
+/// [`commoware_runtime_tasks`](crate::commoware_runtime_tasks), written once.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SleepTasks;
+
+impl Workload for SleepTasks {
+    async fn run<S: Spawner + Clock>(self, context: S, log: EventLog) {
+        let handles: Vec<_> = (1..=3)
+            .map(|task| {
+                let log = log.clone();
+                context.clone().spawn(move |context| async move {
+                    let name = format!("Task {}", task);
+                    log.record(name.as_str(), "Starting");
+                    if task != 3 {
+                        context.sleep(Duration::from_millis(10)).await;
+                    }
+                    log.record(name.as_str(), "Done");
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.expect("Sleep task should run to completion");
+        }
+    }
+}
+
+/// The first step at which the runtimes disagreed. `None` means that runtime
+/// had already finished.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub step: usize,
+    pub tokio: Option<TraceEvent>,
+    pub deterministic: Option<TraceEvent>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |event: &Option<TraceEvent>| match event {
+            Some(event) => event.to_string(),
+            None => "<finished>".to_string(),
+        };
+        write!(
+            f,
+            "step {}: tokio {} vs deterministic {}",
+            self.step,
+            show(&self.tokio),
+            show(&self.deterministic)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShadowReport {
+    /// Steps on which both runtimes produced the same event.
+    pub matched: usize,
+    pub divergence: Option<Divergence>,
+    /// Both complete runs, Tokio on the left, for explaining a divergence.
+    pub diff: LogDiff,
+}
+
+impl ShadowReport {
+    pub fn agreed(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Run `workload` on Tokio with `worker_threads` workers and on the
+/// deterministic runtime with `seed`, concurrently, comparing their events
+/// step by step. Both runs always complete; comparison stops at the first
+/// divergence.
+pub fn shadow<W: Workload>(workload: W, seed: u64, worker_threads: usize) -> ShadowReport {
+    let (tokio_sink, tokio_events) = mpsc::channel();
+    let (deterministic_sink, deterministic_events) = mpsc::channel();
+
+    thread::scope(|scope| {
+        let tokio_workload = workload.clone();
+        scope.spawn(move || {
+            let log = EventLog::streaming(tokio_sink);
+            TokioRunner::new(TokioConfig::default().with_worker_threads(worker_threads))
+                .start(|context| tokio_workload.run(context, log));
+        });
+        scope.spawn(move || {
+            let log = EventLog::streaming(deterministic_sink);
+            DeterministicRunner::new(Config::default().with_seed(seed))
+                .start(|context| workload.run(context, log));
+        });
+
+        compare(tokio_events, deterministic_events)
+    })
+}
+
+fn compare(tokio: Receiver<TraceEvent>, deterministic: Receiver<TraceEvent>) -> ShadowReport {
+    let (mut tokio_events, mut deterministic_events) = (vec![], vec![]);
+    let mut divergence = None;
+    loop {
+        // A closed channel means that runtime finished and dropped its log.
+        let (tokio, deterministic) = (tokio.recv().ok(), deterministic.recv().ok());
+        if tokio.is_none() && deterministic.is_none() {
+            break;
+        }
+        if divergence.is_none() && tokio != deterministic {
+            divergence = Some(Divergence {
+                step: tokio_events.len().min(deterministic_events.len()),
+                tokio: tokio.clone(),
+                deterministic: deterministic.clone(),
+            });
+        }
+        tokio_events.extend(tokio);
+        deterministic_events.extend(deterministic);
+    }
+
+    let diff = LogDiff::new(tokio_events, deterministic_events);
+    ShadowReport {
+        matched: diff.common_prefix,
+        divergence,
+        diff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    /// Records one event, then whether the clock reads as real wall time.
+    #[derive(Clone)]
+    struct ClockProbe;
+
+    impl Workload for ClockProbe {
+        async fn run<S: Spawner + Clock>(self, context: S, log: EventLog) {
+            log.record("probe", "start");
+            let since_epoch = context.current().duration_since(UNIX_EPOCH).unwrap();
+            let year = Duration::from_secs(365 * 24 * 60 * 60);
+            let clock = if since_epoch > year {
+                "wall"
+            } else {
+                "simulated"
+            };
+            log.record("probe", clock);
+        }
+    }
+
+    /// A workload whose effects differ by runtime is caught at the exact step,
+    /// after the steps that agreed, and the diff shows both events there.
+    #[test]
+    fn test_divergence_is_located() {
+        let report = shadow(ClockProbe, 0, 2);
+
+        assert_eq!(report.matched, 1);
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.step, 1);
+        assert_eq!(divergence.tokio.unwrap().message, "wall");
+        assert_eq!(divergence.deterministic.unwrap().message, "simulated");
+        assert_eq!(report.diff.divergence(), Some(1));
+        assert!(
+            report
+                .diff
+                .to_string()
+                .contains(">     1 | probe: wall  | probe: simulated\n")
+        );
+    }
+
+    /// The sleep demo records six events on both runtimes; whatever the
+    /// schedules, the report accounts for all of them or names a divergence.
+    #[test]
+    fn test_sleep_tasks_shadow() {
+        let report = shadow(SleepTasks, 7, 4);
+
+        match report.divergence {
+            None => assert_eq!(report.matched, 6),
+            Some(divergence) => {
+                assert_eq!(divergence.step, report.matched);
+                assert!(report.matched < 6);
+                assert!(divergence.tokio.is_some() && divergence.deterministic.is_some());
+            }
+        }
+    }
+}
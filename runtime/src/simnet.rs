@@ -0,0 +1,781 @@
+//! A simulated network of nodes that talk only by message.
+//!
+//! A [`SimNet`] connects a fixed number of nodes, each driven by its own task
+//! through a [`SimNode`]. A message sent from one node reaches the other's
+//! inbox after a latency drawn from the run's [`DeterministicRng`], so under
+//! the deterministic runtime the same seed delivers the same messages in the
+//! same order at the same virtual instants.
+//!
+//! Everything a node does is stamped with its [`VectorClock`]: events it
+//! records, updates it makes and messages it sends, with the sender's clock
+//! merged into the receiver's on delivery. The log shows the order the
+//! scheduler happened to run things in; the clocks show which of those
+//! orders were forced by messages. [`SimNet::conflicts`] uses them to find
+//! updates to the same key made by nodes that had not heard of each other's.
+//!
+//! A network can also be lossy: [`SimNet::with_loss`] drops each message
+//! with a fixed probability, decided by the same seeded stream as the
+//! latencies. [`SimNet::partition`] cuts the network into groups that
+//! cannot reach each other until [`SimNet::heal`]; a message crossing the
+//! cut is dropped whether it was sent before or after the cut was made.
+//! Nodes can disagree about the time as well; see [`skew`](crate::skew).
+//!
+//! Invariants over the nodes' combined [`SimState`] registered with
+//! [`SimNet::with_invariant`] are checked every time a message is delivered;
+//! see [`oracle`](crate::oracle).
+//!
+//! [`SimNet::snapshot`] captures the network mid-run, including messages
+//! still in flight, and [`SimNet::restore`] builds a network that carries on
+//! from one; see [`snapshot`](crate::snapshot).
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    future::{Future, poll_fn},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, SystemTime},
+};
+
+use commonware_runtime::{Clock, Spawner};
+use rand::Rng;
+
+use crate::{
+    oracle::{Invariant, Violation},
+    parallel_determinism::types::Value,
+    rng::DeterministicRng,
+    skew::Skew,
+    snapshot::{InFlight, Snapshot},
+    stats::elapsed_since,
+    trace::EventLog,
+    vclock::VectorClock,
+};
+
+pub type NodeId = usize;
+
+/// A message in flight, stamped with the sender's clock as of sending.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Envelope<M> {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub clock: VectorClock,
+    pub message: M,
+}
+
+/// Something a node recorded, with its clock just after.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeEvent {
+    pub node: NodeId,
+    pub message: String,
+    pub clock: VectorClock,
+}
+
+/// A write a node made to a key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Update {
+    pub node: NodeId,
+    pub key: String,
+    pub value: Value,
+    pub clock: VectorClock,
+}
+
+/// Two updates to the same key, neither made with knowledge of the other.
+/// `first` is the one made first in the log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub first: Update,
+    pub second: Update,
+}
+
+/// The latest value each node has set for each key.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimState {
+    /// Indexed by node id.
+    pub values: Vec<BTreeMap<String, Value>>,
+}
+
+impl SimState {
+    /// A state in which none of `nodes` nodes has set anything.
+    pub fn new(nodes: usize) -> Self {
+        Self {
+            values: vec![BTreeMap::new(); nodes],
+        }
+    }
+
+    pub fn value(&self, node: NodeId, key: &str) -> Option<Value> {
+        self.values.get(node)?.get(key).copied()
+    }
+
+    /// Every node's value for `key`, for the nodes that have set it.
+    pub fn values_of<'a>(&'a self, key: &'a str) -> impl Iterator<Item = (NodeId, Value)> + 'a {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(move |(node, values)| Some((node, *values.get(key)?)))
+    }
+
+    pub fn apply(&mut self, update: &Update) {
+        self.values[update.node].insert(update.key.clone(), update.value);
+    }
+}
+
+struct Inbox<M> {
+    queue: VecDeque<Envelope<M>>,
+    waker: Option<Waker>,
+}
+
+/// A message sent but not yet delivered.
+struct Pending<M> {
+    envelope: Envelope<M>,
+    arrives_at: SystemTime,
+}
+
+struct Shared<M> {
+    inboxes: Vec<Mutex<Inbox<M>>>,
+    /// Keyed by the order the messages were sent in.
+    in_flight: Mutex<BTreeMap<u64, Pending<M>>>,
+    sent: Mutex<u64>,
+    /// Each node's side of the current partition, if any. Nodes on no side
+    /// reach nobody.
+    partition: Mutex<Option<Vec<Option<usize>>>>,
+    events: Mutex<Vec<NodeEvent>>,
+    updates: Mutex<Vec<Update>>,
+    /// How many events the invariants had been checked through.
+    checked: Mutex<usize>,
+    violations: Mutex<Vec<Violation>>,
+}
+
+/// The network. Clones share it.
+pub struct SimNet<C, M> {
+    context: C,
+    start: SystemTime,
+    rng: DeterministicRng,
+    latency: (Duration, Duration),
+    loss: f64,
+    skews: Vec<Skew>,
+    invariants: Vec<Invariant>,
+    log: EventLog,
+    shared: Arc<Shared<M>>,
+}
+
+impl<C: Clone, M> Clone for SimNet<C, M> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+            start: self.start,
+            rng: self.rng.clone(),
+            latency: self.latency,
+            loss: self.loss,
+            skews: self.skews.clone(),
+            invariants: self.invariants.clone(),
+            log: self.log.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<C: Spawner + Clock, M: Send + 'static> SimNet<C, M> {
+    /// A network of `nodes` nodes with latencies between 1 and 10 ms and
+    /// clocks that agree. Deliveries run as children of `context`'s task, so
+    /// in-flight messages outlive the node that sent them but not the
+    /// network's owner.
+    pub fn new(context: C, nodes: usize, rng: DeterministicRng, log: EventLog) -> Self {
+        Self {
+            start: context.current(),
+            context,
+            rng,
+            latency: (Duration::from_millis(1), Duration::from_millis(10)),
+            loss: 0.0,
+            skews: vec![Skew::default(); nodes],
+            invariants: Vec::new(),
+            log,
+            shared: Arc::new(Shared {
+                inboxes: (0..nodes)
+                    .map(|_| {
+                        Mutex::new(Inbox {
+                            queue: VecDeque::new(),
+                            waker: None,
+                        })
+                    })
+                    .collect(),
+                in_flight: Mutex::new(BTreeMap::new()),
+                sent: Mutex::default(),
+                partition: Mutex::default(),
+                events: Mutex::default(),
+                updates: Mutex::default(),
+                checked: Mutex::default(),
+                violations: Mutex::default(),
+            }),
+        }
+    }
+
+    /// A network that carries on from `snapshot`: the nodes' events,
+    /// updates, clocks and skews are as they were, delivered messages wait
+    /// in their inboxes, and messages in flight arrive as long after now as
+    /// they were due after the snapshot. From here on latencies come from
+    /// `rng` and events go to `log`, which the snapshot's history is not
+    /// copied into.
+    pub fn restore(
+        context: C,
+        snapshot: Snapshot<M>,
+        rng: DeterministicRng,
+        log: EventLog,
+    ) -> Self {
+        let mut net = Self::new(context, snapshot.inboxes.len(), rng, log);
+        net.start = net
+            .start
+            .checked_sub(snapshot.at)
+            .expect("Snapshot time should be representable");
+        net.skews = snapshot.skews;
+        *net.shared.checked.lock().unwrap() = snapshot.events.len();
+        *net.shared.events.lock().unwrap() = snapshot.events;
+        *net.shared.updates.lock().unwrap() = snapshot.updates;
+        for (inbox, queue) in net.shared.inboxes.iter().zip(snapshot.inboxes) {
+            inbox.lock().unwrap().queue = queue.into();
+        }
+        for flight in snapshot.in_flight {
+            net.deliver_after(flight.envelope, flight.arrives_in);
+        }
+        net
+    }
+
+    /// Draw each message's latency from `min..=max` instead.
+    pub fn with_latency(mut self, min: Duration, max: Duration) -> Self {
+        assert!(min <= max, "Minimum latency should not exceed the maximum");
+        self.latency = (min, max);
+        self
+    }
+
+    /// Drop each message with probability `loss`.
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        assert!((0.0..=1.0).contains(&loss), "Loss should be a probability");
+        self.loss = loss;
+        self
+    }
+
+    /// Give every node a clock skew drawn from the seed: an offset of up to
+    /// `max_offset` either way and a drift of up to `max_drift_ppm` either
+    /// way. Each node draws from a stream of its own, so a node's skew does
+    /// not depend on how many nodes there are.
+    pub fn with_skew(mut self, max_offset: Duration, max_drift_ppm: u64) -> Self {
+        let max_offset = max_offset.as_micros() as i64;
+        let max_drift = max_drift_ppm as i64;
+        for (node, skew) in self.skews.iter_mut().enumerate() {
+            let mut rng = DeterministicRng::derived(self.rng.seed(), "clock-skew", node as u64);
+            *skew = Skew::new(
+                rng.random_range(-max_offset..=max_offset),
+                rng.random_range(-max_drift..=max_drift),
+            );
+        }
+        self
+    }
+
+    /// Give node `node` exactly `skew`.
+    pub fn with_node_skew(mut self, node: NodeId, skew: Skew) -> Self {
+        self.skews[node] = skew;
+        self
+    }
+
+    /// Check `check` against the nodes' state after every delivery, as
+    /// the invariant `name`.
+    pub fn with_invariant(
+        mut self,
+        name: impl Into<String>,
+        check: impl Fn(&SimState) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.invariants.push(Invariant::new(name, check));
+        self
+    }
+
+    pub fn skew(&self, node: NodeId) -> Skew {
+        self.skews[node]
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.shared.inboxes.len()
+    }
+
+    /// The handle node `id`'s task drives the node through, starting from
+    /// the clock of its last event, if it has any. Take it once per node.
+    pub fn node(&self, id: NodeId) -> SimNode<C, M> {
+        assert!(id < self.nodes(), "Node {} is not on the network", id);
+        let clock = self
+            .shared
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .rfind(|event| event.node == id)
+            .map_or_else(VectorClock::new, |event| event.clock.clone());
+        SimNode {
+            id,
+            net: self.clone(),
+            clock,
+        }
+    }
+
+    /// Every event recorded so far, in log order.
+    pub fn events(&self) -> Vec<NodeEvent> {
+        self.shared.events.lock().unwrap().clone()
+    }
+
+    /// Every update made so far, in log order.
+    pub fn updates(&self) -> Vec<Update> {
+        self.shared.updates.lock().unwrap().clone()
+    }
+
+    /// The state every update so far has left the nodes in.
+    pub fn state(&self) -> SimState {
+        let mut state = SimState::new(self.nodes());
+        for update in self.updates() {
+            state.apply(&update);
+        }
+        state
+    }
+
+    /// The first violation of each invariant found so far, in the order
+    /// they were found.
+    pub fn violations(&self) -> Vec<Violation> {
+        self.shared.violations.lock().unwrap().clone()
+    }
+
+    /// Cut the network so that only nodes in the same group reach each
+    /// other, replacing any earlier cut. Nodes in no group reach nobody.
+    pub fn partition(&self, groups: &[Vec<NodeId>]) {
+        let mut sides = vec![None; self.nodes()];
+        for (side, group) in groups.iter().enumerate() {
+            for &node in group {
+                sides[node] = Some(side);
+            }
+        }
+        self.log
+            .record("network", format!("partitioned into {:?}", groups));
+        *self.shared.partition.lock().unwrap() = Some(sides);
+    }
+
+    /// Let every node reach every other again.
+    pub fn heal(&self) {
+        self.log.record("network", "healed");
+        *self.shared.partition.lock().unwrap() = None;
+    }
+
+    /// Whether a message from `from` would get to `to` across the current
+    /// partition.
+    pub fn reachable(&self, from: NodeId, to: NodeId) -> bool {
+        match &*self.shared.partition.lock().unwrap() {
+            Some(sides) => from == to || (sides[from].is_some() && sides[from] == sides[to]),
+            None => true,
+        }
+    }
+
+    /// Everything about the network as of now.
+    pub fn snapshot(&self) -> Snapshot<M>
+    where
+        M: Clone,
+    {
+        let now = self.context.current();
+        Snapshot {
+            at: elapsed_since(&self.context, self.start),
+            skews: self.skews.clone(),
+            events: self.events(),
+            updates: self.updates(),
+            inboxes: self
+                .shared
+                .inboxes
+                .iter()
+                .map(|inbox| inbox.lock().unwrap().queue.iter().cloned().collect())
+                .collect(),
+            in_flight: self
+                .shared
+                .in_flight
+                .lock()
+                .unwrap()
+                .values()
+                .map(|pending| InFlight {
+                    envelope: pending.envelope.clone(),
+                    arrives_in: pending.arrives_at.duration_since(now).unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Wait until `at` after the network started, then take a
+    /// [`snapshot`](Self::snapshot). The runtime may wake the caller a
+    /// little later; the snapshot's own time says when it was taken.
+    pub async fn snapshot_at(&self, at: Duration) -> Snapshot<M>
+    where
+        M: Clone,
+    {
+        self.context.sleep_until(self.start + at).await;
+        self.snapshot()
+    }
+
+    /// Every pair of updates to the same key whose clocks are concurrent.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let updates = self.updates();
+        let mut conflicts = Vec::new();
+        for (index, first) in updates.iter().enumerate() {
+            for second in &updates[index + 1..] {
+                if first.key == second.key && first.clock.is_concurrent(&second.clock) {
+                    conflicts.push(Conflict {
+                        first: first.clone(),
+                        second: second.clone(),
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn draw_latency(&self) -> Duration {
+        let (min, max) = self.latency;
+        let micros = self
+            .rng
+            .clone()
+            .random_range(min.as_micros() as u64..=max.as_micros() as u64);
+        Duration::from_micros(micros)
+    }
+
+    /// Put `envelope` in its recipient's inbox after `latency`.
+    fn deliver_after(&self, envelope: Envelope<M>, latency: Duration) {
+        let id = {
+            let mut sent = self.shared.sent.lock().unwrap();
+            *sent += 1;
+            *sent
+        };
+        self.shared.in_flight.lock().unwrap().insert(
+            id,
+            Pending {
+                envelope,
+                arrives_at: self.context.current() + latency,
+            },
+        );
+        let net = self.clone();
+        self.context.clone().spawn(move |context| async move {
+            context.sleep(latency).await;
+            let Some(pending) = net.shared.in_flight.lock().unwrap().remove(&id) else {
+                return;
+            };
+            let (from, to) = (pending.envelope.from, pending.envelope.to);
+            if !net.reachable(from, to) {
+                net.log.record(
+                    "network",
+                    format!(
+                        "dropped message from node {} to node {} at partition",
+                        from, to
+                    ),
+                );
+                return;
+            }
+            {
+                let mut inbox = net.shared.inboxes[to].lock().unwrap();
+                inbox.queue.push_back(pending.envelope);
+                if let Some(waker) = inbox.waker.take() {
+                    waker.wake();
+                }
+            }
+            net.check_invariants();
+        });
+    }
+
+    /// Check every invariant not yet violated against the events since the
+    /// last check, recording the first event after which each one failed.
+    fn check_invariants(&self) {
+        if self.invariants.is_empty() {
+            return;
+        }
+        let events = self.events();
+        let updates = self.updates();
+        let mut checked = self.shared.checked.lock().unwrap();
+        let mut violations = self.shared.violations.lock().unwrap();
+        for invariant in &self.invariants {
+            if violations
+                .iter()
+                .any(|violation| violation.invariant == invariant.name())
+            {
+                continue;
+            }
+            if let Some(violation) =
+                invariant.first_violation(self.nodes(), &events, &updates, *checked)
+            {
+                self.log.record(
+                    "oracle",
+                    format!(
+                        "{} broken by node {}: {}",
+                        violation.invariant, violation.event.node, violation.event.message
+                    ),
+                );
+                violations.push(violation);
+            }
+        }
+        *checked = events.len();
+    }
+
+    /// Whether the next message is lost. Draws nothing on a lossless
+    /// network, so adding loss is the only thing that changes its stream.
+    fn draw_loss(&self) -> bool {
+        self.loss > 0.0 && self.rng.clone().random_bool(self.loss)
+    }
+}
+
+/// One node's view of the network.
+pub struct SimNode<C, M> {
+    id: NodeId,
+    net: SimNet<C, M>,
+    clock: VectorClock,
+}
+
+impl<C: Spawner + Clock, M: Send + 'static> SimNode<C, M> {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn clock(&self) -> &VectorClock {
+        &self.clock
+    }
+
+    /// The time since the network started, as this node's clock reads it.
+    pub fn local_time(&self) -> Duration {
+        self.skew()
+            .local(elapsed_since(&self.net.context, self.net.start))
+    }
+
+    /// Wait until this node's clock has measured `duration`.
+    pub fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send + 'static {
+        self.net.context.sleep(self.skew().to_virtual(duration))
+    }
+
+    fn skew(&self) -> Skew {
+        self.net.skews[self.id]
+    }
+
+    /// Record `message` as this node's next event, logged under `node N`
+    /// with the clock it was stamped with.
+    pub fn record(&mut self, message: impl Into<String>) {
+        self.clock.tick(self.id);
+        let event = NodeEvent {
+            node: self.id,
+            message: message.into(),
+            clock: self.clock.clone(),
+        };
+        self.net.log.record(
+            format!("node {}", self.id),
+            format!("{} at {}", event.message, event.clock),
+        );
+        self.net.shared.events.lock().unwrap().push(event);
+    }
+
+    /// The value this node last set `key` to.
+    pub fn value(&self, key: &str) -> Option<Value> {
+        self.net
+            .shared
+            .updates
+            .lock()
+            .unwrap()
+            .iter()
+            .rfind(|update| update.node == self.id && update.key == key)
+            .map(|update| update.value)
+    }
+
+    /// Set `key` to `value`, as an event of its own.
+    pub fn update(&mut self, key: impl Into<String>, value: Value) {
+        let key = key.into();
+        self.record(format!("set {} to {}", key, value));
+        self.net.shared.updates.lock().unwrap().push(Update {
+            node: self.id,
+            key,
+            value,
+            clock: self.clock.clone(),
+        });
+    }
+
+    /// Send `message` to node `to`, stamped with this node's clock.
+    pub fn send(&mut self, to: NodeId, message: M) {
+        assert!(to < self.net.nodes(), "Node {} is not on the network", to);
+        if !self.net.reachable(self.id, to) {
+            self.record(format!("send to node {}, partitioned", to));
+            return;
+        }
+        if self.net.draw_loss() {
+            self.record(format!("send to node {}, lost", to));
+            return;
+        }
+        self.record(format!("send to node {}", to));
+        let envelope = Envelope {
+            from: self.id,
+            to,
+            clock: self.clock.clone(),
+            message,
+        };
+        let latency = self.net.draw_latency();
+        self.net.deliver_after(envelope, latency);
+    }
+
+    /// Wait for the next message delivered to this node, and merge its
+    /// clock into this node's.
+    pub async fn recv(&mut self) -> Envelope<M> {
+        let envelope = poll_fn(|cx| self.poll_inbox(cx)).await;
+        self.received(envelope)
+    }
+
+    /// Like [`recv`](Self::recv), but give up after `timeout` on this
+    /// node's clock.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Option<Envelope<M>> {
+        let mut timer = Box::pin(self.sleep(timeout));
+        let envelope = poll_fn(|cx| match self.poll_inbox(cx) {
+            Poll::Ready(envelope) => Poll::Ready(Some(envelope)),
+            Poll::Pending => timer.as_mut().poll(cx).map(|_| None),
+        })
+        .await?;
+        Some(self.received(envelope))
+    }
+
+    /// The next message already delivered to this node, if any, without
+    /// waiting.
+    pub fn try_recv(&mut self) -> Option<Envelope<M>> {
+        let envelope = self.net.shared.inboxes[self.id]
+            .lock()
+            .unwrap()
+            .queue
+            .pop_front()?;
+        Some(self.received(envelope))
+    }
+
+    fn poll_inbox(&self, cx: &mut Context<'_>) -> Poll<Envelope<M>> {
+        let mut inbox = self.net.shared.inboxes[self.id].lock().unwrap();
+        match inbox.queue.pop_front() {
+            Some(envelope) => Poll::Ready(envelope),
+            None => {
+                inbox.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn received(&mut self, envelope: Envelope<M>) -> Envelope<M> {
+        self.clock.merge(&envelope.clock);
+        self.record(format!("received from node {}", envelope.from));
+        envelope
+    }
+}
+
+/// Three nodes write to `x`. Node 0 writes and tells node 1, which writes
+/// only once it has heard; node 2 writes without hearing from anyone, and
+/// does so last on the runtime's clock. Returns the conflicts found, which
+/// pair node 2's write with each of the others even though it came last:
+/// coming later is not the same as knowing.
+pub async fn concurrent_updates<C: Spawner + Clock>(
+    context: &C,
+    rng: DeterministicRng,
+    log: &EventLog,
+) -> Vec<Conflict> {
+    let net = SimNet::new(context.clone(), 3, rng, log.clone());
+    let mut first = net.node(0);
+    let mut second = net.node(1);
+    let mut third = net.node(2);
+    let handles = vec![
+        context.clone().spawn(move |_| async move {
+            first.update("x", 1);
+            first.send(1, 1);
+        }),
+        context.clone().spawn(move |_| async move {
+            let envelope = second.recv().await;
+            second.update("x", envelope.message + 1);
+        }),
+        context.clone().spawn(move |context| async move {
+            context.sleep(Duration::from_millis(50)).await;
+            third.update("x", 10);
+        }),
+    ];
+    for handle in handles {
+        handle.await.expect("Node should run to completion");
+    }
+    net.conflicts()
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    fn run(seed: u64) -> (Vec<Conflict>, Vec<String>) {
+        let log = EventLog::new();
+        let conflicts =
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+                let log = log.clone();
+                async move { concurrent_updates(&context, DeterministicRng::new(seed), &log).await }
+            });
+        (
+            conflicts,
+            log.events().iter().map(|event| event.to_string()).collect(),
+        )
+    }
+
+    /// Node 1's write follows node 0's through the message; node 2's is
+    /// concurrent with both despite being logged after them.
+    #[test]
+    fn test_concurrent_updates() {
+        let (conflicts, events) = run(0);
+
+        let pairs: Vec<_> = conflicts
+            .iter()
+            .map(|conflict| (conflict.first.node, conflict.second.node))
+            .collect();
+        assert_eq!(pairs, [(0, 2), (1, 2)]);
+        assert_eq!(
+            events,
+            [
+                "node 0: set x to 1 at [1]",
+                "node 0: send to node 1 at [2]",
+                "node 1: received from node 0 at [2, 1]",
+                "node 1: set x to 2 at [2, 2]",
+                "node 2: set x to 10 at [0, 0, 1]",
+            ]
+        );
+        assert_eq!(run(0), (conflicts, events));
+    }
+
+    /// The same seed delivers every message at the same instant, and
+    /// latency can reorder messages sent back to back.
+    #[test]
+    fn test_deliveries_replay() {
+        let deliveries = |seed| {
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(
+                |context| async move {
+                    let net = SimNet::new(
+                        context.clone(),
+                        2,
+                        DeterministicRng::new(seed),
+                        EventLog::new(),
+                    )
+                    .with_latency(Duration::from_millis(2), Duration::from_millis(5));
+                    let mut sender = net.node(0);
+                    let mut receiver = net.node(1);
+                    let start = context.current();
+                    for message in 0..5 {
+                        sender.send(1, message);
+                    }
+                    let mut arrivals = Vec::new();
+                    for _ in 0..5 {
+                        let envelope = receiver.recv().await;
+                        let at = context.current().duration_since(start).unwrap();
+                        arrivals.push((envelope.message, at));
+                    }
+                    arrivals
+                },
+            )
+        };
+
+        let arrivals = deliveries(3);
+        let order: Vec<_> = arrivals.iter().map(|(message, _)| *message).collect();
+        assert_ne!(order, [0, 1, 2, 3, 4]);
+        assert_eq!(deliveries(3), arrivals);
+    }
+}
@@ -0,0 +1,184 @@
+//! Simulated nodes whose clocks disagree.
+//!
+//! Every node on a [`SimNet`] shares the runtime's virtual time, which real
+//! machines never do: each has a clock that started off by some amount and
+//! runs a little fast or slow. A [`Skew`] models both. A node reads its
+//! [`local_time`](crate::simnet::SimNode::local_time) through its skew, and
+//! its [`sleep`](crate::simnet::SimNode::sleep)s, and the timeouts of calls
+//! made through an [`RpcNode`](crate::rpc::RpcNode), last as long as its own
+//! clock says. [`SimNet::with_skew`] draws every node's skew from the seed, so a
+//! protocol that depends on clocks agreeing fails the same way on every run
+//! of a seed that breaks it.
+//!
+//! [`lease_handover`] is such a protocol: a lease that only stays exclusive
+//! while the holder's clock runs no slower than the lessor's.
+
+use std::time::Duration;
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{simnet::SimNet, stats::elapsed_since};
+
+const PPM: i128 = 1_000_000;
+
+/// How a node's clock differs from virtual time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Skew {
+    /// How far ahead the clock read when the network started, in
+    /// microseconds; negative if behind.
+    pub offset_micros: i64,
+    /// How much faster the clock runs, in parts per million; negative if
+    /// slower.
+    pub drift_ppm: i64,
+}
+
+impl Skew {
+    pub fn new(offset_micros: i64, drift_ppm: i64) -> Self {
+        assert!(
+            drift_ppm > -(PPM as i64),
+            "A clock should run forwards, however slowly"
+        );
+        Self {
+            offset_micros,
+            drift_ppm,
+        }
+    }
+
+    /// What a clock with this skew reads `elapsed` after the network
+    /// started, or zero if it would read earlier.
+    pub fn local(&self, elapsed: Duration) -> Duration {
+        let drifted = elapsed.as_micros() as i128 * (PPM + self.drift_ppm as i128) / PPM;
+        let micros = drifted + self.offset_micros as i128;
+        Duration::from_micros(micros.clamp(0, u64::MAX as i128) as u64)
+    }
+
+    /// How much virtual time passes while a clock with this skew measures
+    /// `local`.
+    pub fn to_virtual(&self, local: Duration) -> Duration {
+        let micros = local.as_micros() as i128 * PPM / (PPM + self.drift_ppm as i128);
+        Duration::from_micros(micros.clamp(0, u64::MAX as i128) as u64)
+    }
+}
+
+/// Node 0 leases a resource to node 1 for `lease` by its own clock, waits
+/// out the lease plus `guard` for messages in flight, then leases it to
+/// node 2. Node 1 holds the lease until its own clock says it is over.
+/// Returns for how long, in virtual time, nodes 1 and 2 both held the
+/// lease, if they overlapped at all.
+pub async fn lease_handover<C: Spawner + Clock>(
+    context: &C,
+    net: SimNet<C, u8>,
+    lease: Duration,
+    guard: Duration,
+) -> Option<Duration> {
+    let start = context.current();
+    let mut lessor = net.node(0);
+    let granting = context.clone().spawn(move |_| async move {
+        lessor.send(1, 1);
+        lessor.sleep(lease + guard).await;
+        lessor.record("lease to node 1 expired");
+        lessor.send(2, 2);
+    });
+    let holding: Vec<_> = [1, 2]
+        .into_iter()
+        .map(|id| {
+            let mut holder = net.node(id);
+            context.clone().spawn(move |context| async move {
+                holder.recv().await;
+                let acquired = elapsed_since(&context, start);
+                holder.record("acquired the lease");
+                holder.sleep(lease).await;
+                holder.record("released the lease");
+                (acquired, elapsed_since(&context, start))
+            })
+        })
+        .collect();
+
+    granting.await.expect("Lessor should run to completion");
+    let mut held = Vec::new();
+    for handle in holding {
+        held.push(handle.await.expect("Holder should run to completion"));
+    }
+    let (_, first_released) = held[0];
+    let (second_acquired, _) = held[1];
+    (second_acquired < first_released).then(|| first_released - second_acquired)
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::{rng::DeterministicRng, trace::EventLog};
+
+    const LEASE: Duration = Duration::from_millis(100);
+    const GUARD: Duration = Duration::from_millis(20);
+
+    fn handover(slow_holder: Option<Skew>) -> Option<Duration> {
+        DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+            let mut net = SimNet::new(
+                context.clone(),
+                3,
+                DeterministicRng::new(0),
+                EventLog::new(),
+            );
+            if let Some(skew) = slow_holder {
+                net = net.with_node_skew(1, skew);
+            }
+            lease_handover(&context, net, LEASE, GUARD).await
+        })
+    }
+
+    /// Reading and waiting through a skew agree with each other.
+    #[test]
+    fn test_skew_conversions() {
+        let fast = Skew::new(-5_000, 100_000);
+        assert_eq!(
+            fast.local(Duration::from_millis(100)),
+            Duration::from_millis(105)
+        );
+        assert_eq!(fast.local(Duration::ZERO), Duration::ZERO);
+        assert_eq!(
+            fast.to_virtual(Duration::from_millis(110)),
+            Duration::from_millis(100)
+        );
+    }
+
+    /// With agreeing clocks the guard keeps the leases apart; a holder whose
+    /// clock runs 30% slow keeps its lease after the next one is granted.
+    #[test]
+    fn test_slow_holder_overlaps() {
+        assert_eq!(handover(None), None);
+        assert_eq!(handover(Some(Skew::new(0, 0))), None);
+
+        let overlap = handover(Some(Skew::new(0, -300_000))).unwrap();
+        assert!(overlap > Duration::from_millis(10));
+    }
+
+    /// Seeded skews stay within their bounds and are the same on every run
+    /// with the seed.
+    #[test]
+    fn test_seeded_skews() {
+        let skews = |seed| {
+            DeterministicRunner::new(Config::default()).start(|context| async move {
+                let net: SimNet<_, ()> =
+                    SimNet::new(context, 4, DeterministicRng::new(seed), EventLog::new())
+                        .with_skew(Duration::from_millis(50), 1_000);
+                (0..4).map(|node| net.skew(node)).collect::<Vec<_>>()
+            })
+        };
+
+        let drawn = skews(9);
+        assert!(
+            drawn.iter().all(|skew| {
+                skew.offset_micros.abs() <= 50_000 && skew.drift_ppm.abs() <= 1_000
+            })
+        );
+        assert_ne!(drawn[0], drawn[1]);
+        assert_eq!(skews(9), drawn);
+    }
+}
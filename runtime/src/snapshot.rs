@@ -0,0 +1,214 @@
+//! Capturing a simulated network mid-run and starting again from there.
+//!
+//! Replaying from time zero is the only way to reach a state deep into a run,
+//! and a test about what happens after, say, the hundredth message pays for
+//! the first ninety-nine every time. A [`Snapshot`] is everything about a
+//! [`SimNet`] at one virtual instant: every event and update so far (and so
+//! every node's state and clock), messages delivered but not yet received,
+//! and messages still in flight with how long each has left to travel.
+//! [`SimNet::restore`] turns one back into a running network, and with the
+//! `snapshot` feature it serializes to JSON, so an interesting state can be
+//! checked in as a fixture.
+//!
+//! Only what goes through the network is captured. A node task that keeps
+//! its state in local variables loses it; one that keeps it in updates,
+//! reading it back with [`SimNode::value`](crate::simnet::SimNode::value),
+//! picks up where it was. [`relay`] is written that way.
+
+use std::time::Duration;
+
+use commonware_runtime::{Clock, Spawner};
+
+use crate::{
+    parallel_determinism::types::Value,
+    simnet::{Envelope, NodeEvent, SimNet, SimState, Update},
+    skew::Skew,
+};
+
+/// A message that had not arrived when the snapshot was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct InFlight<M> {
+    pub envelope: Envelope<M>,
+    /// How long after the snapshot it arrives.
+    pub arrives_in: Duration,
+}
+
+/// A [`SimNet`] at one instant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot<M> {
+    /// Virtual time since the network started.
+    pub at: Duration,
+    pub skews: Vec<Skew>,
+    /// Every event so far, in log order.
+    pub events: Vec<NodeEvent>,
+    /// Every update so far, in log order.
+    pub updates: Vec<Update>,
+    /// Messages delivered to each node but not yet received, by node.
+    pub inboxes: Vec<Vec<Envelope<M>>>,
+    /// In the order they were sent.
+    pub in_flight: Vec<InFlight<M>>,
+}
+
+impl<M> Snapshot<M> {
+    pub fn nodes(&self) -> usize {
+        self.inboxes.len()
+    }
+
+    /// The state the updates so far left the nodes in.
+    pub fn state(&self) -> SimState {
+        let mut state = SimState::new(self.nodes());
+        for update in &self.updates {
+            state.apply(update);
+        }
+        state
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl<M: serde::Serialize> Snapshot<M> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Snapshots always serialize")
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl<M: serde::de::DeserializeOwned> Snapshot<M> {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Pass a token around the ring of `net`'s nodes until `until` after the
+/// network started, and return a snapshot from then. Each node counts the
+/// times it has held the token in its `held` key and forwards the token with
+/// one more hop on it. Node 0 puts the token on the ring if nobody has
+/// recorded anything yet; on a restored network it is already there.
+pub async fn relay<C: Spawner + Clock>(
+    context: &C,
+    net: SimNet<C, Value>,
+    until: Duration,
+) -> Snapshot<Value> {
+    let fresh = net.events().is_empty();
+    let nodes = net.nodes();
+    let handles: Vec<_> = (0..nodes)
+        .map(|id| {
+            let mut node = net.node(id);
+            context.clone().spawn(move |_| async move {
+                if fresh && id == 0 {
+                    node.send(1 % nodes, 1);
+                }
+                loop {
+                    let token = node.recv().await;
+                    let held = node.value("held").unwrap_or(0) + 1;
+                    node.update("held", held);
+                    node.send((id + 1) % nodes, token.message + 1);
+                }
+            })
+        })
+        .collect();
+    let snapshot = net.snapshot_at(until).await;
+    for handle in handles {
+        handle.abort();
+    }
+    snapshot
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::{rng::DeterministicRng, trace::EventLog};
+
+    const HALFWAY: Duration = Duration::from_millis(40);
+
+    fn fresh(seed: u64) -> Snapshot<Value> {
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            let net = SimNet::new(
+                context.clone(),
+                4,
+                DeterministicRng::new(seed),
+                EventLog::new(),
+            );
+            relay(&context, net, HALFWAY).await
+        })
+    }
+
+    fn resumed(snapshot: Snapshot<Value>, seed: u64) -> Snapshot<Value> {
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            let net = SimNet::restore(
+                context.clone(),
+                snapshot,
+                DeterministicRng::new(seed),
+                EventLog::new(),
+            );
+            relay(&context, net, 2 * HALFWAY).await
+        })
+    }
+
+    /// The one token, wherever it is, carries one more hop than the nodes
+    /// have held it in total.
+    fn token_accounted_for(snapshot: &Snapshot<Value>) -> bool {
+        let tokens: Vec<Value> = snapshot
+            .inboxes
+            .iter()
+            .flatten()
+            .chain(snapshot.in_flight.iter().map(|flight| &flight.envelope))
+            .map(|envelope| envelope.message)
+            .collect();
+        let held: Value = snapshot
+            .state()
+            .values_of("held")
+            .map(|(_, held)| held)
+            .sum();
+        tokens == [held + 1]
+    }
+
+    /// A snapshot taken at the same instant of the same seed is the same
+    /// every time, and catches the token between nodes.
+    #[test]
+    fn test_snapshot_replays() {
+        let snapshot = fresh(5);
+
+        assert!(snapshot.at >= HALFWAY);
+        assert!(snapshot.events.len() > 4);
+        assert!(token_accounted_for(&snapshot));
+        assert_eq!(fresh(5), snapshot);
+    }
+
+    /// A restored network carries on from the snapshot: the history is kept,
+    /// node clocks keep counting, and the same seed continues the same way.
+    #[test]
+    fn test_restore_continues() {
+        let snapshot = fresh(5);
+        let resumed_snapshot = resumed(snapshot.clone(), 11);
+
+        assert!(resumed_snapshot.at >= 2 * HALFWAY);
+        assert!(resumed_snapshot.events.starts_with(&snapshot.events));
+        assert!(resumed_snapshot.events.len() > snapshot.events.len());
+        let old = &snapshot.events[snapshot.events.len() - 1];
+        assert!(
+            resumed_snapshot.events[snapshot.events.len()..]
+                .iter()
+                .filter(|event| event.node == old.node)
+                .all(|event| old.clock.happens_before(&event.clock))
+        );
+        assert!(token_accounted_for(&resumed_snapshot));
+        assert_eq!(resumed(snapshot.clone(), 11), resumed_snapshot);
+        assert_ne!(resumed(snapshot, 12), resumed_snapshot);
+    }
+
+    /// A snapshot survives a round trip through JSON.
+    #[cfg(feature = "snapshot")]
+    #[test]
+    fn test_json_round_trip() {
+        let snapshot = fresh(5);
+
+        assert_eq!(Snapshot::from_json(&snapshot.to_json()).unwrap(), snapshot);
+    }
+}
@@ -0,0 +1,526 @@
+//! Stable task identities for spawned tasks.
+//!
+//! A bare `context.spawn` gives a task no identity a trace can refer to, so
+//! every demo hand-writes labels like "Task 1" into its prints. A
+//! [`TaskSpawner`] numbers tasks in the order they are spawned and lets each
+//! carry an optional name. Under the deterministic runtime spawn order is part
+//! of the replayed schedule, so the same seed always hands out the same ids to
+//! the same tasks, and a trace can say "word-selector" instead of pointing at
+//! an anonymous future.
+//!
+//! Start, poll and end [`Hooks`] registered on a spawner run for every task
+//! it spawns, including children spawned through a task's [`TaskScope`].
+//!
+//! A spawner can also carry a [`NondeterminismAudit`]; each task then reaches
+//! real time and other nondeterministic sources through
+//! [`TaskScope::audit`], under its own id.
+//!
+//! A panic inside a spawned task is caught at the task boundary and comes
+//! back through its handle as [`TaskError::Panicked`], on every runtime, so
+//! the caller decides whether the rest of the work carries on.
+//!
+//! The handle is a [`TaskHandle`]: awaiting it gives the task's output or
+//! its [`TaskError`], and it can abort the task or say whether it has
+//! finished. The same type wraps a Tokio `JoinHandle`, so code that manages
+//! tasks does not care which runtime spawned them.
+
+use std::{
+    fmt,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use commonware_runtime::{Handle, Spawner};
+
+use crate::{
+    audit::{NondeterminismAudit, TaskAudit},
+    error::TaskError,
+    hooks::Hooks,
+    trace::EventLog,
+};
+
+/// A task's position in spawn order, starting at 0 for each spawner tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(pub u64);
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task-{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub name: Option<String>,
+}
+
+impl TaskInfo {
+    /// The name if there is one, otherwise the id.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.id.to_string(),
+        }
+    }
+}
+
+/// Spawns tasks on `S`, giving each a [`TaskId`] and recording into a shared
+/// [`EventLog`] under its label. Clones share the id sequence.
+#[derive(Clone)]
+pub struct TaskSpawner<S> {
+    context: S,
+    log: EventLog,
+    next_id: Arc<AtomicU64>,
+    task_start: Hooks<TaskInfo>,
+    task_poll: Hooks<TaskInfo>,
+    task_end: Hooks<TaskInfo>,
+    audit: NondeterminismAudit,
+}
+
+impl<S: Spawner> TaskSpawner<S> {
+    pub fn new(context: S, log: EventLog) -> Self {
+        Self {
+            context,
+            log,
+            next_id: Arc::new(AtomicU64::new(0)),
+            task_start: Hooks::new(),
+            task_poll: Hooks::new(),
+            task_end: Hooks::new(),
+            audit: NondeterminismAudit::disabled(),
+        }
+    }
+
+    /// Audit the tasks' nondeterministic sources into `audit`. Without one
+    /// the shims pass straight through.
+    pub fn with_audit(mut self, audit: NondeterminismAudit) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Run `hook` when each task is first polled, before any of its code.
+    pub fn on_task_start(mut self, hook: impl Fn(&TaskInfo) + Send + Sync + 'static) -> Self {
+        self.task_start.push(hook);
+        self
+    }
+
+    /// Run `hook` every time each task is polled, including the first.
+    pub fn on_task_poll(mut self, hook: impl Fn(&TaskInfo) + Send + Sync + 'static) -> Self {
+        self.task_poll.push(hook);
+        self
+    }
+
+    /// Run `hook` when each task's future completes.
+    pub fn on_task_end(mut self, hook: impl Fn(&TaskInfo) + Send + Sync + 'static) -> Self {
+        self.task_end.push(hook);
+        self
+    }
+
+    pub fn log(&self) -> &EventLog {
+        &self.log
+    }
+
+    /// Spawn an anonymous task; its label is its id.
+    pub fn spawn<F, Fut, T>(&self, f: F) -> TaskHandle<T>
+    where
+        F: FnOnce(TaskScope<S>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_task(None, f)
+    }
+
+    pub fn spawn_named<F, Fut, T>(&self, name: impl Into<String>, f: F) -> TaskHandle<T>
+    where
+        F: FnOnce(TaskScope<S>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_task(Some(name.into()), f)
+    }
+
+    fn spawn_task<F, Fut, T>(&self, name: Option<String>, f: F) -> TaskHandle<T>
+    where
+        F: FnOnce(TaskScope<S>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        // The id is taken here, at the spawn call, not when the task first
+        // runs, so it follows program order rather than poll order.
+        let info = TaskInfo {
+            id: TaskId(self.next_id.fetch_add(1, Ordering::Relaxed)),
+            name,
+        };
+        let task_id = info.id.0;
+        let finished = Arc::new(AtomicBool::new(false));
+        let done = finished.clone();
+        let mut spawner = self.clone();
+        let handle = self.context.clone().spawn(move |context| {
+            spawner.context = context;
+            let (task_start, task_poll, task_end) = (
+                spawner.task_start.clone(),
+                spawner.task_poll.clone(),
+                spawner.task_end.clone(),
+            );
+            let scope = TaskScope {
+                spawner,
+                info: info.clone(),
+            };
+            async move {
+                task_start.call(&info);
+                let output = CatchUnwind {
+                    future: Box::pin(f(scope)),
+                    poll: task_poll,
+                    info: info.clone(),
+                }
+                .await
+                .map_err(|payload| TaskError::panicked(info.id.0, payload));
+                task_end.call(&info);
+                done.store(true, Ordering::Release);
+                output
+            }
+        });
+        TaskHandle {
+            task_id,
+            inner: Inner::Commonware { handle, finished },
+        }
+    }
+}
+
+/// A spawned task: await it for its output, or abort it.
+pub struct TaskHandle<T: Send + 'static> {
+    task_id: u64,
+    inner: Inner<T>,
+}
+
+enum Inner<T: Send + 'static> {
+    Commonware {
+        handle: Handle<Result<T, TaskError>>,
+        finished: Arc<AtomicBool>,
+    },
+    #[cfg(feature = "tokio-backend")]
+    Tokio(tokio::task::JoinHandle<T>),
+}
+
+impl<T: Send + 'static> TaskHandle<T> {
+    /// Wrap a task spawned directly on Tokio, reporting its failures under
+    /// `task_id`.
+    #[cfg(feature = "tokio-backend")]
+    pub fn from_tokio(handle: tokio::task::JoinHandle<T>, task_id: u64) -> Self {
+        Self {
+            task_id,
+            inner: Inner::Tokio(handle),
+        }
+    }
+
+    pub fn task_id(&self) -> u64 {
+        self.task_id
+    }
+
+    /// Stop the task at its next await. Awaiting the handle afterwards gives
+    /// [`TaskError::Aborted`], unless the task had already finished.
+    pub fn abort(&self) {
+        match &self.inner {
+            Inner::Commonware { handle, finished } => {
+                handle.abort();
+                finished.store(true, Ordering::Release);
+            }
+            #[cfg(feature = "tokio-backend")]
+            Inner::Tokio(handle) => handle.abort(),
+        }
+    }
+
+    /// Whether the task has completed, panicked or been aborted.
+    pub fn is_finished(&self) -> bool {
+        match &self.inner {
+            Inner::Commonware { finished, .. } => finished.load(Ordering::Acquire),
+            #[cfg(feature = "tokio-backend")]
+            Inner::Tokio(handle) => handle.is_finished(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Future for TaskHandle<T> {
+    type Output = Result<T, TaskError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let task_id = self.task_id;
+        let aborted = TaskError::Aborted { task_id };
+        match &mut self.inner {
+            Inner::Commonware { handle, .. } => Pin::new(handle)
+                .poll(cx)
+                .map(|result| result.unwrap_or(Err(aborted))),
+            #[cfg(feature = "tokio-backend")]
+            Inner::Tokio(handle) => Pin::new(handle).poll(cx).map(|result| {
+                result.map_err(|error| match error.try_into_panic() {
+                    Ok(payload) => TaskError::panicked(task_id, payload),
+                    Err(_) => aborted,
+                })
+            }),
+        }
+    }
+}
+
+/// Polls the inner future inside `catch_unwind`, resolving to the panic
+/// payload if any poll panics. The poll hooks run before every poll.
+struct CatchUnwind<F> {
+    future: Pin<Box<F>>,
+    poll: Hooks<TaskInfo>,
+    info: TaskInfo,
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn std::any::Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll.call(&self.info);
+        match panic::catch_unwind(AssertUnwindSafe(|| self.future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+/// What a task spawned through a [`TaskSpawner`] receives: its own context,
+/// its identity, and a spawner for children that continues the id sequence.
+pub struct TaskScope<S> {
+    spawner: TaskSpawner<S>,
+    info: TaskInfo,
+}
+
+impl<S: Spawner> TaskScope<S> {
+    pub fn context(&self) -> &S {
+        &self.spawner.context
+    }
+
+    pub fn info(&self) -> &TaskInfo {
+        &self.info
+    }
+
+    pub fn spawner(&self) -> &TaskSpawner<S> {
+        &self.spawner
+    }
+
+    /// Record `message` in the log under this task's label.
+    pub fn record(&self, message: impl Into<String>) {
+        self.spawner.log.record(self.info.label(), message);
+    }
+
+    /// This task's way to the wall clock, the OS RNG and the like, audited
+    /// under its id.
+    pub fn audit(&self) -> TaskAudit {
+        self.spawner.audit.for_task(self.info.id.0 as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use commonware_runtime::{
+        Clock, Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+        tokio::Runner as TokioRunner,
+    };
+
+    use super::*;
+    use crate::audit::Source;
+
+    /// Hooks fire for children too, and a task ends only after it started.
+    #[test]
+    fn test_hooks_cover_every_task() {
+        let log = EventLog::new();
+        let recorder = log.clone();
+        DeterministicRunner::new(Config::default().with_seed(1)).start(|context| async move {
+            let (on_start, on_end) = (recorder.clone(), recorder.clone());
+            let spawner = TaskSpawner::new(context, recorder)
+                .on_task_start(move |info| on_start.record(info.label(), "start"))
+                .on_task_end(move |info| on_end.record(info.label(), "end"));
+            spawner
+                .spawn_named("parent", |scope| async move {
+                    let child = scope.spawner().spawn(|_| async {});
+                    child.await.unwrap();
+                })
+                .await
+                .unwrap();
+        });
+
+        let events: Vec<_> = log.events().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            events,
+            [
+                "parent: start",
+                "task-1: start",
+                "task-1: end",
+                "parent: end"
+            ]
+        );
+    }
+
+    fn run(seed: u64) -> Vec<String> {
+        let log = EventLog::new();
+        let recorder = log.clone();
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            let spawner = TaskSpawner::new(context, recorder);
+            let selector = spawner.spawn_named("word-selector", |scope| async move {
+                let child = scope.spawner().spawn(|scope| async move {
+                    scope.record(format!("child of {}", TaskId(0)));
+                    scope.info().id
+                });
+                scope.context().sleep(Duration::from_millis(1)).await;
+                scope.record("selected");
+                child.await.unwrap()
+            });
+            let counter = spawner.spawn_named("word-counter", |scope| async move {
+                scope.record("counted");
+                scope.info().id
+            });
+
+            assert_eq!(counter.await, Ok(TaskId(1)));
+            assert_eq!(selector.await, Ok(TaskId(2)));
+        });
+        log.events().iter().map(|e| e.to_string()).collect()
+    }
+
+    /// A task's audit records under the task's own id, children included,
+    /// and a spawner without an audit records nothing.
+    #[test]
+    fn test_scope_audit() {
+        let run = |audit: NondeterminismAudit| {
+            DeterministicRunner::new(Config::default().with_seed(1)).start(|context| async move {
+                let spawner = TaskSpawner::new(context, EventLog::discarding()).with_audit(audit);
+                spawner
+                    .spawn_named("parent", |scope| async move {
+                        scope.audit().wall_clock();
+                        let child = scope.spawner().spawn(|scope| async move {
+                            scope.audit().os_rng();
+                        });
+                        child.await.unwrap();
+                    })
+                    .await
+                    .unwrap();
+            })
+        };
+
+        let audit = NondeterminismAudit::enabled();
+        run(audit.clone());
+        let found: Vec<_> = audit
+            .findings()
+            .iter()
+            .map(|f| (f.task_id, f.source))
+            .collect();
+        assert_eq!(found, [(0, Source::WallClock), (1, Source::OsRng)]);
+
+        let disabled = NondeterminismAudit::disabled();
+        run(disabled.clone());
+        assert!(disabled.findings().is_empty());
+    }
+
+    /// Ids follow spawn order, children continue the sequence, names appear
+    /// in the log, and the same seed reproduces all of it.
+    #[test]
+    fn test_named_tasks_in_trace() {
+        let events = run(3);
+
+        assert!(events.contains(&"word-selector: selected".to_string()));
+        assert!(events.contains(&"word-counter: counted".to_string()));
+        assert!(events.contains(&"task-2: child of task-0".to_string()));
+        assert_eq!(events, run(3));
+    }
+
+    /// A panicking task resolves to an error on both runtimes while its
+    /// sibling runs to completion, and still triggers its end hook.
+    #[test]
+    fn test_panics_are_isolated() {
+        fn run<S: Spawner>(context: S) -> impl Future<Output = Vec<String>> {
+            let log = EventLog::new();
+            let on_end = log.clone();
+            let spawner = TaskSpawner::new(context, log.clone())
+                .on_task_end(move |info| on_end.record(info.label(), "end"));
+            async move {
+                let bad = spawner.spawn_named("bad", |_| async { panic!("corrupt input") });
+                let good = spawner.spawn_named("good", |_| async { 7 });
+
+                assert_eq!(
+                    bad.await,
+                    Err::<(), _>(TaskError::Panicked {
+                        task_id: 0,
+                        message: "corrupt input".to_string()
+                    })
+                );
+                assert_eq!(good.await, Ok(7));
+                let mut ended: Vec<_> = log.events().iter().map(|e| e.to_string()).collect();
+                ended.sort();
+                ended
+            }
+        }
+
+        let expected = ["bad: end", "good: end"];
+        assert_eq!(
+            DeterministicRunner::new(Config::default().with_seed(0)).start(run),
+            expected
+        );
+        assert_eq!(TokioRunner::default().start(run), expected);
+    }
+
+    /// Aborting a sleeping task resolves its handle to an abort and marks it
+    /// finished, on both runtimes; a task that returned is finished too.
+    #[test]
+    fn test_abort_and_is_finished() {
+        fn run<S: Spawner + Clock>(context: S) -> impl Future<Output = ()> {
+            let spawner = TaskSpawner::new(context.clone(), EventLog::new());
+            async move {
+                let sleeper = spawner.spawn_named("sleeper", |scope| async move {
+                    scope.context().sleep(Duration::from_secs(60)).await;
+                });
+                let quick = spawner.spawn(|_| async { 1 });
+                context.sleep(Duration::from_millis(50)).await;
+
+                assert!(quick.is_finished());
+                assert!(!sleeper.is_finished());
+                sleeper.abort();
+                assert!(sleeper.is_finished());
+                assert_eq!(sleeper.await, Err(TaskError::Aborted { task_id: 0 }));
+                assert_eq!(quick.await, Ok(1));
+            }
+        }
+
+        DeterministicRunner::new(Config::default().with_seed(0)).start(run);
+        TokioRunner::default().start(run);
+    }
+
+    /// A Tokio `JoinHandle` wrapped directly reports panics and aborts the
+    /// same way.
+    #[cfg(feature = "tokio-backend")]
+    #[test]
+    fn test_wrapped_tokio_handle() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let panicking = TaskHandle::from_tokio(tokio::spawn(async { panic!("boom") }), 4);
+            assert_eq!(
+                panicking.await,
+                Err::<(), _>(TaskError::Panicked {
+                    task_id: 4,
+                    message: "boom".to_string()
+                })
+            );
+
+            let sleeping = TaskHandle::from_tokio(
+                tokio::spawn(tokio::time::sleep(Duration::from_secs(60))),
+                5,
+            );
+            sleeping.abort();
+            assert_eq!(sleeping.await, Err(TaskError::Aborted { task_id: 5 }));
+        });
+    }
+}
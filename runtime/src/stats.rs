@@ -0,0 +1,167 @@
+//! Latency measurement shared by the demos and benchmarks.
+//!
+//! All durations come from the runtime's [`Clock`], so under the deterministic
+//! runtime they are virtual time and reproduce exactly with the seed, while
+//! under Tokio they are wall-clock time. Percentiles use the nearest-rank
+//! method: every reported value is one that was actually observed.
+
+#[cfg(feature = "runtime")]
+use std::time::SystemTime;
+use std::{fmt, time::Duration};
+
+#[cfg(feature = "runtime")]
+use commonware_runtime::Clock;
+
+/// Time elapsed on `context`'s clock since `start`, or zero if the clock
+/// reads earlier than `start`.
+#[cfg(feature = "runtime")]
+pub fn elapsed_since(context: &impl Clock, start: SystemTime) -> Duration {
+    context
+        .current()
+        .duration_since(start)
+        .unwrap_or(Duration::ZERO)
+}
+
+/// p50/p95/p99 of a set of latencies.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50={:?} p95={:?} p99={:?}",
+            self.p50, self.p95, self.p99
+        )
+    }
+}
+
+/// A set of recorded latencies.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyHistogram {
+    samples: Vec<Duration>,
+    sorted: bool,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        self.samples.push(latency);
+        self.sorted = false;
+    }
+
+    /// Record the time elapsed on `context`'s clock since `start`.
+    #[cfg(feature = "runtime")]
+    pub fn record_since(&mut self, context: &impl Clock, start: SystemTime) {
+        self.record(elapsed_since(context, start));
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The nearest-rank `p`th percentile (`0.0..=100.0`), or zero when empty.
+    pub fn percentile(&mut self, p: f64) -> Duration {
+        assert!(
+            (0.0..=100.0).contains(&p),
+            "Percentile should be in 0..=100"
+        );
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        if !self.sorted {
+            self.samples.sort_unstable();
+            self.sorted = true;
+        }
+        let rank = (p / 100.0 * self.samples.len() as f64).ceil() as usize;
+        self.samples[rank.max(1) - 1]
+    }
+
+    pub fn percentiles(&mut self) -> Percentiles {
+        Percentiles {
+            p50: self.percentile(50.0),
+            p95: self.percentile(95.0),
+            p99: self.percentile(99.0),
+        }
+    }
+}
+
+impl Extend<Duration> for LatencyHistogram {
+    fn extend<I: IntoIterator<Item = Duration>>(&mut self, iter: I) {
+        self.samples.extend(iter);
+        self.sorted = false;
+    }
+}
+
+impl FromIterator<Duration> for LatencyHistogram {
+    fn from_iter<I: IntoIterator<Item = Duration>>(iter: I) -> Self {
+        Self {
+            samples: iter.into_iter().collect(),
+            sorted: false,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "runtime"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+
+    /// Nearest-rank percentiles on a known sample, recorded out of order.
+    #[test]
+    fn test_percentiles() {
+        let mut histogram: LatencyHistogram = (1..=100).rev().map(Duration::from_millis).collect();
+
+        assert_eq!(
+            histogram.percentiles(),
+            Percentiles {
+                p50: Duration::from_millis(50),
+                p95: Duration::from_millis(95),
+                p99: Duration::from_millis(99),
+            }
+        );
+        assert_eq!(histogram.percentile(0.0), Duration::from_millis(1));
+        assert_eq!(histogram.percentile(100.0), Duration::from_millis(100));
+        histogram.extend([Duration::ZERO, Duration::from_secs(1)]);
+        assert_eq!(histogram.percentile(0.0), Duration::ZERO);
+        assert_eq!(histogram.percentile(100.0), Duration::from_secs(1));
+        assert_eq!(
+            LatencyHistogram::new().percentiles(),
+            Percentiles::default()
+        );
+    }
+
+    /// Under the deterministic runtime, recorded latencies are virtual time.
+    #[test]
+    fn test_record_since_uses_virtual_time() {
+        let mut histogram =
+            DeterministicRunner::new(Config::default().with_seed(1)).start(|context| async move {
+                let mut histogram = LatencyHistogram::new();
+                for millis in [10, 20, 30] {
+                    let start = context.current();
+                    context.sleep(Duration::from_millis(millis)).await;
+                    histogram.record_since(&context, start);
+                }
+                histogram
+            });
+
+        assert_eq!(histogram.len(), 3);
+        assert!(histogram.percentile(50.0) >= Duration::from_millis(20));
+        assert!(histogram.percentile(50.0) < Duration::from_millis(30));
+    }
+}
@@ -0,0 +1,127 @@
+//! How the nondeterminism surface grows with parallelism.
+//!
+//! The same set of tasks is run many times on Tokio at each worker-thread
+//! count, and we count how many distinct completion orders show up. With one
+//! worker the run queue is close to FIFO and few orders appear; every extra
+//! worker adds tasks racing each other for real, and the number of observed
+//! orders climbs. The deterministic runtime stays at exactly one order per
+//! seed no matter how often it runs.
+
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+    hint::black_box,
+    sync::{Arc, Mutex},
+};
+
+use commonware_runtime::{
+    Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+
+/// Busy work per task, small enough that tasks overlap on multiple workers.
+const SPINS: u64 = 20_000;
+
+/// Spawn `tasks` sibling tasks that each spin briefly, and return the order
+/// they finished in.
+pub async fn completion_order<S: Spawner>(context: &S, tasks: usize) -> Vec<usize> {
+    let finished = Arc::new(Mutex::new(Vec::with_capacity(tasks)));
+    let handles: Vec<_> = (0..tasks)
+        .map(|index| {
+            let finished = finished.clone();
+            context.clone().spawn(move |_| async move {
+                let mut acc = 0u64;
+                for i in 0..SPINS {
+                    acc = black_box(acc.wrapping_add(i));
+                }
+                black_box(acc);
+                finished.lock().unwrap().push(index);
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.await.expect("Sweep task should run to completion");
+    }
+    std::mem::take(&mut *finished.lock().unwrap())
+}
+
+/// Distinct completion orders observed at one worker-thread count.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SweepPoint {
+    pub worker_threads: usize,
+    pub runs: usize,
+    pub distinct_orders: usize,
+}
+
+/// Run `tasks` tasks `runs` times on Tokio for each worker-thread count.
+pub fn sweep_worker_threads(thread_counts: &[usize], tasks: usize, runs: usize) -> Vec<SweepPoint> {
+    thread_counts
+        .iter()
+        .map(|&worker_threads| {
+            let orders: BTreeSet<_> = (0..runs)
+                .map(|_| {
+                    TokioRunner::new(TokioConfig::default().with_worker_threads(worker_threads))
+                        .start(|context| async move { completion_order(&context, tasks).await })
+                })
+                .collect();
+            SweepPoint {
+                worker_threads,
+                runs,
+                distinct_orders: orders.len(),
+            }
+        })
+        .collect()
+}
+
+/// The deterministic baseline: distinct orders over `runs` runs with `seed`.
+pub fn deterministic_distinct_orders(tasks: usize, runs: usize, seed: u64) -> usize {
+    (0..runs)
+        .map(|_| {
+            DeterministicRunner::new(Config::default().with_seed(seed))
+                .start(|context| async move { completion_order(&context, tasks).await })
+        })
+        .collect::<BTreeSet<_>>()
+        .len()
+}
+
+/// Render a sweep as a Markdown table.
+pub fn sweep_table(points: &[SweepPoint]) -> String {
+    let mut table = String::new();
+    writeln!(table, "| worker threads | runs | distinct orders |").unwrap();
+    writeln!(table, "|---|---|---|").unwrap();
+    for point in points {
+        writeln!(
+            table,
+            "| {} | {} | {} |",
+            point.worker_threads, point.runs, point.distinct_orders
+        )
+        .unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every run completes every task, and each thread count gets a row.
+    #[test]
+    fn test_sweep_shape() {
+        let points = sweep_worker_threads(&[1, 4], 16, 5);
+
+        assert_eq!(points.len(), 2);
+        for point in &points {
+            assert_eq!(point.runs, 5);
+            assert!((1..=5).contains(&point.distinct_orders));
+        }
+        assert_eq!(sweep_table(&points).lines().count(), 2 + 2);
+    }
+
+    /// Repeating a seed on the deterministic runtime never adds an order.
+    #[test]
+    fn test_deterministic_single_order() {
+        assert_eq!(deterministic_distinct_orders(16, 5, 7), 1);
+    }
+}
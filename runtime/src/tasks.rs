@@ -5,36 +5,49 @@
 //! Commonware deterministic runtime schedules work (repeatable interleaving).
 //! The same data and seed should lead to the same execution path, which is
 //! the property required by systems that must agree on state transitions.
+//!
+//! That only holds while the workloads keep to the runtime: a task that
+//! needs the wall clock, a thread sleep or the OS RNG goes through its
+//! [`TaskAudit`](crate::audit::TaskAudit) rather than calling it directly, so
+//! an audited run can say where replay would break.
+
+use std::{sync::Arc, time::Duration};
 
-use std::time::Duration;
+use commonware_runtime::{Clock, Spawner};
+use rand::Rng;
 
-use commonware_runtime::Clock;
-use rand::{SeedableRng, seq::IndexedRandom};
+#[cfg(feature = "tokio-backend")]
+use crate::{
+    DEMO_SEED,
+    audit::NondeterminismAudit,
+    backpressure::bounded,
+    rng::DeterministicRng,
+    rwlock::{LockEvent, TracedRwLock},
+    spawn::TaskSpawner,
+    trace::EventLog,
+};
+use crate::{
+    coop::Budget,
+    corpus::{Corpus, CorpusIndex},
+};
 
 /// Load a fixed corpus of words from `src/grimm.txt`.
 ///
 /// This provides stable input for experiments so any differences in output or
 /// ordering are due to scheduling, not data changes.
-pub fn read_file() -> Vec<String> {
+pub fn read_file() -> Corpus {
     let path = std::env::current_dir().expect("Current directory should be accessible");
-    std::fs::read_to_string(format!("{}/src/grimm.txt", path.display()))
-        .expect("File should be read successfully")
-        .split_whitespace()
-        .map(|word| word.to_string())
-        .collect()
+    Corpus::load(path.join("src/grimm.txt")).expect("File should be read successfully")
 }
 
 /// Pick a single word from the corpus.
 ///
-/// When `seed` is provided, selection is deterministic, which makes the
-/// downstream scheduling path reproducible.
-pub async fn select_random_word(words: &[String], seed: Option<u64>) -> String {
-    let mut rng = if let Some(seed) = seed {
-        rand::rngs::StdRng::seed_from_u64(seed)
-    } else {
-        rand::rngs::StdRng::from_os_rng()
-    };
-    let word = words.choose(&mut rng).unwrap().to_string();
+/// The choice is drawn from `rng`, so passing the run's
+/// [`DeterministicRng`](crate::rng::DeterministicRng) makes every selection in
+/// the run follow from one seed, which keeps the downstream scheduling path
+/// reproducible.
+pub async fn select_random_word(words: &Corpus, rng: &mut impl Rng) -> String {
+    let word = words.choose(rng).unwrap().to_string();
     println!("Selected word is: {}", word);
     word
 }
@@ -43,12 +56,300 @@ pub async fn select_random_word(words: &[String], seed: Option<u64>) -> String {
 ///
 /// This is a simple, pure computation used to demonstrate repeatable task
 /// ordering when the runtime is deterministic.
-pub async fn count_word_occurrences(word: &str, words: &[String]) -> usize {
-    let count = words.iter().filter(|&w| w == word).count();
+pub async fn count_word_occurrences(word: &str, words: &Corpus) -> usize {
+    let count = words.words().filter(|&w| w == word).count();
+    println!("The word '{}' appears {} times in the file.", word, count);
+    count
+}
+
+/// [`count_word_occurrences`] through an index built once up front, so
+/// repeated counts cost a lookup instead of a scan of the corpus.
+pub async fn count_word_occurrences_indexed(word: &str, index: &CorpusIndex) -> usize {
+    let count = index.count(word);
+    println!("The word '{}' appears {} times in the file.", word, count);
+    count
+}
+
+/// How the word-workflow demos count the selected word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CountStrategy {
+    /// Scan the whole corpus on every count.
+    #[default]
+    Scan,
+    /// Build a [`CorpusIndex`] once, then look each word up.
+    Indexed,
+}
+
+/// Counts words with a chosen [`CountStrategy`], holding the index if the
+/// strategy needs one.
+pub enum WordCounter {
+    Scan(Arc<Corpus>),
+    Indexed(CorpusIndex),
+}
+
+impl WordCounter {
+    /// Prepare to count in `words`, building the index now if `strategy`
+    /// asks for one.
+    pub fn new(strategy: CountStrategy, words: Arc<Corpus>) -> Self {
+        match strategy {
+            CountStrategy::Scan => WordCounter::Scan(words),
+            CountStrategy::Indexed => WordCounter::Indexed(words.index()),
+        }
+    }
+
+    /// How many times `word` appears, without printing anything.
+    pub fn count(&self, word: &str) -> usize {
+        match self {
+            WordCounter::Scan(words) => words.words().filter(|&w| w == word).count(),
+            WordCounter::Indexed(index) => index.count(word),
+        }
+    }
+}
+
+/// Count a word by splitting the corpus into `shards` contiguous ranges and
+/// counting each range in its own task.
+///
+/// Shards finish in whatever order the scheduler picks, but their partial
+/// counts are reduced in shard order, so the per-shard output and the total
+/// are identical on every run and every runtime.
+pub async fn count_word_occurrences_parallel<S: Spawner>(
+    context: &S,
+    word: &str,
+    words: &Arc<Corpus>,
+    shards: usize,
+) -> usize {
+    let shards = shards.max(1);
+    let shard_len = words.len().div_ceil(shards);
+    let handles: Vec<_> = (0..shards)
+        .map(|shard| {
+            let start = (shard * shard_len).min(words.len());
+            let end = (start + shard_len).min(words.len());
+            let words = words.clone();
+            let word = word.to_string();
+            context.clone().spawn(move |_| async move {
+                words.words_in(start..end).filter(|&w| w == word).count()
+            })
+        })
+        .collect();
+
+    let mut count = 0;
+    for (shard, handle) in handles.into_iter().enumerate() {
+        let partial = handle.await.expect("Shard should run to completion");
+        println!("Shard {}: '{}' appears {} times", shard, word, partial);
+        count += partial;
+    }
     println!("The word '{}' appears {} times in the file.", word, count);
     count
 }
 
+/// The purpose the word workflow derives its per-round selection seeds under.
+#[cfg(feature = "tokio-backend")]
+const SELECTION: &str = "word-selection";
+
+/// How the word workflow's two tasks keep in step.
+#[cfg(feature = "tokio-backend")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Coordination {
+    /// Both tasks pause between rounds, which only makes it likely that the
+    /// counter sees each selection; whether it does is up to the scheduler.
+    #[default]
+    Sleep,
+    /// The selector tells the counter about each word over a channel and
+    /// waits for it to acknowledge the count before selecting the next, so
+    /// every round is in step whatever the runtime, seed or timing. Nothing
+    /// pauses.
+    Handshake,
+}
+
+/// The knobs of the word-selection workflow.
+///
+/// Defaults match the demos: five rounds, [`DEMO_SEED`], a 10ms pause, a
+/// scanning count and [`Coordination::Sleep`]. The seed is the run's master
+/// seed: the deterministic demo seeds its runtime with it, and round `n`
+/// selects its word from a stream seeded with
+/// [`derive_seed`](crate::rng::derive_seed)`(seed, "word-selection", n)`.
+/// Changing the seed changes every round's selection, and both runtimes
+/// select the same words for the same seed.
+#[cfg(feature = "tokio-backend")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorkflowConfig {
+    pub rounds: usize,
+    pub seed: u64,
+    /// How long each task sleeps after each round, under
+    /// [`Coordination::Sleep`].
+    pub pause: Duration,
+    pub strategy: CountStrategy,
+    pub coordination: Coordination,
+}
+
+#[cfg(feature = "tokio-backend")]
+impl Default for WorkflowConfig {
+    fn default() -> Self {
+        Self {
+            rounds: 5,
+            seed: DEMO_SEED,
+            pause: Duration::from_millis(10),
+            strategy: CountStrategy::Scan,
+            coordination: Coordination::Sleep,
+        }
+    }
+}
+
+#[cfg(feature = "tokio-backend")]
+impl WorkflowConfig {
+    pub fn with_rounds(mut self, rounds: usize) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: CountStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_coordination(mut self, coordination: Coordination) -> Self {
+        self.coordination = coordination;
+        self
+    }
+}
+
+/// What one run of the word-selection workflow did.
+#[cfg(feature = "tokio-backend")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkflowOutcome {
+    /// Every word the selector picked, in order.
+    pub selections: Vec<String>,
+    /// Every count the counter took, with the word it counted. Rounds where
+    /// nothing had been selected yet have none.
+    pub counts: Vec<(String, usize)>,
+    /// For each of the counter's rounds, how many selections it could see.
+    /// Round `n` is in step with the selector when it saw `n + 1`.
+    pub visible: Vec<usize>,
+    /// Every acquisition and release of the shared word list.
+    pub lock_events: Vec<LockEvent>,
+}
+
+/// The word-selection workflow behind the demos and benchmarks, on any
+/// runtime.
+///
+/// A "word-selector" task picks `config.rounds` words from `words` and
+/// appends each to a shared list, while a "word-counter" task counts the
+/// latest word on the list each round. Under [`Coordination::Sleep`] both
+/// pause for `config.pause` between rounds, and what the counter sees each
+/// round depends on how the scheduler interleaved the two tasks, which is the
+/// point of the demos; under [`Coordination::Handshake`] it always sees the
+/// round's own word. The tasks record what they do into `log`, and the list
+/// records every grant of its lock there too, timed on `context`'s clock.
+/// Anything nondeterministic the tasks reach goes through `audit`.
+#[cfg(feature = "tokio-backend")]
+pub async fn word_workflow<S: Spawner + Clock>(
+    context: &S,
+    words: Arc<Corpus>,
+    config: WorkflowConfig,
+    log: EventLog,
+    audit: NondeterminismAudit,
+) -> WorkflowOutcome {
+    let counter = WordCounter::new(config.strategy, words.clone());
+    let selected_words =
+        Arc::new(TracedRwLock::new(Vec::<String>::new(), context.clone()).with_log(log.clone()));
+    let spawner = TaskSpawner::new(context.clone(), log).with_audit(audit);
+    let handshake = config.coordination == Coordination::Handshake;
+    let (notify, mut notified) = bounded::<()>(1);
+    let (acknowledge, mut acknowledged) = bounded::<()>(1);
+
+    let selector = spawner.spawn_named("word-selector", {
+        let selected_words = selected_words.clone();
+        move |scope| async move {
+            let mut selections = Vec::with_capacity(config.rounds);
+            for round in 0..config.rounds {
+                let mut rng = DeterministicRng::derived(config.seed, SELECTION, round as u64);
+                let word = words
+                    .choose(&mut rng)
+                    .expect("Corpus should not be empty")
+                    .to_string();
+                scope.record(format!("Selected word is: {}", word));
+                selections.push(word.clone());
+                selected_words.write(scope.info()).await.push(word);
+                if handshake {
+                    notify
+                        .send(())
+                        .await
+                        .expect("Word counter should take every round");
+                    acknowledged
+                        .recv()
+                        .await
+                        .expect("Word counter should acknowledge every round");
+                } else {
+                    scope.context().sleep(config.pause).await;
+                }
+            }
+            selections
+        }
+    });
+    let word_counter = spawner.spawn_named("word-counter", {
+        let selected_words = selected_words.clone();
+        move |scope| async move {
+            let mut counts = Vec::with_capacity(config.rounds);
+            let mut visible = Vec::with_capacity(config.rounds);
+            for _ in 0..config.rounds {
+                if handshake {
+                    notified
+                        .recv()
+                        .await
+                        .expect("Word selector should announce every round");
+                }
+                let word = {
+                    let selected = selected_words.read(scope.info()).await;
+                    visible.push(selected.len());
+                    selected.last().cloned()
+                };
+                if let Some(word) = word {
+                    let count = counter.count(&word);
+                    scope.record(format!(
+                        "The word '{}' appears {} times in the file.",
+                        word, count
+                    ));
+                    counts.push((word, count));
+                } else {
+                    scope.record("No word selected yet, skipping count.");
+                }
+                if handshake {
+                    acknowledge
+                        .send(())
+                        .await
+                        .expect("Word selector should wait for every round");
+                } else {
+                    scope.context().sleep(config.pause).await;
+                }
+            }
+            (counts, visible)
+        }
+    });
+
+    let selections = selector
+        .await
+        .expect("Word selector should run to completion");
+    let (counts, visible) = word_counter
+        .await
+        .expect("Word counter should run to completion");
+    WorkflowOutcome {
+        selections,
+        counts,
+        visible,
+        lock_events: selected_words.events(),
+    }
+}
+
 /// A CPU-bound task that never yields.
 ///
 /// This models a "bad citizen" task that can starve other work on a
@@ -67,15 +368,16 @@ pub fn greedy_task() {
 /// By sleeping briefly, it cooperates with the scheduler so other tasks can
 /// make progress. This shows why cooperative yielding matters.
 pub async fn cpu_cooperative(context: &impl Clock) {
+    cpu_cooperative_with(context, Budget::default()).await;
+}
+
+/// [`cpu_cooperative`], yielding as often as `budget` says.
+pub async fn cpu_cooperative_with(context: &impl Clock, mut budget: Budget) {
     println!("CPU-Coop: Starting computation");
     let mut result = 0u64;
     for i in 0..100_000_000 {
         result = result.wrapping_add(i);
-
-        // Yield every 10M iterations
-        if i % 10_000_000 == 0 {
-            context.sleep(Duration::from_micros(10)).await;
-        }
+        budget.maybe_yield(context).await;
     }
     println!("CPU-Coop: Done (result: {})", result);
 }
@@ -104,8 +406,60 @@ pub async fn delayed_work(context: &impl Clock) {
 #[cfg(test)]
 mod tasks_tests {
     use super::*;
+    #[cfg(feature = "deterministic-backend")]
+    use crate::audit::forbid_wall_clock;
+    use crate::rng::DeterministicRng;
+    #[cfg(feature = "tokio-backend")]
+    use commonware_runtime::tokio::{Config as TokioConfig, Runner as TokioRunner};
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+    #[cfg(feature = "tokio-backend")]
     use tokio::runtime::Runtime;
 
+    /// The sleeping and sharded workloads keep to the runtime: a run that
+    /// forbids the wall clock passes, and the audit records nothing at all.
+    #[cfg(feature = "deterministic-backend")]
+    #[test]
+    fn test_workloads_forbid_wall_clock() {
+        let words = Arc::new(read_file());
+        let findings = forbid_wall_clock(0, |context, audit| async move {
+            io_bound(&context).await;
+            delayed_work(&context).await;
+            count_word_occurrences_parallel(&context, "the", &words, 4).await;
+            audit.findings()
+        })
+        .unwrap();
+        assert!(findings.is_empty());
+    }
+
+    /// The word workflow passes a run that forbids the wall clock, in either
+    /// coordination mode, and reaches no other nondeterministic source.
+    #[cfg(all(feature = "tokio-backend", feature = "deterministic-backend"))]
+    #[test]
+    fn test_word_workflow_forbids_wall_clock() {
+        let words = Arc::new(read_file());
+        for coordination in [Coordination::Sleep, Coordination::Handshake] {
+            let config = WorkflowConfig::default().with_coordination(coordination);
+            let words = words.clone();
+            let (outcome, findings) = forbid_wall_clock(config.seed, |context, audit| async move {
+                let outcome = word_workflow(
+                    &context,
+                    words,
+                    config,
+                    EventLog::discarding(),
+                    audit.clone(),
+                )
+                .await;
+                (outcome, audit.findings())
+            })
+            .unwrap();
+            assert_eq!(outcome.selections.len(), config.rounds);
+            assert!(findings.is_empty());
+        }
+    }
+
     /// Ensures the corpus is present and non-empty.
     #[test]
     fn test_read_file() {
@@ -114,24 +468,191 @@ mod tasks_tests {
     }
 
     /// Verifies that random selection returns a word from the corpus.
+    #[cfg(feature = "tokio-backend")]
     #[test]
     fn test_select_random_word() {
+        let words = Arc::new(read_file());
+        let word = TokioRunner::default().start({
+            let words = words.clone();
+            |mut context| async move {
+                let mut rng = DeterministicRng::from_runtime(&mut context);
+                select_random_word(&words, &mut rng).await
+            }
+        });
+        assert!(words.words().any(|w| w == word));
+    }
+
+    /// A shared seeded stream makes a sequence of selections repeatable.
+    #[cfg(feature = "tokio-backend")]
+    #[test]
+    fn test_select_random_word_sequence() {
         let words = read_file();
-        let word = Runtime::new()
-            .unwrap()
-            .block_on(async { select_random_word(&words, None).await });
-        assert!(words.contains(&word));
+        let select = |seed| {
+            let mut rng = DeterministicRng::new(seed);
+            Runtime::new().unwrap().block_on(async {
+                let mut selected = vec![];
+                for _ in 0..5 {
+                    selected.push(select_random_word(&words, &mut rng).await);
+                }
+                selected
+            })
+        };
+
+        assert_eq!(select(12345), select(12345));
     }
 
     /// Verifies that counting a selected word yields a positive count.
     #[test]
     fn test_count_word_occurrences() {
         let words = read_file();
-        let count = Runtime::new().unwrap().block_on(async {
-            let word = select_random_word(&words, None).await;
-            let count = count_word_occurrences(&word, &words).await;
-            count
-        });
+        let count = DeterministicRunner::new(Config::default().with_seed(1)).start(
+            |mut context| async move {
+                let mut rng = DeterministicRng::from_runtime(&mut context);
+                let word = select_random_word(&words, &mut rng).await;
+                count_word_occurrences(&word, &words).await
+            },
+        );
         assert!(count > 0);
     }
+
+    /// Sharded counting agrees with the sequential count on both runtimes,
+    /// including with more shards than words.
+    #[cfg(feature = "tokio-backend")]
+    #[test]
+    fn test_count_word_occurrences_parallel() {
+        let words = Arc::new(read_file());
+        let expected = Runtime::new()
+            .unwrap()
+            .block_on(count_word_occurrences("the", &words));
+        for shards in [1, 7, words.len() + 3] {
+            let tokio = TokioRunner::default().start({
+                let words = words.clone();
+                |context| async move {
+                    count_word_occurrences_parallel(&context, "the", &words, shards).await
+                }
+            });
+            let deterministic = DeterministicRunner::new(Config::default().with_seed(1)).start({
+                let words = words.clone();
+                |context| async move {
+                    count_word_occurrences_parallel(&context, "the", &words, shards).await
+                }
+            });
+            assert_eq!(tokio, expected);
+            assert_eq!(deterministic, expected);
+        }
+    }
+
+    /// Both counting strategies report the same counts.
+    #[test]
+    fn test_word_counter_strategies_agree() {
+        let words = Arc::new(read_file());
+        let scan = WordCounter::new(CountStrategy::Scan, words.clone());
+        let indexed = WordCounter::new(CountStrategy::Indexed, words.clone());
+
+        for word in words.words().take(50) {
+            assert_eq!(scan.count(word), indexed.count(word));
+        }
+    }
+
+    /// The one workflow runs on both runtimes: the seed alone decides the
+    /// selections, every count is of a selected word, and on the
+    /// deterministic runtime the whole outcome repeats.
+    #[cfg(feature = "tokio-backend")]
+    #[test]
+    fn test_word_workflow() {
+        let words = Arc::new(read_file());
+        let config = WorkflowConfig::default().with_pause(Duration::from_millis(2));
+        let deterministic = |config: WorkflowConfig| {
+            let words = words.clone();
+            DeterministicRunner::new(Config::default().with_seed(config.seed)).start(
+                |context| async move {
+                    word_workflow(
+                        &context,
+                        words,
+                        config,
+                        EventLog::discarding(),
+                        NondeterminismAudit::disabled(),
+                    )
+                    .await
+                },
+            )
+        };
+        let tokio = TokioRunner::default().start({
+            let words = words.clone();
+            |context| async move {
+                word_workflow(
+                    &context,
+                    words,
+                    config,
+                    EventLog::discarding(),
+                    NondeterminismAudit::disabled(),
+                )
+                .await
+            }
+        });
+
+        let outcome = deterministic(config);
+        assert_eq!(outcome.selections.len(), 5);
+        assert_eq!(outcome.visible.len(), 5);
+        assert!(
+            outcome
+                .counts
+                .iter()
+                .all(|(word, count)| outcome.selections.contains(word) && *count > 0)
+        );
+        assert_eq!(deterministic(config), outcome);
+        assert_eq!(tokio.selections, outcome.selections);
+        assert_ne!(
+            deterministic(config.with_seed(8)).selections,
+            outcome.selections
+        );
+    }
+
+    /// Under the handshake every round is fresh, and on both runtimes: no
+    /// round depends on timing, so the counts follow from the seed alone.
+    #[cfg(feature = "tokio-backend")]
+    #[test]
+    fn test_word_workflow_handshake() {
+        let words = Arc::new(read_file());
+        let config = WorkflowConfig::default().with_coordination(Coordination::Handshake);
+        let deterministic = |runtime_seed| {
+            let words = words.clone();
+            DeterministicRunner::new(Config::default().with_seed(runtime_seed)).start(
+                |context| async move {
+                    word_workflow(
+                        &context,
+                        words,
+                        config,
+                        EventLog::discarding(),
+                        NondeterminismAudit::disabled(),
+                    )
+                    .await
+                },
+            )
+        };
+        let tokio = TokioRunner::new(TokioConfig::default().with_worker_threads(4)).start({
+            let words = words.clone();
+            |context| async move {
+                word_workflow(
+                    &context,
+                    words,
+                    config,
+                    EventLog::discarding(),
+                    NondeterminismAudit::disabled(),
+                )
+                .await
+            }
+        });
+
+        let outcome = deterministic(0);
+        for outcome in [&outcome, &tokio] {
+            assert_eq!(outcome.visible, [1, 2, 3, 4, 5]);
+            let counted: Vec<_> = outcome.counts.iter().map(|(word, _)| word).collect();
+            assert_eq!(counted, outcome.selections.iter().collect::<Vec<_>>());
+        }
+        assert_eq!(tokio.counts, outcome.counts);
+        for runtime_seed in 1..5 {
+            assert_eq!(deterministic(runtime_seed).counts, outcome.counts);
+        }
+    }
 }
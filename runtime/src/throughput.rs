@@ -0,0 +1,164 @@
+//! Raw spawn throughput: how many trivial tasks per second each runtime can
+//! spawn and drive to completion.
+//!
+//! The tasks do no work, so the number measures scheduler overhead only. The
+//! deterministic runtime trades some of that throughput for replayability;
+//! this is where the price shows up. Results serialize to JSON so a bench run
+//! can be diffed against the previous one.
+
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use commonware_runtime::{
+    Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+
+/// Spawn `count` sibling tasks that return immediately and wait for all of
+/// them. Returns how many completed.
+pub async fn spawn_trivial<S: Spawner>(context: &S, count: usize) -> usize {
+    let handles: Vec<_> = (0..count)
+        .map(|index| context.clone().spawn(move |_| async move { index }))
+        .collect();
+
+    let mut completed = 0;
+    for handle in handles {
+        handle.await.expect("Trivial task should run to completion");
+        completed += 1;
+    }
+    completed
+}
+
+#[derive(Clone, Debug)]
+pub struct ThroughputSample {
+    pub runtime: &'static str,
+    pub tasks: usize,
+    pub elapsed: Duration,
+}
+
+impl ThroughputSample {
+    /// Tasks completed per second, or `None` if the run was too short for
+    /// the clock to measure. No tasks is a rate of zero.
+    pub fn tasks_per_second(&self) -> Option<f64> {
+        match (self.tasks, self.elapsed) {
+            (0, _) => Some(0.0),
+            (_, Duration::ZERO) => None,
+            (tasks, elapsed) => Some(tasks as f64 / elapsed.as_secs_f64()),
+        }
+    }
+
+    /// The sample as a JSON object. A rate that could not be measured is
+    /// `null`, since JSON has no infinity.
+    pub fn to_json(&self) -> String {
+        let rate = match self.tasks_per_second() {
+            Some(rate) => format!("{:.1}", rate),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"runtime\":\"{}\",\"tasks\":{},\"elapsed_ns\":{},\"tasks_per_second\":{}}}",
+            self.runtime,
+            self.tasks,
+            self.elapsed.as_nanos(),
+            rate
+        )
+    }
+}
+
+/// Time spawning and completing `tasks` trivial tasks on Tokio with
+/// `worker_threads` workers. Wall-clock time is measured inside the runtime,
+/// so startup is not counted.
+pub fn measure_tokio(tasks: usize, worker_threads: usize) -> ThroughputSample {
+    let config = TokioConfig::default().with_worker_threads(worker_threads);
+    let elapsed = TokioRunner::new(config).start(|context| async move {
+        let start = Instant::now();
+        spawn_trivial(&context, tasks).await;
+        start.elapsed()
+    });
+    ThroughputSample {
+        runtime: "tokio",
+        tasks,
+        elapsed,
+    }
+}
+
+/// Same as [`measure_tokio`] on the deterministic runtime. Virtual time does
+/// not advance with CPU work, so this uses the wall clock.
+pub fn measure_deterministic(tasks: usize, seed: u64) -> ThroughputSample {
+    let elapsed =
+        DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| async move {
+            let start = Instant::now();
+            spawn_trivial(&context, tasks).await;
+            start.elapsed()
+        });
+    ThroughputSample {
+        runtime: "deterministic",
+        tasks,
+        elapsed,
+    }
+}
+
+/// Serialize samples as a JSON array, one object per line.
+pub fn to_json(samples: &[ThroughputSample]) -> String {
+    let mut json = String::from("[\n");
+    for (i, sample) in samples.iter().enumerate() {
+        let separator = if i + 1 < samples.len() { "," } else { "" };
+        writeln!(json, "  {}{}", sample.to_json(), separator).unwrap();
+    }
+    json.push(']');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every spawned task completes on both runtimes.
+    #[test]
+    fn test_all_tasks_complete() {
+        assert_eq!(measure_tokio(500, 2).tasks, 500);
+        assert_eq!(measure_deterministic(500, 1).tasks, 500);
+
+        let completed = TokioRunner::default()
+            .start(|context| async move { spawn_trivial(&context, 64).await });
+        assert_eq!(completed, 64);
+    }
+
+    /// The JSON export has one object per sample with the derived rate.
+    #[test]
+    fn test_to_json() {
+        let samples = [ThroughputSample {
+            runtime: "tokio",
+            tasks: 1000,
+            elapsed: Duration::from_millis(10),
+        }];
+
+        assert_eq!(
+            to_json(&samples),
+            "[\n  {\"runtime\":\"tokio\",\"tasks\":1000,\"elapsed_ns\":10000000,\"tasks_per_second\":100000.0}\n]"
+        );
+    }
+
+    /// No tasks, or no measurable time, still serializes as valid JSON.
+    #[test]
+    fn test_to_json_degenerate() {
+        let sample = |tasks, elapsed| ThroughputSample {
+            runtime: "deterministic",
+            tasks,
+            elapsed,
+        };
+
+        assert!(
+            sample(0, Duration::ZERO)
+                .to_json()
+                .ends_with("\"tasks_per_second\":0.0}")
+        );
+        assert!(
+            sample(10, Duration::ZERO)
+                .to_json()
+                .ends_with("\"tasks_per_second\":null}")
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Running long waits faster than real time.
+//!
+//! A workload that sleeps for seconds takes seconds to test on Tokio, whose
+//! clock is the wall clock. The deterministic runtime never has that
+//! problem: when every task is waiting it jumps virtual time straight to the
+//! next timer, so a two-second sleep costs nothing. [`Accelerate`] gives both
+//! runtimes the same entry point. A deterministic context accelerates to
+//! itself, already skipping idle time; a Tokio context accelerates to a
+//! [`Scaled`] clock whose sleeps are divided by the factor and whose
+//! reported time runs that many times faster, so durations measured on it
+//! still read as the workload intended.
+//!
+//! Only the clock is accelerated. Work the workload does between sleeps
+//! takes as long as it takes, and is multiplied by the factor when measured
+//! on a [`Scaled`] clock.
+
+use std::{
+    future::Future,
+    time::{Duration, SystemTime},
+};
+
+use commonware_runtime::{Clock, deterministic, tokio};
+use governor::clock::{Clock as GClock, ReasonablyRealtime};
+
+use crate::stats::elapsed_since;
+
+/// A runtime context that can hand out a clock on which long waits are
+/// cheap.
+pub trait Accelerate {
+    type Clock: Clock;
+
+    /// A clock on which `factor` seconds of waiting cost at most one second
+    /// of wall-clock time.
+    fn accelerate(self, factor: u32) -> Self::Clock;
+}
+
+/// Virtual time already advances to the next timer whenever every task is
+/// idle, so no scaling is needed.
+impl Accelerate for deterministic::Context {
+    type Clock = Self;
+
+    fn accelerate(self, factor: u32) -> Self {
+        assert!(factor > 0, "Time cannot be stopped");
+        self
+    }
+}
+
+impl Accelerate for tokio::Context {
+    type Clock = Scaled<Self>;
+
+    fn accelerate(self, factor: u32) -> Scaled<Self> {
+        Scaled::new(self, factor)
+    }
+}
+
+/// A clock running `factor` times faster than the one it wraps, measured
+/// from when it was created.
+#[derive(Clone, Debug)]
+pub struct Scaled<C> {
+    inner: C,
+    origin: SystemTime,
+    factor: u32,
+}
+
+impl<C: Clock> Scaled<C> {
+    pub fn new(inner: C, factor: u32) -> Self {
+        assert!(factor > 0, "Time cannot be stopped");
+        Self {
+            origin: inner.current(),
+            inner,
+            factor,
+        }
+    }
+
+    pub fn factor(&self) -> u32 {
+        self.factor
+    }
+}
+
+impl<C: Clock> GClock for Scaled<C> {
+    type Instant = SystemTime;
+
+    fn now(&self) -> SystemTime {
+        self.current()
+    }
+}
+
+impl<C: Clock> ReasonablyRealtime for Scaled<C> {}
+
+impl<C: Clock> Clock for Scaled<C> {
+    fn current(&self) -> SystemTime {
+        self.origin + elapsed_since(&self.inner, self.origin).saturating_mul(self.factor)
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send + 'static {
+        self.inner.sleep(duration / self.factor)
+    }
+
+    fn sleep_until(&self, deadline: SystemTime) -> impl Future<Output = ()> + Send + 'static {
+        let remaining = deadline
+            .duration_since(self.current())
+            .unwrap_or(Duration::ZERO);
+        self.sleep(remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+        tokio::{Config as TokioConfig, Runner as TokioRunner},
+    };
+
+    use super::*;
+
+    /// A two-second wait on an accelerated Tokio clock takes a fraction of
+    /// that in real time but still reads as two seconds.
+    #[test]
+    fn test_tokio_sleep_scaled() {
+        let wall = Instant::now();
+        let measured = TokioRunner::new(TokioConfig::default()).start(|context| async move {
+            let clock = context.accelerate(100);
+            let start = clock.current();
+            clock.sleep(Duration::from_secs(2)).await;
+            elapsed_since(&clock, start)
+        });
+
+        assert!(wall.elapsed() < Duration::from_secs(1));
+        assert!(measured >= Duration::from_secs(2));
+    }
+
+    /// The deterministic clock is virtual already: the same wait takes no
+    /// real time and exactly two seconds of virtual time.
+    #[test]
+    fn test_deterministic_sleep_virtual() {
+        let wall = Instant::now();
+        let measured =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+                let clock = context.accelerate(100);
+                let start = clock.current();
+                clock.sleep(Duration::from_secs(2)).await;
+                elapsed_since(&clock, start)
+            });
+
+        assert!(wall.elapsed() < Duration::from_secs(1));
+        assert_eq!(measured, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,426 @@
+//! Recording what tasks did, in the order they did it.
+//!
+//! The demos print their progress, which is fine for a human watching one run
+//! but useless for comparing many. An [`EventLog`] captures the same lines as
+//! data: each event names the task that emitted it, and the order of the log
+//! is the interleaving the scheduler actually chose. Two logs with the same
+//! [`fingerprint`](EventLog::fingerprint) saw the same schedule. When they
+//! did not, [`EventLog::diff`] shows where: the shared prefix, the first step
+//! that differs, and which tasks' own sequences of events changed as opposed
+//! to merely being interleaved differently.
+//!
+//! A log given a clock with [`EventLog::with_clock`] also stamps each event
+//! with the time it was recorded, and [`EventLog::query`] picks events out by
+//! task, kind, time and what they mention; see [`query`](crate::query).
+
+#[cfg(feature = "runtime")]
+use std::time::SystemTime;
+use std::{
+    fmt,
+    sync::{Arc, Mutex, mpsc::Sender},
+    time::Duration,
+};
+
+#[cfg(feature = "runtime")]
+use commonware_runtime::Clock;
+
+#[cfg(feature = "runtime")]
+use crate::stats::elapsed_since;
+use crate::{
+    hooks::Hooks,
+    parallel_determinism::hash::Fnv,
+    query::{Entry, Query},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceEvent {
+    pub task: String,
+    pub message: String,
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.task, self.message)
+    }
+}
+
+/// An event and when it was recorded, if the recording handle had a clock.
+type Stamped = (TraceEvent, Option<Duration>);
+
+/// A shared, append-only log of events. Clones append to the same log.
+#[derive(Clone, Default)]
+pub struct EventLog {
+    events: Arc<Mutex<Vec<Stamped>>>,
+    echo: bool,
+    sink: Option<Sender<TraceEvent>>,
+    hooks: Hooks<TraceEvent>,
+    discard: bool,
+    clock: Option<Arc<dyn Fn() -> Duration + Send + Sync>>,
+}
+
+impl EventLog {
+    /// A log that records silently.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A log that also prints every event as it is recorded, the way the
+    /// demos always have.
+    pub fn echo() -> Self {
+        Self {
+            echo: true,
+            ..Self::default()
+        }
+    }
+
+    /// A log that also sends every event to `sink` as it is recorded, for
+    /// consumers that watch a run live. Events recorded after the receiver is
+    /// dropped are still logged, just not sent.
+    pub fn streaming(sink: Sender<TraceEvent>) -> Self {
+        Self {
+            sink: Some(sink),
+            ..Self::default()
+        }
+    }
+
+    /// A log that keeps nothing. Echo, the sink and hooks still see every
+    /// event, so a run can be watched without holding its whole trace.
+    pub fn discarding() -> Self {
+        Self {
+            discard: true,
+            ..Self::default()
+        }
+    }
+
+    /// Also call `hook` on every event as it is recorded. Hooks run in log
+    /// order, under the log's lock, so they must not record into the same
+    /// log.
+    pub fn with_hook(mut self, hook: impl Fn(&TraceEvent) + Send + Sync + 'static) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Also stamp every event with the time on `clock` since now. Under
+    /// the deterministic runtime that is virtual time. Only this handle and
+    /// clones taken from it stamp; events recorded through earlier clones
+    /// have no time.
+    #[cfg(feature = "runtime")]
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        let start: SystemTime = clock.current();
+        self.clock = Some(Arc::new(move || elapsed_since(&clock, start)));
+        self
+    }
+
+    pub fn record(&self, task: impl Into<String>, message: impl Into<String>) {
+        let event = TraceEvent {
+            task: task.into(),
+            message: message.into(),
+        };
+        if self.echo {
+            println!("{}", event);
+        }
+        // Send under the lock so the stream sees events in log order.
+        let mut events = self.events.lock().unwrap();
+        if let Some(sink) = &self.sink {
+            let _ = sink.send(event.clone());
+        }
+        self.hooks.call(&event);
+        if !self.discard {
+            let at = self.clock.as_ref().map(|clock| clock());
+            events.push((event, at));
+        }
+    }
+
+    pub fn events(&self) -> Vec<TraceEvent> {
+        let events = self.events.lock().unwrap();
+        events.iter().map(|(event, _)| event.clone()).collect()
+    }
+
+    /// Select events from the log as it is now.
+    pub fn query(&self) -> Query {
+        let events = self.events.lock().unwrap();
+        Query::new(
+            events
+                .iter()
+                .enumerate()
+                .map(|(index, (event, at))| Entry {
+                    index,
+                    at: *at,
+                    event: event.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Compare this log with `other`, step by step. This log is the left
+    /// side of the diff.
+    pub fn diff(&self, other: &EventLog) -> LogDiff {
+        LogDiff::new(self.events(), other.events())
+    }
+
+    /// A digest of the whole interleaving. Equal logs always produce equal
+    /// fingerprints, on every machine.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv::new();
+        for (event, _) in self.events.lock().unwrap().iter() {
+            hasher.update(&(event.task.len() as u64).to_le_bytes());
+            hasher.update(event.task.as_bytes());
+            hasher.update(&(event.message.len() as u64).to_le_bytes());
+            hasher.update(event.message.as_bytes());
+        }
+        hasher.finish()
+    }
+}
+
+/// Events shown either side of the first divergence.
+const CONTEXT: usize = 3;
+
+/// Two event sequences compared step by step. `Display` renders them side by
+/// side around the first divergence, followed by one lane per task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogDiff {
+    pub left: Vec<TraceEvent>,
+    pub right: Vec<TraceEvent>,
+    /// Steps at the start on which both sides recorded the same event.
+    pub common_prefix: usize,
+}
+
+/// How one task's own events compare across the two sides.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lane {
+    pub task: String,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+impl Lane {
+    /// The index of the first of this task's events that differs.
+    pub fn divergence(&self) -> Option<usize> {
+        let common = self
+            .left
+            .iter()
+            .zip(&self.right)
+            .take_while(|(left, right)| left == right)
+            .count();
+        (common < self.left.len().max(self.right.len())).then_some(common)
+    }
+}
+
+impl LogDiff {
+    pub fn new(left: Vec<TraceEvent>, right: Vec<TraceEvent>) -> Self {
+        let common_prefix = left
+            .iter()
+            .zip(&right)
+            .take_while(|(left, right)| left == right)
+            .count();
+        Self {
+            left,
+            right,
+            common_prefix,
+        }
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.divergence().is_none()
+    }
+
+    /// The first step at which the sides differ, if any.
+    pub fn divergence(&self) -> Option<usize> {
+        (self.common_prefix < self.left.len().max(self.right.len())).then_some(self.common_prefix)
+    }
+
+    /// Every task's events on each side, in order of first appearance.
+    pub fn lanes(&self) -> Vec<Lane> {
+        let mut lanes: Vec<Lane> = Vec::new();
+        for (side, events) in [(0, &self.left), (1, &self.right)] {
+            for event in events {
+                let index = match lanes.iter().position(|lane| lane.task == event.task) {
+                    Some(index) => index,
+                    None => {
+                        lanes.push(Lane {
+                            task: event.task.clone(),
+                            left: vec![],
+                            right: vec![],
+                        });
+                        lanes.len() - 1
+                    }
+                };
+                let lane = &mut lanes[index];
+                let messages = if side == 0 {
+                    &mut lane.left
+                } else {
+                    &mut lane.right
+                };
+                messages.push(event.message.clone());
+            }
+        }
+        lanes
+    }
+}
+
+impl fmt::Display for LogDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(divergence) = self.divergence() else {
+            return writeln!(f, "identical: {} events", self.left.len());
+        };
+        writeln!(
+            f,
+            "common prefix: {} events; first divergence at step {}",
+            self.common_prefix, divergence
+        )?;
+
+        let first = divergence.saturating_sub(CONTEXT);
+        let last = (divergence + CONTEXT + 1).min(self.left.len().max(self.right.len()));
+        let show = |events: &[TraceEvent], step: usize| {
+            events
+                .get(step)
+                .map_or("<finished>".to_string(), |event| event.to_string())
+        };
+        let width = (first..last)
+            .map(|step| show(&self.left, step).len())
+            .max()
+            .unwrap_or(0);
+        if first > 0 {
+            writeln!(f, "  ... {} matching events", first)?;
+        }
+        for step in first..last {
+            let (left, right) = (show(&self.left, step), show(&self.right, step));
+            let marker = if step == divergence {
+                '>'
+            } else if left == right {
+                ' '
+            } else {
+                '!'
+            };
+            writeln!(
+                f,
+                "{} {:>5} | {:<width$} | {}",
+                marker,
+                step,
+                left,
+                right,
+                width = width
+            )?;
+        }
+        let remaining = self.left.len().max(self.right.len()) - last;
+        if remaining > 0 {
+            writeln!(f, "  ... {} more steps", remaining)?;
+        }
+
+        writeln!(f, "lanes:")?;
+        for lane in self.lanes() {
+            match lane.divergence() {
+                None => writeln!(f, "  {}: same {} events", lane.task, lane.left.len())?,
+                Some(index) => writeln!(
+                    f,
+                    "  {}: differs at its event {}: {} | {}",
+                    lane.task,
+                    index,
+                    lane.left.get(index).map_or("<none>", String::as_str),
+                    lane.right.get(index).map_or("<none>", String::as_str)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clones share one log, and the fingerprint tracks order, not just
+    /// content.
+    #[test]
+    fn test_fingerprint_tracks_order() {
+        let first = EventLog::new();
+        let clone = first.clone();
+        first.record("a", "start");
+        clone.record("b", "start");
+
+        let second = EventLog::new();
+        second.record("b", "start");
+        second.record("a", "start");
+
+        assert_eq!(first.len(), 2);
+        assert_eq!(first.events()[1].to_string(), "b: start");
+        assert_ne!(first.fingerprint(), second.fingerprint());
+        assert_eq!(first.fingerprint(), clone.fingerprint());
+    }
+
+    /// Hooks see every event in order, even when the log keeps none.
+    #[test]
+    fn test_hooks_see_discarded_events() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = EventLog::discarding().with_hook({
+            let seen = seen.clone();
+            move |event| seen.lock().unwrap().push(event.to_string())
+        });
+        log.record("a", "start");
+        log.record("b", "start");
+
+        assert!(log.is_empty());
+        assert_eq!(*seen.lock().unwrap(), ["a: start", "b: start"]);
+    }
+
+    fn log(events: &[(&str, &str)]) -> EventLog {
+        let log = EventLog::new();
+        for (task, message) in events {
+            log.record(*task, *message);
+        }
+        log
+    }
+
+    /// The diff finds the shared prefix and the first differing step, and
+    /// tells a reordering apart from a changed event.
+    #[test]
+    fn test_diff_locates_divergence() {
+        let left = log(&[("a", "start"), ("b", "start"), ("a", "done"), ("b", "done")]);
+        let right = log(&[
+            ("a", "start"),
+            ("b", "start"),
+            ("b", "done"),
+            ("a", "failed"),
+        ]);
+        let diff = left.diff(&right);
+
+        assert_eq!(diff.common_prefix, 2);
+        assert_eq!(diff.divergence(), Some(2));
+        let lanes = diff.lanes();
+        assert_eq!(lanes[0].task, "a");
+        assert_eq!(lanes[0].divergence(), Some(1));
+        assert_eq!(lanes[1].divergence(), None);
+
+        let text = diff.to_string();
+        assert!(text.starts_with("common prefix: 2 events; first divergence at step 2"));
+        assert!(text.contains(">     2 | a: done  | b: done\n"));
+        assert!(text.contains("  a: differs at its event 1: done | failed"));
+        assert!(text.contains("  b: same 2 events"));
+    }
+
+    /// Equal logs are identical, and a log that stops early diverges where
+    /// it stopped.
+    #[test]
+    fn test_diff_identical_and_truncated() {
+        let full = log(&[("a", "start"), ("a", "done")]);
+
+        assert!(full.diff(&full.clone()).is_identical());
+        assert_eq!(full.diff(&full).to_string(), "identical: 2 events\n");
+        let truncated = log(&[("a", "start")]);
+        let diff = full.diff(&truncated);
+        assert_eq!(diff.divergence(), Some(1));
+        assert!(
+            diff.to_string()
+                .contains(">     1 | a: done  | <finished>\n")
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! A compact binary format for event logs, read and written as a stream.
+//!
+//! A large simulation records millions of events, and as JSON each one
+//! repeats its task name and, as often as not, a message some other event
+//! already had. The binary format stores each distinct task name and message
+//! once: the first time one appears it is written out and given the next
+//! index in its table, and every later appearance is just that index.
+//! Indices and lengths are LEB128 varints, so an event that repeats a known
+//! task and message takes two or three bytes.
+//!
+//! The layout is the magic bytes `DTRC`, a version byte, then one record per
+//! event: the task, then the message, each either an index below the size of
+//! its table or exactly the size of the table followed by a length-prefixed
+//! UTF-8 string that becomes that index. A [`TraceWriter`] appends records
+//! as events come in, for instance from an [`EventLog`] hook, and a
+//! [`TraceReader`] yields them one at a time, so neither side holds the
+//! whole trace. With the `json` feature, [`json_to_binary`] and
+//! [`binary_to_json`] convert to and from the JSON form, an array of
+//! `{"task", "message"}` objects.
+//!
+//! [`EventLog`]: crate::trace::EventLog
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use crate::trace::TraceEvent;
+
+const MAGIC: &[u8; 4] = b"DTRC";
+const VERSION: u8 = 1;
+
+/// Strings seen so far, by index and by value.
+#[derive(Default)]
+struct Table {
+    strings: Vec<String>,
+    indices: HashMap<String, u64>,
+}
+
+/// Writes events to `W` in the binary format.
+pub struct TraceWriter<W: Write> {
+    inner: W,
+    tasks: Table,
+    messages: Table,
+}
+
+impl<W: Write> TraceWriter<W> {
+    /// Start a trace by writing the header to `inner`.
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(MAGIC)?;
+        inner.write_all(&[VERSION])?;
+        Ok(Self {
+            inner,
+            tasks: Table::default(),
+            messages: Table::default(),
+        })
+    }
+
+    pub fn write(&mut self, event: &TraceEvent) -> io::Result<()> {
+        write_string(&mut self.inner, &mut self.tasks, &event.task)?;
+        write_string(&mut self.inner, &mut self.messages, &event.message)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Flush and hand back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+}
+
+fn write_string(out: &mut impl Write, table: &mut Table, value: &str) -> io::Result<()> {
+    if let Some(&index) = table.indices.get(value) {
+        return write_varint(out, index);
+    }
+    let index = table.strings.len() as u64;
+    write_varint(out, index)?;
+    write_varint(out, value.len() as u64)?;
+    out.write_all(value.as_bytes())?;
+    table.indices.insert(value.to_string(), index);
+    table.strings.push(value.to_string());
+    Ok(())
+}
+
+pub(crate) fn write_varint(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Reads events from `R` in the binary format, one record at a time.
+pub struct TraceReader<R: Read> {
+    inner: R,
+    tasks: Vec<String>,
+    messages: Vec<String>,
+}
+
+impl<R: Read> TraceReader<R> {
+    /// Check the header of the trace in `inner`.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut header = [0; 5];
+        inner.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a binary trace"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid(format!("unsupported trace version {}", header[4])));
+        }
+        Ok(Self {
+            inner,
+            tasks: Vec::new(),
+            messages: Vec::new(),
+        })
+    }
+
+    /// The next event, or `None` at a clean end of the trace. A trace that
+    /// ends partway through a record is an error.
+    fn read_event(&mut self) -> io::Result<Option<TraceEvent>> {
+        let Some(task) = read_varint(&mut self.inner, true)? else {
+            return Ok(None);
+        };
+        let task = read_string(&mut self.inner, &mut self.tasks, task)?;
+        let message = read_varint(&mut self.inner, false)?
+            .expect("A read off a record boundary should never end cleanly");
+        let message = read_string(&mut self.inner, &mut self.messages, message)?;
+        Ok(Some(TraceEvent { task, message }))
+    }
+}
+
+impl<R: Read> Iterator for TraceReader<R> {
+    type Item = io::Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_event().transpose()
+    }
+}
+
+fn read_string(input: &mut impl Read, table: &mut Vec<String>, index: u64) -> io::Result<String> {
+    let index = index as usize;
+    if index < table.len() {
+        return Ok(table[index].clone());
+    }
+    if index > table.len() {
+        return Err(invalid(format!(
+            "reference to string {} before it was defined",
+            index
+        )));
+    }
+    let value =
+        String::from_utf8(read_bytes(input)?).map_err(|_| invalid("string is not UTF-8"))?;
+    table.push(value.clone());
+    Ok(value)
+}
+
+/// Read a length-prefixed byte string. The length comes from the input, so
+/// the bytes are read as they arrive rather than allocated up front: a
+/// corrupt length runs into the end of the input instead of into memory.
+pub(crate) fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len =
+        read_varint(input, false)?.expect("A read off a record boundary should never end cleanly");
+    let mut bytes = Vec::new();
+    input.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+/// Read a varint. With `at_boundary`, end of input before its first byte is
+/// a clean end and gives `None`; anywhere else it is an error.
+pub(crate) fn read_varint(input: &mut impl Read, at_boundary: bool) -> io::Result<Option<u64>> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        if input.read(&mut byte)? == 0 {
+            if at_boundary && shift == 0 {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // The tenth byte holds only the top bit; anything more overflows.
+        if shift == 63 && byte[0] > 1 {
+            return Err(invalid("varint is too long"));
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(invalid("varint is too long"))
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Write `events` to `out` as a whole binary trace.
+pub fn write_binary<'a>(
+    events: impl IntoIterator<Item = &'a TraceEvent>,
+    out: impl Write,
+) -> io::Result<()> {
+    let mut writer = TraceWriter::new(out)?;
+    for event in events {
+        writer.write(event)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Read a whole binary trace from `input`.
+pub fn read_binary(input: impl Read) -> io::Result<Vec<TraceEvent>> {
+    TraceReader::new(input)?.collect()
+}
+
+/// Convert the JSON form of a trace to the binary form, writing to `out`.
+#[cfg(feature = "json")]
+pub fn json_to_binary(json: &str, out: impl Write) -> io::Result<()> {
+    let events: Vec<TraceEvent> = serde_json::from_str(json)?;
+    write_binary(&events, out)
+}
+
+/// Convert a binary trace read from `input` to the JSON form.
+#[cfg(feature = "json")]
+pub fn binary_to_json(input: impl Read) -> io::Result<String> {
+    Ok(serde_json::to_string(&read_binary(input)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(task: &str, message: &str) -> TraceEvent {
+        TraceEvent {
+            task: task.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// A long, repetitive trace like the simulations produce.
+    fn repetitive() -> Vec<TraceEvent> {
+        (0..1_000)
+            .map(|step| event(&format!("node {}", step % 8), "polled"))
+            .chain([event("node 0", "done ✓")])
+            .collect()
+    }
+
+    /// Events come back as they went in, and repeats cost a couple of bytes
+    /// each.
+    #[test]
+    fn test_round_trip() {
+        let events = repetitive();
+        let mut bytes = Vec::new();
+        write_binary(&events, &mut bytes).unwrap();
+
+        assert_eq!(read_binary(bytes.as_slice()).unwrap(), events);
+        assert!(bytes.len() < 3 * events.len());
+        let mut empty = Vec::new();
+        write_binary(&[], &mut empty).unwrap();
+        assert_eq!(read_binary(empty.as_slice()).unwrap(), []);
+    }
+
+    /// The reader yields events as it reaches them; a cut-off record and a
+    /// foreign file are errors, not short traces.
+    #[test]
+    fn test_streaming_and_errors() {
+        let mut bytes = Vec::new();
+        write_binary(&repetitive(), &mut bytes).unwrap();
+        let mut reader = TraceReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), event("node 0", "polled"));
+
+        let truncated = &bytes[..bytes.len() - 2];
+        let error = read_binary(truncated).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let error = TraceReader::new(&b"[{\"task\""[..]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// A string whose length runs past the end of the input, or a varint
+    /// too large for 64 bits, is rejected without allocating what it claims.
+    #[test]
+    fn test_corrupt_lengths() {
+        let mut huge = b"DTRC\x01\x00".to_vec();
+        write_varint(&mut huge, u64::MAX).unwrap();
+        huge.extend_from_slice(b"short");
+        let error = read_binary(huge.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut max = Vec::new();
+        write_varint(&mut max, u64::MAX).unwrap();
+        assert_eq!(max.len(), 10);
+        assert_eq!(
+            read_varint(&mut max.as_slice(), false).unwrap(),
+            Some(u64::MAX)
+        );
+        *max.last_mut().unwrap() = 0x02;
+        let error = read_varint(&mut max.as_slice(), false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// The binary form converts to JSON and back unchanged, in a fraction
+    /// of the space.
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_conversion() {
+        let events = repetitive();
+        let json = serde_json::to_string(&events).unwrap();
+        let mut bytes = Vec::new();
+        json_to_binary(&json, &mut bytes).unwrap();
+
+        assert_eq!(binary_to_json(bytes.as_slice()).unwrap(), json);
+        assert!(bytes.len() * 5 < json.len());
+    }
+}
@@ -0,0 +1,436 @@
+//! A terminal dashboard that shows a block's tasks move through execution.
+//!
+//! Built with the `tui` feature. [`watch_deterministic`] and [`watch_tokio`]
+//! run the same generated block with the parallel executor on one runtime
+//! and draw, as it runs, every task by state (waiting on a dependency, ready,
+//! running, done), the level being executed, the time on the runtime's clock
+//! and the most recent trace events. Watching both side by side is the
+//! quickest way to see what the other modules describe: the deterministic
+//! run plays out identically every time, the Tokio one does not.
+//!
+//! Execution is far faster than anyone can read, so the dashboard replays
+//! the event stream at a fixed pace rather than at the speed it arrives.
+//! Press `q` to quit.
+
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread,
+    time::Duration,
+};
+
+use commonware_runtime::{
+    Clock, Runner, Spawner,
+    deterministic::{Config, Runner as DeterministicRunner},
+    tokio::{Config as TokioConfig, Runner as TokioRunner},
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode},
+    layout::{Constraint, Layout},
+    widgets::{Block, List, Paragraph},
+};
+
+use crate::{
+    parallel_determinism::{
+        dep_graph::DependencyGraph,
+        executor::ParallelExecutor,
+        generator::{BlockSpec, generate_tasks},
+        middleware::Logging,
+        state::{LatencyStorage, MemoryStorage},
+        types::TaskId,
+    },
+    stats::elapsed_since,
+    trace::{EventLog, TraceEvent},
+};
+
+/// The executor's trace label, under which it records level assignments.
+const SCHEDULER_LABEL: &str = "scheduler";
+
+/// Label of the clock samples interleaved with the trace.
+const CLOCK_LABEL: &str = "clock";
+
+/// How often the runtime's clock is sampled into the trace.
+const CLOCK_TICK: Duration = Duration::from_millis(1);
+
+/// How many trace events the dashboard keeps on screen.
+const RECENT_EVENTS: usize = 8;
+
+/// Time between replayed events.
+const FRAME: Duration = Duration::from_millis(80);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    /// Some dependency has not finished.
+    Waiting,
+    /// Every dependency has finished, but the task has not started.
+    Ready,
+    Running,
+    Done,
+}
+
+impl TaskState {
+    const ALL: [TaskState; 4] = [
+        TaskState::Waiting,
+        TaskState::Ready,
+        TaskState::Running,
+        TaskState::Done,
+    ];
+}
+
+/// What the dashboard knows about a run, rebuilt from its trace.
+pub struct Dashboard {
+    title: String,
+    names: Vec<String>,
+    dependencies: Vec<Vec<TaskId>>,
+    states: Vec<TaskState>,
+    level: Option<usize>,
+    elapsed: Duration,
+    recent: VecDeque<TraceEvent>,
+}
+
+impl Dashboard {
+    /// A dashboard for a run of `graph`, before anything has started.
+    pub fn new(title: impl Into<String>, graph: &DependencyGraph) -> Self {
+        let dependencies: Vec<Vec<TaskId>> = (0..graph.tasks.len())
+            .map(|id| {
+                graph
+                    .dependencies
+                    .get(&id)
+                    .map(|deps| deps.iter().copied().collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let states = dependencies
+            .iter()
+            .map(|deps| match deps.is_empty() {
+                true => TaskState::Ready,
+                false => TaskState::Waiting,
+            })
+            .collect();
+        Self {
+            title: title.into(),
+            names: graph.tasks.iter().map(|task| task.name.clone()).collect(),
+            dependencies,
+            states,
+            level: None,
+            elapsed: Duration::ZERO,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self, task: TaskId) -> TaskState {
+        self.states[task]
+    }
+
+    /// The level being executed, once the first one has been scheduled.
+    pub fn level(&self) -> Option<usize> {
+        self.level
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.states.iter().all(|&state| state == TaskState::Done)
+    }
+
+    /// Apply one event from the run's trace: a clock sample, a level
+    /// assignment, or a task starting or finishing.
+    pub fn observe(&mut self, event: TraceEvent) {
+        if event.task == CLOCK_LABEL {
+            if let Ok(micros) = event.message.parse() {
+                self.elapsed = Duration::from_micros(micros);
+            }
+            return;
+        }
+        if event.task == SCHEDULER_LABEL
+            && let Some(level) = event
+                .message
+                .strip_prefix("level ")
+                .and_then(|rest| rest.split(' ').next())
+                .and_then(|level| level.parse().ok())
+        {
+            self.level = Some(level);
+        }
+        if event.message == "started" {
+            self.transition(
+                &event.task,
+                &[TaskState::Waiting, TaskState::Ready],
+                TaskState::Running,
+            );
+        } else if event.message.starts_with("ok:") || event.message.starts_with("failed:") {
+            self.transition(&event.task, &[TaskState::Running], TaskState::Done);
+            self.release();
+        }
+        if self.recent.len() == RECENT_EVENTS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(event);
+    }
+
+    /// Move the first task named `name` that is in one of `from` to `to`.
+    /// Names need not be unique; tasks sharing one go through in id order.
+    fn transition(&mut self, name: &str, from: &[TaskState], to: TaskState) {
+        if let Some(id) = (0..self.names.len())
+            .find(|&id| self.names[id] == name && from.contains(&self.states[id]))
+        {
+            self.states[id] = to;
+        }
+    }
+
+    /// Mark waiting tasks whose dependencies have all finished as ready.
+    fn release(&mut self) {
+        for id in 0..self.states.len() {
+            if self.states[id] == TaskState::Waiting
+                && self.dependencies[id]
+                    .iter()
+                    .all(|&dep| self.states[dep] == TaskState::Done)
+            {
+                self.states[id] = TaskState::Ready;
+            }
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let [header, columns, events] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(RECENT_EVENTS as u16 + 2),
+        ])
+        .areas(frame.area());
+
+        let level = match self.level {
+            Some(level) => level.to_string(),
+            None => "-".to_string(),
+        };
+        let summary = format!(
+            "level {}   clock {:?}   {}",
+            level,
+            self.elapsed,
+            if self.is_finished() {
+                "finished"
+            } else {
+                "running"
+            }
+        );
+        frame.render_widget(
+            Paragraph::new(summary)
+                .block(Block::bordered().title(format!("{} (q to quit)", self.title))),
+            header,
+        );
+
+        let areas = Layout::horizontal([Constraint::Ratio(1, 4); 4]).split(columns);
+        for (state, area) in TaskState::ALL.into_iter().zip(areas.iter()) {
+            let names: Vec<&str> = (0..self.names.len())
+                .filter(|&id| self.states[id] == state)
+                .map(|id| self.names[id].as_str())
+                .collect();
+            let title = format!("{:?} ({})", state, names.len());
+            frame.render_widget(
+                List::new(names).block(Block::bordered().title(title)),
+                *area,
+            );
+        }
+
+        let recent: Vec<String> = self.recent.iter().map(|event| event.to_string()).collect();
+        frame.render_widget(
+            List::new(recent).block(Block::bordered().title("Recent events")),
+            events,
+        );
+    }
+}
+
+/// Run `graph` on `context`, logging into `log`, with the clock sampled into
+/// the same log so times line up with the events around them.
+async fn simulate<S: Spawner + Clock>(
+    context: &S,
+    graph: &DependencyGraph,
+    latency: Duration,
+    log: &EventLog,
+) {
+    let done = Arc::new(AtomicBool::new(false));
+    let clock = context.clone().spawn({
+        let done = done.clone();
+        let log = log.clone();
+        move |context| async move {
+            let start = context.current();
+            loop {
+                let elapsed = elapsed_since(&context, start);
+                log.record(CLOCK_LABEL, elapsed.as_micros().to_string());
+                if done.load(Ordering::SeqCst) {
+                    break;
+                }
+                context.sleep(CLOCK_TICK).await;
+            }
+        }
+    });
+    let store = LatencyStorage::new(MemoryStorage::new(), latency);
+    ParallelExecutor::new()
+        .with_trace(log.clone())
+        .with_middleware(Logging::new(log.clone()))
+        .execute_with_state(context, graph, &store)
+        .await;
+    done.store(true, Ordering::SeqCst);
+    clock.await.expect("Clock sampler should run to completion");
+}
+
+/// Draw `dashboard` until the user quits, applying one event from `events`
+/// per frame.
+fn show(
+    terminal: &mut DefaultTerminal,
+    mut dashboard: Dashboard,
+    events: Receiver<TraceEvent>,
+) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| dashboard.render(frame))?;
+        if event::poll(FRAME)?
+            && let Event::Key(key) = event::read()?
+            && key.code == KeyCode::Char('q')
+        {
+            return Ok(());
+        }
+        // Clock samples are not worth a frame of their own.
+        while let Ok(event) = events.try_recv() {
+            let is_clock = event.task == CLOCK_LABEL;
+            dashboard.observe(event);
+            if !is_clock {
+                break;
+            }
+        }
+    }
+}
+
+/// Run the block on a background thread with `start` and show it.
+fn watch(
+    title: String,
+    spec: &BlockSpec,
+    start: impl FnOnce(DependencyGraph, EventLog) + Send + 'static,
+) -> io::Result<()> {
+    let graph = DependencyGraph::from_tasks(generate_tasks(spec));
+    let dashboard = Dashboard::new(title, &graph);
+    let (sink, events) = mpsc::channel();
+    let run = thread::spawn(move || start(graph, EventLog::streaming(sink)));
+
+    let mut terminal = ratatui::init();
+    let shown = show(&mut terminal, dashboard, events);
+    ratatui::restore();
+    run.join().expect("Simulation thread should not panic");
+    shown
+}
+
+/// Watch the block in `spec` run on the deterministic runtime, seeded with
+/// the spec's seed, with every storage access taking `latency` of virtual
+/// time.
+pub fn watch_deterministic(spec: &BlockSpec, latency: Duration) -> io::Result<()> {
+    let seed = spec.seed;
+    watch(
+        format!("Deterministic runtime, seed {}", seed),
+        spec,
+        move |graph, log| {
+            DeterministicRunner::new(Config::default().with_seed(seed)).start(
+                |context| async move {
+                    simulate(&context, &graph, latency, &log).await;
+                },
+            );
+        },
+    )
+}
+
+/// Watch the block in `spec` run on Tokio, with every storage access
+/// sleeping for `latency`.
+pub fn watch_tokio(spec: &BlockSpec, latency: Duration) -> io::Result<()> {
+    watch("Tokio runtime".to_string(), spec, move |graph, log| {
+        TokioRunner::new(TokioConfig::default()).start(|context| async move {
+            simulate(&context, &graph, latency, &log).await;
+        });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use super::*;
+
+    fn event(task: &str, message: &str) -> TraceEvent {
+        TraceEvent {
+            task: task.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn graph() -> DependencyGraph {
+        DependencyGraph::from_tasks(generate_tasks(&BlockSpec {
+            size: 6,
+            conflict_rate: 1.0,
+            seed: 1,
+        }))
+    }
+
+    /// Replaying a deterministic run's trace walks every task from waiting
+    /// or ready through running to done, and tracks level and clock.
+    #[test]
+    fn test_replay_reaches_done() {
+        let graph = graph();
+        let log = EventLog::new();
+        DeterministicRunner::new(Config::default().with_seed(3)).start(|context| {
+            let graph = &graph;
+            let log = log.clone();
+            async move { simulate(&context, graph, Duration::from_millis(2), &log).await }
+        });
+
+        let mut dashboard = Dashboard::new("test", &graph);
+        let blocked = (0..graph.tasks.len())
+            .find(|&id| dashboard.state(id) == TaskState::Waiting)
+            .expect("Conflicting tasks should wait on each other");
+        let mut seen = vec![];
+        for event in log.events() {
+            dashboard.observe(event);
+            seen.push(dashboard.state(blocked));
+        }
+
+        assert!(dashboard.is_finished());
+        assert_eq!(dashboard.level(), Some(graph.execution_levels().len() - 1));
+        assert!(dashboard.elapsed() >= Duration::from_millis(2));
+        seen.dedup();
+        assert_eq!(
+            seen,
+            [
+                TaskState::Waiting,
+                TaskState::Ready,
+                TaskState::Running,
+                TaskState::Done
+            ]
+        );
+    }
+
+    /// The rendered frame shows each state's tasks and the recent events.
+    #[test]
+    fn test_render() {
+        let graph = graph();
+        let mut dashboard = Dashboard::new("test", &graph);
+        dashboard.observe(event(SCHEDULER_LABEL, "level 0 worker 0: [0]"));
+        dashboard.observe(event("tx0", "started"));
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+        terminal.draw(|frame| dashboard.render(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+
+        assert!(screen.contains("Running (1)"));
+        assert!(screen.contains("level 0"));
+        assert!(screen.contains("tx0: started"));
+    }
+}
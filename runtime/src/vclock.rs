@@ -0,0 +1,109 @@
+//! Vector clocks: causal order between nodes that only talk by message.
+//!
+//! A [`VectorClock`] holds one counter per node. A node ticks its own
+//! counter for everything it does, stamps outgoing messages with its clock,
+//! and merges the clock of every message it receives. Comparing two stamps
+//! then says whether one event could have influenced the other: if every
+//! counter of `a` is at most the matching counter of `b`, `a` happened
+//! before `b`; if each has a counter the other lacks, neither node had
+//! heard of the other's event and the two were concurrent, whatever order
+//! they happened to be logged in.
+
+use std::{cmp::Ordering, fmt};
+
+/// Counters indexed by node id. Missing counters are zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct VectorClock {
+    // Only ever grown to cover a nonzero counter, so it never ends in a
+    // zero and the derived equality agrees with `partial_cmp`.
+    counts: Vec<u64>,
+}
+
+impl VectorClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, node: usize) -> u64 {
+        self.counts.get(node).copied().unwrap_or(0)
+    }
+
+    /// Count one more event on `node`.
+    pub fn tick(&mut self, node: usize) {
+        if self.counts.len() <= node {
+            self.counts.resize(node + 1, 0);
+        }
+        self.counts[node] += 1;
+    }
+
+    /// Take the larger of each counter, as on receiving a message stamped
+    /// with `other`.
+    pub fn merge(&mut self, other: &VectorClock) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, &theirs) in self.counts.iter_mut().zip(&other.counts) {
+            *count = (*count).max(theirs);
+        }
+    }
+
+    pub fn happens_before(&self, other: &VectorClock) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Less)
+    }
+
+    pub fn is_concurrent(&self, other: &VectorClock) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+}
+
+impl PartialOrd for VectorClock {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let nodes = self.counts.len().max(other.counts.len());
+        let (mut less, mut greater) = (false, false);
+        for node in 0..nodes {
+            match self.get(node).cmp(&other.get(node)) {
+                Ordering::Less => less = true,
+                Ordering::Greater => greater = true,
+                Ordering::Equal => {}
+            }
+        }
+        match (less, greater) {
+            (false, false) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (true, true) => None,
+        }
+    }
+}
+
+impl fmt::Display for VectorClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A merged clock follows both inputs; clocks that each ticked on their
+    /// own are concurrent.
+    #[test]
+    fn test_ordering() {
+        let mut a = VectorClock::new();
+        a.tick(0);
+        let mut b = VectorClock::new();
+        b.tick(2);
+        assert!(a.is_concurrent(&b));
+
+        let mut c = b.clone();
+        c.merge(&a);
+        c.tick(2);
+        assert!(a.happens_before(&c));
+        assert!(b.happens_before(&c));
+        assert!(!c.happens_before(&a));
+        assert_eq!(c.to_string(), "[1, 0, 2]");
+        assert_eq!(a.partial_cmp(&a.clone()), Some(Ordering::Equal));
+    }
+}
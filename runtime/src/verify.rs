@@ -0,0 +1,255 @@
+//! Checking workload results against an independent reference.
+//!
+//! Each verifier recomputes what a workload should have produced by a
+//! different, simpler route and reports the first disagreement as a
+//! [`VerificationError`]:
+//!
+//! - [`verify_word_counts`] counts each reported word again with
+//!   [`Corpus::count`].
+//! - [`verify_pipeline_output`] checks a block's outcome against the block:
+//!   one receipt per transaction, rejections exactly where signatures fail,
+//!   and a failing receipt for each rejection.
+//! - [`verify_state_root`] re-executes a graph with the
+//!   [`SequentialExecutor`] from the same genesis state and compares state
+//!   roots.
+//!
+//! Keeping these next to the workloads means a demo, a test and a one-off
+//! experiment all check results the same way.
+
+use std::{collections::BTreeMap, fmt};
+
+use commonware_runtime::Clock;
+
+use crate::{
+    corpus::Corpus,
+    parallel_determinism::{
+        block::{Block, BlockOutcome},
+        chain::{StateRoot, state_root},
+        dep_graph::DependencyGraph,
+        sequential::SequentialExecutor,
+        state::{MemoryStorage, Storage, Value},
+        types::{ResourceId, TaskId},
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationError {
+    /// A word was reported with the wrong count.
+    WordCount {
+        word: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// The outcome does not have one receipt per transaction, in a valid
+    /// order.
+    ReceiptCount {
+        transactions: usize,
+        receipts: usize,
+    },
+    /// The transaction at `position` was accepted or rejected wrongly, or its
+    /// receipt does not match.
+    Transaction { position: TaskId, reason: String },
+    /// The state differs from the sequential reference.
+    StateRoot {
+        expected: StateRoot,
+        actual: StateRoot,
+    },
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WordCount {
+                word,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "'{}' counted {} times, corpus has {}",
+                word, actual, expected
+            ),
+            Self::ReceiptCount {
+                transactions,
+                receipts,
+            } => write!(f, "{} receipts for {} transactions", receipts, transactions),
+            Self::Transaction { position, reason } => {
+                write!(f, "transaction {}: {}", position, reason)
+            }
+            Self::StateRoot { expected, actual } => write!(
+                f,
+                "state root {:016x}, sequential reference {:016x}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// Check every `(word, count)` pair against `corpus`.
+pub fn verify_word_counts<W: AsRef<str>>(
+    corpus: &Corpus,
+    counts: impl IntoIterator<Item = (W, usize)>,
+) -> Result<(), VerificationError> {
+    for (word, actual) in counts {
+        let expected = corpus.count(word.as_ref());
+        if actual != expected {
+            return Err(VerificationError::WordCount {
+                word: word.as_ref().to_string(),
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check that `outcome` is what the verify-then-execute pipeline must
+/// produce for `block`.
+pub fn verify_pipeline_output(
+    block: &Block,
+    outcome: &BlockOutcome,
+) -> Result<(), VerificationError> {
+    let transactions = block.transactions.len();
+    let receipts = &outcome.report.receipts;
+    let mut positions = outcome.order.clone();
+    positions.sort_unstable();
+    if receipts.len() != transactions || positions != (0..transactions).collect::<Vec<_>>() {
+        return Err(VerificationError::ReceiptCount {
+            transactions,
+            receipts: receipts.len(),
+        });
+    }
+
+    for (receipt, &position) in receipts.iter().zip(&outcome.order) {
+        let transaction = &block.transactions[position];
+        let rejected = outcome.rejected.contains(&position);
+        let reason = if receipt.name != transaction.task.name {
+            Some(format!("receipt belongs to '{}'", receipt.name))
+        } else if transaction.verify() == rejected {
+            Some(if rejected {
+                "rejected with a valid signature".to_string()
+            } else {
+                "accepted with an invalid signature".to_string()
+            })
+        } else if rejected && receipt.output.is_ok() {
+            Some("rejected but its receipt succeeded".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(VerificationError::Transaction { position, reason });
+        }
+    }
+    Ok(())
+}
+
+/// Execute `graph` sequentially from `genesis` and check that `storage`
+/// ended up in the same state. Returns the agreed root.
+pub async fn verify_state_root(
+    context: &impl Clock,
+    graph: &DependencyGraph,
+    genesis: BTreeMap<ResourceId, Value>,
+    storage: &impl Storage,
+) -> Result<StateRoot, VerificationError> {
+    let reference = genesis.into_iter().collect::<MemoryStorage>();
+    SequentialExecutor::new()
+        .execute_with_state(context, graph, &reference)
+        .await;
+    let expected = state_root(&reference.snapshot());
+    let actual = state_root(&storage.snapshot());
+    if actual == expected {
+        Ok(actual)
+    } else {
+        Err(VerificationError::StateRoot { expected, actual })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::parallel_determinism::{
+        block::{BlockExecutor, SignedTask},
+        executor::ParallelExecutor,
+        generator::{BlockSpec, generate_tasks},
+    };
+
+    /// Correct counts pass, including for absent words; a wrong count names
+    /// the word.
+    #[test]
+    fn test_verify_word_counts() {
+        let corpus = Corpus::new("a b a c a".into());
+
+        assert_eq!(
+            verify_word_counts(&corpus, [("a", 3), ("b", 1), ("z", 0)]),
+            Ok(())
+        );
+        assert_eq!(
+            verify_word_counts(&corpus, [("a", 3), ("c", 2)]),
+            Err(VerificationError::WordCount {
+                word: "c".into(),
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    /// A real outcome passes; one whose rejections were dropped does not.
+    #[test]
+    fn test_verify_pipeline_output() {
+        let tasks = generate_tasks(&BlockSpec {
+            size: 8,
+            conflict_rate: 0.5,
+            seed: 3,
+        });
+        let mut transactions: Vec<_> = tasks.into_iter().map(SignedTask::sign).collect();
+        transactions[2].signature ^= 1;
+        let block = Block { transactions };
+        let mut outcome =
+            DeterministicRunner::new(Config::default().with_seed(0)).start(|context| {
+                let block = block.clone();
+                async move { BlockExecutor::new().execute(&context, block).await }
+            });
+
+        assert_eq!(verify_pipeline_output(&block, &outcome), Ok(()));
+        outcome.rejected.clear();
+        assert!(matches!(
+            verify_pipeline_output(&block, &outcome),
+            Err(VerificationError::Transaction { position: 2, .. })
+        ));
+    }
+
+    /// The parallel executor's state matches the sequential reference; state
+    /// touched afterwards does not.
+    #[test]
+    fn test_verify_state_root() {
+        let graph = DependencyGraph::from_tasks(generate_tasks(&BlockSpec {
+            size: 12,
+            conflict_rate: 0.6,
+            seed: 4,
+        }));
+        DeterministicRunner::new(Config::default().with_seed(0)).start(|context| async move {
+            let storage = MemoryStorage::new();
+            ParallelExecutor::new()
+                .execute_with_state(&context, &graph, &storage)
+                .await;
+
+            assert!(
+                verify_state_root(&context, &graph, BTreeMap::new(), &storage)
+                    .await
+                    .is_ok()
+            );
+            storage.put("tampered".into(), 1);
+            storage.commit(&context).await;
+            assert!(matches!(
+                verify_state_root(&context, &graph, BTreeMap::new(), &storage).await,
+                Err(VerificationError::StateRoot { .. })
+            ));
+        });
+    }
+}
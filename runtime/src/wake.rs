@@ -0,0 +1,339 @@
+//! Recording what woke a task, not just that it ran.
+//!
+//! An [`EventLog`] shows the order tasks did things in, but not why a task
+//! ran when it did: a consumer that resumes right after a producer sends
+//! could have been woken by the send or by a timer that happened to fire
+//! then. A [`WakeTracer`] answers that. [`WakeTracer::instrument`] wraps a
+//! task's future and records every poll; [`WakeTracer::source`] wraps the
+//! individual awaits inside it with what they wait on (a timer, a channel, a
+//! lock). When a wrapped await is woken, the wake is recorded there and
+//! then, under the task's label and attributed to its source, so in the log
+//! a wake sits right after the event that caused it. Wakes that reach the
+//! task without passing through a wrapped await are recorded as coming from
+//! an unknown source. A wake issued while another instrumented task is
+//! being polled also names that task, which is what
+//! [`CausalityGraph`](crate::causality::CausalityGraph) links on.
+//!
+//! Wakes are attributed by wrapping the waker each await is polled with, so
+//! anything between the instrumented task and the wrapped await, such as a
+//! `join!`, keeps working unchanged.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use commonware_runtime::{Clock, Spawner};
+use tokio::sync::{Mutex as AsyncMutex, mpsc};
+
+use crate::trace::{EventLog, TraceEvent};
+
+/// What an await was waiting on when it was woken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WakeSource {
+    Timer,
+    Channel,
+    Lock,
+}
+
+impl fmt::Display for WakeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            WakeSource::Timer => "timer",
+            WakeSource::Channel => "channel",
+            WakeSource::Lock => "lock release",
+        };
+        write!(f, "{}", source)
+    }
+}
+
+thread_local! {
+    /// Set while a wake is being passed up the waker chain, so the wakers
+    /// further up know it has already been recorded.
+    static RECORDED: Cell<bool> = const { Cell::new(false) };
+
+    /// The instrumented task being polled on this thread, which is the one
+    /// responsible for any wake issued meanwhile.
+    static POLLING: RefCell<Option<Arc<Shared>>> = const { RefCell::new(None) };
+}
+
+/// The task named as the waker in a wake recorded by a [`WakeTracer`], if
+/// `event` is one and its waker was instrumented.
+pub fn waker_of(event: &TraceEvent) -> Option<&str> {
+    let (_, waker) = event
+        .message
+        .strip_prefix("woken by ")?
+        .split_once(" from ")?;
+    Some(waker)
+}
+
+struct Shared {
+    task: String,
+    log: EventLog,
+    wakes: Mutex<Vec<Option<WakeSource>>>,
+}
+
+/// Records one task's polls and wakes into a shared [`EventLog`]. Clones
+/// record for the same task.
+#[derive(Clone)]
+pub struct WakeTracer {
+    shared: Arc<Shared>,
+}
+
+impl WakeTracer {
+    pub fn new(task: impl Into<String>, log: EventLog) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                task: task.into(),
+                log,
+                wakes: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Wrap the task's future, recording every poll, its completion, and
+    /// any wake no wrapped await claimed.
+    pub fn instrument<F: Future>(&self, future: F) -> Instrumented<F> {
+        Instrumented {
+            shared: self.shared.clone(),
+            inner: Box::pin(future),
+        }
+    }
+
+    /// Wrap one await inside the instrumented future, attributing its wakes
+    /// to `source`. If wrapped awaits are nested, the innermost one wins.
+    pub fn source<F: Future>(&self, source: WakeSource, future: F) -> Attributed<F> {
+        Attributed {
+            shared: self.shared.clone(),
+            source,
+            inner: Box::pin(future),
+        }
+    }
+
+    /// Every wake recorded so far, in order, with `None` for wakes from an
+    /// unknown source.
+    pub fn wakes(&self) -> Vec<Option<WakeSource>> {
+        self.shared.wakes.lock().unwrap().clone()
+    }
+}
+
+/// Records a wake, unless a waker further down the chain already did, then
+/// passes it on to the waker it wraps.
+struct Recording {
+    shared: Arc<Shared>,
+    source: Option<WakeSource>,
+    inner: Waker,
+}
+
+impl Recording {
+    fn waker(shared: &Arc<Shared>, source: Option<WakeSource>, inner: &Waker) -> Waker {
+        Waker::from(Arc::new(Recording {
+            shared: shared.clone(),
+            source,
+            inner: inner.clone(),
+        }))
+    }
+}
+
+impl Wake for Recording {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let recorded = RECORDED.replace(true);
+        if !recorded {
+            let mut message = match self.source {
+                Some(source) => format!("woken by {}", source),
+                None => "woken by an unknown source".to_string(),
+            };
+            POLLING.with_borrow(|polling| {
+                if let Some(waker) = polling {
+                    message.push_str(&format!(" from {}", waker.task));
+                }
+            });
+            self.shared.log.record(self.shared.task.as_str(), message);
+            self.shared.wakes.lock().unwrap().push(self.source);
+        }
+        self.inner.wake_by_ref();
+        RECORDED.set(recorded);
+    }
+}
+
+/// The future returned by [`WakeTracer::instrument`].
+pub struct Instrumented<F: Future> {
+    shared: Arc<Shared>,
+    inner: Pin<Box<F>>,
+}
+
+// Nothing is ever pinned in place: the inner future lives in its own box.
+impl<F: Future> Unpin for Instrumented<F> {}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.shared.log.record(this.shared.task.as_str(), "polled");
+        let waker = Recording::waker(&this.shared, None, cx.waker());
+        let polling = POLLING.replace(Some(this.shared.clone()));
+        let result = this.inner.as_mut().poll(&mut Context::from_waker(&waker));
+        POLLING.set(polling);
+        if result.is_ready() {
+            this.shared.log.record(this.shared.task.as_str(), "ready");
+        }
+        result
+    }
+}
+
+/// The future returned by [`WakeTracer::source`].
+pub struct Attributed<F: Future> {
+    shared: Arc<Shared>,
+    source: WakeSource,
+    inner: Pin<Box<F>>,
+}
+
+// Nothing is ever pinned in place: the inner future lives in its own box.
+impl<F: Future> Unpin for Attributed<F> {}
+
+impl<F: Future> Future for Attributed<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let waker = Recording::waker(&this.shared, Some(this.source), cx.waker());
+        this.inner.as_mut().poll(&mut Context::from_waker(&waker))
+    }
+}
+
+/// A producer and a consumer that wake each other through a timer, a
+/// channel and a lock. The producer takes the lock, sleeps, sends a value
+/// and sleeps again before releasing; the consumer waits for the value and
+/// then for the lock. Returns the wakes each recorded, producer first.
+pub async fn wake_causality<S: Spawner + Clock>(
+    context: &S,
+    log: &EventLog,
+) -> (Vec<Option<WakeSource>>, Vec<Option<WakeSource>>) {
+    let lock = Arc::new(AsyncMutex::new(()));
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let producer = WakeTracer::new("producer", log.clone());
+    let consumer = WakeTracer::new("consumer", log.clone());
+
+    let producing = context.clone().spawn({
+        let (lock, log, tracer) = (lock.clone(), log.clone(), producer.clone());
+        move |context| {
+            tracer.clone().instrument(async move {
+                let guard = lock.lock().await;
+                tracer
+                    .source(WakeSource::Timer, context.sleep(Duration::from_millis(2)))
+                    .await;
+                log.record("producer", "sending");
+                sender.send(1).expect("Consumer should be waiting");
+                tracer
+                    .source(WakeSource::Timer, context.sleep(Duration::from_millis(1)))
+                    .await;
+                log.record("producer", "releasing the lock");
+                drop(guard);
+            })
+        }
+    });
+    let consuming = context.clone().spawn({
+        let (log, tracer) = (log.clone(), consumer.clone());
+        move |_| {
+            tracer.clone().instrument(async move {
+                tracer
+                    .source(WakeSource::Channel, receiver.recv())
+                    .await
+                    .expect("Producer should send");
+                log.record("consumer", "received");
+                let _guard = tracer.source(WakeSource::Lock, lock.lock()).await;
+                log.record("consumer", "acquired the lock");
+            })
+        }
+    });
+
+    producing.await.expect("Producer should run to completion");
+    consuming.await.expect("Consumer should run to completion");
+    (producer.wakes(), consumer.wakes())
+}
+
+#[cfg(all(test, feature = "deterministic-backend"))]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+        tokio::Runner as TokioRunner,
+    };
+
+    use super::*;
+
+    fn messages(log: &EventLog) -> Vec<String> {
+        log.events().iter().map(|event| event.to_string()).collect()
+    }
+
+    /// Both runtimes attribute the same wakes, and each wake is logged right
+    /// after the event that caused it.
+    #[test]
+    fn test_wake_causality() {
+        let deterministic = EventLog::new();
+        let wakes = DeterministicRunner::new(Config::default().with_seed(0)).start(|context| {
+            let log = deterministic.clone();
+            async move { wake_causality(&context, &log).await }
+        });
+        let tokio = EventLog::new();
+        let tokio_wakes = TokioRunner::default().start(|context| {
+            let log = tokio.clone();
+            async move { wake_causality(&context, &log).await }
+        });
+
+        let timer = Some(WakeSource::Timer);
+        assert_eq!(wakes.0, [timer, timer]);
+        assert_eq!(wakes.1, [Some(WakeSource::Channel), Some(WakeSource::Lock)]);
+        assert_eq!(tokio_wakes, wakes);
+        for log in [&deterministic, &tokio] {
+            let messages = messages(log);
+            for (cause, wake) in [
+                (
+                    "producer: sending",
+                    "consumer: woken by channel from producer",
+                ),
+                (
+                    "producer: releasing the lock",
+                    "consumer: woken by lock release from producer",
+                ),
+            ] {
+                let at = messages
+                    .iter()
+                    .position(|message| message == cause)
+                    .unwrap();
+                assert_eq!(messages[at + 1], wake);
+            }
+        }
+    }
+
+    /// A wake that no wrapped await claims is still recorded, as unknown.
+    #[test]
+    fn test_unattributed_wake() {
+        let log = EventLog::new();
+        let tracer = WakeTracer::new("sleeper", log.clone());
+        DeterministicRunner::new(Config::default()).start(|context| {
+            tracer.instrument(async move { context.sleep(Duration::from_millis(1)).await })
+        });
+
+        assert_eq!(tracer.wakes(), [None]);
+        assert_eq!(
+            messages(&log),
+            [
+                "sleeper: polled",
+                "sleeper: woken by an unknown source",
+                "sleeper: polled",
+                "sleeper: ready"
+            ]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Seeded random walks over a small state graph.
+//!
+//! Most demos assert on a result: a count, a root, a final state. A random
+//! walk makes the path itself the artifact. Every step is a choice drawn from
+//! the runtime's RNG, so two runs agree on the whole sequence of states only
+//! if they made every choice the same way, in the same order.
+//!
+//! [`walk`] takes one walk from any RNG. [`random_walks`] spawns several
+//! walkers that draw from the same runtime RNG and sleep between steps, so
+//! which walker gets which draw also depends on how the runtime interleaves
+//! them. Under the deterministic runtime the seed fixes both, and the paths
+//! repeat exactly; under Tokio the RNG is OS randomness and they do not.
+
+use std::{sync::Arc, time::Duration};
+
+use commonware_runtime::{Clock, Spawner};
+use rand_core::RngCore;
+
+use crate::trace::EventLog;
+
+/// A directed graph of named states. Every state has at least one outgoing
+/// edge, so a walk can always take another step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateGraph {
+    names: Vec<&'static str>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl StateGraph {
+    /// A graph with `edges[s]` the successors of state `s`.
+    pub fn new(names: Vec<&'static str>, edges: Vec<Vec<usize>>) -> Self {
+        assert_eq!(names.len(), edges.len(), "Every state needs a name");
+        for (state, successors) in edges.iter().enumerate() {
+            assert!(
+                !successors.is_empty(),
+                "State {} has no outgoing edge",
+                names[state]
+            );
+            assert!(
+                successors.iter().all(|&next| next < names.len()),
+                "State {} has an edge to a missing state",
+                names[state]
+            );
+        }
+        Self { names, edges }
+    }
+
+    /// The lifecycle of a job: queued, run, sometimes blocked or requeued,
+    /// eventually done and idle again.
+    pub fn job_lifecycle() -> Self {
+        Self::new(
+            vec!["idle", "queued", "running", "blocked", "done"],
+            vec![vec![1], vec![2, 0], vec![2, 3, 4], vec![2, 1], vec![0]],
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn name(&self, state: usize) -> &'static str {
+        self.names[state]
+    }
+
+    /// Draw the state after `state`, uniformly among its successors.
+    pub fn step(&self, state: usize, rng: &mut impl RngCore) -> usize {
+        let successors = &self.edges[state];
+        successors[rng.next_u64() as usize % successors.len()]
+    }
+}
+
+/// Walk `steps` steps from `start`. The path includes `start`, so it holds
+/// `steps + 1` states.
+pub fn walk(graph: &StateGraph, start: usize, steps: usize, rng: &mut impl RngCore) -> Vec<usize> {
+    let mut path = Vec::with_capacity(steps + 1);
+    path.push(start);
+    for _ in 0..steps {
+        let next = graph.step(*path.last().unwrap(), rng);
+        path.push(next);
+    }
+    path
+}
+
+/// Spawn `walkers` walkers from state 0, each taking `steps` steps with a
+/// millisecond's sleep after each, all drawing from the runtime's RNG. Every
+/// transition is recorded in `log` under `walker N`. Returns each walker's
+/// path, in walker order.
+pub async fn random_walks<S: Spawner + Clock + RngCore>(
+    context: &S,
+    graph: Arc<StateGraph>,
+    walkers: usize,
+    steps: usize,
+    log: &EventLog,
+) -> Vec<Vec<usize>> {
+    let handles: Vec<_> = (0..walkers)
+        .map(|walker| {
+            let (graph, log) = (graph.clone(), log.clone());
+            context.clone().spawn(move |mut context| async move {
+                let name = format!("walker {}", walker);
+                let mut path = vec![0];
+                for _ in 0..steps {
+                    let state = *path.last().unwrap();
+                    let next = graph.step(state, &mut context);
+                    log.record(
+                        name.as_str(),
+                        format!("{} -> {}", graph.name(state), graph.name(next)),
+                    );
+                    path.push(next);
+                    context.sleep(Duration::from_millis(1)).await;
+                }
+                path
+            })
+        })
+        .collect();
+
+    let mut paths = Vec::with_capacity(walkers);
+    for handle in handles {
+        paths.push(handle.await.expect("Walker should run to completion"));
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use commonware_runtime::{
+        Runner,
+        deterministic::{Config, Runner as DeterministicRunner},
+    };
+
+    use super::*;
+    use crate::trace::TraceEvent;
+
+    fn deterministic_walks(seed: u64) -> (Vec<Vec<usize>>, Vec<TraceEvent>) {
+        let log = EventLog::new();
+        let paths = DeterministicRunner::new(Config::default().with_seed(seed)).start(|context| {
+            let log = log.clone();
+            async move {
+                random_walks(&context, Arc::new(StateGraph::job_lifecycle()), 4, 20, &log).await
+            }
+        });
+        (paths, log.events())
+    }
+
+    /// Every step of a walk follows an edge of the graph.
+    #[test]
+    fn test_walk_follows_edges() {
+        let graph = StateGraph::job_lifecycle();
+        let path = {
+            let graph = graph.clone();
+            DeterministicRunner::new(Config::default().with_seed(3))
+                .start(|mut context| async move { walk(&graph, 0, 100, &mut context) })
+        };
+
+        assert_eq!(path.len(), 101);
+        assert_eq!(path[0], 0);
+        for pair in path.windows(2) {
+            assert!(graph.edges[pair[0]].contains(&pair[1]));
+        }
+    }
+
+    /// The same seed walks the same paths, in the same interleaving; other
+    /// seeds walk others.
+    #[test]
+    fn test_paths_repeat_with_seed() {
+        let (paths, events) = deterministic_walks(11);
+
+        assert_eq!(paths.len(), 4);
+        assert!(paths.iter().all(|path| path.len() == 21));
+        assert_eq!(events.len(), 4 * 20);
+        assert_eq!(deterministic_walks(11), (paths.clone(), events));
+        assert!((12..20).any(|seed| deterministic_walks(seed).0 != paths));
+    }
+
+    /// A state with nowhere to go is rejected up front.
+    #[test]
+    #[should_panic(expected = "no outgoing edge")]
+    fn test_dead_end_rejected() {
+        StateGraph::new(vec!["start", "stuck"], vec![vec![1], vec![]]);
+    }
+}